@@ -9,6 +9,10 @@ use std::{sync::Arc, time::Duration};
 use tokio::time::sleep;
 use tracing::{debug, info};
 
+mod provider;
+
+pub use provider::{FailoverClient, FailoverClientError, ProviderConfig, RpcProvider};
+
 /// The result of a single submission attempt.
 #[derive(Debug)]
 pub enum SubmissionAction {
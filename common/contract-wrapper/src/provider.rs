@@ -0,0 +1,243 @@
+use async_trait::async_trait;
+use ethers::providers::{
+    Http, HttpRateLimitRetryPolicy, JsonRpcClient, JsonRpcError, Provider, ProviderError,
+    RetryClient, RetryClientBuilder, RetryClientError, RpcError,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Duration to try other endpoints first after an endpoint fails.
+const UNHEALTHY_DURATION: Duration = Duration::from_secs(30);
+
+/// Provider of the blockchain RPC endpoints shared by log sync, miner and pruner.
+pub type RpcProvider = Provider<FailoverClient>;
+
+#[derive(Clone, Debug)]
+pub struct ProviderConfig {
+    /// Blockchain RPC endpoints in the order of preference.
+    pub endpoints: Vec<String>,
+    /// The number of retries for rate limited responses.
+    pub rate_limit_retries: u32,
+    /// The number of retries after a connection times out, which only applies if there is a
+    /// single endpoint, otherwise the next endpoint is tried instead.
+    pub timeout_retries: u32,
+    /// The duration to wait before retry, in ms.
+    pub initial_backoff: u64,
+    /// The timeout of HTTP requests, or no timeout if `None`.
+    pub request_timeout: Option<Duration>,
+}
+
+impl ProviderConfig {
+    pub fn make_provider(&self) -> Result<RpcProvider, String> {
+        Ok(Provider::new(FailoverClient::new(self)?))
+    }
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    client: RetryClient<Http>,
+    /// The endpoint is tried after healthy ones until this time.
+    unhealthy_until: Mutex<Option<Instant>>,
+}
+
+impl Endpoint {
+    fn is_healthy(&self, now: Instant) -> bool {
+        match *self.unhealthy_until.lock().expect("endpoint lock poisoned") {
+            Some(until) => until <= now,
+            None => true,
+        }
+    }
+
+    fn set_healthy(&self, healthy: bool) {
+        *self.unhealthy_until.lock().expect("endpoint lock poisoned") = if healthy {
+            None
+        } else {
+            Some(Instant::now() + UNHEALTHY_DURATION)
+        };
+    }
+}
+
+/// JSON-RPC client over multiple endpoints, which fails over to the next endpoint on connection
+/// errors and timeouts.
+///
+/// Failed endpoints are tried after the healthy ones for a while, and become healthy again once
+/// a request succeeds. Error responses of the JSON-RPC server are returned directly.
+#[derive(Debug)]
+pub struct FailoverClient {
+    endpoints: Vec<Endpoint>,
+}
+
+impl FailoverClient {
+    pub fn new(config: &ProviderConfig) -> Result<Self, String> {
+        if config.endpoints.is_empty() {
+            return Err("No blockchain endpoint configured".into());
+        }
+
+        let timeout_retries = match config.endpoints.len() {
+            1 => config.timeout_retries,
+            _ => 0,
+        };
+
+        let endpoints = config
+            .endpoints
+            .iter()
+            .map(|url| {
+                let mut builder = reqwest::Client::builder();
+                if let Some(timeout) = config.request_timeout {
+                    builder = builder.timeout(timeout).connect_timeout(timeout);
+                }
+                let http = Http::new_with_client(
+                    url::Url::parse(url)
+                        .map_err(|e| format!("Cannot parse blockchain endpoint: {:?}", e))?,
+                    builder
+                        .build()
+                        .map_err(|e| format!("Cannot build http client: {:?}", e))?,
+                );
+                let client = RetryClientBuilder::default()
+                    .rate_limit_retries(config.rate_limit_retries)
+                    .timeout_retries(timeout_retries)
+                    .initial_backoff(Duration::from_millis(config.initial_backoff))
+                    .build(http, Box::new(HttpRateLimitRetryPolicy));
+
+                Ok(Endpoint {
+                    url: url.clone(),
+                    client,
+                    unhealthy_until: Mutex::new(None),
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Self { endpoints })
+    }
+
+    /// Returns the endpoints in the order to try, where the unhealthy ones are tried last.
+    fn ordered_endpoints(&self) -> impl Iterator<Item = &Endpoint> {
+        let now = Instant::now();
+        let (healthy, unhealthy): (Vec<_>, Vec<_>) =
+            self.endpoints.iter().partition(|e| e.is_healthy(now));
+        healthy.into_iter().chain(unhealthy)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FailoverClientError {
+    #[error(transparent)]
+    Endpoint(#[from] RetryClientError),
+    #[error(transparent)]
+    SerdeJson(#[from] serde_json::Error),
+}
+
+impl RpcError for FailoverClientError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            FailoverClientError::Endpoint(e) => e.as_error_response(),
+            FailoverClientError::SerdeJson(_) => None,
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            FailoverClientError::Endpoint(e) => e.as_serde_error(),
+            FailoverClientError::SerdeJson(e) => Some(e),
+        }
+    }
+}
+
+impl From<FailoverClientError> for ProviderError {
+    fn from(e: FailoverClientError) -> Self {
+        ProviderError::JsonRpcClientError(Box::new(e))
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for FailoverClient {
+    type Error = FailoverClientError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let params = serde_json::to_value(params)?;
+        let mut last_error = None;
+
+        for endpoint in self.ordered_endpoints() {
+            match endpoint.client.request(method, &params).await {
+                Ok(result) => {
+                    endpoint.set_healthy(true);
+                    return Ok(result);
+                }
+                // the endpoint works, but fails to handle the request
+                Err(e) if e.is_error_response() => return Err(e.into()),
+                Err(e) => {
+                    warn!(url = %endpoint.url, %method, error = ?e, "Blockchain endpoint failed");
+                    endpoint.set_healthy(false);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.expect("endpoints not empty").into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(endpoints: &[&str]) -> ProviderConfig {
+        ProviderConfig {
+            endpoints: endpoints.iter().map(|e| e.to_string()).collect(),
+            rate_limit_retries: 0,
+            timeout_retries: 0,
+            initial_backoff: 0,
+            request_timeout: None,
+        }
+    }
+
+    #[test]
+    fn test_ordered_endpoints() {
+        let client = FailoverClient::new(&config(&[
+            "http://127.0.0.1:8545",
+            "http://127.0.0.2:8545",
+            "http://127.0.0.3:8545",
+        ]))
+        .unwrap();
+        let urls = |client: &FailoverClient| -> Vec<String> {
+            client.ordered_endpoints().map(|e| e.url.clone()).collect()
+        };
+
+        client.endpoints[0].set_healthy(false);
+        assert_eq!(
+            urls(&client),
+            [
+                "http://127.0.0.2:8545",
+                "http://127.0.0.3:8545",
+                "http://127.0.0.1:8545",
+            ]
+        );
+
+        client.endpoints[0].set_healthy(true);
+        assert_eq!(urls(&client)[0], "http://127.0.0.1:8545");
+
+        assert!(FailoverClient::new(&config(&[])).is_err());
+        assert!(FailoverClient::new(&config(&["invalid"])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        // nothing listens on port 1
+        let client =
+            FailoverClient::new(&config(&["http://127.0.0.1:1", "http://127.0.0.1:2"])).unwrap();
+        let result: Result<String, _> = client.request("eth_chainId", ()).await;
+        assert!(matches!(result, Err(FailoverClientError::Endpoint(_))));
+        assert!(client
+            .endpoints
+            .iter()
+            .all(|e| !e.is_healthy(Instant::now())));
+    }
+}
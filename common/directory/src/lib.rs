@@ -1,3 +1,7 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
 /// Names for the default directories.
 pub const DEFAULT_ROOT_DIR: &str = ".lighthouse";
 pub const DEFAULT_BEACON_NODE_DIR: &str = "beacon";
@@ -10,3 +14,29 @@ pub const DEFAULT_HARDCODED_NETWORK: &str = "mainnet";
 
 /// Base directory name for unnamed testnets passed through the --testnet-dir flag
 pub const CUSTOM_TESTNET_DIR: &str = "custom";
+
+/// Writes `data` to `path` via a temporary file in the same directory, so that a crash never
+/// leaves a truncated file.
+///
+/// Both the temporary file and the directory are synced to disk, so that the renamed file
+/// survives a power loss.
+pub fn atomic_write(path: &Path, data: &[u8]) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&tmp_path, path)?;
+
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        File::open(dir)?.sync_all()?;
+    }
+
+    Ok(())
+}
@@ -1,15 +1,21 @@
 use crate::Config;
 use metrics::{register_meter_with_group, Histogram, Meter, Sample};
-use network::types::SignedAnnounceFile;
+use network::types::{peer_id_to_public_key, HasSignature, SignedAnnounceFile};
 use network::PeerId;
 use parking_lot::Mutex;
 use priority_queue::PriorityQueue;
 use rand::seq::IteratorRandom;
 use shared_types::{timestamp_now, TxID};
+use ssz::{Decode, Encode};
+use ssz_derive::{Decode as DeriveDecode, Encode as DeriveEncode};
 use std::cmp::Reverse;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use storage::config::ShardConfig;
+use tracing::{info, warn};
 
 lazy_static::lazy_static! {
     pub static ref INSERT_QPS: Arc<dyn Meter> = register_meter_with_group("file_location_cache_insert", "qps");
@@ -253,6 +259,27 @@ impl FileCache {
         TOTAL_CACHED.update(self.total_announcements as u64);
         Some(result)
     }
+
+    /// Returns all unexpired announcements. An announcement of multiple files is returned once.
+    fn unexpired(&self) -> Vec<SignedAnnounceFile> {
+        let now = timestamp_now();
+        let mut seen = HashSet::new();
+        self.files
+            .values()
+            .flat_map(|item| item.items.values())
+            .filter(|announcement| {
+                announcement.timestamp + self.config.entry_expiration_time_secs > now
+            })
+            .filter(|announcement| seen.insert(*announcement))
+            .cloned()
+            .collect()
+    }
+}
+
+/// The on-disk format of the persisted cache.
+#[derive(DeriveEncode, DeriveDecode)]
+struct PersistedCache {
+    announcements: Vec<SignedAnnounceFile>,
 }
 
 #[derive(Default)]
@@ -273,6 +300,7 @@ impl PeerShardConfigCache {
 pub struct FileLocationCache {
     cache: Mutex<FileCache>,
     peer_cache: Mutex<PeerShardConfigCache>,
+    persist_path: Option<PathBuf>,
 }
 
 impl Default for FileLocationCache {
@@ -280,16 +308,30 @@ impl Default for FileLocationCache {
         FileLocationCache {
             cache: Mutex::new(FileCache::new(Default::default())),
             peer_cache: Mutex::new(Default::default()),
+            persist_path: None,
         }
     }
 }
 
 impl FileLocationCache {
     pub fn new(config: Config) -> Self {
-        FileLocationCache {
+        let persist_path = config.persist_path.clone();
+        let cache = FileLocationCache {
             cache: Mutex::new(FileCache::new(config)),
             peer_cache: Mutex::new(Default::default()),
+            persist_path,
+        };
+
+        if let Some(path) = &cache.persist_path {
+            match cache.load(path) {
+                Ok(num_announcements) => {
+                    info!(%num_announcements, "File location cache restored")
+                }
+                Err(e) => warn!(error = %e, "Failed to restore file location cache"),
+            }
         }
+
+        cache
     }
 
     pub fn insert(&self, announcement: SignedAnnounceFile) {
@@ -335,6 +377,70 @@ impl FileLocationCache {
     pub fn get_peer_config(&self, peer: &PeerId) -> Option<ShardConfig> {
         self.peer_cache.lock().get(peer)
     }
+
+    /// Persists the unexpired announcements, and returns the number of persisted announcements.
+    /// Does nothing if persistence is disabled.
+    ///
+    /// The latest shard configs of peers are persisted in the peer db instead, which are restored
+    /// after the announcements once the network starts.
+    pub fn persist(&self) -> Result<usize, String> {
+        let path = match &self.persist_path {
+            Some(path) => path,
+            None => return Ok(0),
+        };
+
+        let announcements = self.cache.lock().unexpired();
+        let num_announcements = announcements.len();
+        let data = PersistedCache { announcements }.as_ssz_bytes();
+
+        directory::atomic_write(path, &data)
+            .map_err(|e| format!("Failed to write {:?}: {:?}", path, e))?;
+
+        Ok(num_announcements)
+    }
+
+    /// Restores the persisted announcements, dropping the expired ones and the ones with invalid
+    /// signatures, and returns the number of restored announcements.
+    fn load(&self, path: &Path) -> Result<usize, String> {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(format!("Failed to read {:?}: {:?}", path, e)),
+        };
+        let persisted = PersistedCache::from_ssz_bytes(&data)
+            .map_err(|e| format!("Failed to decode {:?}: {:?}", path, e))?;
+
+        let expiration_secs = self.cache.lock().config.entry_expiration_time_secs;
+        let now = timestamp_now();
+        let mut num_announcements = 0;
+        for announcement in persisted.announcements {
+            if announcement.timestamp + expiration_secs <= now {
+                continue;
+            }
+
+            let peer_id = announcement.peer_id.clone().into();
+            match peer_id_to_public_key(&peer_id) {
+                Ok(public_key) if announcement.verify_signature(&public_key) => {}
+                _ => {
+                    warn!(%peer_id, "Invalid signature of persisted file announcement");
+                    continue;
+                }
+            }
+
+            self.insert(announcement);
+            num_announcements += 1;
+        }
+
+        Ok(num_announcements)
+    }
+}
+
+impl Drop for FileLocationCache {
+    fn drop(&mut self) {
+        if let Err(e) = self.persist() {
+            warn!(error = %e, "Failed to persist file location cache");
+        }
+    }
 }
 
 #[cfg(test)]
@@ -344,9 +450,12 @@ mod tests {
     use network::{types::SignedAnnounceFile, PeerId};
     use shared_types::{timestamp_now, TxID};
 
+    use network::libp2p::identity;
+    use storage::config::ShardConfig;
+
     use crate::{test_util::AnnounceFileBuilder, Config};
 
-    use super::{AnnouncementCache, FileCache};
+    use super::{AnnouncementCache, FileCache, FileLocationCache};
 
     fn create_file(peer_id: Option<PeerId>, timestamp: u32) -> SignedAnnounceFile {
         let builder = AnnounceFileBuilder::default().with_timestamp(timestamp);
@@ -539,6 +648,7 @@ mod tests {
             max_entries_total: total_entries,
             max_entries_per_file: file_entries,
             entry_expiration_time_secs: timeout,
+            ..Default::default()
         })
     }
 
@@ -609,4 +719,57 @@ mod tests {
             vec![now - 3, now - 2, now - 1],
         );
     }
+
+    #[test]
+    fn test_file_location_cache_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            persist_path: Some(dir.path().join("file_location_cache")),
+            ..Default::default()
+        };
+        let now = timestamp_now();
+        let keypair = identity::Keypair::generate_secp256k1();
+        let peer_id = keypair.public().to_peer_id();
+        let (tx1, tx2, tx3) = (
+            TxID::random_hash(1),
+            TxID::random_hash(2),
+            TxID::random_hash(3),
+        );
+
+        let cache = FileLocationCache::new(config.clone());
+        cache.insert(
+            AnnounceFileBuilder::default()
+                .with_tx_id(tx1)
+                .with_keypair(keypair)
+                .with_timestamp(now - 1)
+                .build(),
+        );
+        // expired
+        cache.insert(
+            AnnounceFileBuilder::default()
+                .with_tx_id(tx2)
+                .with_keypair(identity::Keypair::generate_secp256k1())
+                .with_timestamp(now - 100000)
+                .build(),
+        );
+        // signed by another peer
+        cache.insert(
+            AnnounceFileBuilder::default()
+                .with_tx_id(tx3)
+                .with_timestamp(now - 1)
+                .build(),
+        );
+        assert_eq!(cache.persist().unwrap(), 2);
+        drop(cache);
+
+        let cache = FileLocationCache::new(config);
+        assert_eq!(cache.get_all(tx1).len(), 1);
+        assert_eq!(cache.get_all(tx2), vec![]);
+        assert_eq!(cache.get_all(tx3), vec![]);
+        // restored from the announcement
+        assert_eq!(
+            cache.get_peer_config(&peer_id),
+            Some(ShardConfig::default())
+        );
+    }
 }
@@ -2,15 +2,20 @@ mod file_location_cache;
 pub mod test_util;
 
 use serde::Deserialize;
+use std::path::PathBuf;
 
 pub use crate::file_location_cache::FileLocationCache;
 
-#[derive(Clone, Copy, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 #[serde(default)]
 pub struct Config {
     pub max_entries_total: usize,
     pub max_entries_per_file: usize,
     pub entry_expiration_time_secs: u32,
+    /// The file to persist unexpired announcements, so that they survive restarts.
+    /// Persistence is disabled if `None`.
+    pub persist_path: Option<PathBuf>,
+    pub persist_interval_secs: u64,
 }
 
 impl Default for Config {
@@ -19,6 +24,8 @@ impl Default for Config {
             max_entries_total: 1000000,
             max_entries_per_file: 4,
             entry_expiration_time_secs: 86400,
+            persist_path: None,
+            persist_interval_secs: 300,
         }
     }
 }
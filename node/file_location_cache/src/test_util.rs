@@ -10,6 +10,7 @@ pub struct AnnounceFileBuilder {
     tx_id: Option<TxID>,
     peer_id: Option<PeerId>,
     timestamp: Option<u32>,
+    keypair: Option<identity::Keypair>,
 }
 
 impl AnnounceFileBuilder {
@@ -28,6 +29,13 @@ impl AnnounceFileBuilder {
         self
     }
 
+    /// Signs the announcement with `keypair`, and announces the peer id of `keypair`.
+    pub fn with_keypair(mut self, keypair: identity::Keypair) -> Self {
+        self.peer_id = Some(keypair.public().to_peer_id());
+        self.keypair = Some(keypair);
+        self
+    }
+
     pub fn build(self) -> SignedAnnounceFile {
         let tx_id = self.tx_id.unwrap_or_else(|| TxID::random_hash(0));
         let peer_id = self.peer_id.unwrap_or_else(PeerId::random);
@@ -44,7 +52,9 @@ impl AnnounceFileBuilder {
             timestamp,
         };
 
-        let keypair = identity::Keypair::generate_secp256k1();
+        let keypair = self
+            .keypair
+            .unwrap_or_else(identity::Keypair::generate_secp256k1);
         SignedMessage::sign_message(msg, &keypair).unwrap()
     }
 }
@@ -4,7 +4,12 @@ mod sync_manager;
 
 use ethers::prelude::H160;
 pub use sync_manager::{
+    archive::{export_log_archive, ArchiveInfo, ArchivedLogSource, LogArchive},
     config::{CacheConfig, LogSyncConfig},
+    log_source::{BlockInfo, EvmLogSource, LogSource, SubmitLog},
+    mock_flow::MockFlow,
+    quorum::QuorumLogSource,
+    subscription::SubscribedLogSource,
     LogSyncEvent, LogSyncManager,
 };
 
@@ -0,0 +1,746 @@
+//! Archive of the `Submit` events of the Flow contract.
+//!
+//! Bootstrapping log sync from `log_sync_start_block_number` pages through every block with
+//! `eth_getLogs`, which takes a long time on a long chain. A log archive records the `Submit`
+//! events up to a finalized block, so that a new node could replay them locally and only sync
+//! the recent blocks from the blockchain.
+//!
+//! Archive layout:
+//! - header: `magic (8) | version (4) | contract address (20) | start block (8)`
+//! - records: `tag = 1 (1) | block number (8) | block hash (32) | tx hash (32) | tx index (8) |
+//!   event`, where event is `sender (20) | identity (32) | submission index (32) |
+//!   start pos (32) | length (32) | submission length (32) | tags_len (4) | tags |
+//!   num_nodes (4) | (root (32) | height (32)) * num_nodes`
+//! - trailer: `tag = 0 (1) | end block (8) | end block hash (32) | num_logs (8) | crc32 (4)`,
+//!   where the checksum covers everything before itself.
+
+use crate::sync_manager::log_source::{BlockInfo, EvmLogSource, LogSource, SubmitLog};
+use crate::{ContractAddress, LogSyncConfig};
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use contract_interface::{Submission, SubmissionNode, SubmitFilter};
+use ethers::prelude::BlockNumber;
+use ethers::types::{Bytes, H256, U256};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use jsonrpsee::tracing::{info, warn};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+const MAGIC: &[u8; 8] = b"ZGSLOGS\0";
+const VERSION: u32 = 1;
+const TAG_END: u8 = 0;
+const TAG_LOG: u8 = 1;
+const HEADER_SIZE: u64 = 40;
+const PROGRESS_LOG_INTERVAL: u64 = 100_000;
+/// Number of logs between the index entries to seek logs by block number.
+const INDEX_INTERVAL: u64 = 1000;
+/// Number of logs to read at a time on the blocking thread pool.
+const READ_PAGE_SIZE: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArchiveInfo {
+    pub contract_address: ContractAddress,
+    /// The first block covered by the archive.
+    pub start_block: u64,
+    /// The last block covered by the archive, which is checked against the chain before the
+    /// archive is replayed.
+    pub end_block: u64,
+    pub end_block_hash: H256,
+    pub num_logs: u64,
+}
+
+/// Exports the `Submit` events from the blockchain endpoints in `config` to the archive file
+/// `path`, which covers the blocks from `config.start_block_number` to the finalized block.
+pub async fn export_log_archive(
+    config: &LogSyncConfig,
+    path: impl AsRef<Path>,
+) -> Result<ArchiveInfo> {
+    let source = EvmLogSource::new(config, config.rpc_endpoint_urls.clone())?;
+    let end_block = match source
+        .get_block(config.finality_tag.unwrap_or(BlockNumber::Finalized))
+        .await
+    {
+        Ok(Some(block)) => block.number,
+        e => {
+            warn!(?e, "unable to get finalized block");
+            source
+                .get_block_number()
+                .await?
+                .saturating_sub(config.confirmation_block_count)
+        }
+    };
+
+    let path = path.as_ref();
+    if path.exists() {
+        bail!("{:?} already exists", path);
+    }
+    // written to a temporary file in the same directory, which is removed if export fails
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let file = tempfile::NamedTempFile::new_in(dir)
+        .map_err(|e| anyhow!("failed to create temporary file in {:?}: {:?}", dir, e))?;
+    let mut writer = BufWriter::new(file.as_file());
+    let info = export(
+        &source,
+        config.contract_address,
+        config.start_block_number,
+        end_block,
+        Duration::from_millis(config.recover_query_delay),
+        &mut writer,
+    )
+    .await?;
+    writer.into_inner()?.sync_all()?;
+    file.persist_noclobber(path)
+        .map_err(|e| anyhow!("failed to create {:?}: {:?}", path, e.error))?;
+    Ok(info)
+}
+
+/// Exports the `Submit` events in blocks `[start_block, end_block]` from `source`.
+pub async fn export(
+    source: &dyn LogSource,
+    contract_address: ContractAddress,
+    start_block: u64,
+    end_block: u64,
+    log_query_delay: Duration,
+    writer: impl Write,
+) -> Result<ArchiveInfo> {
+    let end_block_hash = source
+        .get_block(end_block.into())
+        .await?
+        .ok_or_else(|| anyhow!("None for block {}", end_block))?
+        .hash;
+    info!(start_block, end_block, "Start to export log archive");
+
+    let mut writer = ChecksumWriter::new(writer);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_be_bytes())?;
+    writer.write_all(contract_address.as_bytes())?;
+    writer.write_all(&start_block.to_be_bytes())?;
+
+    let mut num_logs = 0;
+    let mut logs = source.submit_logs(start_block, end_block, log_query_delay);
+    while let Some(log) = logs.next().await {
+        writer.write_all(&[TAG_LOG])?;
+        write_log(&mut writer, &log?)?;
+        num_logs += 1;
+        if num_logs % PROGRESS_LOG_INTERVAL == 0 {
+            info!(num_logs, "Exporting log archive");
+        }
+    }
+
+    writer.write_all(&[TAG_END])?;
+    writer.write_all(&end_block.to_be_bytes())?;
+    writer.write_all(end_block_hash.as_bytes())?;
+    writer.write_all(&num_logs.to_be_bytes())?;
+    let checksum = writer.hasher.clone().finalize();
+    writer.inner.write_all(&checksum.to_be_bytes())?;
+    writer.inner.flush()?;
+
+    info!(num_logs, "Log archive exported");
+    Ok(ArchiveInfo {
+        contract_address,
+        start_block,
+        end_block,
+        end_block_hash,
+        num_logs,
+    })
+}
+
+/// Log archive file, which is verified once opened and read on demand.
+pub struct LogArchive {
+    path: PathBuf,
+    info: ArchiveInfo,
+    /// `(block number, offset)` of every [`INDEX_INTERVAL`] logs.
+    index: Vec<(u64, u64)>,
+}
+
+impl LogArchive {
+    /// Opens the archive file `path` and verifies its checksum.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path).map_err(|e| anyhow!("failed to open {:?}: {:?}", path, e))?;
+        let (info, index) = verify(BufReader::new(file))?;
+        Ok(Self { path, info, index })
+    }
+
+    pub fn info(&self) -> &ArchiveInfo {
+        &self.info
+    }
+
+    /// Returns the `Submit` events in blocks `[from_block, to_block]` in order, which are read
+    /// from the indexed offset before `from_block` on the blocking thread pool.
+    fn logs(&self, from_block: u64, to_block: u64) -> BoxStream<'static, Result<SubmitLog>> {
+        // the logs of `from_block` are all after an index entry of a lower block
+        let offset = match self.index.partition_point(|(block, _)| *block < from_block) {
+            0 => HEADER_SIZE,
+            i => self.index[i - 1].1,
+        };
+        let path = self.path.clone();
+        let open = move || -> Result<ArchiveLogs<BufReader<File>>> {
+            let mut file =
+                File::open(&path).map_err(|e| anyhow!("failed to open {:?}: {:?}", path, e))?;
+            file.seek(SeekFrom::Start(offset))?;
+            Ok(ArchiveLogs::new(BufReader::new(file), from_block, to_block))
+        };
+
+        stream::try_unfold(None, move |logs| {
+            let open = open.clone();
+            async move {
+                let (logs, page) = tokio::task::spawn_blocking(move || -> Result<_> {
+                    let mut logs = match logs {
+                        Some(logs) => logs,
+                        None => open()?,
+                    };
+                    let page: Vec<_> = logs.by_ref().take(READ_PAGE_SIZE).collect::<Result<_>>()?;
+                    Ok((logs, page))
+                })
+                .await??;
+
+                let next = (!page.is_empty()).then(|| (stream::iter(page).map(Ok), Some(logs)));
+                Ok::<_, anyhow::Error>(next)
+            }
+        })
+        .try_flatten()
+        .boxed()
+    }
+}
+
+/// Reads the whole archive and returns its info along with the index if the archive is intact.
+fn verify(reader: impl Read) -> Result<(ArchiveInfo, Vec<(u64, u64)>)> {
+    let mut reader = ChecksumReader::new(reader);
+    let (contract_address, start_block) = read_header(&mut reader)?;
+
+    let mut num_logs = 0;
+    let mut index = vec![];
+    loop {
+        let offset = reader.offset;
+        match read_record(&mut reader)? {
+            Some(log) => {
+                if num_logs > 0 && num_logs % INDEX_INTERVAL == 0 {
+                    index.push((log.block_number, offset));
+                }
+                num_logs += 1;
+            }
+            None => break,
+        }
+    }
+
+    let end_block = read_u64(&mut reader)?;
+    let mut end_block_hash = H256::zero();
+    reader.read_exact(end_block_hash.as_bytes_mut())?;
+    let expected_logs = read_u64(&mut reader)?;
+    let checksum = reader.hasher.clone().finalize();
+    let mut expected_checksum = [0u8; 4];
+    reader.inner.read_exact(&mut expected_checksum)?;
+    if expected_logs != num_logs || u32::from_be_bytes(expected_checksum) != checksum {
+        bail!("log archive is corrupted");
+    }
+
+    let info = ArchiveInfo {
+        contract_address,
+        start_block,
+        end_block,
+        end_block_hash,
+        num_logs,
+    };
+    Ok((info, index))
+}
+
+/// Iterator of the archived `Submit` events in a range of blocks.
+struct ArchiveLogs<R> {
+    reader: R,
+    from_block: u64,
+    to_block: u64,
+    done: bool,
+}
+
+impl<R: Read> ArchiveLogs<R> {
+    /// Creates the iterator from a reader at the start of a record.
+    fn new(reader: R, from_block: u64, to_block: u64) -> Self {
+        Self {
+            reader,
+            from_block,
+            to_block,
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Iterator for ArchiveLogs<R> {
+    type Item = Result<SubmitLog>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            match read_record(&mut self.reader) {
+                Ok(Some(log)) if log.block_number < self.from_block => continue,
+                Ok(Some(log)) if log.block_number <= self.to_block => return Some(Ok(log)),
+                Ok(_) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Log source that replays the `Submit` events in a log archive, and switches to the inner
+/// source for the blocks after the archive.
+pub struct ArchivedLogSource {
+    archive: LogArchive,
+    inner: Arc<dyn LogSource>,
+}
+
+impl ArchivedLogSource {
+    /// Creates the log source if the last block of the archive is on the chain of `inner`, so
+    /// that the archived events are continued by the events of `inner` at the handover.
+    pub async fn new(archive: LogArchive, inner: Arc<dyn LogSource>) -> Result<Self> {
+        let info = *archive.info();
+        match inner.get_block(info.end_block.into()).await? {
+            Some(block) if block.hash == info.end_block_hash => {}
+            Some(block) => bail!(
+                "log archive is not on chain, block={} archive={:?} chain={:?}",
+                info.end_block,
+                info.end_block_hash,
+                block.hash
+            ),
+            None => bail!("log archive is ahead of chain, block={}", info.end_block),
+        }
+
+        Ok(Self { archive, inner })
+    }
+}
+
+#[async_trait]
+impl LogSource for ArchivedLogSource {
+    async fn get_block_number(&self) -> Result<u64> {
+        self.inner.get_block_number().await
+    }
+
+    async fn get_block(&self, block: BlockNumber) -> Result<Option<BlockInfo>> {
+        self.inner.get_block(block).await
+    }
+
+    async fn has_receipt(&self, tx_hash: H256) -> Result<bool> {
+        self.inner.has_receipt(tx_hash).await
+    }
+
+    fn submit_logs(
+        &self,
+        from_block: u64,
+        to_block: u64,
+        delay: Duration,
+    ) -> BoxStream<'_, Result<SubmitLog>> {
+        let info = self.archive.info();
+        if from_block < info.start_block || from_block > info.end_block {
+            return self.inner.submit_logs(from_block, to_block, delay);
+        }
+
+        let archived = self.archive.logs(from_block, to_block.min(info.end_block));
+        if to_block > info.end_block {
+            archived
+                .chain(self.inner.submit_logs(info.end_block + 1, to_block, delay))
+                .boxed()
+        } else {
+            archived
+        }
+    }
+
+    async fn get_flow_root_by_tx_seq(&self, tx_seq: u64) -> Result<H256> {
+        self.inner.get_flow_root_by_tx_seq(tx_seq).await
+    }
+
+    async fn wait_for_new_block(&self, interval: Duration) {
+        self.inner.wait_for_new_block(interval).await
+    }
+}
+
+fn read_header(reader: &mut impl Read) -> Result<(ContractAddress, u64)> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        bail!("not a log archive");
+    }
+    let version = read_u32(reader)?;
+    if version != VERSION {
+        bail!("unsupported log archive version {}", version);
+    }
+    let mut contract_address = ContractAddress::zero();
+    reader.read_exact(contract_address.as_bytes_mut())?;
+    let start_block = read_u64(reader)?;
+    Ok((contract_address, start_block))
+}
+
+/// Reads the next log, or returns `None` if the trailer is reached.
+fn read_record(reader: &mut impl Read) -> Result<Option<SubmitLog>> {
+    let mut tag = [0u8; 1];
+    reader.read_exact(&mut tag)?;
+    match tag[0] {
+        TAG_LOG => Ok(Some(read_log(reader)?)),
+        TAG_END => Ok(None),
+        t => bail!("invalid record tag {}", t),
+    }
+}
+
+fn write_log(writer: &mut impl Write, log: &SubmitLog) -> Result<()> {
+    writer.write_all(&log.block_number.to_be_bytes())?;
+    writer.write_all(log.block_hash.as_bytes())?;
+    writer.write_all(log.tx_hash.as_bytes())?;
+    writer.write_all(&log.tx_index.to_be_bytes())?;
+
+    let event = &log.event;
+    writer.write_all(event.sender.as_bytes())?;
+    writer.write_all(&event.identity)?;
+    for value in [
+        event.submission_index,
+        event.start_pos,
+        event.length,
+        event.submission.length,
+    ] {
+        write_u256(writer, value)?;
+    }
+    writer.write_all(&(event.submission.tags.len() as u32).to_be_bytes())?;
+    writer.write_all(&event.submission.tags)?;
+    writer.write_all(&(event.submission.nodes.len() as u32).to_be_bytes())?;
+    for node in &event.submission.nodes {
+        writer.write_all(&node.root)?;
+        write_u256(writer, node.height)?;
+    }
+    Ok(())
+}
+
+fn read_log(reader: &mut impl Read) -> Result<SubmitLog> {
+    let block_number = read_u64(reader)?;
+    let block_hash = read_h256(reader)?;
+    let tx_hash = read_h256(reader)?;
+    let tx_index = read_u64(reader)?;
+
+    let mut event = SubmitFilter::default();
+    reader.read_exact(event.sender.as_bytes_mut())?;
+    reader.read_exact(&mut event.identity)?;
+    event.submission_index = read_u256(reader)?;
+    event.start_pos = read_u256(reader)?;
+    event.length = read_u256(reader)?;
+
+    let length = read_u256(reader)?;
+    // not to allocate by the length before reading, which may be corrupted
+    let tags_len = read_u32(reader)? as usize;
+    let mut tags = vec![];
+    if reader
+        .by_ref()
+        .take(tags_len as u64)
+        .read_to_end(&mut tags)?
+        != tags_len
+    {
+        bail!("unexpected end of log archive");
+    }
+    let num_nodes = read_u32(reader)?;
+    let nodes = (0..num_nodes)
+        .map(|_| {
+            let mut root = [0u8; 32];
+            reader.read_exact(&mut root)?;
+            Ok(SubmissionNode {
+                root,
+                height: read_u256(reader)?,
+            })
+        })
+        .collect::<Result<_>>()?;
+    event.submission = Submission {
+        length,
+        tags: Bytes::from(tags),
+        nodes,
+    };
+
+    Ok(SubmitLog {
+        block_number,
+        block_hash,
+        tx_hash,
+        tx_index,
+        event,
+    })
+}
+
+fn write_u256(writer: &mut impl Write, value: U256) -> Result<()> {
+    let mut buf = [0u8; 32];
+    value.to_big_endian(&mut buf);
+    writer.write_all(&buf)?;
+    Ok(())
+}
+
+fn read_u256(reader: &mut impl Read) -> Result<U256> {
+    let mut buf = [0u8; 32];
+    reader.read_exact(&mut buf)?;
+    Ok(U256::from_big_endian(&buf))
+}
+
+fn read_h256(reader: &mut impl Read) -> Result<H256> {
+    let mut hash = H256::zero();
+    reader.read_exact(hash.as_bytes_mut())?;
+    Ok(hash)
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+struct ChecksumWriter<W> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct ChecksumReader<R> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+    /// The number of bytes read.
+    offset: u64,
+}
+
+impl<R: Read> ChecksumReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+            offset: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Source with blocks `[0, latest]`, and a `Submit` log in every even block.
+    struct EvenLogSource {
+        latest: u64,
+        /// Distinguishes the block hashes of different chains.
+        fork: u64,
+    }
+
+    impl EvenLogSource {
+        fn block_hash(&self, number: u64) -> H256 {
+            H256::from_low_u64_be((self.fork << 32) + number)
+        }
+    }
+
+    #[async_trait]
+    impl LogSource for EvenLogSource {
+        async fn get_block_number(&self) -> Result<u64> {
+            Ok(self.latest)
+        }
+
+        async fn get_block(&self, block: BlockNumber) -> Result<Option<BlockInfo>> {
+            let number = match block {
+                BlockNumber::Number(number) => number.as_u64(),
+                _ => self.latest,
+            };
+            Ok((number <= self.latest).then(|| BlockInfo {
+                number,
+                hash: self.block_hash(number),
+                parent_hash: self.block_hash(number.saturating_sub(1)),
+                tx_hashes: vec![],
+            }))
+        }
+
+        async fn has_receipt(&self, _tx_hash: H256) -> Result<bool> {
+            Ok(true)
+        }
+
+        fn submit_logs(
+            &self,
+            from_block: u64,
+            to_block: u64,
+            _delay: Duration,
+        ) -> BoxStream<'_, Result<SubmitLog>> {
+            let logs: Vec<_> = (from_block..=to_block.min(self.latest))
+                .filter(|number| number % 2 == 0)
+                .map(|number| {
+                    Ok(SubmitLog {
+                        block_number: number,
+                        block_hash: self.block_hash(number),
+                        tx_hash: H256::from_low_u64_be(number),
+                        tx_index: 1,
+                        event: SubmitFilter {
+                            submission_index: (number / 2).into(),
+                            submission: Submission {
+                                length: 256.into(),
+                                tags: Bytes::from(vec![number as u8]),
+                                nodes: vec![SubmissionNode {
+                                    root: [number as u8; 32],
+                                    height: 0.into(),
+                                }],
+                            },
+                            ..Default::default()
+                        },
+                    })
+                })
+                .collect();
+            stream::iter(logs).boxed()
+        }
+
+        async fn get_flow_root_by_tx_seq(&self, _tx_seq: u64) -> Result<H256> {
+            Ok(H256::zero())
+        }
+    }
+
+    async fn export_to_file(source: &dyn LogSource, end_block: u64) -> tempfile::NamedTempFile {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        export(
+            source,
+            ContractAddress::repeat_byte(1),
+            2,
+            end_block,
+            Duration::ZERO,
+            file.as_file(),
+        )
+        .await
+        .unwrap();
+        file
+    }
+
+    #[tokio::test]
+    async fn test_archive() {
+        let chain = Arc::new(EvenLogSource {
+            latest: 20,
+            fork: 0,
+        });
+        let file = export_to_file(chain.as_ref(), 10).await;
+
+        let archive = LogArchive::open(file.path()).unwrap();
+        assert_eq!(
+            *archive.info(),
+            ArchiveInfo {
+                contract_address: ContractAddress::repeat_byte(1),
+                start_block: 2,
+                end_block: 10,
+                end_block_hash: chain.block_hash(10),
+                num_logs: 5,
+            }
+        );
+
+        // the archive is not on another chain
+        let fork = Arc::new(EvenLogSource {
+            latest: 20,
+            fork: 1,
+        });
+        assert!(
+            ArchivedLogSource::new(LogArchive::open(file.path()).unwrap(), fork)
+                .await
+                .is_err()
+        );
+
+        let source = ArchivedLogSource::new(archive, chain.clone())
+            .await
+            .unwrap();
+        for (from, to) in [(2, 20), (3, 9), (10, 14), (0, 6)] {
+            let expected: Vec<_> = chain
+                .submit_logs(from, to, Duration::ZERO)
+                .map(|log| log.unwrap())
+                .collect()
+                .await;
+            let logs: Vec<_> = source
+                .submit_logs(from, to, Duration::ZERO)
+                .map(|log| log.unwrap())
+                .collect()
+                .await;
+            assert_eq!(logs, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_archive_index() {
+        let chain = EvenLogSource {
+            latest: 5000,
+            fork: 0,
+        };
+        let file = export_to_file(&chain, 5000).await;
+        let archive = LogArchive::open(file.path()).unwrap();
+        assert_eq!(archive.info().num_logs, 2500);
+        assert_eq!(
+            archive
+                .index
+                .iter()
+                .map(|(block, _)| *block)
+                .collect::<Vec<_>>(),
+            vec![2002, 4002]
+        );
+
+        for (from, to) in [
+            (0, 5000),
+            (2001, 2003),
+            (2002, 2002),
+            (2003, 4500),
+            (4002, 5000),
+        ] {
+            let expected: Vec<_> = chain
+                .submit_logs(from.max(2), to, Duration::ZERO)
+                .map(|log| log.unwrap())
+                .collect()
+                .await;
+            let logs: Vec<_> = archive
+                .logs(from, to)
+                .map(|log| log.unwrap())
+                .collect()
+                .await;
+            assert_eq!(logs, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_archive() {
+        let chain = EvenLogSource {
+            latest: 20,
+            fork: 0,
+        };
+        let file = export_to_file(&chain, 20).await;
+        let mut data = std::fs::read(file.path()).unwrap();
+        let len = data.len();
+        data[len / 2] ^= 1;
+        std::fs::write(file.path(), &data).unwrap();
+
+        assert!(LogArchive::open(file.path()).is_err());
+    }
+}
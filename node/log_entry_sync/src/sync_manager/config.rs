@@ -1,9 +1,12 @@
 use std::time::Duration;
 
+use ethers::types::BlockNumber;
+
 use crate::ContractAddress;
 
 pub struct LogSyncConfig {
-    pub rpc_endpoint_url: String,
+    /// Blockchain RPC endpoints in the order of preference.
+    pub rpc_endpoint_urls: Vec<String>,
     pub contract_address: ContractAddress,
     pub cache_config: CacheConfig,
 
@@ -12,8 +15,12 @@ pub struct LogSyncConfig {
     pub start_block_number: u64,
     /// The number of blocks needed for confirmation on the blockchain.
     /// This is used to rollback to a stable height if reorg happens during node restart.
-    /// TODO(zz): Some blockchains have better confirmation/finalization mechanisms.
+    /// It is ignored if `finality_tag` is set.
     pub confirmation_block_count: u64,
+    /// Block tag of the chain's finality, i.e. `finalized` or `safe`. If set, logs are synced
+    /// up to the latest block, and the txs in the blocks at or below the tagged block are safe
+    /// from chain reorg, instead of guessing the finality by block counts.
+    pub finality_tag: Option<BlockNumber>,
     /// Maximum number of event logs to poll at a time.
     pub log_page_size: u64,
 
@@ -28,7 +35,8 @@ pub struct LogSyncConfig {
     // This is set to avoid triggering the throttling mechanism in the RPC server.
     pub recover_query_delay: u64,
 
-    // the counter assumed the finalized block behind the latest block
+    // the counter assumed the finalized block behind the latest block, if the finalized block
+    // is unavailable and `finality_tag` is not set
     pub default_finalized_block_count: u64,
     // remove finalized block trigger interval
     pub remove_finalized_block_interval_minutes: u64,
@@ -39,6 +47,19 @@ pub struct LogSyncConfig {
 
     // the timeout for blockchain rpc connection
     pub blockchain_rpc_timeout: Duration,
+
+    /// WebSocket URL or IPC path to subscribe to new heads, so that logs are queried once a
+    /// block is mined instead of polling `rpc_endpoint_urls` periodically. It's wake-up only:
+    /// `Submit` logs are not subscribed, and each wake-up still calls `eth_getLogs`.
+    pub subscription_endpoint: Option<String>,
+    /// The number of endpoints that must return the same blocks and `Submit` logs before they
+    /// are accepted. Endpoints are queried in turn for failover if it is no more than 1.
+    pub quorum: usize,
+    /// Log archive to replay the `Submit` events from, before syncing from the blockchain.
+    pub archive_path: Option<String>,
+
+    /// Whether to sync logs from an in-process mock Flow contract instead of the blockchain.
+    pub dev_mode: bool,
 }
 
 #[derive(Clone)]
@@ -52,7 +73,7 @@ pub struct CacheConfig {
 impl LogSyncConfig {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
-        rpc_endpoint_url: String,
+        rpc_endpoint_urls: Vec<String>,
         contract_address: ContractAddress,
         start_block_number: u64,
         confirmation_block_count: u64,
@@ -69,11 +90,12 @@ impl LogSyncConfig {
         blockchain_rpc_timeout: Duration,
     ) -> Self {
         Self {
-            rpc_endpoint_url,
+            rpc_endpoint_urls,
             contract_address,
             cache_config,
             start_block_number,
             confirmation_block_count,
+            finality_tag: None,
             log_page_size,
             rate_limit_retries,
             timeout_retries,
@@ -84,6 +106,10 @@ impl LogSyncConfig {
             watch_loop_wait_time_ms,
             force_log_sync_from_start_block_number,
             blockchain_rpc_timeout,
+            subscription_endpoint: None,
+            archive_path: None,
+            quorum: 1,
+            dev_mode: false,
         }
     }
 }
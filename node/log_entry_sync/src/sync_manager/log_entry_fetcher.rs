@@ -1,13 +1,11 @@
-use crate::sync_manager::log_query::LogQuery;
+use crate::sync_manager::log_source::{BlockInfo, LogSource, SubmitLog};
 use crate::sync_manager::{metrics, RETRY_WAIT_MS};
-use crate::{ContractAddress, LogSyncConfig};
+use crate::LogSyncConfig;
 use anyhow::{anyhow, bail, Result};
 use append_merkle::{Algorithm, Sha3Algorithm};
-use contract_interface::{SubmissionNode, SubmitFilter, ZgsFlow};
-use ethers::abi::RawLog;
-use ethers::prelude::{BlockNumber, EthLogDecode, Http, Middleware, Provider};
-use ethers::providers::{HttpRateLimitRetryPolicy, RetryClient, RetryClientBuilder};
-use ethers::types::{Block, Log, H256};
+use contract_interface::{SubmissionNode, SubmitFilter};
+use ethers::prelude::BlockNumber;
+use ethers::types::H256;
 use futures::StreamExt;
 use jsonrpsee::tracing::{debug, error, info, warn};
 use shared_types::{DataRoot, Transaction};
@@ -21,39 +19,33 @@ use tokio::sync::{
     RwLock,
 };
 
+/// Rule to confirm blocks before syncing their logs.
+#[derive(Clone, Copy, Debug)]
+enum Confirmation {
+    /// Blocks are confirmed once the specified number of blocks are built on top of them.
+    Depth(u64),
+    /// Blocks are synced up to the latest block, and confirmed once they are at or below the
+    /// block of the tag, e.g. `finalized`.
+    Tag(BlockNumber),
+}
+
 pub struct LogEntryFetcher {
-    contract_address: ContractAddress,
-    log_page_size: u64,
-    provider: Arc<Provider<RetryClient<Http>>>,
+    source: Arc<dyn LogSource>,
 
-    confirmation_delay: u64,
+    confirmation: Confirmation,
 }
 
 impl LogEntryFetcher {
-    pub async fn new(config: &LogSyncConfig) -> Result<Self> {
-        let provider = Arc::new(Provider::new(
-            RetryClientBuilder::default()
-                .rate_limit_retries(config.rate_limit_retries)
-                .timeout_retries(config.timeout_retries)
-                .initial_backoff(Duration::from_millis(config.initial_backoff))
-                .build(
-                    Http::new_with_client(
-                        url::Url::parse(&config.rpc_endpoint_url)?,
-                        reqwest::Client::builder()
-                            .timeout(config.blockchain_rpc_timeout)
-                            .connect_timeout(config.blockchain_rpc_timeout)
-                            .build()?,
-                    ),
-                    Box::new(HttpRateLimitRetryPolicy),
-                ),
-        ));
-        // TODO: `error` types are removed from the ABI json file.
-        Ok(Self {
-            contract_address: config.contract_address,
-            provider,
-            log_page_size: config.log_page_size,
-            confirmation_delay: config.confirmation_block_count,
-        })
+    pub fn new(source: Arc<dyn LogSource>, config: &LogSyncConfig) -> Self {
+        let confirmation = match config.finality_tag {
+            Some(tag) => Confirmation::Tag(tag),
+            None => Confirmation::Depth(config.confirmation_block_count),
+        };
+
+        Self {
+            source,
+            confirmation,
+        }
     }
 
     pub fn handle_reorg(
@@ -64,7 +56,7 @@ impl LogEntryFetcher {
         block_hash_cache: Arc<RwLock<BTreeMap<u64, Option<BlockHashAndSubmissionIndex>>>>,
     ) -> UnboundedReceiver<LogFetchProgress> {
         let (reorg_tx, reorg_rx) = tokio::sync::mpsc::unbounded_channel();
-        let provider = self.provider.clone();
+        let source = self.source.clone();
 
         executor.spawn(
             async move {
@@ -77,9 +69,9 @@ impl LogEntryFetcher {
                 );
 
                 loop {
-                    match provider.get_block(block_number).await {
+                    match source.get_block(block_number.into()).await {
                         Ok(Some(b)) => {
-                            if b.hash == Some(block_hash) {
+                            if b.hash == block_hash {
                                 break;
                             } else {
                                 warn!(
@@ -93,7 +85,7 @@ impl LogEntryFetcher {
                                     block_number,
                                     &reorg_tx,
                                     &block_hash_cache,
-                                    provider.as_ref(),
+                                    source.as_ref(),
                                 )
                                 .await
                                 {
@@ -124,10 +116,11 @@ impl LogEntryFetcher {
         executor: &TaskExecutor,
         store: Arc<dyn Store>,
         block_hash_cache: Arc<RwLock<BTreeMap<u64, Option<BlockHashAndSubmissionIndex>>>>,
-        default_finalized_block_count: u64,
+        finality_tag: BlockNumber,
+        default_finalized_block_count: Option<u64>,
         remove_finalized_block_interval_minutes: u64,
     ) {
-        let provider = self.provider.clone();
+        let source = self.source.clone();
         executor.spawn(
             async move {
                 loop {
@@ -152,26 +145,20 @@ impl LogEntryFetcher {
                     };
 
                     if let Some(processed_block_number) = processed_block_number {
-                        let finalized_block_number =
-                            match provider.get_block(BlockNumber::Finalized).await {
-                                Ok(block) => match block {
-                                    Some(b) => match b.number {
-                                        Some(f) => Some(f.as_u64()),
-                                        None => {
-                                            error!("block number is none for finalized block");
-                                            None
-                                        }
-                                    },
-                                    None => {
-                                        error!("finalized block is none");
-                                        None
-                                    }
-                                },
-                                Err(e) => {
-                                    error!("get finalized block number: e={:?}", e);
-                                    Some(processed_block_number - default_finalized_block_count)
+                        let finalized_block_number = match source.get_block(finality_tag).await {
+                            Ok(block) => match block {
+                                Some(b) => Some(b.number),
+                                None => {
+                                    error!("finalized block is none");
+                                    None
                                 }
-                            };
+                            },
+                            Err(e) => {
+                                error!("get finalized block number: e={:?}", e);
+                                default_finalized_block_count
+                                    .map(|count| processed_block_number.saturating_sub(count))
+                            }
+                        };
 
                         if let Some(finalized_block_number) = finalized_block_number {
                             let safe_block_number = std::cmp::min(
@@ -217,85 +204,57 @@ impl LogEntryFetcher {
         executor: &TaskExecutor,
         log_query_delay: Duration,
     ) -> UnboundedReceiver<LogFetchProgress> {
-        let provider = self.provider.clone();
+        let source = self.source.clone();
         let (recover_tx, recover_rx) = tokio::sync::mpsc::unbounded_channel();
-        let contract = self.flow_contract();
-        let log_page_size = self.log_page_size;
 
         executor.spawn(
             async move {
                 let mut progress = start_block_number;
-                let mut filter = contract
-                    .submit_filter()
-                    .from_block(progress)
-                    .to_block(end_block_number)
-                    .address(contract.address().into())
-                    .filter;
-                let mut stream = LogQuery::new(&provider, &filter, log_query_delay)
-                    .with_page_size(log_page_size);
+                let mut stream = source.submit_logs(progress, end_block_number, log_query_delay);
                 info!(
                     "start_recover starts, start={} end={}",
                     start_block_number, end_block_number
                 );
-                let (mut block_hash_sent, mut block_number_sent) = (None, None);
+                let mut block_sent = None;
                 while let Some(maybe_log) = stream.next().await {
                     let start_time = Instant::now();
                     match maybe_log {
                         Ok(log) => {
-                            let sync_progress =
-                                if log.block_hash.is_some() && log.block_number.is_some() {
-                                    if block_hash_sent != log.block_hash
-                                        || block_number_sent != log.block_number
-                                    {
-                                        let synced_block = LogFetchProgress::SyncedBlock((
-                                            log.block_number.unwrap().as_u64(),
-                                            log.block_hash.unwrap(),
-                                            None,
-                                        ));
-                                        progress = log.block_number.unwrap().as_u64();
-                                        Some(synced_block)
-                                    } else {
-                                        None
-                                    }
-                                } else {
-                                    None
-                                };
+                            let block = (log.block_number, log.block_hash);
+                            let sync_progress = if block_sent != Some(block) {
+                                progress = log.block_number;
+                                Some(LogFetchProgress::SyncedBlock((
+                                    log.block_number,
+                                    log.block_hash,
+                                    None,
+                                )))
+                            } else {
+                                None
+                            };
                             debug!("recover: progress={:?}", sync_progress);
 
-                            match SubmitFilter::decode_log(&RawLog {
-                                topics: log.topics,
-                                data: log.data.to_vec(),
-                            }) {
-                                Ok(event) => {
-                                    if let Err(e) = recover_tx
-                                        .send(submission_event_to_transaction(
-                                            event,
-                                            log.block_number.expect("block number exist").as_u64(),
-                                        ))
-                                        .and_then(|_| match sync_progress {
-                                            Some(b) => {
-                                                recover_tx.send(b)?;
-                                                block_hash_sent = log.block_hash;
-                                                block_number_sent = log.block_number;
-                                                Ok(())
-                                            }
-                                            None => Ok(()),
-                                        })
-                                    {
-                                        error!("send error: e={:?}", e);
-                                        break;
+                            if let Err(e) = recover_tx
+                                .send(submission_event_to_transaction(log.event, log.block_number))
+                                .and_then(|_| match sync_progress {
+                                    Some(b) => {
+                                        recover_tx.send(b)?;
+                                        block_sent = Some(block);
+                                        Ok(())
                                     }
-                                }
-                                Err(e) => {
-                                    error!("log decode error: e={:?}", e);
-                                }
+                                    None => Ok(()),
+                                })
+                            {
+                                error!("send error: e={:?}", e);
+                                break;
                             }
                         }
+                        Err(e) if SubmitLog::is_invalid(&e) => {
+                            warn!("skip invalid log: e={:?}", e);
+                        }
                         Err(e) => {
                             error!("log query error: e={:?}", e);
-                            filter = filter.from_block(progress).address(contract.address());
-                            stream = LogQuery::new(&provider, &filter, log_query_delay)
-                                .with_page_size(log_page_size);
+                            stream =
+                                source.submit_logs(progress, end_block_number, log_query_delay);
                             tokio::time::sleep(Duration::from_millis(RETRY_WAIT_MS)).await;
                         }
                     }
@@ -319,10 +278,8 @@ impl LogEntryFetcher {
         mut watch_progress_rx: UnboundedReceiver<u64>,
     ) -> UnboundedReceiver<LogFetchProgress> {
         let (watch_tx, watch_rx) = tokio::sync::mpsc::unbounded_channel();
-        let contract = self.flow_contract();
-        let provider = self.provider.clone();
-        let confirmation_delay = self.confirmation_delay;
-        let log_page_size = self.log_page_size;
+        let source = self.source.clone();
+        let confirmation = self.confirmation;
         let mut progress_reset_history = BTreeMap::new();
         executor.spawn(
             async move {
@@ -338,19 +295,17 @@ impl LogEntryFetcher {
                         &mut progress_reset_history,
                         watch_loop_wait_time_ms,
                         &block_hash_cache,
-                        provider.as_ref(),
+                        source.as_ref(),
                     )
                     .await;
 
                     match Self::watch_loop(
-                        provider.as_ref(),
+                        source.as_ref(),
                         progress,
                         parent_block_hash,
                         &watch_tx,
-                        confirmation_delay,
-                        &contract,
+                        confirmation,
                         &block_hash_cache,
-                        log_page_size,
                     )
                     .await
                     {
@@ -369,7 +324,9 @@ impl LogEntryFetcher {
                             )
                         }
                     }
-                    tokio::time::sleep(Duration::from_millis(watch_loop_wait_time_ms)).await;
+                    source
+                        .wait_for_new_block(Duration::from_millis(watch_loop_wait_time_ms))
+                        .await;
                 }
             },
             "log watch",
@@ -377,43 +334,56 @@ impl LogEntryFetcher {
         watch_rx
     }
 
-    #[allow(clippy::too_many_arguments)]
     async fn watch_loop(
-        provider: &Provider<RetryClient<Http>>,
+        source: &dyn LogSource,
         from_block_number: u64,
         parent_block_hash: H256,
         watch_tx: &UnboundedSender<LogFetchProgress>,
-        confirmation_delay: u64,
-        contract: &ZgsFlow<Provider<RetryClient<Http>>>,
+        confirmation: Confirmation,
         block_hash_cache: &Arc<RwLock<BTreeMap<u64, Option<BlockHashAndSubmissionIndex>>>>,
-        log_page_size: u64,
     ) -> Result<Option<(u64, H256, Option<Option<u64>>)>> {
-        let latest_block_number = provider.get_block_number().await?.as_u64();
+        let latest_block_number = source.get_block_number().await?;
+        let to_block_number = match confirmation {
+            Confirmation::Depth(confirmation_delay) => {
+                latest_block_number.saturating_sub(confirmation_delay)
+            }
+            Confirmation::Tag(tag) => {
+                let finalized_block_number = source
+                    .get_block(tag)
+                    .await?
+                    .ok_or_else(|| anyhow!("None for block {}", tag))?
+                    .number;
+                // the blocks before `from_block_number` are synced already, and those at or
+                // below the finalized block will never be reverted
+                if let Some(synced_block_number) = from_block_number.checked_sub(1) {
+                    watch_tx.send(LogFetchProgress::Finalized(std::cmp::min(
+                        finalized_block_number,
+                        synced_block_number,
+                    )))?;
+                }
+                latest_block_number
+            }
+        };
         debug!(
-            "from block number {}, latest block number {}, confirmation delay {}",
-            from_block_number, latest_block_number, confirmation_delay
+            "from block number {}, latest block number {}, confirmation {:?}",
+            from_block_number, latest_block_number, confirmation
         );
-        let to_block_number = latest_block_number.saturating_sub(confirmation_delay);
         if from_block_number > to_block_number {
             return Ok(None);
         }
 
-        let block = provider
-            .get_block_with_txs(from_block_number)
+        let block = source
+            .get_block(from_block_number.into())
             .await?
             .ok_or_else(|| anyhow!("None for block {}", from_block_number))?;
-        if Some(from_block_number.into()) != block.number {
+        if from_block_number != block.number {
             bail!(
-                "block number mismatch, expected {}, actual {:?}",
+                "block number mismatch, expected {}, actual {}",
                 from_block_number,
                 block.number
             );
         }
 
-        if block.logs_bloom.is_none() {
-            bail!("block {:?} logs bloom is none", block.number);
-        }
-
         if from_block_number > 0 && block.parent_hash != parent_block_hash {
             // reorg happened
             let (parent_block_number, block_hash) = revert_one_block(
@@ -421,28 +391,28 @@ impl LogEntryFetcher {
                 from_block_number.saturating_sub(1),
                 watch_tx,
                 block_hash_cache,
-                provider,
+                source,
             )
             .await?;
             return Ok(Some((parent_block_number, block_hash, None)));
         }
 
-        let mut blocks: HashMap<u64, Block<ethers::types::Transaction>> = Default::default();
+        let mut blocks: HashMap<u64, BlockInfo> = Default::default();
         let mut parent_block_hash = block.hash;
         blocks.insert(from_block_number, block);
         for block_number in from_block_number + 1..to_block_number + 1 {
-            let block = provider
-                .get_block_with_txs(block_number)
+            let block = source
+                .get_block(block_number.into())
                 .await?
                 .ok_or_else(|| anyhow!("None for block {}", block_number))?;
-            if Some(block_number.into()) != block.number {
+            if block_number != block.number {
                 bail!(
-                    "block number mismatch, expected {}, actual {:?}",
+                    "block number mismatch, expected {}, actual {}",
                     block_number,
                     block.number
                 );
             }
-            if parent_block_hash.is_none() || Some(block.parent_hash) != parent_block_hash {
+            if block.parent_hash != parent_block_hash {
                 bail!(
                     "parent block hash mismatch, expected {:?}, actual {}",
                     parent_block_hash,
@@ -450,44 +420,27 @@ impl LogEntryFetcher {
                 );
             }
 
-            if block_number == to_block_number && block.hash.is_none() {
-                bail!("block {:?} hash is none", block.number);
-            }
-
-            if block.logs_bloom.is_none() {
-                bail!("block {:?} logs bloom is none", block.number);
-            }
-
             parent_block_hash = block.hash;
             blocks.insert(block_number, block);
         }
 
-        let filter = contract
-            .submit_filter()
-            .from_block(from_block_number)
-            .to_block(to_block_number)
-            .address(contract.address().into())
-            .filter;
-        let mut stream = LogQuery::new(provider, &filter, Duration::from_millis(10))
-            .with_page_size(log_page_size);
-        let mut block_logs: BTreeMap<u64, Vec<Log>> = BTreeMap::new();
+        let mut stream = source.submit_logs(
+            from_block_number,
+            to_block_number,
+            Duration::from_millis(10),
+        );
+        let mut block_logs: BTreeMap<u64, Vec<SubmitLog>> = BTreeMap::new();
         while let Some(maybe_log) = stream.next().await {
-            let log = maybe_log?;
-            let block_number = log
-                .block_number
-                .ok_or_else(|| anyhow!("block number missing"))?
-                .as_u64();
-            block_logs.entry(block_number).or_default().push(log);
+            match maybe_log {
+                Ok(log) => block_logs.entry(log.block_number).or_default().push(log),
+                Err(e) if SubmitLog::is_invalid(&e) => warn!("skip invalid log: e={:?}", e),
+                Err(e) => return Err(e),
+            }
         }
 
         let mut progress = None;
         for block_number in from_block_number..to_block_number + 1 {
             if let Some(block) = blocks.remove(&block_number) {
-                let txs_hm = block
-                    .transactions
-                    .iter()
-                    .map(|tx| (tx.transaction_index, tx))
-                    .collect::<HashMap<_, _>>();
                 let mut log_events = vec![];
                 let mut first_submission_index = None;
 
@@ -500,70 +453,30 @@ impl LogEntryFetcher {
                             );
                             return Ok(progress);
                         }
-                        if log.block_number != block.number {
-                            warn!(
-                                "log block num mismatch, log block number {:?}, block number {:?}",
-                                log.block_number, block.number
-                            );
-                            return Ok(progress);
-                        }
 
-                        let tx = txs_hm[&log.transaction_index];
-                        if log.transaction_hash.is_none() || log.transaction_hash != Some(tx.hash) {
+                        let tx_hash = block.tx_hashes.get(log.tx_index as usize);
+                        if tx_hash != Some(&log.tx_hash) {
                             warn!(
-                            "log tx hash mismatch, log transaction {:?}, block transaction {:?}",
-                            log.transaction_hash,
-                            tx.hash
-                        );
-                            return Ok(progress);
-                        }
-                        if log.transaction_index.is_none()
-                            || log.transaction_index != tx.transaction_index
-                        {
-                            warn!(
-                            "log tx index mismatch, log tx index {:?}, block transaction index {:?}",
-                            log.transaction_index,
-                            tx.transaction_index
-                        );
+                                "log tx mismatch, log tx {:?} at index {}, block tx {:?}",
+                                log.tx_hash, log.tx_index, tx_hash
+                            );
                             return Ok(progress);
                         }
 
-                        let submit_filter = match SubmitFilter::decode_log(&RawLog {
-                            topics: log.topics,
-                            data: log.data.to_vec(),
-                        }) {
-                            Ok(v) => v,
-                            Err(e) => {
-                                return {
-                                    warn!("decode log failed: {:?}", e);
-                                    Ok(progress)
-                                }
-                            }
-                        };
-
+                        let submission_index = log.event.submission_index.as_u64();
                         if first_submission_index.is_none()
-                            || first_submission_index
-                                > Some(submit_filter.submission_index.as_u64())
+                            || first_submission_index > Some(submission_index)
                         {
-                            first_submission_index = Some(submit_filter.submission_index.as_u64());
+                            first_submission_index = Some(submission_index);
                         }
 
-                        log_events
-                            .push(submission_event_to_transaction(submit_filter, block_number));
+                        log_events.push(submission_event_to_transaction(log.event, block_number));
                     }
 
                     info!("synced {} events", log_events.len());
                 }
 
-                let new_progress = if block.hash.is_some() && block.number.is_some() {
-                    Some((
-                        block.number.unwrap().as_u64(),
-                        block.hash.unwrap(),
-                        Some(first_submission_index),
-                    ))
-                } else {
-                    None
-                };
+                let new_progress = (block.number, block.hash, Some(first_submission_index));
                 for log in log_events.into_iter() {
                     if let Err(e) = watch_tx.send(log) {
                         warn!("send LogFetchProgress::Transaction failed: {:?}", e);
@@ -571,35 +484,29 @@ impl LogEntryFetcher {
                     }
                 }
 
-                if let Some(p) = &new_progress {
-                    if let Err(e) = watch_tx.send(LogFetchProgress::SyncedBlock(*p)) {
-                        warn!("send LogFetchProgress::SyncedBlock failed: {:?}", e);
-                        return Ok(progress);
-                    } else {
-                        let mut cache = block_hash_cache.write().await;
-                        match cache.get(&p.0) {
-                            Some(Some(v))
-                                if v.block_hash == p.1
-                                    && v.first_submission_index == p.2.unwrap() => {}
-                            _ => {
-                                cache.insert(p.0, None);
-                            }
+                if let Err(e) = watch_tx.send(LogFetchProgress::SyncedBlock(new_progress)) {
+                    warn!("send LogFetchProgress::SyncedBlock failed: {:?}", e);
+                    return Ok(progress);
+                } else {
+                    let mut cache = block_hash_cache.write().await;
+                    match cache.get(&new_progress.0) {
+                        Some(Some(v))
+                            if v.block_hash == new_progress.1
+                                && v.first_submission_index == first_submission_index => {}
+                        _ => {
+                            cache.insert(new_progress.0, None);
                         }
                     }
                 }
-                progress = new_progress;
+                progress = Some(new_progress);
             }
         }
 
         Ok(progress)
     }
 
-    pub fn provider(&self) -> &Provider<RetryClient<Http>> {
-        self.provider.as_ref()
-    }
-
-    pub fn flow_contract(&self) -> ZgsFlow<Provider<RetryClient<Http>>> {
-        ZgsFlow::new(self.contract_address, self.provider.clone())
+    pub fn source(&self) -> &dyn LogSource {
+        self.source.as_ref()
     }
 }
 
@@ -610,7 +517,7 @@ async fn check_watch_process(
     progress_reset_history: &mut BTreeMap<u64, (Instant, usize)>,
     watch_loop_wait_time_ms: u64,
     block_hash_cache: &Arc<RwLock<BTreeMap<u64, Option<BlockHashAndSubmissionIndex>>>>,
-    provider: &Provider<RetryClient<Http>>,
+    source: &dyn LogSource,
 ) {
     let mut min_received_progress = None;
     while let Ok(v) = watch_progress_rx.try_recv() {
@@ -677,9 +584,9 @@ async fn check_watch_process(
                     *progress - 1
                 );
                 let hash = loop {
-                    match provider.get_block(*progress - 1).await {
+                    match source.get_block((*progress - 1).into()).await {
                         Ok(Some(v)) => {
-                            break v.hash.expect("parent block hash expect exist");
+                            break v.hash;
                         }
                         Ok(None) => {
                             panic!("parent block {} expect exist", *progress - 1);
@@ -706,7 +613,7 @@ async fn revert_one_block(
     block_number: u64,
     watch_tx: &UnboundedSender<LogFetchProgress>,
     block_hash_cache: &Arc<RwLock<BTreeMap<u64, Option<BlockHashAndSubmissionIndex>>>>,
-    provider: &Provider<RetryClient<Http>>,
+    source: &dyn LogSource,
 ) -> Result<(u64, H256), anyhow::Error> {
     debug!("revert block {}, block hash {:?}", block_number, block_hash);
     let block = loop {
@@ -735,12 +642,11 @@ async fn revert_one_block(
         Some(v) => v.clone().as_ref().unwrap().block_hash,
         _ => {
             debug!("assume parent block {} is not reorged", parent_block_number);
-            provider
-                .get_block(parent_block_number)
+            source
+                .get_block(parent_block_number.into())
                 .await?
                 .ok_or_else(|| anyhow!("None for block {}", parent_block_number))?
                 .hash
-                .ok_or_else(|| anyhow!("None block hash for block {}", parent_block_number))?
         }
     };
 
@@ -756,27 +662,30 @@ pub enum LogFetchProgress {
     SyncedBlock((u64, H256, Option<Option<u64>>)),
     Transaction((Transaction, u64)),
     Reverted(u64),
+    /// The synced block at or below the chain's finalized block.
+    Finalized(u64),
 }
 
 fn submission_event_to_transaction(e: SubmitFilter, block_number: u64) -> LogFetchProgress {
-    LogFetchProgress::Transaction((
-        Transaction {
-            stream_ids: vec![],
-            data: vec![],
-            data_merkle_root: nodes_to_root(&e.submission.nodes),
-            merkle_nodes: e
-                .submission
-                .nodes
-                .iter()
-                // the submission height is the height of the root node starting from height 0.
-                .map(|SubmissionNode { root, height }| (height.as_usize() + 1, root.into()))
-                .collect(),
-            start_entry_index: e.start_pos.as_u64(),
-            size: e.submission.length.as_u64(),
-            seq: e.submission_index.as_u64(),
-        },
-        block_number,
-    ))
+    LogFetchProgress::Transaction((submission_to_transaction(e), block_number))
+}
+
+pub(crate) fn submission_to_transaction(e: SubmitFilter) -> Transaction {
+    Transaction {
+        stream_ids: vec![],
+        data: vec![],
+        data_merkle_root: nodes_to_root(&e.submission.nodes),
+        merkle_nodes: e
+            .submission
+            .nodes
+            .iter()
+            // the submission height is the height of the root node starting from height 0.
+            .map(|SubmissionNode { root, height }| (height.as_usize() + 1, root.into()))
+            .collect(),
+        start_entry_index: e.start_pos.as_u64(),
+        size: e.submission.length.as_u64(),
+        seq: e.submission_index.as_u64(),
+    }
 }
 
 fn nodes_to_root(node_list: &[SubmissionNode]) -> DataRoot {
@@ -786,3 +695,326 @@ fn nodes_to_root(node_list: &[SubmissionNode]) -> DataRoot {
     }
     root
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use contract_interface::Submission;
+    use ethers::types::Log;
+    use futures::stream::{self, BoxStream};
+    use task_executor::test_utils::TestRuntime;
+
+    /// Blocks in memory, where the blocks since `fork_from` are of another fork.
+    struct TestLogSource {
+        blocks: Vec<BlockInfo>,
+        logs: Vec<SubmitLog>,
+        /// Block numbers of the logs that are not `Submit` events.
+        invalid_logs: Vec<u64>,
+        finalized: u64,
+    }
+
+    fn block_hash(number: u64, fork: u8) -> H256 {
+        let mut hash = H256::from_low_u64_be(number);
+        hash.0[0] = fork;
+        hash
+    }
+
+    impl TestLogSource {
+        fn new(num_blocks: u64, fork_from: u64) -> Self {
+            let fork = |number| if number >= fork_from { 1 } else { 0 };
+            let blocks = (0..num_blocks)
+                .map(|number| BlockInfo {
+                    number,
+                    hash: block_hash(number, fork(number)),
+                    parent_hash: match number {
+                        0 => H256::zero(),
+                        _ => block_hash(number - 1, fork(number - 1)),
+                    },
+                    tx_hashes: vec![],
+                })
+                .collect();
+
+            Self {
+                blocks,
+                logs: vec![],
+                invalid_logs: vec![],
+                finalized: num_blocks - 1,
+            }
+        }
+
+        fn submit(&mut self, block_number: u64, submission_index: u64) {
+            let block = &mut self.blocks[block_number as usize];
+            let tx_hash = H256::from_low_u64_be(submission_index + 1000);
+            block.tx_hashes.push(tx_hash);
+
+            self.logs.push(SubmitLog {
+                block_number,
+                block_hash: block.hash,
+                tx_hash,
+                tx_index: block.tx_hashes.len() as u64 - 1,
+                event: SubmitFilter {
+                    submission_index: submission_index.into(),
+                    start_pos: (submission_index + 1).into(),
+                    length: 256.into(),
+                    submission: Submission {
+                        length: 256.into(),
+                        nodes: vec![SubmissionNode {
+                            root: [1u8; 32],
+                            height: 0.into(),
+                        }],
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            });
+        }
+    }
+
+    #[async_trait]
+    impl LogSource for TestLogSource {
+        async fn get_block_number(&self) -> Result<u64> {
+            Ok(self.blocks.len() as u64 - 1)
+        }
+
+        async fn get_block(&self, block: BlockNumber) -> Result<Option<BlockInfo>> {
+            let number = match block {
+                BlockNumber::Number(number) => number.as_u64(),
+                BlockNumber::Latest => self.blocks.len() as u64 - 1,
+                BlockNumber::Finalized => self.finalized,
+                _ => bail!("unsupported block {}", block),
+            };
+            Ok(self.blocks.get(number as usize).cloned())
+        }
+
+        async fn has_receipt(&self, _tx_hash: H256) -> Result<bool> {
+            Ok(true)
+        }
+
+        fn submit_logs(
+            &self,
+            from_block: u64,
+            to_block: u64,
+            _delay: Duration,
+        ) -> BoxStream<'_, Result<SubmitLog>> {
+            let in_range = |block_number| block_number >= from_block && block_number <= to_block;
+            let invalid_logs = self.invalid_logs.iter().map(|block_number| {
+                let log = Log {
+                    block_number: Some((*block_number).into()),
+                    ..Default::default()
+                };
+                (*block_number, SubmitLog::try_from(log))
+            });
+            let mut logs: Vec<_> = self
+                .logs
+                .iter()
+                .map(|log| (log.block_number, Ok(log.clone())))
+                .chain(invalid_logs)
+                .filter(|(block_number, _)| in_range(*block_number))
+                .collect();
+            logs.sort_by_key(|(block_number, _)| *block_number);
+            stream::iter(logs.into_iter().map(|(_, log)| log)).boxed()
+        }
+
+        async fn get_flow_root_by_tx_seq(&self, _tx_seq: u64) -> Result<H256> {
+            Ok(H256::zero())
+        }
+    }
+
+    fn synced_block_cache(
+        fork: u8,
+        blocks: &[(u64, Option<u64>)],
+    ) -> Arc<RwLock<BTreeMap<u64, Option<BlockHashAndSubmissionIndex>>>> {
+        let cache = blocks
+            .iter()
+            .map(|(number, first_submission_index)| {
+                let block = BlockHashAndSubmissionIndex {
+                    block_hash: block_hash(*number, fork),
+                    first_submission_index: *first_submission_index,
+                };
+                (*number, Some(block))
+            })
+            .collect();
+        Arc::new(RwLock::new(cache))
+    }
+
+    #[tokio::test]
+    async fn test_watch_loop() {
+        let mut source = TestLogSource::new(6, u64::MAX);
+        source.submit(3, 0);
+        source.invalid_logs.push(3);
+        source.submit(3, 1);
+        let cache = Arc::new(RwLock::new(BTreeMap::new()));
+        let (watch_tx, mut watch_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        // the latest block is not confirmed yet
+        let progress = LogEntryFetcher::watch_loop(
+            &source,
+            1,
+            block_hash(0, 0),
+            &watch_tx,
+            Confirmation::Depth(1),
+            &cache,
+        )
+        .await
+        .unwrap();
+        assert_eq!(progress, Some((4, block_hash(4, 0), Some(None))));
+
+        let mut events = vec![];
+        while let Ok(event) = watch_rx.try_recv() {
+            events.push(event);
+        }
+        assert_eq!(events.len(), 6);
+        assert!(matches!(
+            events[0],
+            LogFetchProgress::SyncedBlock((1, _, Some(None)))
+        ));
+        assert!(matches!(
+            events[1],
+            LogFetchProgress::SyncedBlock((2, _, Some(None)))
+        ));
+        assert!(matches!(&events[2], LogFetchProgress::Transaction((tx, 3)) if tx.seq == 0));
+        assert!(matches!(&events[3], LogFetchProgress::Transaction((tx, 3)) if tx.seq == 1));
+        assert!(matches!(
+            events[4],
+            LogFetchProgress::SyncedBlock((3, _, Some(Some(0))))
+        ));
+        assert!(matches!(
+            events[5],
+            LogFetchProgress::SyncedBlock((4, _, Some(None)))
+        ));
+        assert_eq!(cache.read().await.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_recover() {
+        let runtime = TestRuntime::default();
+        let mut source = TestLogSource::new(6, u64::MAX);
+        source.submit(2, 0);
+        source.invalid_logs.push(2);
+        source.invalid_logs.push(3);
+        source.submit(4, 1);
+        let fetcher = LogEntryFetcher {
+            source: Arc::new(source),
+            confirmation: Confirmation::Depth(0),
+        };
+
+        // invalid logs are skipped instead of queried again
+        let mut recover_rx =
+            fetcher.start_recover(1, 5, &runtime.task_executor, Duration::from_millis(10));
+        let mut events = vec![];
+        while let Some(event) = recover_rx.recv().await {
+            events.push(event);
+        }
+        assert_eq!(events.len(), 4);
+        assert!(matches!(&events[0], LogFetchProgress::Transaction((tx, 2)) if tx.seq == 0));
+        assert!(matches!(
+            events[1],
+            LogFetchProgress::SyncedBlock((2, _, None))
+        ));
+        assert!(matches!(&events[2], LogFetchProgress::Transaction((tx, 4)) if tx.seq == 1));
+        assert!(matches!(
+            events[3],
+            LogFetchProgress::SyncedBlock((4, _, None))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_watch_finalized() {
+        let mut source = TestLogSource::new(6, u64::MAX);
+        source.finalized = 2;
+        let cache = Arc::new(RwLock::new(BTreeMap::new()));
+        let (watch_tx, mut watch_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        // blocks are synced up to the latest block, and only block 0 is synced and finalized
+        let progress = LogEntryFetcher::watch_loop(
+            &source,
+            1,
+            block_hash(0, 0),
+            &watch_tx,
+            Confirmation::Tag(BlockNumber::Finalized),
+            &cache,
+        )
+        .await
+        .unwrap();
+        assert_eq!(progress, Some((5, block_hash(5, 0), Some(None))));
+        assert!(matches!(
+            watch_rx.try_recv(),
+            Ok(LogFetchProgress::Finalized(0))
+        ));
+        for number in 1..6 {
+            assert!(matches!(
+                watch_rx.try_recv(),
+                Ok(LogFetchProgress::SyncedBlock((n, _, _))) if n == number
+            ));
+        }
+        assert!(watch_rx.try_recv().is_err());
+
+        // the synced blocks up to the finalized block are finalized
+        let progress = LogEntryFetcher::watch_loop(
+            &source,
+            6,
+            block_hash(5, 0),
+            &watch_tx,
+            Confirmation::Tag(BlockNumber::Finalized),
+            &cache,
+        )
+        .await
+        .unwrap();
+        assert_eq!(progress, None);
+        assert!(matches!(
+            watch_rx.try_recv(),
+            Ok(LogFetchProgress::Finalized(2))
+        ));
+        assert!(watch_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reorg() {
+        let runtime = TestRuntime::default();
+
+        // blocks since 4 are replaced by another fork
+        let source = Arc::new(TestLogSource::new(6, 4));
+
+        // reorg detected by the parent hash of new block
+        let cache = synced_block_cache(0, &[(3, None), (4, Some(2))]);
+        let (watch_tx, mut watch_rx) = tokio::sync::mpsc::unbounded_channel();
+        let progress = LogEntryFetcher::watch_loop(
+            source.as_ref(),
+            5,
+            block_hash(4, 0),
+            &watch_tx,
+            Confirmation::Depth(0),
+            &cache,
+        )
+        .await
+        .unwrap();
+        assert_eq!(progress, Some((3, block_hash(3, 0), None)));
+        assert!(matches!(
+            watch_rx.try_recv(),
+            Ok(LogFetchProgress::Reverted(2))
+        ));
+        assert!(matches!(
+            watch_rx.try_recv(),
+            Ok(LogFetchProgress::SyncedBlock((3, hash, None))) if hash == block_hash(3, 0)
+        ));
+
+        // reorg detected after restart
+        let fetcher = LogEntryFetcher {
+            source,
+            confirmation: Confirmation::Depth(0),
+        };
+        let mut reorg_rx =
+            fetcher.handle_reorg(4, block_hash(4, 0), &runtime.task_executor, cache.clone());
+        assert!(matches!(
+            reorg_rx.recv().await,
+            Some(LogFetchProgress::Reverted(2))
+        ));
+        assert!(matches!(
+            reorg_rx.recv().await,
+            Some(LogFetchProgress::SyncedBlock((3, hash, None))) if hash == block_hash(3, 0)
+        ));
+        // stops once the block hash matches
+        assert!(reorg_rx.recv().await.is_none());
+    }
+}
@@ -0,0 +1,241 @@
+use crate::sync_manager::log_query::LogQuery;
+use crate::LogSyncConfig;
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use contract_interface::{SubmitFilter, ZgsFlow};
+use contract_wrapper::{ProviderConfig, RpcProvider};
+use ethers::abi::RawLog;
+use ethers::prelude::{BlockNumber, EthLogDecode, Middleware};
+use ethers::types::{Block, Log, H256};
+use futures::stream::{BoxStream, StreamExt};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Block fields that log sync depends on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockInfo {
+    pub number: u64,
+    pub hash: H256,
+    pub parent_hash: H256,
+    /// Hashes of transactions in the block, ordered by transaction index.
+    pub tx_hashes: Vec<H256>,
+}
+
+impl TryFrom<Block<H256>> for BlockInfo {
+    type Error = anyhow::Error;
+
+    fn try_from(block: Block<H256>) -> Result<Self> {
+        // these fields are only absent for pending blocks
+        if block.logs_bloom.is_none() {
+            return Err(anyhow!("block {:?} logs bloom is none", block.number));
+        }
+
+        Ok(Self {
+            number: block
+                .number
+                .ok_or_else(|| anyhow!("None block number for block {:?}", block.hash))?
+                .as_u64(),
+            hash: block
+                .hash
+                .ok_or_else(|| anyhow!("None block hash for block {:?}", block.number))?,
+            parent_hash: block.parent_hash,
+            tx_hashes: block.transactions,
+        })
+    }
+}
+
+/// `Submit` event of the Flow contract, along with the transaction that emits it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubmitLog {
+    pub block_number: u64,
+    pub block_hash: H256,
+    pub tx_hash: H256,
+    pub tx_index: u64,
+    pub event: SubmitFilter,
+}
+
+impl SubmitLog {
+    /// Returns whether the error is of an invalid log rather than of querying logs, which is not
+    /// recovered by querying again.
+    pub fn is_invalid(e: &anyhow::Error) -> bool {
+        e.downcast_ref::<InvalidSubmitLog>().is_some()
+    }
+
+    fn decode(log: Log) -> Result<Self> {
+        let block_number = log
+            .block_number
+            .ok_or_else(|| anyhow!("block number missing"))?
+            .as_u64();
+        let block_hash = log
+            .block_hash
+            .ok_or_else(|| anyhow!("block hash missing"))?;
+        let tx_hash = log
+            .transaction_hash
+            .ok_or_else(|| anyhow!("transaction hash missing"))?;
+        let tx_index = log
+            .transaction_index
+            .ok_or_else(|| anyhow!("transaction index missing"))?
+            .as_u64();
+        let event = SubmitFilter::decode_log(&RawLog {
+            topics: log.topics,
+            data: log.data.to_vec(),
+        })?;
+
+        Ok(Self {
+            block_number,
+            block_hash,
+            tx_hash,
+            tx_index,
+            event,
+        })
+    }
+}
+
+/// Context of the errors converting a log to [`SubmitLog`].
+#[derive(Debug)]
+struct InvalidSubmitLog {
+    block_number: Option<u64>,
+    tx_hash: Option<H256>,
+}
+
+impl fmt::Display for InvalidSubmitLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid Submit log of transaction {:?} in block {:?}",
+            self.tx_hash, self.block_number
+        )
+    }
+}
+
+impl TryFrom<Log> for SubmitLog {
+    type Error = anyhow::Error;
+
+    fn try_from(log: Log) -> Result<Self> {
+        let context = InvalidSubmitLog {
+            block_number: log.block_number.map(|n| n.as_u64()),
+            tx_hash: log.transaction_hash,
+        };
+        Self::decode(log).context(context)
+    }
+}
+
+/// Source of blocks and `Submit` events of the Flow contract to sync log entries from.
+///
+/// Besides the JSON-RPC endpoint of blockchain, it could be implemented by other transports,
+/// recorded events or a test double.
+#[async_trait]
+pub trait LogSource: Send + Sync {
+    /// Returns the number of the latest block.
+    async fn get_block_number(&self) -> Result<u64>;
+
+    /// Returns the block of the specified number or tag, e.g. the finalized block, or `None` if
+    /// the block does not exist yet.
+    async fn get_block(&self, block: BlockNumber) -> Result<Option<BlockInfo>>;
+
+    /// Returns whether the receipt of the transaction is available.
+    async fn has_receipt(&self, tx_hash: H256) -> Result<bool>;
+
+    /// Returns the `Submit` events in blocks `[from_block, to_block]` in order, which may be
+    /// queried in pages with `delay` between pages.
+    fn submit_logs(
+        &self,
+        from_block: u64,
+        to_block: u64,
+        delay: Duration,
+    ) -> BoxStream<'_, Result<SubmitLog>>;
+
+    /// Returns the flow root after the transaction is appended, which is zero for transactions
+    /// submitted before the root is recorded.
+    async fn get_flow_root_by_tx_seq(&self, tx_seq: u64) -> Result<H256>;
+
+    /// Waits until new blocks may be available to watch, which sleeps `interval` for sources
+    /// that could only be polled.
+    async fn wait_for_new_block(&self, interval: Duration) {
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Log source of the JSON-RPC endpoints of an EVM compatible blockchain, which fails over to
+/// the next endpoint if one is unavailable.
+pub struct EvmLogSource {
+    provider: Arc<RpcProvider>,
+    flow_contract: ZgsFlow<RpcProvider>,
+    log_page_size: u64,
+}
+
+impl EvmLogSource {
+    /// Creates the log source of the specified endpoints in the order of preference.
+    pub fn new(config: &LogSyncConfig, endpoints: Vec<String>) -> Result<Self> {
+        let provider = ProviderConfig {
+            endpoints,
+            rate_limit_retries: config.rate_limit_retries,
+            timeout_retries: config.timeout_retries,
+            initial_backoff: config.initial_backoff,
+            request_timeout: Some(config.blockchain_rpc_timeout),
+        }
+        .make_provider()
+        .map_err(|e| anyhow!(e))?;
+        let provider = Arc::new(provider);
+        // TODO: `error` types are removed from the ABI json file.
+        let flow_contract = ZgsFlow::new(config.contract_address, provider.clone());
+
+        Ok(Self {
+            provider,
+            flow_contract,
+            log_page_size: config.log_page_size,
+        })
+    }
+}
+
+#[async_trait]
+impl LogSource for EvmLogSource {
+    async fn get_block_number(&self) -> Result<u64> {
+        Ok(self.provider.get_block_number().await?.as_u64())
+    }
+
+    async fn get_block(&self, block: BlockNumber) -> Result<Option<BlockInfo>> {
+        match self.provider.get_block(block).await? {
+            Some(block) => Ok(Some(block.try_into()?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn has_receipt(&self, tx_hash: H256) -> Result<bool> {
+        Ok(self
+            .provider
+            .get_transaction_receipt(tx_hash)
+            .await?
+            .is_some())
+    }
+
+    fn submit_logs(
+        &self,
+        from_block: u64,
+        to_block: u64,
+        delay: Duration,
+    ) -> BoxStream<'_, Result<SubmitLog>> {
+        let filter = self
+            .flow_contract
+            .submit_filter()
+            .from_block(from_block)
+            .to_block(to_block)
+            .address(self.flow_contract.address().into())
+            .filter;
+
+        LogQuery::new(self.provider.as_ref(), &filter, delay)
+            .with_page_size(self.log_page_size)
+            .map(|maybe_log| -> Result<SubmitLog> { maybe_log?.try_into() })
+            .boxed()
+    }
+
+    async fn get_flow_root_by_tx_seq(&self, tx_seq: u64) -> Result<H256> {
+        let root_bytes = self
+            .flow_contract
+            .get_flow_root_by_tx_seq(tx_seq.into())
+            .call()
+            .await?;
+        Ok(H256::from_slice(&root_bytes))
+    }
+}
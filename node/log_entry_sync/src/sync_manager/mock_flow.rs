@@ -0,0 +1,261 @@
+use crate::sync_manager::log_entry_fetcher::submission_to_transaction;
+use crate::sync_manager::log_source::{BlockInfo, LogSource, SubmitLog};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use contract_interface::{Submission, SubmissionNode, SubmitFilter};
+use ethers::types::{Address, BlockNumber, Bytes, H256, U256};
+use ethers::utils::keccak256;
+use futures::stream::{self, BoxStream, StreamExt};
+use shared_types::{DataRoot, Transaction};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+use storage::log_store::log_manager::split_nodes;
+use storage::log_store::Store;
+use tokio::sync::Notify;
+
+/// In-process Flow contract for the dev mode, which appends submissions to the flow in the same
+/// way as the on-chain contract and serves the `Submit` events to the log sync as a
+/// [`LogSource`].
+///
+/// Every submission is packed into a new block, and blocks are final once created, so there is
+/// no chain reorg in dev mode.
+pub struct MockFlow {
+    state: Mutex<MockFlowState>,
+    new_block: Notify,
+}
+
+struct MockFlowState {
+    next_tx_seq: u64,
+    /// The number of entries in the flow, including the padding entries.
+    flow_length: u64,
+    block_number: u64,
+    /// Logs submitted since the mock flow is created, while earlier ones are synced already.
+    logs: Vec<SubmitLog>,
+}
+
+/// Block hashes are derived from block numbers, so that blocks are the same after restart.
+fn block_hash(block_number: u64) -> H256 {
+    H256(keccak256(block_number.to_be_bytes()))
+}
+
+impl MockFlow {
+    /// Creates a mock flow that continues with the transactions in store.
+    pub(crate) fn new(store: &dyn Store) -> Result<Self> {
+        let (_, flow_length) = store.get_context()?;
+        let block_number = store.get_log_latest_block_number()?.unwrap_or_default();
+
+        Ok(Self {
+            state: Mutex::new(MockFlowState {
+                next_tx_seq: store.next_tx_seq(),
+                flow_length,
+                block_number,
+                logs: vec![],
+            }),
+            new_block: Notify::new(),
+        })
+    }
+
+    /// Submits a file of `length` bytes, where `nodes` are the `(root, height)` of subtrees as
+    /// the `submit` method of Flow contract, and returns the submitted transaction.
+    ///
+    /// The transaction is synced to store asynchronously.
+    pub fn submit(&self, length: u64, nodes: &[(DataRoot, usize)]) -> Result<Transaction> {
+        let expected_sizes = split_nodes(length as usize);
+        if length == 0
+            || nodes.len() != expected_sizes.len()
+            || nodes
+                .iter()
+                .zip(expected_sizes.iter())
+                .any(|((_, height), size)| 1usize.checked_shl(*height as u32) != Some(*size))
+        {
+            bail!("invalid submission nodes for file length {}", length);
+        }
+
+        let mut state = self.state();
+
+        // pad the flow so that the first subtree is aligned, and the following ones are aligned
+        // naturally since they are smaller.
+        let first_size = expected_sizes[0] as u64;
+        let start_pos = (state.flow_length + first_size - 1) / first_size * first_size;
+        let num_entries: u64 = expected_sizes.iter().map(|size| *size as u64).sum();
+
+        let event = SubmitFilter {
+            sender: Address::zero(),
+            identity: [0u8; 32],
+            submission_index: state.next_tx_seq.into(),
+            start_pos: start_pos.into(),
+            length: length.into(),
+            submission: Submission {
+                length: length.into(),
+                tags: Bytes::default(),
+                nodes: nodes
+                    .iter()
+                    .map(|(root, height)| SubmissionNode {
+                        root: root.0,
+                        height: U256::from(*height),
+                    })
+                    .collect(),
+            },
+        };
+
+        let block_number = state.block_number + 1;
+        let tx = submission_to_transaction(event.clone());
+        state.logs.push(SubmitLog {
+            block_number,
+            block_hash: block_hash(block_number),
+            tx_hash: H256(keccak256(tx.seq.to_be_bytes())),
+            tx_index: 0,
+            event,
+        });
+
+        state.next_tx_seq += 1;
+        state.flow_length = start_pos + num_entries;
+        state.block_number = block_number;
+        drop(state);
+
+        self.new_block.notify_one();
+        Ok(tx)
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockFlowState> {
+        self.state.lock().expect("mock flow lock poisoned")
+    }
+}
+
+#[async_trait]
+impl LogSource for MockFlow {
+    async fn get_block_number(&self) -> Result<u64> {
+        Ok(self.state().block_number)
+    }
+
+    async fn get_block(&self, block: BlockNumber) -> Result<Option<BlockInfo>> {
+        let state = self.state();
+        let number = match block {
+            BlockNumber::Number(number) => number.as_u64(),
+            BlockNumber::Earliest => 0,
+            // the latest block is final too
+            _ => state.block_number,
+        };
+        if number > state.block_number {
+            return Ok(None);
+        }
+
+        Ok(Some(BlockInfo {
+            number,
+            hash: block_hash(number),
+            parent_hash: match number {
+                0 => H256::zero(),
+                _ => block_hash(number - 1),
+            },
+            tx_hashes: state
+                .logs
+                .iter()
+                .filter(|log| log.block_number == number)
+                .map(|log| log.tx_hash)
+                .collect(),
+        }))
+    }
+
+    async fn has_receipt(&self, _tx_hash: H256) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn submit_logs(
+        &self,
+        from_block: u64,
+        to_block: u64,
+        _delay: Duration,
+    ) -> BoxStream<'_, Result<SubmitLog>> {
+        let logs: Vec<_> = self
+            .state()
+            .logs
+            .iter()
+            .filter(|log| log.block_number >= from_block && log.block_number <= to_block)
+            .cloned()
+            .map(Ok)
+            .collect();
+        stream::iter(logs).boxed()
+    }
+
+    async fn get_flow_root_by_tx_seq(&self, _tx_seq: u64) -> Result<H256> {
+        // the flow root is not computed by the mock flow, which skips the root check
+        Ok(H256::zero())
+    }
+
+    async fn wait_for_new_block(&self, interval: Duration) {
+        let _ = tokio::time::timeout(interval, self.new_block.notified()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CacheConfig, LogSyncConfig, LogSyncEvent, LogSyncManager};
+    use shared_types::{Transaction, CHUNK_SIZE};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use storage::log_store::log_manager::{tx_subtree_root_list_padded, LogConfig};
+    use storage::log_store::LogStoreRead;
+    use storage::LogManager;
+    use task_executor::test_utils::TestRuntime;
+
+    fn dev_config() -> LogSyncConfig {
+        let cache_config = CacheConfig {
+            max_data_size: 1024 * 1024,
+            tx_seq_ttl: 500,
+        };
+        let mut config = LogSyncConfig::new(
+            vec![],
+            Default::default(),
+            0,
+            0,
+            cache_config,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            false,
+            Duration::from_secs(1),
+        );
+        config.dev_mode = true;
+        config
+    }
+
+    #[tokio::test]
+    async fn test_submit() {
+        let runtime = TestRuntime::default();
+        let store = Arc::new(LogManager::memorydb(LogConfig::default()).unwrap());
+        let (event_send, _, mock_flow) =
+            LogSyncManager::spawn_dev(dev_config(), runtime.task_executor.clone(), store.clone())
+                .await
+                .unwrap();
+        let mut event_recv = event_send.subscribe();
+
+        for (seq, chunks) in [(0, 3), (1, 1025)] {
+            let data = vec![seq as u8 + 1; chunks * CHUNK_SIZE];
+            let nodes: Vec<_> = tx_subtree_root_list_padded(&data)
+                .into_iter()
+                .map(|(depth, root)| (root, depth - 1))
+                .collect();
+
+            // the number of nodes does not match the file size
+            assert!(mock_flow.submit(data.len() as u64, &nodes[1..]).is_err());
+
+            let tx = mock_flow.submit(data.len() as u64, &nodes).unwrap();
+            assert_eq!(tx.seq, seq);
+            let first_node_size = Transaction::num_entries_of_node(tx.merkle_nodes[0].0);
+            assert_eq!(tx.start_entry_index % first_node_size as u64, 0);
+
+            match tokio::time::timeout(Duration::from_secs(5), event_recv.recv()).await {
+                Ok(Ok(LogSyncEvent::TxSynced { tx: synced })) => assert_eq!(synced, tx),
+                e => panic!("unexpected event: {:?}", e),
+            }
+            assert_eq!(store.get_tx_by_seq_number(seq).unwrap(), Some(tx));
+        }
+
+        assert_eq!(store.next_tx_seq(), 2);
+    }
+}
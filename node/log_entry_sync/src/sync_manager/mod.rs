@@ -1,11 +1,16 @@
+use crate::sync_manager::archive::{ArchivedLogSource, LogArchive};
 use crate::sync_manager::config::LogSyncConfig;
 use crate::sync_manager::data_cache::DataCache;
 use crate::sync_manager::log_entry_fetcher::{LogEntryFetcher, LogFetchProgress};
+use crate::sync_manager::log_source::{EvmLogSource, LogSource};
+use crate::sync_manager::mock_flow::MockFlow;
+use crate::sync_manager::quorum::QuorumLogSource;
+use crate::sync_manager::subscription::SubscribedLogSource;
 use anyhow::{anyhow, bail, Result};
 use ethereum_types::H256;
-use ethers::{prelude::Middleware, types::BlockNumber};
+use ethers::types::BlockNumber;
 use futures::FutureExt;
-use jsonrpsee::tracing::{debug, error, warn};
+use jsonrpsee::tracing::{debug, error, info, warn};
 use shared_types::{bytes_to_chunks, ChunkArray, Transaction};
 use std::collections::BTreeMap;
 use std::fmt::Debug;
@@ -64,10 +69,56 @@ pub struct LogSyncManager {
 }
 
 impl LogSyncManager {
+    /// Spawns the log sync from the blockchain RPC endpoints in config.
     pub async fn spawn(
         config: LogSyncConfig,
         executor: TaskExecutor,
         store: Arc<dyn Store>,
+    ) -> Result<(broadcast::Sender<LogSyncEvent>, oneshot::Receiver<()>)> {
+        let mut source: Arc<dyn LogSource> = if config.quorum > 1 {
+            let sources = config
+                .rpc_endpoint_urls
+                .iter()
+                .map(|url| -> Result<Arc<dyn LogSource>> {
+                    Ok(Arc::new(EvmLogSource::new(&config, vec![url.clone()])?))
+                })
+                .collect::<Result<_>>()?;
+            Arc::new(QuorumLogSource::new(sources, config.quorum)?)
+        } else {
+            Arc::new(EvmLogSource::new(
+                &config,
+                config.rpc_endpoint_urls.clone(),
+            )?)
+        };
+        if let Some(endpoint) = &config.subscription_endpoint {
+            // new heads only wake up the watch loop, so that it's safe in quorum mode
+            source = Arc::new(SubscribedLogSource::spawn(
+                source,
+                endpoint.clone(),
+                &executor,
+            ));
+        }
+        if let Some(path) = &config.archive_path {
+            let archive = LogArchive::open(path)?;
+            let info = *archive.info();
+            if info.contract_address != config.contract_address {
+                bail!(
+                    "log archive of another contract {:?}",
+                    info.contract_address
+                );
+            }
+            source = Arc::new(ArchivedLogSource::new(archive, source).await?);
+            info!(?info, "log sync replays the log archive");
+        }
+        Self::spawn_with_source(config, executor, store, source).await
+    }
+
+    /// Spawns the log sync from the specified log source.
+    pub async fn spawn_with_source(
+        config: LogSyncConfig,
+        executor: TaskExecutor,
+        store: Arc<dyn Store>,
+        source: Arc<dyn LogSource>,
     ) -> Result<(broadcast::Sender<LogSyncEvent>, oneshot::Receiver<()>)> {
         let next_tx_seq = store.next_tx_seq();
 
@@ -87,7 +138,7 @@ impl LogSyncManager {
                         .expect("shutdown send error")
                 },
                 async move {
-                    let log_fetcher = LogEntryFetcher::new(&config).await?;
+                    let log_fetcher = LogEntryFetcher::new(source, &config);
                     let data_cache = DataCache::new(config.cache_config.clone());
 
                     let block_hash_cache = Arc::new(RwLock::new(
@@ -110,8 +161,12 @@ impl LogSyncManager {
                     let (mut start_block_number, mut start_block_hash) =
                         get_start_block_number_with_hash(&log_sync_manager).await?;
 
+                    let finality_tag = log_sync_manager
+                        .config
+                        .finality_tag
+                        .unwrap_or(BlockNumber::Finalized);
                     let (mut finalized_block_number, mut finalized_block_hash) =
-                        match log_sync_manager.get_block(BlockNumber::Finalized).await {
+                        match log_sync_manager.get_block(finality_tag).await {
                             Ok(finalized) => finalized,
                             Err(e) => {
                                 warn!(?e, "unable to get finalized block");
@@ -197,19 +252,19 @@ impl LogSyncManager {
                             // wait tx receipt is ready
                             if let Ok(Some(block)) = log_sync_manager
                                 .log_fetcher
-                                .provider()
-                                .get_block_with_txs(finalized_block_number)
+                                .source()
+                                .get_block(finalized_block_number.into())
                                 .await
                             {
-                                if let Some(tx) = block.transactions.first() {
+                                if let Some(tx_hash) = block.tx_hashes.first() {
                                     loop {
                                         match log_sync_manager
                                             .log_fetcher
-                                            .provider()
-                                            .get_transaction_receipt(tx.hash)
+                                            .source()
+                                            .has_receipt(*tx_hash)
                                             .await
                                         {
-                                            Ok(Some(_)) => break,
+                                            Ok(true) => break,
                                             _ => {
                                                 tokio::time::sleep(Duration::from_secs(1)).await;
                                                 continue;
@@ -242,7 +297,7 @@ impl LogSyncManager {
                             start_block_number = finalized_block_number.saturating_add(1);
 
                             let new_finalized_block =
-                                log_sync_manager.get_block(BlockNumber::Finalized).await?;
+                                log_sync_manager.get_block(finality_tag).await?;
                             if new_finalized_block.0.saturating_sub(finalized_block_number)
                                 <= CATCH_UP_END_GAP
                             {
@@ -263,7 +318,12 @@ impl LogSyncManager {
                             &executor_clone,
                             log_sync_manager.store.clone(),
                             log_sync_manager.block_hash_cache.clone(),
-                            log_sync_manager.config.default_finalized_block_count,
+                            finality_tag,
+                            // never guess the finalized block if the chain's finality is used
+                            match log_sync_manager.config.finality_tag {
+                                Some(_) => None,
+                                None => Some(log_sync_manager.config.default_finalized_block_count),
+                            },
                             log_sync_manager
                                 .config
                                 .remove_finalized_block_interval_minutes,
@@ -295,6 +355,28 @@ impl LogSyncManager {
         Ok((event_send_cloned, catch_up_end_receiver))
     }
 
+    /// Spawns the log sync in dev mode, where transactions are submitted to the returned
+    /// in-process [`MockFlow`] instead of synced from the blockchain.
+    pub async fn spawn_dev(
+        mut config: LogSyncConfig,
+        executor: TaskExecutor,
+        store: Arc<dyn Store>,
+    ) -> Result<(
+        broadcast::Sender<LogSyncEvent>,
+        oneshot::Receiver<()>,
+        Arc<MockFlow>,
+    )> {
+        let mock_flow = Arc::new(MockFlow::new(store.as_ref())?);
+        // blocks of the mock flow start from the genesis and are final once created
+        config.start_block_number = 0;
+        config.force_log_sync_from_start_block_number = false;
+        config.confirmation_block_count = 0;
+
+        let (event_send, catch_up_end_recv) =
+            Self::spawn_with_source(config, executor, store, mock_flow.clone()).await?;
+        Ok((event_send, catch_up_end_recv, mock_flow))
+    }
+
     async fn put_tx(&mut self, tx: Transaction) -> Option<bool> {
         // We call this after process chain reorg, so the sequence number should match.
         match tx.seq.cmp(&self.next_tx_seq) {
@@ -386,13 +468,18 @@ impl LogSyncManager {
                         first_submission_index,
                     ))?;
 
-                    match self.log_fetcher.provider().get_block(block_number).await {
+                    match self
+                        .log_fetcher
+                        .source()
+                        .get_block(block_number.into())
+                        .await
+                    {
                         Ok(Some(b)) => {
-                            if b.number != Some(block_number.into()) {
+                            if b.number != block_number {
                                 error!(
                                     "block number not match, reorg possible happened, block number {:?}, received {}", b.number, block_number 
                                 );
-                            } else if b.hash != Some(block_hash) {
+                            } else if b.hash != block_hash {
                                 error!("block hash not match, reorg possible happened, block hash {:?}, received {}", b.hash, block_hash);
                             }
                         }
@@ -443,6 +530,9 @@ impl LogSyncManager {
                 LogFetchProgress::Reverted(reverted) => {
                     self.process_reverted(reverted).await;
                 }
+                LogFetchProgress::Finalized(block_number) => {
+                    self.store.put_log_finalized_block_number(block_number)?;
+                }
             }
         }
         Ok(())
@@ -512,15 +602,13 @@ impl LogSyncManager {
             // Check if the computed data root matches on-chain state.
             // If the call fails, we won't check the root here and return `true` directly.
             if self.next_tx_seq % CHECK_ROOT_INTERVAL == 0 {
-                let flow_contract = self.log_fetcher.flow_contract();
-
-                match flow_contract
-                    .get_flow_root_by_tx_seq(tx.seq.into())
-                    .call()
+                match self
+                    .log_fetcher
+                    .source()
+                    .get_flow_root_by_tx_seq(tx.seq)
                     .await
                 {
-                    Ok(contract_root_bytes) => {
-                        let contract_root = H256::from_slice(&contract_root_bytes);
+                    Ok(contract_root) => {
                         // contract_root is zero for tx submitted before upgrading.
                         if !contract_root.is_zero() {
                             match self.store.get_context() {
@@ -555,24 +643,15 @@ impl LogSyncManager {
     }
 
     async fn get_block(&self, block_number: BlockNumber) -> Result<(u64, H256)> {
-        let block = match self.log_fetcher.provider().get_block(block_number).await {
-            Ok(Some(block)) => block,
+        match self.log_fetcher.source().get_block(block_number).await {
+            Ok(Some(block)) => Ok((block.number, block.hash)),
             Ok(None) => {
                 bail!("None for block {}", block_number);
             }
             e => {
                 bail!("unable to get block: {:?}", e);
             }
-        };
-        Ok((
-            block
-                .number
-                .ok_or_else(|| anyhow!("None block number for finalized block"))?
-                .as_u64(),
-            block
-                .hash
-                .ok_or_else(|| anyhow!("None block hash for finalized block"))?,
-        ))
+        }
     }
 
     /// Return the ending block number and the parent block hash.
@@ -652,8 +731,13 @@ where
     }
 }
 
+pub(crate) mod archive;
 pub(crate) mod config;
 mod data_cache;
 mod log_entry_fetcher;
 mod log_query;
+pub(crate) mod log_source;
 mod metrics;
+pub(crate) mod mock_flow;
+pub(crate) mod quorum;
+pub(crate) mod subscription;
@@ -0,0 +1,311 @@
+use crate::sync_manager::log_source::{BlockInfo, LogSource, SubmitLog};
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use ethers::prelude::BlockNumber;
+use ethers::types::H256;
+use futures::future::{self, join_all};
+use futures::stream::{self, BoxStream, StreamExt};
+use jsonrpsee::tracing::warn;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Log source over multiple sources, which only accepts blocks and `Submit` logs that at least
+/// `quorum` sources agree on, so that a single faulty or malicious endpoint could neither stall
+/// nor corrupt log sync.
+pub struct QuorumLogSource {
+    sources: Vec<Arc<dyn LogSource>>,
+    quorum: usize,
+}
+
+impl QuorumLogSource {
+    pub fn new(sources: Vec<Arc<dyn LogSource>>, quorum: usize) -> Result<Self> {
+        if quorum == 0 || quorum > sources.len() {
+            bail!(
+                "invalid quorum {} for {} log sources",
+                quorum,
+                sources.len()
+            );
+        }
+
+        Ok(Self { sources, quorum })
+    }
+
+    /// Returns the block number that at least `quorum` sources have reached.
+    fn quorum_number(&self, mut numbers: Vec<u64>) -> Option<u64> {
+        numbers.sort_unstable_by(|a, b| b.cmp(a));
+        numbers.get(self.quorum - 1).copied()
+    }
+}
+
+/// Returns the value that at least `quorum` of `values` are equal to.
+fn agreed<T: PartialEq>(values: Vec<T>, quorum: usize) -> Option<T> {
+    let mut votes: Vec<(T, usize)> = vec![];
+    for value in values {
+        match votes.iter_mut().find(|(v, _)| *v == value) {
+            Some((_, count)) => *count += 1,
+            None => votes.push((value, 1)),
+        }
+    }
+
+    votes
+        .into_iter()
+        .find(|(_, count)| *count >= quorum)
+        .map(|(value, _)| value)
+}
+
+/// Drops the failed results, which are not counted as votes.
+fn successful<T>(results: Vec<Result<T>>, what: &str) -> Vec<T> {
+    results
+        .into_iter()
+        .filter_map(|r| match r {
+            Ok(v) => Some(v),
+            Err(e) => {
+                warn!("log source fails to get {}: e={:?}", what, e);
+                None
+            }
+        })
+        .collect()
+}
+
+#[async_trait]
+impl LogSource for QuorumLogSource {
+    async fn get_block_number(&self) -> Result<u64> {
+        let results = join_all(self.sources.iter().map(|s| s.get_block_number())).await;
+        self.quorum_number(successful(results, "block number"))
+            .ok_or_else(|| anyhow!("no quorum on the latest block number"))
+    }
+
+    async fn get_block(&self, block: BlockNumber) -> Result<Option<BlockInfo>> {
+        let number = match block {
+            BlockNumber::Number(number) => number.as_u64(),
+            // sources may be at different heights, so use the lowest one of the quorum
+            tag => {
+                let results = join_all(self.sources.iter().map(|s| s.get_block(tag))).await;
+                let numbers = successful(results, "block")
+                    .into_iter()
+                    .flatten()
+                    .map(|b| b.number)
+                    .collect();
+                match self.quorum_number(numbers) {
+                    Some(number) => number,
+                    None => bail!("no quorum on block {}", tag),
+                }
+            }
+        };
+
+        let results = join_all(self.sources.iter().map(|s| s.get_block(number.into()))).await;
+        agreed(successful(results, "block"), self.quorum)
+            .ok_or_else(|| anyhow!("no quorum on block {}", number))
+    }
+
+    async fn has_receipt(&self, tx_hash: H256) -> Result<bool> {
+        let results = join_all(self.sources.iter().map(|s| s.has_receipt(tx_hash))).await;
+        let count = successful(results, "receipt")
+            .into_iter()
+            .filter(|available| *available)
+            .count();
+        Ok(count >= self.quorum)
+    }
+
+    fn submit_logs(
+        &self,
+        from_block: u64,
+        to_block: u64,
+        delay: Duration,
+    ) -> BoxStream<'_, Result<SubmitLog>> {
+        // `None` for the sources that fail, which are not queried any more
+        let streams: Vec<_> = self
+            .sources
+            .iter()
+            .map(|s| {
+                // invalid logs are skipped before voting, like the sync of a single source
+                let logs = s
+                    .submit_logs(from_block, to_block, delay)
+                    .filter(|maybe_log| {
+                        let valid = match maybe_log {
+                            Err(e) if SubmitLog::is_invalid(e) => {
+                                warn!("skip invalid log: e={:?}", e);
+                                false
+                            }
+                            _ => true,
+                        };
+                        future::ready(valid)
+                    })
+                    .boxed();
+                Some(logs.fuse())
+            })
+            .collect();
+        let quorum = self.quorum;
+
+        // logs are compared one by one, where the end of logs is voted as `None`
+        stream::unfold(Some(streams), move |streams| async move {
+            let mut streams = streams?;
+            let items = join_all(streams.iter_mut().map(|maybe_stream| async move {
+                match maybe_stream {
+                    Some(stream) => Some(stream.next().await),
+                    None => None,
+                }
+            }))
+            .await;
+
+            let mut votes = vec![];
+            for (maybe_stream, item) in streams.iter_mut().zip(items) {
+                match item {
+                    Some(Some(Ok(log))) => votes.push(Some(log)),
+                    Some(None) => votes.push(None),
+                    Some(Some(Err(e))) => {
+                        warn!("log source fails to get Submit logs: e={:?}", e);
+                        *maybe_stream = None;
+                    }
+                    None => {}
+                }
+            }
+
+            match agreed(votes, quorum) {
+                Some(Some(log)) => Some((Ok(log), Some(streams))),
+                Some(None) => None,
+                None => Some((
+                    Err(anyhow!(
+                        "no quorum on Submit logs in blocks [{}, {}]",
+                        from_block,
+                        to_block
+                    )),
+                    None,
+                )),
+            }
+        })
+        .boxed()
+    }
+
+    async fn get_flow_root_by_tx_seq(&self, tx_seq: u64) -> Result<H256> {
+        let results = join_all(
+            self.sources
+                .iter()
+                .map(|s| s.get_flow_root_by_tx_seq(tx_seq)),
+        )
+        .await;
+        agreed(successful(results, "flow root"), self.quorum)
+            .ok_or_else(|| anyhow!("no quorum on flow root of tx {}", tx_seq))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use contract_interface::SubmitFilter;
+
+    /// Source with blocks `[0, latest]`, and a `Submit` log in each block.
+    struct FixedLogSource {
+        latest: u64,
+        /// Hashes of blocks since this number are different from other sources.
+        fork_from: u64,
+    }
+
+    impl FixedLogSource {
+        fn new(latest: u64, fork_from: u64) -> Arc<dyn LogSource> {
+            Arc::new(Self { latest, fork_from })
+        }
+
+        fn block_hash(&self, number: u64) -> H256 {
+            let fork = if number >= self.fork_from { 1 } else { 0 };
+            H256::from_low_u64_be((fork << 32) + number)
+        }
+    }
+
+    #[async_trait]
+    impl LogSource for FixedLogSource {
+        async fn get_block_number(&self) -> Result<u64> {
+            Ok(self.latest)
+        }
+
+        async fn get_block(&self, block: BlockNumber) -> Result<Option<BlockInfo>> {
+            let number = match block {
+                BlockNumber::Number(number) => number.as_u64(),
+                _ => self.latest,
+            };
+            if number > self.latest {
+                return Ok(None);
+            }
+
+            Ok(Some(BlockInfo {
+                number,
+                hash: self.block_hash(number),
+                parent_hash: self.block_hash(number.saturating_sub(1)),
+                tx_hashes: vec![H256::from_low_u64_be(number)],
+            }))
+        }
+
+        async fn has_receipt(&self, _tx_hash: H256) -> Result<bool> {
+            Ok(true)
+        }
+
+        fn submit_logs(
+            &self,
+            from_block: u64,
+            to_block: u64,
+            _delay: Duration,
+        ) -> BoxStream<'_, Result<SubmitLog>> {
+            let logs: Vec<_> = (from_block..=to_block.min(self.latest))
+                .map(|number| {
+                    Ok(SubmitLog {
+                        block_number: number,
+                        block_hash: self.block_hash(number),
+                        tx_hash: H256::from_low_u64_be(number),
+                        tx_index: 0,
+                        event: SubmitFilter {
+                            submission_index: number.into(),
+                            ..Default::default()
+                        },
+                    })
+                })
+                .collect();
+            stream::iter(logs).boxed()
+        }
+
+        async fn get_flow_root_by_tx_seq(&self, _tx_seq: u64) -> Result<H256> {
+            Ok(H256::zero())
+        }
+    }
+
+    #[test]
+    fn test_agreed() {
+        assert_eq!(agreed(vec![1, 2, 1], 2), Some(1));
+        assert_eq!(agreed(vec![1, 2, 3], 2), None);
+        assert_eq!(agreed(vec![None, Some(1), None], 2), Some(None));
+    }
+
+    #[tokio::test]
+    async fn test_quorum_log_source() {
+        let sources = vec![
+            FixedLogSource::new(10, u64::MAX),
+            FixedLogSource::new(12, u64::MAX),
+            // a faulty source on another fork since block 11
+            FixedLogSource::new(20, 11),
+        ];
+        assert!(QuorumLogSource::new(sources.clone(), 4).is_err());
+        let source = QuorumLogSource::new(sources, 2).unwrap();
+
+        assert_eq!(source.get_block_number().await.unwrap(), 12);
+        let block = source.get_block(10.into()).await.unwrap().unwrap();
+        assert_eq!(block.hash, H256::from_low_u64_be(10));
+        // only one source has the block
+        assert_eq!(source.get_block(15.into()).await.unwrap(), None);
+        // the other source that has the latest block 12 is faulty
+        assert!(source.get_block(BlockNumber::Latest).await.is_err());
+
+        let logs: Vec<_> = source
+            .submit_logs(3, 12, Duration::ZERO)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(logs.len(), 9);
+        for (number, log) in (3..=10).zip(logs.iter()) {
+            assert_eq!(log.as_ref().unwrap().block_number, number);
+        }
+        // logs of block 11 and 12 diverge
+        assert!(logs[8].is_err());
+
+        let logs: Vec<_> = source.submit_logs(0, 4, Duration::ZERO).collect().await;
+        assert_eq!(logs.len(), 5);
+        assert!(logs.iter().all(|log| log.is_ok()));
+    }
+}
@@ -0,0 +1,143 @@
+use crate::sync_manager::log_source::{BlockInfo, LogSource, SubmitLog};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use ethers::prelude::{BlockNumber, Middleware, Provider};
+use ethers::providers::{Ipc, PubsubClient, Ws};
+use ethers::types::H256;
+use futures::stream::{BoxStream, StreamExt};
+use jsonrpsee::tracing::{debug, info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use task_executor::TaskExecutor;
+use tokio::sync::Notify;
+
+/// Interval to subscribe again after the connection is lost.
+const RESUBSCRIBE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Maximum time to wait for a new head, in case the subscription stalls without disconnection.
+const MAX_NEW_HEAD_WAIT: Duration = Duration::from_secs(60);
+
+/// Log source that subscribes to `newHeads` over WebSocket or IPC to wake up the watch loop
+/// instead of polling, while blocks and logs are always queried from the inner source.
+///
+/// `Submit` logs are not subscribed, since a subscription of a single endpoint could neither be
+/// trusted in quorum mode nor guarded against missed logs, so each wake-up still calls
+/// `eth_getLogs`.
+///
+/// Whenever the subscription is lost, it falls back to polling until subscribed again.
+pub struct SubscribedLogSource {
+    inner: Arc<dyn LogSource>,
+    subscribed: Arc<AtomicBool>,
+    new_head: Arc<Notify>,
+}
+
+impl SubscribedLogSource {
+    /// Creates the log source and spawns the task to subscribe from `endpoint`, which is either a
+    /// WebSocket URL or the path of an IPC socket.
+    pub fn spawn(inner: Arc<dyn LogSource>, endpoint: String, executor: &TaskExecutor) -> Self {
+        let subscribed = Arc::new(AtomicBool::new(false));
+        let new_head = Arc::new(Notify::new());
+
+        let task_subscribed = subscribed.clone();
+        let task_new_head = new_head.clone();
+        executor.spawn(
+            async move {
+                loop {
+                    let result = if endpoint.starts_with("ws://") || endpoint.starts_with("wss://")
+                    {
+                        match Provider::<Ws>::connect(&endpoint).await {
+                            Ok(provider) => {
+                                subscribe(provider, &task_subscribed, &task_new_head).await
+                            }
+                            Err(e) => Err(e.into()),
+                        }
+                    } else {
+                        match Provider::connect_ipc(&endpoint).await {
+                            Ok(provider) => {
+                                subscribe::<Ipc>(provider, &task_subscribed, &task_new_head).await
+                            }
+                            Err(e) => Err(e.into()),
+                        }
+                    };
+
+                    // fall back to polling, and wake up the watch loop which may wait for heads
+                    task_subscribed.store(false, Ordering::Relaxed);
+                    task_new_head.notify_one();
+                    warn!(
+                        "log sync subscription lost, endpoint={}, e={:?}",
+                        endpoint, result
+                    );
+
+                    tokio::time::sleep(RESUBSCRIBE_INTERVAL).await;
+                }
+            },
+            "log sync subscription",
+        );
+
+        Self {
+            inner,
+            subscribed,
+            new_head,
+        }
+    }
+}
+
+/// Subscribes to new heads until the connection is lost.
+async fn subscribe<P: PubsubClient>(
+    provider: Provider<P>,
+    subscribed: &AtomicBool,
+    new_head: &Notify,
+) -> Result<()> {
+    let mut heads = provider.subscribe_blocks().await?;
+    subscribed.store(true, Ordering::Relaxed);
+    info!("log sync subscribed to new heads");
+
+    loop {
+        let number = heads
+            .next()
+            .await
+            .ok_or_else(|| anyhow!("newHeads subscription closed"))?
+            .number
+            .ok_or_else(|| anyhow!("None block number for new head"))?;
+        debug!("log sync new head {}", number);
+
+        new_head.notify_one();
+    }
+}
+
+#[async_trait]
+impl LogSource for SubscribedLogSource {
+    async fn get_block_number(&self) -> Result<u64> {
+        self.inner.get_block_number().await
+    }
+
+    async fn get_block(&self, block: BlockNumber) -> Result<Option<BlockInfo>> {
+        self.inner.get_block(block).await
+    }
+
+    async fn has_receipt(&self, tx_hash: H256) -> Result<bool> {
+        self.inner.has_receipt(tx_hash).await
+    }
+
+    fn submit_logs(
+        &self,
+        from_block: u64,
+        to_block: u64,
+        delay: Duration,
+    ) -> BoxStream<'_, Result<SubmitLog>> {
+        self.inner.submit_logs(from_block, to_block, delay)
+    }
+
+    async fn get_flow_root_by_tx_seq(&self, tx_seq: u64) -> Result<H256> {
+        self.inner.get_flow_root_by_tx_seq(tx_seq).await
+    }
+
+    async fn wait_for_new_block(&self, interval: Duration) {
+        if self.subscribed.load(Ordering::Relaxed) {
+            let _ = tokio::time::timeout(MAX_NEW_HEAD_WAIT, self.new_head.notified()).await;
+        } else {
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
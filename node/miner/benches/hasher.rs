@@ -0,0 +1,50 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use miner::hasher::{LaneBatchedHasher, PoraHasher, ScalarHasher, BLAKE2B_OUTPUT_BYTES};
+use rand::Rng;
+use zgs_spec::BYTES_PER_SEAL;
+
+const NUM_PAD_SEEDS: usize = 16;
+
+fn hashers() -> Vec<Box<dyn PoraHasher>> {
+    vec![Box::new(ScalarHasher), Box::new(LaneBatchedHasher)]
+}
+
+fn scratch_pad_performance(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    let pad_seeds: Vec<[u8; BLAKE2B_OUTPUT_BYTES]> = (0..NUM_PAD_SEEDS)
+        .map(|_| {
+            let mut seed = [0u8; BLAKE2B_OUTPUT_BYTES];
+            rng.fill(&mut seed[..]);
+            seed
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("scratch_pad");
+    group.throughput(Throughput::Elements(NUM_PAD_SEEDS as u64));
+    for hasher in hashers() {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(hasher.name()),
+            &pad_seeds,
+            |b, pad_seeds| b.iter(|| hasher.make_scratch_pads(pad_seeds)),
+        );
+    }
+    group.finish();
+}
+
+fn pora_performance(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    let mut mixed_data = [0u8; BYTES_PER_SEAL];
+    rng.fill(&mut mixed_data[..]);
+    let pad_seed = [1u8; BLAKE2B_OUTPUT_BYTES];
+
+    let mut group = c.benchmark_group("pora");
+    for hasher in hashers() {
+        group.bench_function(hasher.name(), |b| {
+            b.iter(|| hasher.pora(0, &mixed_data, &pad_seed))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, scratch_pad_performance, pora_performance);
+criterion_main!(benches);
@@ -1,17 +1,12 @@
-use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
-use contract_wrapper::SubmitConfig;
+use crate::MineEngineConfig;
+use contract_wrapper::{ProviderConfig, RpcProvider, SubmitConfig};
 use ethereum_types::{Address, H256};
 use ethers::core::k256::SecretKey;
 use ethers::middleware::SignerMiddleware;
-use ethers::providers::Http;
-use ethers::providers::HttpRateLimitRetryPolicy;
 use ethers::providers::Middleware;
-use ethers::providers::Provider;
-use ethers::providers::RetryClient;
-use ethers::providers::RetryClientBuilder;
 use ethers::signers::LocalWallet;
 use ethers::signers::Signer;
 use storage::config::ShardConfig;
@@ -19,7 +14,7 @@ use storage::config::ShardConfig;
 pub struct MinerConfig {
     pub(crate) miner_id: Option<H256>,
     pub(crate) miner_key: H256,
-    pub(crate) rpc_endpoint_url: String,
+    pub(crate) rpc_endpoint_urls: Vec<String>,
     pub(crate) mine_address: Address,
     pub(crate) flow_address: Address,
     pub(crate) cpu_percentage: u64,
@@ -30,16 +25,23 @@ pub struct MinerConfig {
     pub(crate) timeout_retries: u32,
     pub(crate) initial_backoff: u64,
     pub(crate) submission_config: SubmitConfig,
+    /// Whether to skip answers whose estimated reward is below the gas cost.
+    pub(crate) skip_unprofitable: bool,
+    /// The number of threads to seal data.
+    pub(crate) seal_threads: usize,
+    pub(crate) engine_config: MineEngineConfig,
+    /// Whether to mine the local flow without chain, where answers are not submitted.
+    pub(crate) dev_mode: bool,
 }
 
-pub type MineServiceMiddleware = SignerMiddleware<Arc<Provider<RetryClient<Http>>>, LocalWallet>;
+pub type MineServiceMiddleware = SignerMiddleware<Arc<RpcProvider>, LocalWallet>;
 
 impl MinerConfig {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         miner_id: Option<H256>,
         miner_key: Option<H256>,
-        rpc_endpoint_url: String,
+        rpc_endpoint_urls: Vec<String>,
         mine_address: Address,
         flow_address: Address,
         cpu_percentage: u64,
@@ -50,11 +52,15 @@ impl MinerConfig {
         timeout_retries: u32,
         initial_backoff: u64,
         submission_config: SubmitConfig,
+        skip_unprofitable: bool,
+        seal_threads: usize,
+        engine_config: MineEngineConfig,
+        dev_mode: bool,
     ) -> Option<MinerConfig> {
         miner_key.map(|miner_key| MinerConfig {
             miner_id,
             miner_key,
-            rpc_endpoint_url,
+            rpc_endpoint_urls,
             mine_address,
             flow_address,
             cpu_percentage,
@@ -65,21 +71,22 @@ impl MinerConfig {
             timeout_retries,
             initial_backoff,
             submission_config,
+            skip_unprofitable,
+            seal_threads,
+            engine_config,
+            dev_mode,
         })
     }
 
-    pub(crate) fn make_provider(&self) -> Result<Arc<Provider<RetryClient<Http>>>, String> {
-        Ok(Arc::new(Provider::new(
-            RetryClientBuilder::default()
-                .rate_limit_retries(self.rate_limit_retries)
-                .timeout_retries(self.timeout_retries)
-                .initial_backoff(Duration::from_millis(self.initial_backoff))
-                .build(
-                    Http::from_str(&self.rpc_endpoint_url)
-                        .map_err(|e| format!("Cannot parse blockchain endpoint: {:?}", e))?,
-                    Box::new(HttpRateLimitRetryPolicy),
-                ),
-        )))
+    pub(crate) fn make_provider(&self) -> Result<Arc<RpcProvider>, String> {
+        let config = ProviderConfig {
+            endpoints: self.rpc_endpoint_urls.clone(),
+            rate_limit_retries: self.rate_limit_retries,
+            timeout_retries: self.timeout_retries,
+            initial_backoff: self.initial_backoff,
+            request_timeout: None,
+        };
+        Ok(Arc::new(config.make_provider()?))
     }
 
     pub(crate) async fn make_signing_provider(&self) -> Result<MineServiceMiddleware, String> {
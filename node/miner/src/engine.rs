@@ -0,0 +1,116 @@
+use crate::hasher::{make_hasher, PoraHasher};
+use crate::metrics::SCRATCH_PAD_ITER_RATE;
+use crate::pora::{batch_nonce, AnswerWithoutProof, Miner};
+use ethereum_types::H256;
+use lighthouse_metrics::set_float_gauge;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::runtime::Handle;
+use tokio::sync::oneshot;
+
+#[derive(Clone, Debug)]
+pub struct MineEngineConfig {
+    /// The number of threads to mine.
+    pub threads: usize,
+    /// CPU cores to pin the mining threads to in turn, or not to pin if empty.
+    pub cpu_affinity: Vec<usize>,
+    /// Hasher implementation, i.e. `auto`, `scalar` or `batched`.
+    pub hasher: String,
+}
+
+impl Default for MineEngineConfig {
+    fn default() -> Self {
+        Self {
+            threads: 1,
+            cpu_affinity: vec![],
+            hasher: "auto".to_string(),
+        }
+    }
+}
+
+/// Thread pool to search PoRA answers, which spreads the nonces of a batch over the threads.
+pub(crate) struct MineEngine {
+    pool: rayon::ThreadPool,
+    hasher: Arc<dyn PoraHasher>,
+    runtime: Handle,
+}
+
+impl MineEngine {
+    pub fn new(config: &MineEngineConfig, runtime: Handle) -> Result<Self, String> {
+        let hasher = make_hasher(&config.hasher)?;
+        let cpu_affinity = config.cpu_affinity.clone();
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(config.threads.max(1))
+            .thread_name(|index| format!("pora_miner_{}", index))
+            .start_handler(move |index| {
+                if cpu_affinity.is_empty() {
+                    return;
+                }
+                let core = cpu_affinity[index % cpu_affinity.len()];
+                if !core_affinity::set_for_current(core_affinity::CoreId { id: core }) {
+                    warn!(index, core, "Failed to pin mining thread to CPU core");
+                }
+            })
+            .build()
+            .map_err(|e| format!("Failed to build mining thread pool: {:?}", e))?;
+
+        info!(
+            threads = pool.current_num_threads(),
+            cpu_affinity = ?config.cpu_affinity,
+            hasher = hasher.name(),
+            "Mine engine started"
+        );
+
+        Ok(Self {
+            pool,
+            hasher,
+            runtime,
+        })
+    }
+
+    /// Tries `batch_size` nonces derived from `nonce` per thread, and returns the first answer
+    /// found with the number of scratch pad iterations done.
+    ///
+    /// The scratch pad iteration rate of the batch is reported whether an answer is found or
+    /// not, counting only the iterations done before the answer.
+    pub async fn batch_iteration(
+        &self,
+        miner: Miner,
+        nonce: H256,
+        batch_size: usize,
+    ) -> (Option<AnswerWithoutProof>, usize) {
+        let num_nonces = batch_size * self.pool.current_num_threads();
+        let lanes = self.hasher.lanes();
+        let hasher = self.hasher.clone();
+        let runtime = self.runtime.clone();
+        let (answer_send, answer_recv) = oneshot::channel();
+
+        let timer = Instant::now();
+        self.pool.spawn(move || {
+            let iterations = AtomicUsize::new(0);
+            // nonces are grouped by the lanes of hasher, and the remaining groups are skipped
+            // once an answer is found
+            let answer = (0..num_nonces.div_ceil(lanes))
+                .into_par_iter()
+                .find_map_any(|group| {
+                    let nonces: Vec<_> = (group * lanes..num_nonces.min((group + 1) * lanes))
+                        .map(|index| batch_nonce(nonce, index))
+                        .collect();
+                    let (answer, group_iterations) =
+                        miner.iterations(hasher.as_ref(), &runtime, &nonces);
+                    iterations.fetch_add(group_iterations, Ordering::Relaxed);
+                    answer
+                });
+            let _ = answer_send.send((answer, iterations.into_inner()));
+        });
+        let (answer, iterations) = answer_recv.await.unwrap_or_default();
+
+        set_float_gauge(
+            &SCRATCH_PAD_ITER_RATE,
+            iterations as f64 / timer.elapsed().as_secs_f64(),
+        );
+        (answer, iterations)
+    }
+}
@@ -0,0 +1,382 @@
+//! Hashers to compute PoRA scratch pads and qualities.
+//!
+//! Building the scratch pad of a nonce takes `2 * BYTES_PER_SCRATCHPAD / 64` sequential keccak
+//! hashes, which dominates the CPU time of mining. Since the hashes of different nonces are
+//! independent, [`LaneBatchedHasher`] computes the scratch pads of several nonces together, one
+//! nonce per lane, so that the compiler can vectorize the keccak rounds over the lanes.
+
+use blake2::{Blake2b512, Digest};
+use ethereum_types::U256;
+use ethers::utils::keccak256;
+use std::sync::Arc;
+use zgs_spec::{BYTES_PER_SCRATCHPAD, BYTES_PER_SEAL};
+
+pub const BLAKE2B_OUTPUT_BYTES: usize = 64;
+pub const KECCAK256_OUTPUT_BYTES: usize = 32;
+
+const SCRATCH_PAD_CELLS: usize = BYTES_PER_SCRATCHPAD / BLAKE2B_OUTPUT_BYTES;
+
+pub struct ScratchPad {
+    pub scratch_pad: [u8; BYTES_PER_SCRATCHPAD],
+    pub recall_seed: [u8; KECCAK256_OUTPUT_BYTES],
+    pub pad_seed: [u8; BLAKE2B_OUTPUT_BYTES],
+}
+
+impl ScratchPad {
+    fn new(pad_seed: [u8; BLAKE2B_OUTPUT_BYTES]) -> Self {
+        Self {
+            scratch_pad: [0u8; BYTES_PER_SCRATCHPAD],
+            recall_seed: [0u8; KECCAK256_OUTPUT_BYTES],
+            pad_seed,
+        }
+    }
+}
+
+/// Hash functions of PoRA mining, which are implemented in different ways to be selected and
+/// benchmarked on the mining machine.
+pub trait PoraHasher: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// The number of nonces that are efficient to compute scratch pads together.
+    fn lanes(&self) -> usize;
+
+    /// Computes the scratch pad of each pad seed, which is the blake2b digest of the nonce and
+    /// mine context.
+    fn make_scratch_pads(&self, pad_seeds: &[[u8; BLAKE2B_OUTPUT_BYTES]]) -> Vec<ScratchPad>;
+
+    /// Computes the quality of the sealed data mixed with the scratch pad, where the smaller
+    /// value is the better.
+    fn pora(
+        &self,
+        seal_index: usize,
+        mixed_data: &[u8; BYTES_PER_SEAL],
+        pad_seed: &[u8; BLAKE2B_OUTPUT_BYTES],
+    ) -> U256 {
+        let mut hasher = Blake2b512::new();
+        hasher.update([0u8; 24]);
+        hasher.update((seal_index as u64).to_be_bytes());
+
+        hasher.update(pad_seed);
+        hasher.update([0u8; 32]);
+
+        hasher.update(mixed_data);
+
+        let digest = hasher.finalize();
+
+        U256::from_big_endian(&digest[0..32])
+    }
+}
+
+/// Returns the hasher of `name`, i.e. `scalar`, `batched`, or `auto` to use the lane-batched
+/// hasher if the CPU supports AVX2.
+pub fn make_hasher(name: &str) -> Result<Arc<dyn PoraHasher>, String> {
+    match name {
+        "scalar" => Ok(Arc::new(ScalarHasher)),
+        "batched" => Ok(Arc::new(LaneBatchedHasher)),
+        "auto" if avx2_available() => Ok(Arc::new(LaneBatchedHasher)),
+        "auto" => Ok(Arc::new(ScalarHasher)),
+        _ => Err(format!("Unknown miner hasher: {}", name)),
+    }
+}
+
+/// Returns whether the lanes can be computed with AVX2, which is only detected on x86_64.
+fn avx2_available() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_x86_feature_detected!("avx2")
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+/// Hasher that computes one scratch pad at a time.
+pub struct ScalarHasher;
+
+impl PoraHasher for ScalarHasher {
+    fn name(&self) -> &'static str {
+        "scalar"
+    }
+
+    fn lanes(&self) -> usize {
+        1
+    }
+
+    fn make_scratch_pads(&self, pad_seeds: &[[u8; BLAKE2B_OUTPUT_BYTES]]) -> Vec<ScratchPad> {
+        pad_seeds
+            .iter()
+            .map(|pad_seed| {
+                let mut pad = ScratchPad::new(*pad_seed);
+                let mut digest = *pad_seed;
+                for cell in pad.scratch_pad.chunks_exact_mut(BLAKE2B_OUTPUT_BYTES) {
+                    let output0 = keccak256(digest);
+                    digest[..32].copy_from_slice(&output0);
+                    let output1 = keccak256(digest);
+                    digest[32..].copy_from_slice(&output1);
+
+                    cell.copy_from_slice(&digest);
+                }
+                pad.recall_seed = keccak256(digest);
+                pad
+            })
+            .collect()
+    }
+}
+
+const LANES: usize = 4;
+
+type Lanes = [u64; LANES];
+
+/// Hasher that computes the scratch pads of [`LANES`] nonces together with a lane-batched
+/// keccak, which is portable code compiled with AVX2 enabled on x86_64 if the CPU supports it.
+/// There is no hand-written SIMD, so the speedup depends on how the compiler vectorizes it.
+pub struct LaneBatchedHasher;
+
+impl PoraHasher for LaneBatchedHasher {
+    fn name(&self) -> &'static str {
+        "batched"
+    }
+
+    fn lanes(&self) -> usize {
+        LANES
+    }
+
+    fn make_scratch_pads(&self, pad_seeds: &[[u8; BLAKE2B_OUTPUT_BYTES]]) -> Vec<ScratchPad> {
+        let mut pads: Vec<_> = pad_seeds
+            .iter()
+            .map(|seed| ScratchPad::new(*seed))
+            .collect();
+        for chunk in pads.chunks_mut(LANES) {
+            // unused lanes of the last chunk are computed and dropped
+            let mut digests = [[0u8; BLAKE2B_OUTPUT_BYTES]; LANES];
+            for (digest, pad) in digests.iter_mut().zip(chunk.iter()) {
+                *digest = pad.pad_seed;
+            }
+            make_scratch_pads_lanes(&mut digests, chunk);
+        }
+        pads
+    }
+}
+
+fn make_scratch_pads_lanes(
+    digests: &mut [[u8; BLAKE2B_OUTPUT_BYTES]; LANES],
+    pads: &mut [ScratchPad],
+) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // SAFETY: AVX2 is supported by the CPU.
+        unsafe { make_scratch_pads_avx2(digests, pads) };
+        return;
+    }
+
+    make_scratch_pads_generic(digests, pads)
+}
+
+/// [`make_scratch_pads_generic`] compiled with AVX2 enabled, so that the lane loops can be
+/// auto-vectorized.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn make_scratch_pads_avx2(
+    digests: &mut [[u8; BLAKE2B_OUTPUT_BYTES]; LANES],
+    pads: &mut [ScratchPad],
+) {
+    make_scratch_pads_generic(digests, pads)
+}
+
+/// Computes the scratch pads in the same way as [`ScalarHasher`], which is inlined to be
+/// compiled with the target features of the caller.
+#[inline(always)]
+fn make_scratch_pads_generic(
+    digests: &mut [[u8; BLAKE2B_OUTPUT_BYTES]; LANES],
+    pads: &mut [ScratchPad],
+) {
+    for cell in 0..SCRATCH_PAD_CELLS {
+        let output0 = keccak256_lanes(digests);
+        for (digest, output) in digests.iter_mut().zip(output0.iter()) {
+            digest[..32].copy_from_slice(output);
+        }
+        let output1 = keccak256_lanes(digests);
+        for (digest, output) in digests.iter_mut().zip(output1.iter()) {
+            digest[32..].copy_from_slice(output);
+        }
+
+        let range = cell * BLAKE2B_OUTPUT_BYTES..(cell + 1) * BLAKE2B_OUTPUT_BYTES;
+        for (pad, digest) in pads.iter_mut().zip(digests.iter()) {
+            pad.scratch_pad[range.clone()].copy_from_slice(digest);
+        }
+    }
+
+    let recall_seeds = keccak256_lanes(digests);
+    for (pad, recall_seed) in pads.iter_mut().zip(recall_seeds) {
+        pad.recall_seed = recall_seed;
+    }
+}
+
+/// Keccak256 of a 64-byte input in each lane.
+#[inline(always)]
+fn keccak256_lanes(
+    inputs: &[[u8; BLAKE2B_OUTPUT_BYTES]; LANES],
+) -> [[u8; KECCAK256_OUTPUT_BYTES]; LANES] {
+    let mut state = [[0u64; LANES]; 25];
+    for (lane, input) in inputs.iter().enumerate() {
+        for (word, bytes) in state.iter_mut().zip(input.chunks_exact(8)) {
+            word[lane] = u64::from_le_bytes(bytes.try_into().expect("8 bytes"));
+        }
+    }
+    // keccak padding of the 136-byte rate: 0x01 after the input and 0x80 at the last byte
+    state[8] = [0x01; LANES];
+    state[16] = [0x80 << 56; LANES];
+
+    keccak_f1600_lanes(&mut state);
+
+    let mut outputs = [[0u8; KECCAK256_OUTPUT_BYTES]; LANES];
+    for (lane, output) in outputs.iter_mut().enumerate() {
+        for (bytes, word) in output.chunks_exact_mut(8).zip(state.iter()) {
+            bytes.copy_from_slice(&word[lane].to_le_bytes());
+        }
+    }
+    outputs
+}
+
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808a,
+    0x8000000080008000,
+    0x000000000000808b,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008a,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000a,
+    0x000000008000808b,
+    0x800000000000008b,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800a,
+    0x800000008000000a,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+const RHO: [u32; 24] = [
+    1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44,
+];
+const PI: [usize; 24] = [
+    10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+];
+
+/// Keccak-f[1600] permutation of the state in each lane, where `state[x + 5 * y]` is the word
+/// at `(x, y)`.
+#[inline(always)]
+#[allow(clippy::needless_range_loop)]
+fn keccak_f1600_lanes(state: &mut [Lanes; 25]) {
+    for round_constant in ROUND_CONSTANTS {
+        // theta
+        let mut parity = [[0u64; LANES]; 5];
+        for x in 0..5 {
+            for lane in 0..LANES {
+                parity[x][lane] = state[x][lane]
+                    ^ state[x + 5][lane]
+                    ^ state[x + 10][lane]
+                    ^ state[x + 15][lane]
+                    ^ state[x + 20][lane];
+            }
+        }
+        for x in 0..5 {
+            for lane in 0..LANES {
+                let d = parity[(x + 4) % 5][lane] ^ parity[(x + 1) % 5][lane].rotate_left(1);
+                for y in 0..5 {
+                    state[x + 5 * y][lane] ^= d;
+                }
+            }
+        }
+
+        // rho and pi
+        let mut last = state[1];
+        for (rho, pi) in RHO.iter().zip(PI.iter()) {
+            let next = state[*pi];
+            for lane in 0..LANES {
+                state[*pi][lane] = last[lane].rotate_left(*rho);
+            }
+            last = next;
+        }
+
+        // chi
+        for y in 0..5 {
+            let row = [
+                state[5 * y],
+                state[5 * y + 1],
+                state[5 * y + 2],
+                state[5 * y + 3],
+                state[5 * y + 4],
+            ];
+            for x in 0..5 {
+                for lane in 0..LANES {
+                    state[5 * y + x][lane] =
+                        row[x][lane] ^ (!row[(x + 1) % 5][lane] & row[(x + 2) % 5][lane]);
+                }
+            }
+        }
+
+        // iota
+        for lane in 0..LANES {
+            state[0][lane] ^= round_constant;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pad_seeds(n: usize) -> Vec<[u8; BLAKE2B_OUTPUT_BYTES]> {
+        (0..n)
+            .map(|i| {
+                let mut seed = [0u8; BLAKE2B_OUTPUT_BYTES];
+                for (j, byte) in seed.iter_mut().enumerate() {
+                    *byte = (i * 31 + j * 7) as u8;
+                }
+                seed
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_keccak256_lanes() {
+        let inputs: [[u8; BLAKE2B_OUTPUT_BYTES]; LANES] = pad_seeds(LANES).try_into().unwrap();
+        let outputs = keccak256_lanes(&inputs);
+        for (input, output) in inputs.iter().zip(outputs.iter()) {
+            assert_eq!(*output, keccak256(input));
+        }
+    }
+
+    #[test]
+    fn test_lane_batched_hasher() {
+        // not a multiple of the lanes
+        let seeds = pad_seeds(LANES * 2 + 1);
+        let expected = ScalarHasher.make_scratch_pads(&seeds);
+        let pads = LaneBatchedHasher.make_scratch_pads(&seeds);
+
+        assert_eq!(pads.len(), seeds.len());
+        for (pad, expected) in pads.iter().zip(expected.iter()) {
+            assert_eq!(pad.pad_seed, expected.pad_seed);
+            assert_eq!(pad.recall_seed, expected.recall_seed);
+            assert!(pad.scratch_pad == expected.scratch_pad);
+        }
+    }
+
+    #[test]
+    fn test_make_hasher() {
+        assert_eq!(make_hasher("scalar").unwrap().name(), "scalar");
+        assert_eq!(make_hasher("batched").unwrap().lanes(), LANES);
+        assert!(make_hasher("auto").is_ok());
+        assert!(make_hasher("gpu").is_err());
+    }
+}
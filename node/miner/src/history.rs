@@ -0,0 +1,238 @@
+use crate::pora::AnswerWithoutProof;
+use ethereum_types::{H256, U256};
+use ethers::types::TransactionReceipt;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use storage::log_store::config::ConfigTx;
+use storage::log_store::log_manager::DATA_DB_KEY;
+use storage_async::Store;
+
+/// DB key for the number of submissions ever pushed to the history.
+const KEY_SUBMISSION_COUNT: &str = "mine.submission_history.count";
+
+/// The maximum number of submissions kept in the history, where the oldest ones are overwritten.
+const MAX_SUBMISSION_HISTORY: u64 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SubmissionStatus {
+    /// The submission transaction is executed successfully.
+    Confirmed,
+    /// The submission transaction is reverted on chain.
+    Reverted,
+    /// Failed to send the submission transaction.
+    Failed,
+    /// Not submitted because the estimated reward is below the gas cost.
+    Unprofitable,
+    /// The mine context changed before the transaction was sent.
+    Cancelled,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmissionReceipt {
+    pub tx_hash: H256,
+    pub block_number: Option<u64>,
+    pub gas_used: Option<U256>,
+    pub effective_gas_price: Option<U256>,
+}
+
+impl From<&TransactionReceipt> for SubmissionReceipt {
+    fn from(receipt: &TransactionReceipt) -> Self {
+        Self {
+            tx_hash: receipt.transaction_hash,
+            block_number: receipt.block_number.map(|n| n.as_u64()),
+            gas_used: receipt.gas_used,
+            effective_gas_price: receipt.effective_gas_price,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmissionRecord {
+    pub context_digest: H256,
+    pub miner_id: H256,
+    pub nonce: H256,
+    pub recall_position: u64,
+    /// Unix timestamp in seconds when the submission finished.
+    pub timestamp: u64,
+    pub status: SubmissionStatus,
+    pub estimated_reward: Option<U256>,
+    pub estimated_gas_cost: Option<U256>,
+    pub receipt: Option<SubmissionReceipt>,
+    pub error: Option<String>,
+}
+
+impl SubmissionRecord {
+    pub(crate) fn new(answer: &AnswerWithoutProof, status: SubmissionStatus) -> Self {
+        Self {
+            context_digest: answer.context_digest,
+            miner_id: answer.miner_id,
+            nonce: answer.nonce,
+            recall_position: answer.recall_position,
+            timestamp: 0,
+            status,
+            estimated_reward: None,
+            estimated_gas_cost: None,
+            receipt: None,
+            error: None,
+        }
+    }
+}
+
+/// DB key of the record at `index`, where the records are stored in a ring buffer.
+fn key_record(index: u64) -> String {
+    format!(
+        "mine.submission_history.record.{}",
+        index % MAX_SUBMISSION_HISTORY
+    )
+}
+
+async fn load_count(store: &Store) -> Result<u64, String> {
+    store
+        .get_config_decoded(&KEY_SUBMISSION_COUNT, DATA_DB_KEY)
+        .await
+        .map(|count| count.unwrap_or(0))
+        .map_err(|e| format!("Failed to load submission count: {:?}", e))
+}
+
+/// Loads the latest `limit` submitted answers from the db, ordered from the oldest to the
+/// latest.
+pub async fn load_submission_history(
+    store: &Store,
+    limit: Option<usize>,
+) -> Result<Vec<SubmissionRecord>, String> {
+    let count = load_count(store).await?;
+    let limit = limit.map_or(MAX_SUBMISSION_HISTORY, |limit| {
+        (limit as u64).min(MAX_SUBMISSION_HISTORY)
+    });
+
+    let mut records = vec![];
+    for index in count.saturating_sub(limit)..count {
+        let encoded: Option<Vec<u8>> = store
+            .get_config_decoded(&key_record(index), DATA_DB_KEY)
+            .await
+            .map_err(|e| format!("Failed to load submission record: {:?}", e))?;
+        let encoded = encoded.ok_or_else(|| format!("Submission record {} is missing", index))?;
+        records.push(
+            serde_json::from_slice(&encoded)
+                .map_err(|e| format!("Submission record on db corrupt: {:?}", e))?,
+        );
+    }
+
+    Ok(records)
+}
+
+/// History of submitted answers, where every submission is persisted as a separate record in
+/// the db.
+pub(crate) struct SubmissionHistory {
+    store: Arc<Store>,
+    count: u64,
+}
+
+impl SubmissionHistory {
+    /// Loads the history from the db, or starts a new one if it's corrupted.
+    pub async fn load(store: Arc<Store>) -> Self {
+        let count = match load_count(&store).await {
+            Ok(count) => count,
+            Err(e) => {
+                warn!(%e, "Start a new submission history");
+                0
+            }
+        };
+        Self { store, count }
+    }
+
+    pub async fn push(&mut self, mut record: SubmissionRecord) -> Result<(), String> {
+        record.timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let encoded = serde_json::to_vec(&record)
+            .map_err(|e| format!("Failed to encode submission record: {:?}", e))?;
+
+        let mut tx = ConfigTx::default();
+        tx.set_config(&key_record(self.count), &encoded);
+        tx.set_config(&KEY_SUBMISSION_COUNT, &(self.count + 1));
+        self.store
+            .exec_configs(tx, DATA_DB_KEY)
+            .await
+            .map_err(|e| format!("Failed to save submission record: {:?}", e))?;
+
+        self.count += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use storage::log_store::log_manager::LogConfig;
+    use storage::LogManager;
+    use task_executor::test_utils::TestRuntime;
+
+    fn record(recall_position: u64) -> SubmissionRecord {
+        SubmissionRecord {
+            context_digest: H256::repeat_byte(1),
+            miner_id: H256::repeat_byte(2),
+            nonce: H256::repeat_byte(3),
+            recall_position,
+            timestamp: 1700000000,
+            status: SubmissionStatus::Confirmed,
+            estimated_reward: Some(U256::from(100)),
+            estimated_gas_cost: Some(U256::from(10)),
+            receipt: Some(SubmissionReceipt {
+                tx_hash: H256::repeat_byte(4),
+                block_number: Some(10),
+                gas_used: Some(U256::from(1)),
+                effective_gas_price: None,
+            }),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_record_json() {
+        let record = record(1024);
+        let encoded = serde_json::to_vec(&record).unwrap();
+        let decoded: SubmissionRecord = serde_json::from_slice(&encoded).unwrap();
+        assert_eq!(decoded, record);
+    }
+
+    #[tokio::test]
+    async fn test_history() {
+        let runtime = TestRuntime::default();
+        let store = Arc::new(Store::new(
+            Arc::new(LogManager::memorydb(LogConfig::default()).unwrap()),
+            runtime.task_executor.clone(),
+        ));
+
+        let mut history = SubmissionHistory::load(store.clone()).await;
+        for i in 0..(MAX_SUBMISSION_HISTORY + 2) {
+            history.push(record(i)).await.unwrap();
+        }
+
+        // the oldest records are overwritten
+        let records = load_submission_history(&store, None).await.unwrap();
+        let positions: Vec<_> = records.iter().map(|r| r.recall_position).collect();
+        assert_eq!(
+            positions,
+            (2..MAX_SUBMISSION_HISTORY + 2).collect::<Vec<_>>()
+        );
+
+        let records = load_submission_history(&store, Some(2)).await.unwrap();
+        let positions: Vec<_> = records.iter().map(|r| r.recall_position).collect();
+        assert_eq!(
+            positions,
+            [MAX_SUBMISSION_HISTORY, MAX_SUBMISSION_HISTORY + 1]
+        );
+
+        // continues after a restart
+        let mut history = SubmissionHistory::load(store.clone()).await;
+        history.push(record(0)).await.unwrap();
+        let records = load_submission_history(&store, Some(1)).await.unwrap();
+        assert_eq!(records[0].recall_position, 0);
+    }
+}
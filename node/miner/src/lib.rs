@@ -5,6 +5,9 @@ extern crate contract_interface;
 extern crate lazy_static;
 
 mod config;
+mod engine;
+pub mod hasher;
+mod history;
 mod loader;
 mod metrics;
 mod mine;
@@ -14,12 +17,20 @@ pub mod pora;
 mod recall_range;
 mod sealer;
 mod service;
+pub mod simulator;
+mod status;
 mod submitter;
 mod watcher;
 
 pub use config::MinerConfig;
+pub use engine::MineEngineConfig;
+pub use history::{load_submission_history, SubmissionReceipt, SubmissionRecord, SubmissionStatus};
 pub use loader::PoraLoader;
 pub use mine::MineRangeConfig;
 pub use miner_id::load_miner_id;
 pub use service::{MineService, MinerMessage};
+pub use status::{
+    AnswerStatus, MinerStatus, MinerStatusHandle, PuzzleStatus, RecallRangeStatus, SealingStatus,
+    SubmissionStats,
+};
 pub use storage::config::ShardConfig;
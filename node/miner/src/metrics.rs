@@ -1,4 +1,7 @@
-use lighthouse_metrics::{try_create_int_counter, IntCounter, Result};
+use lighthouse_metrics::{
+    try_create_float_gauge, try_create_float_gauge_vec, try_create_int_counter,
+    try_create_int_gauge, Gauge, GaugeVec, IntCounter, IntGauge, Result,
+};
 
 lazy_static! {
     pub static ref SCRATCH_PAD_ITER_COUNT: Result<IntCounter> = try_create_int_counter(
@@ -15,6 +18,21 @@ lazy_static! {
     );
     pub static ref HIT_COUNT: Result<IntCounter> =
         try_create_int_counter("miner_hit", "Number of hit for PoRA");
+    pub static ref SCRATCH_PAD_ITER_RATE: Result<Gauge> = try_create_float_gauge(
+        "miner_scratch_pad_iter_rate",
+        "Scratch pad iterations per second of all mining threads in the last mining batch"
+    );
+    pub static ref SEALED_COUNT: Result<IntCounter> =
+        try_create_int_counter("miner_sealed", "Number of sealed seals");
+    pub static ref SEAL_PENDING_TASKS: Result<IntGauge> = try_create_int_gauge(
+        "miner_seal_pending_tasks",
+        "Number of seals in the store waiting to seal"
+    );
+    pub static ref SEALED_FRACTION: Result<GaugeVec> = try_create_float_gauge_vec(
+        "miner_sealed_fraction",
+        "Fraction of the seals stored in the shard that are sealed",
+        &["shard"]
+    );
 }
 
 pub fn report() -> String {
@@ -22,11 +40,16 @@ pub fn report() -> String {
         Ok(x) => format!("{}", x.get()),
         Err(_) => "n/a".to_string(),
     };
+    let iter_rate = match &*SCRATCH_PAD_ITER_RATE {
+        Ok(x) => format!("{:.1}", x.get()),
+        Err(_) => "n/a".to_string(),
+    };
     format!(
-        "scratch pad: {}, loading: {}, pad_mix: {}, hit: {}",
+        "scratch pad: {}, loading: {}, pad_mix: {}, hit: {}, scratch pad rate: {}/s",
         s(&SCRATCH_PAD_ITER_COUNT),
         s(&LOADING_COUNT),
         s(&PAD_MIX_COUNT),
-        s(&HIT_COUNT)
+        s(&HIT_COUNT),
+        iter_rate
     )
 }
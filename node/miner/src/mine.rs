@@ -9,7 +9,9 @@ use tokio::time::{sleep, Duration, Instant};
 use storage::config::ShardConfig;
 use zgs_spec::{SECTORS_PER_LOAD, SECTORS_PER_MAX_MINING_RANGE, SECTORS_PER_PRICING};
 
+use crate::engine::MineEngine;
 use crate::recall_range::RecallRange;
+use crate::status::{MinerStatusHandle, PuzzleStatus};
 use crate::{
     pora::{AnswerWithoutProof, Miner},
     watcher::MineContextMessage,
@@ -30,6 +32,8 @@ pub struct PoraService {
 
     cpu_percentage: u64,
     iter_batch: usize,
+    engine: MineEngine,
+    status: MinerStatusHandle,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -55,6 +59,10 @@ impl PoraPuzzle {
         }
     }
 
+    pub fn context(&self) -> &MineContext {
+        &self.context
+    }
+
     pub fn max_shards(&self) -> u64 {
         self.max_shards
     }
@@ -62,6 +70,16 @@ impl PoraPuzzle {
     pub fn context_digest(&self) -> H256 {
         H256(self.context.digest)
     }
+
+    pub fn status(&self) -> PuzzleStatus {
+        PuzzleStatus {
+            context_digest: self.context_digest(),
+            epoch: self.context.epoch,
+            flow_length: self.context.flow_length,
+            pora_target: self.pora_target,
+            max_shards: self.max_shards,
+        }
+    }
 }
 #[derive(Clone, Debug, Default)]
 pub struct MineRangeConfig {
@@ -71,8 +89,17 @@ pub struct MineRangeConfig {
 }
 
 impl MineRangeConfig {
+    /// Mines the whole flow in the shard.
+    pub(crate) fn new(shard_config: ShardConfig) -> Self {
+        Self {
+            start_position: Some(0),
+            end_position: Some(u64::MAX),
+            shard_config,
+        }
+    }
+
     #[inline]
-    fn to_valid_range(&self, context: &MineContext) -> Option<RecallRange> {
+    pub(crate) fn to_valid_range(&self, context: &MineContext) -> Option<RecallRange> {
         let self_start_position = self.start_position?;
         let self_end_position = self.end_position?;
 
@@ -123,14 +150,13 @@ impl PoraService {
         loader: Arc<dyn PoraLoader>,
         config: &MinerConfig,
         miner_id: H256,
-    ) -> mpsc::UnboundedReceiver<AnswerWithoutProof> {
+        status: MinerStatusHandle,
+    ) -> Result<mpsc::UnboundedReceiver<AnswerWithoutProof>, String> {
+        let runtime = executor.handle().ok_or("runtime is shut down")?;
+        let engine = MineEngine::new(&config.engine_config, runtime)?;
         let (mine_answer_sender, mine_answer_receiver) =
             mpsc::unbounded_channel::<AnswerWithoutProof>();
-        let mine_range = MineRangeConfig {
-            start_position: Some(0),
-            end_position: Some(u64::MAX),
-            shard_config: config.shard_config,
-        };
+        let mine_range = MineRangeConfig::new(config.shard_config);
         let pora = PoraService {
             mine_context_receiver,
             mine_answer_sender,
@@ -141,14 +167,18 @@ impl PoraService {
             loader,
             cpu_percentage: config.cpu_percentage,
             iter_batch: config.iter_batch,
+            engine,
+            status,
         };
         executor.spawn(async move { Box::pin(pora.start()).await }, "pora_master");
-        mine_answer_receiver
+        Ok(mine_answer_receiver)
     }
 
     async fn start(mut self) {
         let mut mining_enabled = true;
         let mut channel_opened = true;
+        self.status.set_mining_enabled(mining_enabled);
+        self.report_reason_if_mine_stop("start");
 
         let cpu_percent: u64 = self.cpu_percentage;
         let diastole = sleep(Duration::from_secs(0));
@@ -164,6 +194,7 @@ impl PoraService {
                         Ok(MinerMessage::ToggleMining(enable)) => {
                             info!("Toggle mining: {}", if enable { "on" } else { "off" });
                             mining_enabled = enable;
+                            self.status.set_mining_enabled(enable);
                         }
                         Ok(MinerMessage::SetStartPosition(pos)) => {
                             info!("Change start position to: {:?}", pos);
@@ -216,16 +247,17 @@ impl PoraService {
 
                     let timer = time::Instant::now();
 
-                    if let Some(answer) = miner.batch_iteration(nonce, self.iter_batch).await {
+                    let (answer, _) =
+                        self.engine.batch_iteration(miner, nonce, self.iter_batch).await;
+                    if let Some(answer) = answer {
                         info!("Hit Pora answer {:?}", answer);
+                        self.status.add_answer(&answer);
                         if self.mine_answer_sender.send(answer).is_err() {
                             warn!("Mine submitter channel closed");
                         }
                     } else if cpu_percent < 100 {
-                        // 2^64 ns = 500 years
-                        let elapsed = timer.elapsed().as_nanos() as u64;
-                        let diastole_time = elapsed / cpu_percent * (100 - cpu_percent);
-                        diastole.as_mut().reset(Instant::now() + Duration::from_nanos(diastole_time));
+                        let diastole_time = diastole_time(timer.elapsed(), cpu_percent);
+                        diastole.as_mut().reset(Instant::now() + diastole_time);
                     }
                 }
             }
@@ -259,18 +291,28 @@ impl PoraService {
 
         Ok(Miner {
             range,
-            miner_id: &self.miner_id,
-            mine_range_config: &self.mine_range,
-            context: &puzzle.context,
-            subtask_digest: &puzzle.subtask_digest,
-            pora_target: &puzzle.pora_target,
-            loader: &*self.loader,
+            miner_id: self.miner_id,
+            mine_range_config: self.mine_range.clone(),
+            context: puzzle.context.clone(),
+            subtask_digest: puzzle.subtask_digest,
+            pora_target: puzzle.pora_target,
+            loader: self.loader.clone(),
         })
     }
 
     fn report_reason_if_mine_stop(&self, event: &'static str) {
-        if let Err(reason) = self.as_miner() {
+        let range = self.as_miner().map(|miner| miner.range);
+        self.status.set_puzzle(self.puzzle.as_ref(), range);
+        if let Err(reason) = range {
             info!(reason, "Mine stopped on {}", event);
         }
     }
 }
+
+/// Returns how long to rest after mining for `busy`, so that mining only takes
+/// `cpu_percentage` (which is positive) of the CPU time.
+pub(crate) fn diastole_time(busy: Duration, cpu_percentage: u64) -> Duration {
+    // 2^64 ns = 500 years
+    let busy = busy.as_nanos() as u64;
+    Duration::from_nanos(busy / cpu_percentage * 100u64.saturating_sub(cpu_percentage))
+}
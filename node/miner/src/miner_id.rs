@@ -4,6 +4,7 @@ use contract_interface::{NewMinerIdFilter, PoraMine};
 use ethereum_types::Address;
 use ethers::contract::ContractCall;
 use ethers::contract::EthEvent;
+use ethers::utils::keccak256;
 use std::sync::Arc;
 use storage::log_store::log_manager::DATA_DB_KEY;
 use storage::H256;
@@ -66,6 +67,32 @@ pub(crate) async fn check_and_request_miner_id(
     }
 }
 
+/// Returns the miner id in dev mode, which is not registered on chain and defaults to the hash of
+/// the miner key.
+pub(crate) async fn load_or_make_dev_miner_id(
+    config: &MinerConfig,
+    store: &Store,
+) -> Result<H256, String> {
+    let db_miner_id = load_miner_id(store)
+        .await
+        .map_err(|e| format!("miner_id on db corrupt: {:?}", e))?;
+
+    match (db_miner_id, config.miner_id) {
+        (Some(d_id), Some(c_id)) if d_id != c_id => Err(format!(
+            "database miner id {} != configuration miner id {}",
+            d_id, c_id
+        )),
+        (Some(d_id), _) => Ok(d_id),
+        (None, c_id) => {
+            let id = c_id.unwrap_or_else(|| H256(keccak256(config.miner_key)));
+            set_miner_id(store, &id)
+                .await
+                .map_err(|e| format!("set miner id on db corrupt: {:?}", e))?;
+            Ok(id)
+        }
+    }
+}
+
 async fn check_miner_id(
     mine_contract: &PoraMine<MineServiceMiddleware>,
     miner_id: H256,
@@ -1,34 +1,28 @@
 use super::metrics::*;
+use crate::hasher::{PoraHasher, ScratchPad};
 use crate::recall_range::RecallRange;
 use crate::{MineRangeConfig, PoraLoader};
 use blake2::{Blake2b512, Digest};
 use contract_interface::pora_mine::MineContext;
 use ethereum_types::{H256, U256};
-use ethers::utils::keccak256;
 use lighthouse_metrics::inc_counter;
+use std::sync::Arc;
 use storage::log_store::MineLoadChunk;
-use tiny_keccak::{Hasher, Keccak};
+use tokio::runtime::Handle;
 use zgs_spec::{BYTES_PER_SCRATCHPAD, BYTES_PER_SEAL, SECTORS_PER_LOAD, SECTORS_PER_SEAL};
 
-pub const BLAKE2B_OUTPUT_BYTES: usize = 64;
-pub const KECCAK256_OUTPUT_BYTES: usize = 32;
+pub use crate::hasher::{BLAKE2B_OUTPUT_BYTES, KECCAK256_OUTPUT_BYTES};
 
-fn keccak(input: impl AsRef<[u8]>) -> [u8; KECCAK256_OUTPUT_BYTES] {
-    let mut hasher = Keccak::v256();
-    let mut output = [0u8; 32];
-    hasher.update(input.as_ref());
-    hasher.finalize(&mut output);
-    output
-}
-
-pub(crate) struct Miner<'a> {
+/// Puzzle to mine with, which is shared by the mining threads.
+#[derive(Clone)]
+pub(crate) struct Miner {
     pub range: RecallRange,
-    pub miner_id: &'a H256,
-    pub context: &'a MineContext,
-    pub subtask_digest: &'a H256,
-    pub pora_target: &'a U256,
-    pub loader: &'a dyn PoraLoader,
-    pub mine_range_config: &'a MineRangeConfig,
+    pub miner_id: H256,
+    pub context: MineContext,
+    pub subtask_digest: H256,
+    pub pora_target: U256,
+    pub loader: Arc<dyn PoraLoader>,
+    pub mine_range_config: MineRangeConfig,
 }
 #[derive(Debug)]
 pub struct AnswerWithoutProof {
@@ -42,32 +36,54 @@ pub struct AnswerWithoutProof {
     pub sealed_data: [u8; BYTES_PER_SEAL],
 }
 
-impl<'a> Miner<'a> {
-    pub async fn batch_iteration(
+/// Returns the `index`-th nonce of a batch starting from `nonce`.
+pub(crate) fn batch_nonce(nonce: H256, index: usize) -> H256 {
+    let mut current_nonce = nonce;
+    for (pos, b) in index.to_ne_bytes().into_iter().enumerate() {
+        current_nonce.0[pos] ^= b;
+    }
+    current_nonce
+}
+
+impl Miner {
+    /// Tries the nonces, and returns the first answer found along with the number of scratch pad
+    /// iterations done, which stop at the answer.
+    ///
+    /// This blocks the current thread to load sealed data from `runtime`, so it must run
+    /// outside of the async runtime.
+    pub fn iterations(
         &self,
-        nonce: H256,
-        batch_size: usize,
-    ) -> Option<AnswerWithoutProof> {
-        for i in 0..batch_size {
-            let bytes = i.to_ne_bytes();
-            let mut current_nonce = nonce;
-            for (pos, b) in bytes.into_iter().enumerate() {
-                current_nonce.0[pos] ^= b;
-            }
-            if let Some(answer) = self.iteration(current_nonce).await {
-                return Some(answer);
-            }
-        }
-        None
+        hasher: &dyn PoraHasher,
+        runtime: &Handle,
+        nonces: &[H256],
+    ) -> (Option<AnswerWithoutProof>, usize) {
+        let pad_seeds: Vec<_> = nonces.iter().map(|nonce| self.pad_seed(nonce)).collect();
+        let scratch_pads = hasher.make_scratch_pads(&pad_seeds);
+
+        let mut iterations = 0;
+        let answer = nonces
+            .iter()
+            .zip(scratch_pads)
+            .find_map(|(nonce, scratch_pad)| {
+                iterations += 1;
+                self.iteration(hasher, runtime, *nonce, scratch_pad)
+            });
+        (answer, iterations)
     }
 
-    pub async fn iteration(&self, nonce: H256) -> Option<AnswerWithoutProof> {
+    fn iteration(
+        &self,
+        hasher: &dyn PoraHasher,
+        runtime: &Handle,
+        nonce: H256,
+        scratch_pad: ScratchPad,
+    ) -> Option<AnswerWithoutProof> {
         inc_counter(&SCRATCH_PAD_ITER_COUNT);
         let ScratchPad {
             scratch_pad,
             recall_seed,
             pad_seed,
-        } = self.make_scratch_pad(&nonce);
+        } = scratch_pad;
 
         let recall_position = self.range.load_position(recall_seed)?;
         if !self.mine_range_config.is_covered(recall_position).unwrap() {
@@ -82,10 +98,10 @@ impl<'a> Miner<'a> {
         let MineLoadChunk {
             loaded_chunk,
             availabilities,
-        } = self
-            .loader
-            .load_sealed_data(recall_position / SECTORS_PER_LOAD as u64)
-            .await?;
+        } = runtime.block_on(
+            self.loader
+                .load_sealed_data(recall_position / SECTORS_PER_LOAD as u64),
+        )?;
 
         let scratch_pad: [[u8; BYTES_PER_SEAL]; BYTES_PER_SCRATCHPAD / BYTES_PER_SEAL] =
             unsafe { std::mem::transmute(scratch_pad) };
@@ -103,7 +119,7 @@ impl<'a> Miner<'a> {
                 *x ^= y;
             }
 
-            let quality = self.pora(idx, &sealed_data, pad_seed);
+            let quality = hasher.pora(idx, &sealed_data, &pad_seed);
             let difficulty_scale_x64 = self
                 .range
                 .difficulty_scale_x64(self.context.flow_length.as_u64());
@@ -124,7 +140,7 @@ impl<'a> Miner<'a> {
                     context_digest: H256::from(self.context.digest),
                     context_flow_root: self.context.flow_root.into(),
                     nonce,
-                    miner_id: *self.miner_id,
+                    miner_id: self.miner_id,
                     range: self.range,
                     recall_position: recall_position + idx as u64 * SECTORS_PER_SEAL as u64,
                     seal_offset: idx,
@@ -135,63 +151,12 @@ impl<'a> Miner<'a> {
         None
     }
 
-    fn make_scratch_pad(&self, nonce: &H256) -> ScratchPad {
-        let mut digest: [u8; BLAKE2B_OUTPUT_BYTES] = {
-            let mut hasher = Blake2b512::new();
-            hasher.update(self.miner_id);
-            hasher.update(nonce);
-            hasher.update(self.subtask_digest);
-            hasher.update(self.range.digest());
-            hasher.finalize().into()
-        };
-
-        let pad_seed = digest;
-
-        let mut scratch_pad =
-            [[0u8; BLAKE2B_OUTPUT_BYTES]; BYTES_PER_SCRATCHPAD / BLAKE2B_OUTPUT_BYTES];
-        for scratch_pad_cell in scratch_pad.iter_mut() {
-            let output0 = keccak256(digest);
-            digest[..32].copy_from_slice(&output0);
-            let output1 = keccak256(digest);
-            digest[32..].copy_from_slice(&output1);
-
-            *scratch_pad_cell = digest;
-        }
-
-        let scratch_pad: [u8; BYTES_PER_SCRATCHPAD] = unsafe { std::mem::transmute(scratch_pad) };
-        let recall_seed: [u8; KECCAK256_OUTPUT_BYTES] = keccak(digest);
-
-        ScratchPad {
-            scratch_pad,
-            recall_seed,
-            pad_seed,
-        }
-    }
-
-    #[inline]
-    fn pora(
-        &self,
-        seal_index: usize,
-        mixed_data: &[u8; BYTES_PER_SEAL],
-        pad_seed: [u8; BLAKE2B_OUTPUT_BYTES],
-    ) -> U256 {
+    fn pad_seed(&self, nonce: &H256) -> [u8; BLAKE2B_OUTPUT_BYTES] {
         let mut hasher = Blake2b512::new();
-        hasher.update([0u8; 24]);
-        hasher.update((seal_index as u64).to_be_bytes());
-
-        hasher.update(pad_seed);
-        hasher.update([0u8; 32]);
-
-        hasher.update(mixed_data);
-
-        let digest = hasher.finalize();
-
-        U256::from_big_endian(&digest[0..32])
+        hasher.update(self.miner_id);
+        hasher.update(nonce);
+        hasher.update(self.subtask_digest);
+        hasher.update(self.range.digest());
+        hasher.finalize().into()
     }
 }
-
-struct ScratchPad {
-    scratch_pad: [u8; BYTES_PER_SCRATCHPAD],
-    recall_seed: [u8; KECCAK256_OUTPUT_BYTES],
-    pad_seed: [u8; BLAKE2B_OUTPUT_BYTES],
-}
@@ -1,50 +1,89 @@
-use std::{collections::BTreeMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
 
 use ethereum_types::H256;
-use ethers::prelude::{Http, Provider, RetryClient};
+use futures::future::join_all;
+use lighthouse_metrics::{inc_counter_by, set_float_gauge_vec, set_gauge};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use tokio::sync::oneshot;
 use tokio::time::{sleep, Duration, Instant};
 
 use contract_interface::{EpochRangeWithContextDigest, ZgsFlow};
+use contract_wrapper::RpcProvider;
 use storage::{
     error::Result,
     log_store::{SealAnswer, SealTask},
 };
 use storage_async::Store;
 use task_executor::TaskExecutor;
-use zgs_spec::SECTORS_PER_SEAL;
+use zgs_spec::{SEALS_PER_LOAD, SECTORS_PER_SEAL};
 
 use crate::config::MinerConfig;
+use crate::metrics::{SEALED_COUNT, SEALED_FRACTION, SEAL_PENDING_TASKS};
+use crate::status::MinerStatusHandle;
 
 const DB_QUERY_PERIOD_ON_NO_TASK: u64 = 1;
 const DB_QUERY_PERIOD_ON_ERROR: u64 = 5;
 const CHAIN_STATUS_QUERY_PERIOD: u64 = 5;
+const MAX_PARALLEL_CONTEXT_QUERIES: usize = 8;
+
+/// The seal context in dev mode, where the whole flow is sealed with a single context since
+/// there are no epochs on chain.
+const DEV_SEAL_CONTEXT: EpochRangeWithContextDigest = EpochRangeWithContextDigest {
+    start: 0,
+    end: u64::MAX as u128,
+    digest: [0u8; 32],
+};
 
 pub struct Sealer {
-    flow_contract: ZgsFlow<Provider<RetryClient<Http>>>,
+    /// `None` in dev mode, where the data is sealable once synced to store.
+    flow_contract: Option<ZgsFlow<RpcProvider>>,
     store: Arc<Store>,
     context_cache: BTreeMap<u128, EpochRangeWithContextDigest>,
     last_context_flow_length: u64,
     miner_id: H256,
+    status: MinerStatusHandle,
+    /// Thread pool to seal the tasks of an iteration in parallel.
+    pool: rayon::ThreadPool,
 }
 
 impl Sealer {
+    /// Spawns the sealer, which seals with the contexts on chain, or with [`DEV_SEAL_CONTEXT`] if
+    /// `provider` is `None` in dev mode.
     pub fn spawn(
         executor: TaskExecutor,
-        provider: Arc<Provider<RetryClient<Http>>>,
+        provider: Option<Arc<RpcProvider>>,
         store: Arc<Store>,
         config: &MinerConfig,
         miner_id: H256,
-    ) {
-        let flow_contract = ZgsFlow::new(config.flow_address, provider);
+        status: MinerStatusHandle,
+    ) -> Result<(), String> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(config.seal_threads.max(1))
+            .thread_name(|index| format!("data_sealer_{}", index))
+            .build()
+            .map_err(|e| format!("Failed to build sealing thread pool: {:?}", e))?;
+        info!(threads = pool.current_num_threads(), "Data sealer started");
+
+        let flow_contract = provider.map(|provider| ZgsFlow::new(config.flow_address, provider));
+        let mut context_cache = BTreeMap::new();
+        if flow_contract.is_none() {
+            context_cache.insert(DEV_SEAL_CONTEXT.start, DEV_SEAL_CONTEXT);
+        }
         let sealer = Sealer {
             flow_contract,
             store,
-            context_cache: Default::default(),
+            context_cache,
             last_context_flow_length: 0,
             miner_id,
+            status,
+            pool,
         };
 
         executor.spawn(async move { Box::pin(sealer.start()).await }, "data_sealer");
+        Ok(())
     }
 
     async fn start(mut self) {
@@ -86,13 +125,16 @@ impl Sealer {
     }
 
     async fn update_flow_length(&mut self) -> Result<()> {
-        let recent_context = self.flow_contract.make_context_with_result().call().await?;
+        let flow_contract = match &self.flow_contract {
+            Some(flow_contract) => flow_contract,
+            None => return self.update_dev_flow_length().await,
+        };
+        let recent_context = flow_contract.make_context_with_result().call().await?;
         debug!("Recent context is {:?}", recent_context);
 
         let recent_flow_length = recent_context.flow_length.as_u64();
         if self.last_context_flow_length < recent_flow_length {
-            let epoch_range = self
-                .flow_contract
+            let epoch_range = flow_contract
                 .get_epoch_range(recent_context.digest)
                 .call()
                 .await?;
@@ -105,53 +147,133 @@ impl Sealer {
                 },
             );
             self.last_context_flow_length = recent_flow_length;
+            self.status.set_sealable_flow_length(recent_flow_length);
             info!(target: "seal", "Update sealable flow length: {}", recent_flow_length)
         }
         Ok(())
     }
 
-    async fn fetch_context(&mut self, seal_index: u64) -> Result<Option<(H256, u64)>> {
-        let last_entry = ((seal_index as usize + 1) * SECTORS_PER_SEAL - 1) as u128;
-        if self.last_context_flow_length <= last_entry as u64 {
-            return Ok(None);
+    /// In dev mode, all the synced flow is sealable with [`DEV_SEAL_CONTEXT`], which is always
+    /// cached.
+    async fn update_dev_flow_length(&mut self) -> Result<()> {
+        let (_, flow_length) = self.store.get_context().await?;
+        if self.last_context_flow_length < flow_length {
+            self.last_context_flow_length = flow_length;
+            self.status.set_sealable_flow_length(flow_length);
+            info!(target: "seal", "Update sealable flow length: {}", flow_length)
         }
+        Ok(())
+    }
+
+    /// Return the cached context of the last entry of a seal, or `None` if it is not cached.
+    fn cached_context(&self, last_entry: u128) -> Option<(H256, u64)> {
+        let (_, context) = self.context_cache.range(..=last_entry).next_back()?;
+        (context.start <= last_entry && context.end > last_entry).then(|| {
+            (
+                H256(context.digest),
+                context.end as u64 / SECTORS_PER_SEAL as u64,
+            )
+        })
+    }
+
+    async fn fetch_task(&self) -> Result<Option<Vec<SealTask>>> {
+        let seal_index_max = self.last_context_flow_length as usize / SECTORS_PER_SEAL;
+        // Pull a load chunk for each sealing thread.
+        let max_seals = self.pool.current_num_threads() * SEALS_PER_LOAD;
+        self.store.pull_seal_chunk(seal_index_max, max_seals).await
+    }
 
-        if let Some((_, context)) = self.context_cache.range(..=last_entry).next_back() {
-            if context.start <= last_entry && context.end > last_entry {
-                return Ok(Some((
-                    H256(context.digest),
-                    context.end as u64 / SECTORS_PER_SEAL as u64,
-                )));
+    /// Resolves the contexts of all tasks before sealing, so that the sealing threads never wait
+    /// for the chain. As a context covers a range of consecutive tasks, each round queries the
+    /// contexts at a few positions spread over the tasks whose context is not cached, in
+    /// parallel. The tasks whose context is not on chain yet are dropped.
+    async fn prefetch_contexts(&mut self, tasks: Vec<SealTask>) -> Vec<(SealTask, H256, u64)> {
+        let last_entries: Vec<u128> = tasks
+            .iter()
+            .map(|task| ((task.seal_index as usize + 1) * SECTORS_PER_SEAL - 1) as u128)
+            .collect();
+        let mut queried = BTreeSet::new();
+        loop {
+            let missing: Vec<u128> = last_entries
+                .iter()
+                .copied()
+                .filter(|&last_entry| {
+                    (last_entry as u64) < self.last_context_flow_length
+                        && !queried.contains(&last_entry)
+                        && self.cached_context(last_entry).is_none()
+                })
+                .collect();
+            if missing.is_empty() {
+                break;
             }
-        }
 
-        let context = match self
-            .flow_contract
-            .query_context_at_position(last_entry)
-            .call()
-            .await
-        {
-            Ok(context) => context,
-            Err(err) => {
-                info!("Error when fetch entries {:?}", err);
-                return Ok(None);
+            let flow_contract = match &self.flow_contract {
+                Some(flow_contract) => flow_contract,
+                None => break,
+            };
+            let step = missing.len().div_ceil(MAX_PARALLEL_CONTEXT_QUERIES);
+            let calls: Vec<_> = missing
+                .into_iter()
+                .step_by(step)
+                .map(|last_entry| {
+                    queried.insert(last_entry);
+                    flow_contract.query_context_at_position(last_entry)
+                })
+                .collect();
+            let results = join_all(calls.iter().map(|call| call.call())).await;
+            for result in results {
+                match result {
+                    Ok(context) => {
+                        info!(
+                            target: "seal", "Fetch new context: range {} -> {}",
+                            context.start, context.end
+                        );
+                        self.context_cache.insert(context.start, context);
+                    }
+                    Err(err) => info!("Error when fetch context {:?}", err),
+                }
             }
-        };
-        info!(
-            target:"seal", "Fetch new context: range {} -> {}",
-            context.start, context.end
-        );
-        self.context_cache.insert(context.start, context.clone());
+        }
 
-        Ok(Some((
-            H256(context.digest),
-            context.end as u64 / SECTORS_PER_SEAL as u64,
-        )))
+        let mut ready = Vec::with_capacity(tasks.len());
+        for (task, last_entry) in tasks.into_iter().zip(last_entries) {
+            match self.cached_context(last_entry) {
+                Some((context_digest, end_seal)) => ready.push((task, context_digest, end_seal)),
+                None => {
+                    trace!(target: "seal", "Index {} is not ready for seal", task.seal_index);
+                }
+            }
+        }
+        ready
     }
 
-    async fn fetch_task(&self) -> Result<Option<Vec<SealTask>>> {
-        let seal_index_max = self.last_context_flow_length as usize / SECTORS_PER_SEAL;
-        self.store.pull_seal_chunk(seal_index_max).await
+    async fn seal(&self, tasks: Vec<(SealTask, H256, u64)>) -> Result<Vec<SealAnswer>> {
+        let miner_id = self.miner_id;
+        let (answer_send, answer_recv) = oneshot::channel();
+        self.pool.spawn(move || {
+            let answers = tasks
+                .into_par_iter()
+                .map(|(task, context_digest, end_seal)| {
+                    let mut data = task.non_sealed_data;
+                    zgs_seal::seal(
+                        &mut data,
+                        &miner_id,
+                        &context_digest,
+                        task.seal_index * SECTORS_PER_SEAL as u64,
+                    );
+                    SealAnswer {
+                        seal_index: task.seal_index,
+                        version: task.version,
+                        sealed_data: data,
+                        miner_id,
+                        seal_context: context_digest,
+                        context_end_seal: end_seal,
+                    }
+                })
+                .collect();
+            let _ = answer_send.send(answers);
+        });
+        Ok(answer_recv.await?)
     }
 
     async fn submit_answer(&self, answers: Vec<SealAnswer>) -> Result<()> {
@@ -162,6 +284,7 @@ impl Sealer {
         let tasks = match self.fetch_task().await? {
             Some(tasks) if !tasks.is_empty() => tasks,
             _ => {
+                self.report_progress().await?;
                 return Ok(false);
             }
         };
@@ -171,35 +294,39 @@ impl Sealer {
             tasks.iter().map(|x| x.seal_index).collect::<Vec<u64>>()
         );
 
-        let mut answers = Vec::with_capacity(tasks.len());
-
-        for task in tasks {
-            let (context_digest, end_seal) =
-                if let Some(context) = self.fetch_context(task.seal_index).await? {
-                    context
-                } else {
-                    trace!(target: "seal", "Index {} is not ready for seal", task.seal_index);
-                    continue;
-                };
-            let mut data = task.non_sealed_data;
-            zgs_seal::seal(
-                &mut data,
-                &self.miner_id,
-                &context_digest,
-                task.seal_index * SECTORS_PER_SEAL as u64,
-            );
-            answers.push(SealAnswer {
-                seal_index: task.seal_index,
-                version: task.version,
-                sealed_data: data,
-                miner_id: self.miner_id,
-                seal_context: context_digest,
-                context_end_seal: end_seal,
-            });
-        }
+        let tasks = self.prefetch_contexts(tasks).await;
+        let answers = self.seal(tasks).await?;
 
+        let sealed = answers.len() as u64;
         self.submit_answer(answers).await?;
+        inc_counter_by(&SEALED_COUNT, sealed);
+        self.report_progress().await?;
 
         Ok(true)
     }
+
+    /// Reports the seals waiting to seal, and the sealed fraction of the seals stored in the
+    /// shard.
+    async fn report_progress(&self) -> Result<()> {
+        let pending_tasks = self.store.get_num_seal_tasks().await?;
+        let sealed_seals = self.store.get_num_sealed_seals().await?;
+        let shard_config = self.store.get_store().get_shard_config();
+        let sealed_fraction = match sealed_seals + pending_tasks {
+            0 => 1.0,
+            stored_seals => sealed_seals as f64 / stored_seals as f64,
+        };
+
+        set_gauge(&SEAL_PENDING_TASKS, pending_tasks as i64);
+        set_float_gauge_vec(
+            &SEALED_FRACTION,
+            &[&format!(
+                "{}/{}",
+                shard_config.shard_id, shard_config.num_shard
+            )],
+            sealed_fraction,
+        );
+        self.status
+            .set_seal_progress(sealed_seals, pending_tasks, sealed_fraction);
+        Ok(())
+    }
 }
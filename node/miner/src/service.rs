@@ -1,6 +1,7 @@
-use crate::miner_id::check_and_request_miner_id;
+use crate::miner_id::{check_and_request_miner_id, load_or_make_dev_miner_id};
 use crate::monitor::Monitor;
 use crate::sealer::Sealer;
+use crate::status::MinerStatusHandle;
 use crate::submitter::Submitter;
 use crate::{config::MinerConfig, mine::PoraService, watcher::MineContextWatcher};
 use network::NetworkSender;
@@ -31,11 +32,16 @@ impl MineService {
         _network_send: NetworkSender,
         config: MinerConfig,
         store: Arc<Store>,
-    ) -> Result<broadcast::Sender<MinerMessage>, String> {
+    ) -> Result<(broadcast::Sender<MinerMessage>, MinerStatusHandle), String> {
+        if config.dev_mode {
+            return Self::spawn_dev(executor, config, store).await;
+        }
+
         let provider = config.make_provider()?;
         let signing_provider = Arc::new(config.make_signing_provider().await?);
 
         let (msg_send, msg_recv) = broadcast::channel(1024);
+        let status = MinerStatusHandle::default();
 
         let miner_id =
             check_and_request_miner_id(&config, store.as_ref(), &signing_provider).await?;
@@ -56,7 +62,8 @@ impl MineService {
             store.clone(),
             &config,
             miner_id,
-        );
+            status.clone(),
+        )?;
 
         Submitter::spawn(
             executor.clone(),
@@ -66,14 +73,71 @@ impl MineService {
             signing_provider,
             store.clone(),
             &config,
+            status.clone(),
         );
 
-        Sealer::spawn(executor.clone(), provider, store, &config, miner_id);
+        Sealer::spawn(
+            executor.clone(),
+            Some(provider),
+            store,
+            &config,
+            miner_id,
+            status.clone(),
+        )?;
 
         Monitor::spawn(executor, Duration::from_secs(5));
 
         debug!("Starting miner service");
 
-        Ok(msg_send)
+        Ok((msg_send, status))
+    }
+
+    /// Spawns the miner in dev mode, which mines the flow in store without chain. The answers
+    /// are only reported in the miner status, since there is no contract to submit them to.
+    async fn spawn_dev(
+        executor: task_executor::TaskExecutor,
+        config: MinerConfig,
+        store: Arc<Store>,
+    ) -> Result<(broadcast::Sender<MinerMessage>, MinerStatusHandle), String> {
+        let (msg_send, msg_recv) = broadcast::channel(1024);
+        let status = MinerStatusHandle::default();
+
+        let miner_id = load_or_make_dev_miner_id(&config, store.as_ref()).await?;
+        info!(?miner_id, "Mining in dev mode");
+
+        let mine_context_receiver = MineContextWatcher::spawn_dev(
+            executor.clone(),
+            msg_recv.resubscribe(),
+            store.clone(),
+            &config,
+            miner_id,
+        );
+
+        let mut mine_answer_receiver = PoraService::spawn(
+            executor.clone(),
+            msg_recv.resubscribe(),
+            mine_context_receiver,
+            store.clone(),
+            &config,
+            miner_id,
+            status.clone(),
+        )?;
+        executor.spawn(
+            async move { while mine_answer_receiver.recv().await.is_some() {} },
+            "dev_mine_answer_receiver",
+        );
+
+        Sealer::spawn(
+            executor.clone(),
+            None,
+            store,
+            &config,
+            miner_id,
+            status.clone(),
+        )?;
+
+        Monitor::spawn(executor, Duration::from_secs(5));
+
+        Ok((msg_send, status))
     }
 }
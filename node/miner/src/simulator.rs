@@ -0,0 +1,296 @@
+//! Offline PoRA mining simulator.
+//!
+//! It mines a synthetic mine context with the same engine as [`crate::MineService`], but
+//! without the PoraMine contract, so that operators could measure the mining performance of a
+//! machine and compare settings before mining on chain.
+
+use crate::engine::{MineEngine, MineEngineConfig};
+use crate::mine::diastole_time;
+use crate::pora::Miner;
+use crate::{MineRangeConfig, PoraLoader};
+use async_trait::async_trait;
+use contract_interface::pora_mine::MineContext;
+use ethereum_types::{H256, U256};
+use rand::Rng;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use storage::config::ShardConfig;
+use storage::log_store::MineLoadChunk;
+use tokio::runtime::Handle;
+use zgs_spec::SEALS_PER_LOAD;
+
+pub struct SimulatorConfig {
+    /// The number of sectors in the flow.
+    pub flow_length: u64,
+    pub pora_target: U256,
+    pub shard_config: ShardConfig,
+    pub engine_config: MineEngineConfig,
+    pub iter_batch: usize,
+    /// Percentage of the CPU time to mine, which throttles mining as [`crate::MineService`].
+    pub cpu_percentage: u64,
+    /// How long to mine.
+    pub duration: Duration,
+    /// Duration of a mining epoch on chain, to estimate the answers per epoch.
+    pub epoch_duration: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct SimulationReport {
+    pub elapsed: Duration,
+    /// The number of nonces tried.
+    pub iterations: u64,
+    pub hash_rate: f64,
+    /// The number of loads, including the ones without any data.
+    pub loads: u64,
+    pub average_load_latency: Duration,
+    /// The number of seals in loaded chunks that are available to mine.
+    pub sealed_seals: u64,
+    /// The number of seals in loaded chunks that are not sealed or stored yet.
+    pub unsealed_seals: u64,
+    /// The number of answers found.
+    pub hits: u64,
+    /// The expected number of answers per epoch with the measured rate of sealed seals.
+    pub expected_answers_per_epoch: f64,
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "elapsed: {:.1}s, iterations: {}, hash rate: {:.1}/s, loads: {}, \
+            average load latency: {:?}, sealed seals: {}, unsealed seals: {}, hits: {}, \
+            expected answers per epoch: {:.4}",
+            self.elapsed.as_secs_f64(),
+            self.iterations,
+            self.hash_rate,
+            self.loads,
+            self.average_load_latency,
+            self.sealed_seals,
+            self.unsealed_seals,
+            self.hits,
+            self.expected_answers_per_epoch
+        )
+    }
+}
+
+/// Loader of random sealed data, where each seal is available with probability
+/// `sealed_ratio`.
+pub struct GeneratedLoader {
+    sealed_ratio: f64,
+    /// Simulated latency of each load, e.g. of the disk.
+    latency: Duration,
+}
+
+impl GeneratedLoader {
+    pub fn new(sealed_ratio: f64, latency: Duration) -> Self {
+        Self {
+            sealed_ratio: sealed_ratio.clamp(0.0, 1.0),
+            latency,
+        }
+    }
+}
+
+#[async_trait]
+impl PoraLoader for GeneratedLoader {
+    async fn load_sealed_data(&self, _index: u64) -> Option<MineLoadChunk> {
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+
+        let mut chunk = MineLoadChunk::default();
+        let mut rng = rand::thread_rng();
+        for (seal, available) in chunk
+            .loaded_chunk
+            .iter_mut()
+            .zip(chunk.availabilities.iter_mut())
+        {
+            *available = rng.gen_bool(self.sealed_ratio);
+            if *available {
+                rng.fill(&mut seal[..]);
+            }
+        }
+        Some(chunk)
+    }
+}
+
+#[derive(Default)]
+struct LoadStats {
+    loads: u64,
+    latency: Duration,
+    sealed_seals: u64,
+    unsealed_seals: u64,
+}
+
+/// Loader that measures the loads of the inner loader.
+struct MeasuredLoader {
+    inner: Arc<dyn PoraLoader>,
+    stats: Mutex<LoadStats>,
+}
+
+#[async_trait]
+impl PoraLoader for MeasuredLoader {
+    async fn load_sealed_data(&self, index: u64) -> Option<MineLoadChunk> {
+        let timer = Instant::now();
+        let chunk = self.inner.load_sealed_data(index).await;
+        let latency = timer.elapsed();
+
+        let sealed = match &chunk {
+            Some(chunk) => chunk.availabilities.iter().filter(|a| **a).count() as u64,
+            None => 0,
+        };
+        let mut stats = self.stats.lock().expect("load stats lock poisoned");
+        stats.loads += 1;
+        stats.latency += latency;
+        stats.sealed_seals += sealed;
+        stats.unsealed_seals += SEALS_PER_LOAD as u64 - sealed;
+        drop(stats);
+
+        chunk
+    }
+}
+
+/// Mines a synthetic mine context with the data of `loader` for `config.duration`.
+///
+/// This must run in a tokio runtime, and takes over the CPU cores of the mining threads.
+pub async fn simulate(
+    config: &SimulatorConfig,
+    loader: Arc<dyn PoraLoader>,
+) -> Result<SimulationReport, String> {
+    if config.cpu_percentage == 0 {
+        return Err("mining is disabled by zero cpu percentage".into());
+    }
+
+    let context = MineContext {
+        epoch: U256::one(),
+        mine_start: U256::zero(),
+        flow_root: rand::thread_rng().gen(),
+        flow_length: config.flow_length.into(),
+        block_digest: rand::thread_rng().gen(),
+        digest: rand::thread_rng().gen(),
+    };
+    let mine_range_config = MineRangeConfig::new(config.shard_config);
+    let range = mine_range_config
+        .to_valid_range(&context)
+        .filter(|range| range.mining_length > 0)
+        .ok_or("flow is too short to mine")?;
+
+    let loader = Arc::new(MeasuredLoader {
+        inner: loader,
+        stats: Default::default(),
+    });
+    let miner = Miner {
+        range,
+        miner_id: H256(rand::thread_rng().gen()),
+        context,
+        subtask_digest: H256(rand::thread_rng().gen()),
+        pora_target: config.pora_target,
+        loader: loader.clone(),
+        mine_range_config,
+    };
+    let engine = MineEngine::new(&config.engine_config, Handle::current())?;
+
+    let mut iterations = 0;
+    let mut hits = 0;
+    let timer = Instant::now();
+    while timer.elapsed() < config.duration {
+        let nonce = H256(rand::thread_rng().gen());
+        let batch_timer = Instant::now();
+        let (answer, batch_iterations) = engine
+            .batch_iteration(miner.clone(), nonce, config.iter_batch)
+            .await;
+        iterations += batch_iterations as u64;
+
+        if let Some(answer) = answer {
+            debug!(?answer, "Simulator hits an answer");
+            hits += 1;
+        } else if config.cpu_percentage < 100 {
+            let diastole_time = diastole_time(batch_timer.elapsed(), config.cpu_percentage);
+            tokio::time::sleep(diastole_time).await;
+        }
+    }
+    let elapsed = timer.elapsed();
+
+    let stats = loader.stats.lock().expect("load stats lock poisoned");
+    // the probability that a sealed seal is an answer
+    let difficulty_scale_x64 = range.difficulty_scale_x64(config.flow_length);
+    let hit_probability =
+        u256_to_f64((config.pora_target / difficulty_scale_x64) << 64) / u256_to_f64(U256::MAX);
+    let expected_answers_per_epoch = stats.sealed_seals as f64 / elapsed.as_secs_f64()
+        * config.epoch_duration.as_secs_f64()
+        * hit_probability;
+
+    Ok(SimulationReport {
+        elapsed,
+        iterations,
+        hash_rate: iterations as f64 / elapsed.as_secs_f64(),
+        loads: stats.loads,
+        average_load_latency: stats
+            .latency
+            .checked_div(stats.loads as u32)
+            .unwrap_or_default(),
+        sealed_seals: stats.sealed_seals,
+        unsealed_seals: stats.unsealed_seals,
+        hits,
+        expected_answers_per_epoch,
+    })
+}
+
+fn u256_to_f64(value: U256) -> f64 {
+    value
+        .0
+        .iter()
+        .rev()
+        .fold(0.0, |acc, word| acc * 2f64.powi(64) + *word as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zgs_spec::SECTORS_PER_PRICING;
+
+    #[test]
+    fn test_u256_to_f64() {
+        assert_eq!(u256_to_f64(U256::zero()), 0.0);
+        assert_eq!(u256_to_f64(U256::from(12345)), 12345.0);
+        assert_eq!(u256_to_f64(U256::one() << 200), 2f64.powi(200));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_simulate() {
+        let config = SimulatorConfig {
+            flow_length: SECTORS_PER_PRICING as u64 * 4,
+            // every sealed seal is an answer
+            pora_target: U256::MAX,
+            shard_config: ShardConfig::default(),
+            engine_config: MineEngineConfig {
+                threads: 2,
+                ..Default::default()
+            },
+            iter_batch: 4,
+            cpu_percentage: 100,
+            duration: Duration::from_millis(100),
+            epoch_duration: Duration::from_secs(60),
+        };
+
+        let loader = Arc::new(GeneratedLoader::new(1.0, Duration::ZERO));
+        let report = simulate(&config, loader).await.unwrap();
+        assert!(report.iterations > 0);
+        // answers found by other threads of a batch are dropped
+        assert!(report.hits > 0 && report.hits <= report.loads);
+        assert_eq!(report.unsealed_seals, 0);
+
+        let loader = Arc::new(GeneratedLoader::new(0.0, Duration::ZERO));
+        let report = simulate(&config, loader).await.unwrap();
+        assert_eq!(report.hits, 0);
+        assert_eq!(report.sealed_seals, 0);
+        assert_eq!(report.expected_answers_per_epoch, 0.0);
+
+        let config = SimulatorConfig {
+            cpu_percentage: 0,
+            ..config
+        };
+        let loader = Arc::new(GeneratedLoader::new(1.0, Duration::ZERO));
+        assert!(simulate(&config, loader).await.is_err());
+    }
+}
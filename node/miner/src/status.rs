@@ -0,0 +1,251 @@
+use crate::history::{SubmissionRecord, SubmissionStatus};
+use crate::metrics::SCRATCH_PAD_ITER_RATE;
+use crate::mine::PoraPuzzle;
+use crate::pora::AnswerWithoutProof;
+use crate::recall_range::RecallRange;
+use ethereum_types::{H256, U256};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The maximum number of answers kept in [`MinerStatus::last_answers`].
+const MAX_LAST_ANSWERS: usize = 10;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PuzzleStatus {
+    pub context_digest: H256,
+    pub epoch: U256,
+    pub flow_length: U256,
+    pub pora_target: U256,
+    pub max_shards: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecallRangeStatus {
+    pub start_position: u64,
+    pub mining_length: u64,
+    pub shard_mask: u64,
+    pub shard_id: u64,
+}
+
+impl From<RecallRange> for RecallRangeStatus {
+    fn from(range: RecallRange) -> Self {
+        Self {
+            start_position: range.start_position,
+            mining_length: range.mining_length,
+            shard_mask: range.shard_mask,
+            shard_id: range.shard_id,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnswerStatus {
+    pub context_digest: H256,
+    pub nonce: H256,
+    pub recall_position: u64,
+    /// Unix timestamp in seconds when the answer is found.
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmissionStats {
+    pub confirmed: u64,
+    pub reverted: u64,
+    pub failed: u64,
+    pub unprofitable: u64,
+    pub cancelled: u64,
+    pub last_submission: Option<SubmissionRecord>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SealingStatus {
+    /// Flow length of the latest context on chain, below which data is sealable.
+    pub sealable_flow_length: u64,
+    /// The number of seals in the store that are sealed.
+    pub sealed_seals: u64,
+    /// The number of seals in the store waiting to seal.
+    pub pending_tasks: u64,
+    /// Fraction of the seals stored in the shard that are sealed.
+    pub sealed_fraction: f64,
+}
+
+/// Live status of the miner since the node started.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MinerStatus {
+    pub mining_enabled: bool,
+    pub puzzle: Option<PuzzleStatus>,
+    /// Effective recall range of the current puzzle.
+    pub recall_range: Option<RecallRangeStatus>,
+    /// The reason why mining is stopped.
+    pub stop_reason: Option<String>,
+    /// Scratch pad iterations per second of all mining threads in the last mining batch, which
+    /// is zero if not mining.
+    pub hash_rate: f64,
+    /// The latest found answers, ordered from the latest.
+    pub last_answers: VecDeque<AnswerStatus>,
+    pub submissions: SubmissionStats,
+    pub sealing: SealingStatus,
+}
+
+/// Status shared by the mining services, which is updated by them and read by the RPC.
+#[derive(Clone, Default)]
+pub struct MinerStatusHandle {
+    inner: Arc<RwLock<MinerStatus>>,
+}
+
+impl MinerStatus {
+    /// Returns the status of a node that does not run the miner.
+    pub fn stopped(reason: &str) -> Self {
+        Self {
+            stop_reason: Some(reason.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+impl MinerStatusHandle {
+    pub fn get(&self) -> MinerStatus {
+        let mut status = self
+            .inner
+            .read()
+            .expect("miner status lock poisoned")
+            .clone();
+        if !status.mining_enabled {
+            status.stop_reason = Some("mining is disabled".to_string());
+        }
+        // the rate of the last batch is kept after mining stops
+        status.hash_rate = match &*SCRATCH_PAD_ITER_RATE {
+            Ok(iter_rate) if status.stop_reason.is_none() => iter_rate.get(),
+            _ => 0.0,
+        };
+        status
+    }
+
+    fn update(&self, f: impl FnOnce(&mut MinerStatus)) {
+        f(&mut self.inner.write().expect("miner status lock poisoned"))
+    }
+
+    pub(crate) fn set_mining_enabled(&self, enabled: bool) {
+        self.update(|status| status.mining_enabled = enabled);
+    }
+
+    pub(crate) fn set_puzzle(
+        &self,
+        puzzle: Option<&PoraPuzzle>,
+        range: Result<RecallRange, &'static str>,
+    ) {
+        self.update(|status| {
+            status.puzzle = puzzle.map(PoraPuzzle::status);
+            match range {
+                Ok(range) => {
+                    status.recall_range = Some(range.into());
+                    status.stop_reason = None;
+                }
+                Err(reason) => {
+                    status.recall_range = None;
+                    status.stop_reason = Some(reason.to_string());
+                }
+            }
+        });
+    }
+
+    pub(crate) fn add_answer(&self, answer: &AnswerWithoutProof) {
+        self.update(|status| {
+            status.last_answers.push_front(AnswerStatus {
+                context_digest: answer.context_digest,
+                nonce: answer.nonce,
+                recall_position: answer.recall_position,
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default(),
+            });
+            status.last_answers.truncate(MAX_LAST_ANSWERS);
+        });
+    }
+
+    pub(crate) fn add_submission(&self, record: &SubmissionRecord) {
+        self.update(|status| {
+            let stats = &mut status.submissions;
+            match record.status {
+                SubmissionStatus::Confirmed => stats.confirmed += 1,
+                SubmissionStatus::Reverted => stats.reverted += 1,
+                SubmissionStatus::Failed => stats.failed += 1,
+                SubmissionStatus::Unprofitable => stats.unprofitable += 1,
+                SubmissionStatus::Cancelled => stats.cancelled += 1,
+            }
+            stats.last_submission = Some(record.clone());
+        });
+    }
+
+    pub(crate) fn set_sealable_flow_length(&self, flow_length: u64) {
+        self.update(|status| status.sealing.sealable_flow_length = flow_length);
+    }
+
+    pub(crate) fn set_seal_progress(
+        &self,
+        sealed_seals: u64,
+        pending_tasks: u64,
+        sealed_fraction: f64,
+    ) {
+        self.update(|status| {
+            status.sealing.sealed_seals = sealed_seals;
+            status.sealing.pending_tasks = pending_tasks;
+            status.sealing.sealed_fraction = sealed_fraction;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zgs_spec::BYTES_PER_SEAL;
+
+    #[test]
+    fn test_last_answers() {
+        let status = MinerStatusHandle::default();
+        for i in 0..(MAX_LAST_ANSWERS as u64 + 2) {
+            status.add_answer(&AnswerWithoutProof {
+                context_digest: H256::zero(),
+                context_flow_root: H256::zero(),
+                nonce: H256::zero(),
+                miner_id: H256::zero(),
+                range: RecallRange {
+                    start_position: 0,
+                    mining_length: 0,
+                    shard_mask: u64::MAX,
+                    shard_id: 0,
+                },
+                recall_position: i,
+                seal_offset: 0,
+                sealed_data: [0u8; BYTES_PER_SEAL],
+            });
+        }
+
+        let answers = status.get().last_answers;
+        assert_eq!(answers.len(), MAX_LAST_ANSWERS);
+        assert_eq!(answers[0].recall_position, MAX_LAST_ANSWERS as u64 + 1);
+    }
+
+    #[test]
+    fn test_stopped() {
+        let status = MinerStatusHandle::default();
+        let stopped = status.get();
+        assert_eq!(stopped.stop_reason.as_deref(), Some("mining is disabled"));
+        assert_eq!(stopped.hash_rate, 0.0);
+
+        status.set_mining_enabled(true);
+        status.set_puzzle(None, Err("no mine context"));
+        let stopped = status.get();
+        assert_eq!(stopped.stop_reason.as_deref(), Some("no mine context"));
+        assert_eq!(stopped.hash_rate, 0.0);
+    }
+}
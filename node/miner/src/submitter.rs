@@ -1,10 +1,14 @@
-use contract_interface::PoraAnswer;
+use contract_interface::{ChunkLinearReward, PoraAnswer};
 use contract_interface::{PoraMine, ZgsFlow};
-use contract_wrapper::SubmitConfig;
+use contract_wrapper::{RpcProvider, SubmitConfig};
+use ethereum_types::U256;
 use ethers::contract::ContractCall;
-use ethers::prelude::{Http, Provider, RetryClient};
+use ethers::providers::Middleware;
 use hex::ToHex;
 use shared_types::FlowRangeProof;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use storage::H256;
 use storage_async::Store;
@@ -12,18 +16,108 @@ use task_executor::TaskExecutor;
 use tokio::sync::{broadcast, mpsc};
 
 use crate::config::{MineServiceMiddleware, MinerConfig};
+use crate::history::{SubmissionHistory, SubmissionReceipt, SubmissionRecord, SubmissionStatus};
 use crate::pora::AnswerWithoutProof;
+use crate::status::MinerStatusHandle;
 use crate::watcher::MineContextMessage;
 
-use zgs_spec::{BYTES_PER_SEAL, SECTORS_PER_SEAL};
+use zgs_spec::{BYTES_PER_SEAL, SECTORS_PER_PRICING, SECTORS_PER_SEAL};
+
+const BASIS_POINTS: u64 = 10000;
+
+type SubmissionFuture = Pin<Box<dyn Future<Output = SubmissionRecord> + Send>>;
+
+/// Submission in flight, along with the record to save if it's cancelled.
+struct InFlight {
+    cancelled: SubmissionRecord,
+    /// Whether the transaction has been sent, after which the submission is not cancelled since
+    /// the transaction may still be mined.
+    sent: Arc<AtomicBool>,
+    future: SubmissionFuture,
+}
+
+/// Answers waiting to submit, which keeps at most one pending answer per context, i.e. either
+/// queued or in flight.
+#[derive(Default)]
+struct SubmissionQueue {
+    context_digest: Option<H256>,
+    queued: Option<AnswerWithoutProof>,
+    in_flight: Option<H256>,
+    /// The context digest that no more answers are submitted for, since an answer has been
+    /// confirmed or found unprofitable.
+    finished: Option<H256>,
+}
+
+impl SubmissionQueue {
+    /// Drops the queued answer if it's stale for the new context, and returns whether the
+    /// answer in flight is stale, which should be cancelled unless its transaction is sent.
+    fn set_context(&mut self, context_digest: Option<H256>) -> bool {
+        self.context_digest = context_digest;
+        if let Some(answer) = &self.queued {
+            if Some(answer.context_digest) != context_digest {
+                info!(context_digest = ?answer.context_digest, "Drop stale mine answer");
+                self.queued = None;
+            }
+        }
+        if self.in_flight.is_some() && self.in_flight != context_digest {
+            self.in_flight = None;
+            return true;
+        }
+        false
+    }
+
+    fn push(&mut self, answer: AnswerWithoutProof) -> Result<(), &'static str> {
+        let context_digest = answer.context_digest;
+        if Some(context_digest) != self.context_digest {
+            return Err("inconsistent context digest");
+        }
+        if self.finished == Some(context_digest) {
+            return Err("an answer is already confirmed or unprofitable for the context");
+        }
+        if self.in_flight == Some(context_digest) || self.queued.is_some() {
+            return Err("another answer is pending for the context");
+        }
+        self.queued = Some(answer);
+        Ok(())
+    }
+
+    /// Takes the queued answer to submit if no answer is in flight.
+    fn pop(&mut self) -> Option<AnswerWithoutProof> {
+        if self.in_flight.is_some() {
+            return None;
+        }
+        let answer = self.queued.take()?;
+        self.in_flight = Some(answer.context_digest);
+        Some(answer)
+    }
+
+    fn finish(&mut self, context_digest: H256, status: SubmissionStatus) {
+        self.in_flight = None;
+        if matches!(
+            status,
+            SubmissionStatus::Confirmed | SubmissionStatus::Unprofitable
+        ) {
+            self.finished = Some(context_digest);
+        }
+    }
+}
 
 pub struct Submitter {
     mine_answer_receiver: mpsc::UnboundedReceiver<AnswerWithoutProof>,
     mine_context_receiver: broadcast::Receiver<MineContextMessage>,
+    client: Arc<SubmitClient>,
+    queue: SubmissionQueue,
+    status: MinerStatusHandle,
+}
+
+/// Contracts to submit answers with.
+struct SubmitClient {
     mine_contract: PoraMine<MineServiceMiddleware>,
-    flow_contract: ZgsFlow<Provider<RetryClient<Http>>>,
+    flow_contract: ZgsFlow<RpcProvider>,
+    provider: Arc<RpcProvider>,
     store: Arc<Store>,
     config: SubmitConfig,
+    skip_unprofitable: bool,
 }
 
 impl Submitter {
@@ -31,10 +125,11 @@ impl Submitter {
         executor: TaskExecutor,
         mine_answer_receiver: mpsc::UnboundedReceiver<AnswerWithoutProof>,
         mine_context_receiver: broadcast::Receiver<MineContextMessage>,
-        provider: Arc<Provider<RetryClient<Http>>>,
+        provider: Arc<RpcProvider>,
         signing_provider: Arc<MineServiceMiddleware>,
         store: Arc<Store>,
         config: &MinerConfig,
+        status: MinerStatusHandle,
     ) {
         let mine_contract = PoraMine::new(config.mine_address, signing_provider);
         let flow_contract = ZgsFlow::new(config.flow_address, provider.clone());
@@ -42,10 +137,16 @@ impl Submitter {
         let submitter = Submitter {
             mine_answer_receiver,
             mine_context_receiver,
-            mine_contract,
-            flow_contract,
-            store,
-            config: config.submission_config,
+            client: Arc::new(SubmitClient {
+                mine_contract,
+                flow_contract,
+                provider,
+                store,
+                config: config.submission_config,
+                skip_unprofitable: config.skip_unprofitable,
+            }),
+            queue: Default::default(),
+            status,
         };
         executor.spawn(
             async move { Box::pin(submitter.start()).await },
@@ -54,18 +155,30 @@ impl Submitter {
     }
 
     async fn start(mut self) {
-        let mut current_context_digest: Option<H256> = None;
+        let mut history = SubmissionHistory::load(self.client.store.clone()).await;
+        let mut in_flight: Option<InFlight> = None;
         loop {
+            if in_flight.is_none() {
+                if let Some(answer) = self.queue.pop() {
+                    let client = self.client.clone();
+                    let sent = Arc::new(AtomicBool::new(false));
+                    let sent_flag = sent.clone();
+                    in_flight = Some(InFlight {
+                        cancelled: SubmissionRecord::new(&answer, SubmissionStatus::Cancelled),
+                        sent,
+                        future: Box::pin(
+                            async move { client.submit_answer(answer, &sent_flag).await },
+                        ),
+                    });
+                }
+            }
+
             tokio::select! {
                 answer_msg = self.mine_answer_receiver.recv() => {
                     match answer_msg {
                         Some(answer) => {
-                            if Some(answer.context_digest) != current_context_digest {
-                                info!("Skip submission because of inconsistent context digest");
-                                continue;
-                            }
-                            if let Err(e) = self.submit_answer(answer).await {
-                                warn!(e);
+                            if let Err(reason) = self.queue.push(answer) {
+                                info!(reason, "Skip submission");
                             }
                         }
                         None => {
@@ -78,7 +191,26 @@ impl Submitter {
                 context_msg = self.mine_context_receiver.recv() => {
                     match context_msg {
                         Ok(puzzle) => {
-                            current_context_digest = puzzle.map(|p| p.context_digest());
+                            if self.queue.set_context(puzzle.map(|p| p.context_digest())) {
+                                match in_flight.take() {
+                                    // wait for the receipt, and the next answer is submitted after
+                                    Some(submission) if submission.sent.load(Ordering::SeqCst) => {
+                                        info!(
+                                            context_digest = ?submission.cancelled.context_digest,
+                                            "Wait for stale mine answer submission already sent"
+                                        );
+                                        in_flight = Some(submission);
+                                    }
+                                    Some(InFlight { cancelled, .. }) => {
+                                        info!(
+                                            context_digest = ?cancelled.context_digest,
+                                            "Cancel stale mine answer submission"
+                                        );
+                                        self.save(&mut history, cancelled).await;
+                                    }
+                                    None => {}
+                                }
+                            }
                         }
                         Err(broadcast::error::RecvError::Closed) => {
                             warn!("Mine context channel closed.");
@@ -86,11 +218,48 @@ impl Submitter {
                         Err(_) => {}
                     }
                 }
+
+                record = async { in_flight.as_mut().expect("checked").future.as_mut().await },
+                    if in_flight.is_some() => {
+                    in_flight = None;
+                    self.queue.finish(record.context_digest, record.status);
+                    if let Some(e) = &record.error {
+                        warn!(e);
+                    }
+                    self.save(&mut history, record).await;
+                }
             }
         }
     }
 
-    async fn submit_answer(&mut self, mine_answer: AnswerWithoutProof) -> Result<(), String> {
+    async fn save(&self, history: &mut SubmissionHistory, record: SubmissionRecord) {
+        self.status.add_submission(&record);
+        if let Err(e) = history.push(record).await {
+            warn!(e);
+        }
+    }
+}
+
+impl SubmitClient {
+    /// Submits the answer, and sets `sent` once the transaction is about to be sent.
+    async fn submit_answer(
+        &self,
+        mine_answer: AnswerWithoutProof,
+        sent: &AtomicBool,
+    ) -> SubmissionRecord {
+        let mut record = SubmissionRecord::new(&mine_answer, SubmissionStatus::Failed);
+        if let Err(e) = self.try_submit_answer(mine_answer, sent, &mut record).await {
+            record.error = Some(e);
+        }
+        record
+    }
+
+    async fn try_submit_answer(
+        &self,
+        mine_answer: AnswerWithoutProof,
+        sent: &AtomicBool,
+        record: &mut SubmissionRecord,
+    ) -> Result<(), String> {
         debug!("submit answer: {:?}", mine_answer);
         let sealed_context_digest = self
             .flow_contract
@@ -112,6 +281,7 @@ impl Submitter {
             .await
             .map_err(|e| e.to_string())?;
 
+        let recall_position = mine_answer.recall_position;
         let answer = PoraAnswer {
             context_digest: mine_answer.context_digest.0,
             nonce: mine_answer.nonce.0,
@@ -139,12 +309,26 @@ impl Submitter {
         }
 
         debug!("Local construct tx: {:?}", &submission_call.tx);
-        debug!(
-            "Estimate gas result: {:?}",
-            submission_call.estimate_gas().await
-        );
+        let estimated_gas = submission_call.estimate_gas().await;
+        debug!("Estimate gas result: {:?}", estimated_gas);
 
-        contract_wrapper::submit_with_retry(
+        if let Ok(estimated_gas) = estimated_gas {
+            match self.estimate_profit(recall_position, estimated_gas).await {
+                Ok((reward, gas_cost)) => {
+                    record.estimated_reward = Some(reward);
+                    record.estimated_gas_cost = Some(gas_cost);
+                    if self.skip_unprofitable && reward < gas_cost {
+                        info!(%reward, %gas_cost, "Skip unprofitable mine answer");
+                        record.status = SubmissionStatus::Unprofitable;
+                        return Ok(());
+                    }
+                }
+                Err(e) => warn!(%e, "Failed to estimate mining profit"),
+            }
+        }
+
+        sent.store(true, Ordering::SeqCst);
+        let receipt = contract_wrapper::submit_with_retry(
             submission_call,
             &self.config,
             self.mine_contract.client().clone(),
@@ -152,8 +336,66 @@ impl Submitter {
         .await
         .map_err(|e| format!("Failed to submit mine answer: {:?}", e))?;
 
+        record.status = if receipt.status == Some(1.into()) {
+            SubmissionStatus::Confirmed
+        } else {
+            SubmissionStatus::Reverted
+        };
+        record.receipt = Some(SubmissionReceipt::from(&receipt));
         Ok(())
     }
+
+    /// Returns the estimated reward of the pricing chunk at `recall_position` from the reward
+    /// contract, and the gas cost to submit the answer.
+    async fn estimate_profit(
+        &self,
+        recall_position: u64,
+        estimated_gas: U256,
+    ) -> Result<(U256, U256), String> {
+        let reward_address = self
+            .mine_contract
+            .reward()
+            .call()
+            .await
+            .map_err(|e| format!("Failed to query reward contract: {:?}", e))?;
+        let reward_contract = ChunkLinearReward::new(reward_address, self.provider.clone());
+
+        let pricing_index = recall_position / SECTORS_PER_PRICING as u64;
+        let (_, claimable_reward, _, _, _) = reward_contract
+            .rewards(pricing_index.into())
+            .call()
+            .await
+            .map_err(|e| format!("Failed to query chunk reward: {:?}", e))?;
+        let base_reward = reward_contract
+            .base_reward()
+            .call()
+            .await
+            .map_err(|e| format!("Failed to query base reward: {:?}", e))?;
+        let total_base_reward = reward_contract
+            .total_base_reward()
+            .call()
+            .await
+            .map_err(|e| format!("Failed to query total base reward: {:?}", e))?;
+        let service_fee_rate_bps = reward_contract
+            .service_fee_rate_bps()
+            .call()
+            .await
+            .map_err(|e| format!("Failed to query service fee rate: {:?}", e))?;
+
+        let service_fee_rate_bps = service_fee_rate_bps.min(BASIS_POINTS.into());
+        let reward = U256::from(claimable_reward)
+            * (U256::from(BASIS_POINTS) - service_fee_rate_bps)
+            / BASIS_POINTS
+            + base_reward.min(total_base_reward);
+
+        let gas_price = self
+            .provider
+            .get_gas_price()
+            .await
+            .map_err(|e| format!("Failed to fetch gas price: {:?}", e))?;
+
+        Ok((reward, estimated_gas * gas_price))
+    }
 }
 
 // TODO: The conversion will be simpler if we optimize range proof structure.
@@ -163,3 +405,88 @@ fn flow_proof_to_pora_merkle_proof(flow_proof: FlowRangeProof) -> Vec<[u8; 32]>
     // Exclude `item`, the nodes in the sealed data subtree, and `root`.
     full_proof[depth_in_sealed_data + 1..full_proof.len() - 1].to_vec()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recall_range::RecallRange;
+
+    fn answer(context_digest: H256) -> AnswerWithoutProof {
+        AnswerWithoutProof {
+            context_digest,
+            context_flow_root: H256::zero(),
+            nonce: H256::zero(),
+            miner_id: H256::zero(),
+            range: RecallRange {
+                start_position: 0,
+                mining_length: 0,
+                shard_mask: u64::MAX,
+                shard_id: 0,
+            },
+            recall_position: 0,
+            seal_offset: 0,
+            sealed_data: [0u8; BYTES_PER_SEAL],
+        }
+    }
+
+    #[test]
+    fn test_queue_dedup() {
+        let digest = H256::repeat_byte(1);
+        let mut queue = SubmissionQueue::default();
+        assert!(queue.push(answer(digest)).is_err());
+
+        queue.set_context(Some(digest));
+        assert!(queue.push(answer(digest)).is_ok());
+        assert!(queue.push(answer(digest)).is_err());
+
+        // one answer in flight, and another one is not queued for the same context
+        assert!(queue.pop().is_some());
+        assert!(queue.pop().is_none());
+        assert!(queue.push(answer(digest)).is_err());
+
+        // retry after a failed submission
+        queue.finish(digest, SubmissionStatus::Failed);
+        assert!(queue.push(answer(digest)).is_ok());
+        assert!(queue.pop().is_some());
+
+        queue.finish(digest, SubmissionStatus::Confirmed);
+        assert!(queue.push(answer(digest)).is_err());
+    }
+
+    #[test]
+    fn test_queue_skip_unprofitable() {
+        let digest = H256::repeat_byte(1);
+        let new_digest = H256::repeat_byte(2);
+        let mut queue = SubmissionQueue::default();
+        queue.set_context(Some(digest));
+        assert!(queue.push(answer(digest)).is_ok());
+        assert!(queue.pop().is_some());
+
+        // not estimated again for the same context
+        queue.finish(digest, SubmissionStatus::Unprofitable);
+        assert!(queue.push(answer(digest)).is_err());
+
+        queue.set_context(Some(new_digest));
+        assert!(queue.push(answer(new_digest)).is_ok());
+    }
+
+    #[test]
+    fn test_queue_drop_stale() {
+        let digest = H256::repeat_byte(1);
+        let new_digest = H256::repeat_byte(2);
+        let mut queue = SubmissionQueue::default();
+        queue.set_context(Some(digest));
+        assert!(queue.push(answer(digest)).is_ok());
+
+        assert!(!queue.set_context(Some(new_digest)));
+        assert!(queue.pop().is_none());
+        assert!(queue.push(answer(new_digest)).is_ok());
+        assert_eq!(queue.pop().unwrap().context_digest, new_digest);
+
+        // the answer in flight is cancelled for a new context
+        assert!(!queue.set_context(Some(new_digest)));
+        assert!(queue.set_context(Some(digest)));
+        assert!(queue.push(answer(digest)).is_ok());
+        assert_eq!(queue.pop().unwrap().context_digest, digest);
+    }
+}
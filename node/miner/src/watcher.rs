@@ -1,12 +1,15 @@
 #![allow(unused)]
 
-use contract_interface::{zgs_flow::MineContext, PoraMine, WorkerContext, ZgsFlow};
+use contract_interface::{pora_mine, zgs_flow::MineContext, PoraMine, WorkerContext, ZgsFlow};
+use contract_wrapper::RpcProvider;
 use ethereum_types::{Address, H256, U256};
 use ethers::{
     contract::Contract,
     providers::{JsonRpcClient, Middleware, Provider, StreamExt},
     types::BlockId,
+    utils::keccak256,
 };
+use storage_async::Store;
 use task_executor::TaskExecutor;
 use tokio::{
     sync::{broadcast, mpsc},
@@ -15,7 +18,6 @@ use tokio::{
 };
 
 use crate::{config::MineServiceMiddleware, mine::PoraPuzzle, MinerConfig, MinerMessage};
-use ethers::prelude::{Http, RetryClient};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Duration;
@@ -30,10 +32,24 @@ lazy_static! {
 
 const PORA_VERSION: u64 = 1;
 
+/// The puzzle difficulty in dev mode, which is easy enough to find answers in seconds.
+const DEV_DIFFICULTY: u64 = 4096;
+
+/// Where the watcher gets the pora puzzle from.
+enum PuzzleSource {
+    /// The `PoraMine` contract on chain.
+    Chain {
+        provider: Arc<RpcProvider>,
+        flow_contract: ZgsFlow<RpcProvider>,
+        mine_contract: PoraMine<RpcProvider>,
+    },
+    /// The flow in store, which is final once synced in dev mode without chain. A new epoch
+    /// starts whenever the flow grows.
+    Dev(Arc<Store>),
+}
+
 pub struct MineContextWatcher {
-    provider: Arc<Provider<RetryClient<Http>>>,
-    flow_contract: ZgsFlow<Provider<RetryClient<Http>>>,
-    mine_contract: PoraMine<Provider<RetryClient<Http>>>,
+    source: PuzzleSource,
 
     mine_context_sender: broadcast::Sender<MineContextMessage>,
     last_report: MineContextMessage,
@@ -47,19 +63,49 @@ impl MineContextWatcher {
     pub fn spawn(
         executor: TaskExecutor,
         msg_recv: broadcast::Receiver<MinerMessage>,
-        provider: Arc<Provider<RetryClient<Http>>>,
+        provider: Arc<RpcProvider>,
         config: &MinerConfig,
         miner_id: H256,
     ) -> broadcast::Receiver<MineContextMessage> {
         let mine_contract = PoraMine::new(config.mine_address, provider.clone());
         let flow_contract = ZgsFlow::new(config.flow_address, provider.clone());
+        let source = PuzzleSource::Chain {
+            provider,
+            flow_contract,
+            mine_contract,
+        };
+        Self::spawn_with_source(executor, msg_recv, source, config, miner_id)
+    }
+
+    /// Spawns a stub watcher for dev mode, which makes the puzzle from the flow in `store`
+    /// instead of querying the chain.
+    pub fn spawn_dev(
+        executor: TaskExecutor,
+        msg_recv: broadcast::Receiver<MinerMessage>,
+        store: Arc<Store>,
+        config: &MinerConfig,
+        miner_id: H256,
+    ) -> broadcast::Receiver<MineContextMessage> {
+        Self::spawn_with_source(
+            executor,
+            msg_recv,
+            PuzzleSource::Dev(store),
+            config,
+            miner_id,
+        )
+    }
 
+    fn spawn_with_source(
+        executor: TaskExecutor,
+        msg_recv: broadcast::Receiver<MinerMessage>,
+        source: PuzzleSource,
+        config: &MinerConfig,
+        miner_id: H256,
+    ) -> broadcast::Receiver<MineContextMessage> {
         let (mine_context_sender, mine_context_receiver) =
             broadcast::channel::<MineContextMessage>(4096);
         let watcher = MineContextWatcher {
-            provider,
-            flow_contract,
-            mine_contract,
+            source,
             mine_context_sender,
             msg_recv,
             last_report: None,
@@ -127,8 +173,12 @@ impl MineContextWatcher {
     }
 
     async fn fetch_pora_puzzle(&self) -> Result<Option<PoraPuzzle>, String> {
-        let pora_version = self
-            .mine_contract
+        let mine_contract = match &self.source {
+            PuzzleSource::Chain { mine_contract, .. } => mine_contract,
+            PuzzleSource::Dev(store) => return self.make_dev_pora_puzzle(store).await,
+        };
+
+        let pora_version = mine_contract
             .pora_version()
             .call()
             .await
@@ -144,8 +194,7 @@ impl MineContextWatcher {
             pora_target,
             subtask_digest,
             max_shards,
-        } = self
-            .mine_contract
+        } = mine_contract
             .compute_worker_context(miner_id)
             .call()
             .await
@@ -162,4 +211,41 @@ impl MineContextWatcher {
             H256(subtask_digest),
         )))
     }
+
+    /// Makes the puzzle of the flow in store, which is kept until the flow grows.
+    async fn make_dev_pora_puzzle(&self, store: &Store) -> Result<Option<PoraPuzzle>, String> {
+        let (flow_root, flow_length) = store
+            .get_context()
+            .await
+            .map_err(|e| format!("Failed to get flow context: {:?}", e))?;
+
+        let epoch = match &self.last_report {
+            Some(puzzle) if puzzle.context().flow_root == flow_root.0 => {
+                return Ok(self.last_report.clone());
+            }
+            Some(puzzle) => puzzle.context().epoch + 1,
+            None => U256::one(),
+        };
+
+        let mut epoch_bytes = [0u8; 32];
+        epoch.to_big_endian(&mut epoch_bytes);
+        let block_digest = keccak256(epoch_bytes);
+        let digest = keccak256([flow_root.0, block_digest].concat());
+        let context = pora_mine::MineContext {
+            epoch,
+            mine_start: U256::zero(),
+            flow_root: flow_root.0,
+            flow_length: flow_length.into(),
+            block_digest,
+            digest,
+        };
+        let subtask_digest = H256(keccak256([digest, self.miner_id.0].concat()));
+
+        Ok(Some(PoraPuzzle::new(
+            context,
+            U256::MAX / DEV_DIFFICULTY,
+            u64::MAX,
+            subtask_digest,
+        )))
+    }
 }
@@ -3,10 +3,10 @@ use crate::config::gossipsub_config;
 use crate::discovery::{Discovery, DiscoveryEvent, FIND_NODE_QUERY_CLOSEST_PEERS};
 use crate::peer_manager::{
     config::Config as PeerManagerCfg, peerdb::score::PeerAction, peerdb::score::ReportSource,
-    ConnectionDirection, PeerManager, PeerManagerEvent,
+    peerdb::PEER_DB_FILENAME, ConnectionDirection, PeerManager, PeerManagerEvent,
 };
 use crate::rpc::methods::DataByHashRequest;
-use crate::rpc::methods::GetChunksRequest;
+use crate::rpc::methods::{GetChunksRequest, GetShardParityRequest, ShardParity};
 use crate::rpc::*;
 use crate::service::Context as ServiceContext;
 use crate::types::{GossipEncoding, GossipKind, GossipTopic, SnappyTransform};
@@ -271,6 +271,10 @@ impl<AppReqId: ReqId> Behaviour<AppReqId> {
             target_peer_count: config.target_peers,
             ..config.peer_manager
         };
+        let peer_db_file = config
+            .peer_db
+            .persist_enabled
+            .then(|| config.network_dir.join(PEER_DB_FILENAME));
 
         // let slot_duration = std::time::Duration::from_secs(12);
         // let slot_duration = std::time::Duration::from_secs(ctx.chain_spec.seconds_per_slot);
@@ -284,7 +288,8 @@ impl<AppReqId: ReqId> Behaviour<AppReqId> {
             discovery,
             identify: Identify::new(identify_config),
             // Auxiliary fields
-            peer_manager: PeerManager::new(peer_manager_cfg, network_globals.clone()).await?,
+            peer_manager: PeerManager::new(peer_manager_cfg, network_globals.clone(), peer_db_file)
+                .await?,
             events: VecDeque::new(),
             internal_events: VecDeque::new(),
             network_globals,
@@ -553,6 +558,9 @@ impl<AppReqId: ReqId> Behaviour<AppReqId> {
             Request::GetChunks { .. } => {
                 metrics::inc_counter_vec(&metrics::TOTAL_RPC_REQUESTS, &["get_chunks"])
             }
+            Request::GetShardParity { .. } => {
+                metrics::inc_counter_vec(&metrics::TOTAL_RPC_REQUESTS, &["get_shard_parity"])
+            }
         }
         self.add_event(BehaviourEvent::RequestReceived {
             peer_id,
@@ -786,6 +794,11 @@ where
                     InboundRequest::GetChunks(req) => {
                         self.propagate_request(peer_request_id, peer_id, Request::GetChunks(req))
                     }
+                    InboundRequest::GetShardParity(req) => self.propagate_request(
+                        peer_request_id,
+                        peer_id,
+                        Request::GetShardParity(req),
+                    ),
                 }
             }
             Ok(RPCReceived::Response(id, resp)) => {
@@ -805,6 +818,9 @@ where
                     RPCResponse::Chunks(resp) => {
                         self.propagate_response(id, peer_id, Response::Chunks(resp))
                     }
+                    RPCResponse::ShardParity(resp) => {
+                        self.propagate_response(id, peer_id, Response::ShardParity(resp))
+                    }
                 }
             }
             Ok(RPCReceived::EndOfStream(id, termination)) => {
@@ -1001,6 +1017,8 @@ pub enum Request {
     AnswerFile(ShardedFile),
     /// A GetChunks request.
     GetChunks(GetChunksRequest),
+    /// A GetShardParity request.
+    GetShardParity(GetShardParityRequest),
 }
 
 impl std::convert::From<Request> for OutboundRequest {
@@ -1010,6 +1028,7 @@ impl std::convert::From<Request> for OutboundRequest {
             Request::DataByHash(r) => OutboundRequest::DataByHash(r),
             Request::AnswerFile(r) => OutboundRequest::AnswerFile(r),
             Request::GetChunks(r) => OutboundRequest::GetChunks(r),
+            Request::GetShardParity(r) => OutboundRequest::GetShardParity(r),
         }
     }
 }
@@ -1028,6 +1047,8 @@ pub enum Response {
     DataByHash(Option<Box<ZgsData>>),
     /// A response to a GET_CHUNKS request.
     Chunks(ChunkArrayWithProof),
+    /// A response to a GET_SHARD_PARITY request.
+    ShardParity(ShardParity),
 }
 
 impl std::convert::From<Response> for RPCCodedResponse {
@@ -1039,6 +1060,7 @@ impl std::convert::From<Response> for RPCCodedResponse {
                 None => RPCCodedResponse::StreamTermination(ResponseTermination::DataByHash),
             },
             Response::Chunks(c) => RPCCodedResponse::Success(RPCResponse::Chunks(c)),
+            Response::ShardParity(p) => RPCCodedResponse::Success(RPCResponse::ShardParity(p)),
         }
     }
 }
@@ -172,6 +172,21 @@ impl Discovery {
             });
         }
 
+        // Add peers known before restart to routing table, so that we do not depend on the
+        // bootnodes only
+        let known_enrs: Vec<Enr> = network_globals
+            .peers
+            .read()
+            .peers()
+            .filter(|(_, info)| !info.is_banned())
+            .filter_map(|(_, info)| info.enr().cloned())
+            .collect();
+        for enr in known_enrs {
+            if let Err(e) = discv5.add_enr(enr) {
+                debug!(error = %e, "Could not add known peer to the local routing table");
+            }
+        }
+
         // Start the discv5 service and obtain an event stream
         let event_stream = if !config.disable_discovery {
             discv5
@@ -27,6 +27,7 @@ use peerdb::score::{PeerAction, ReportSource};
 pub use peerdb::sync_status::{SyncInfo, SyncStatus};
 use std::collections::HashMap;
 use std::net::IpAddr;
+use std::path::PathBuf;
 pub mod config;
 mod network_behaviour;
 
@@ -69,6 +70,10 @@ pub struct PeerManager {
     discovery_enabled: bool,
     /// Keeps track if the current instance is reporting metrics or not.
     metrics_enabled: bool,
+    /// The file to persist the peer db to, if enabled.
+    peer_db_file: Option<PathBuf>,
+    /// The last time the peer db was persisted.
+    peer_db_persisted_at: Instant,
 
     filters: config::Filters,
 }
@@ -97,10 +102,13 @@ pub enum PeerManagerEvent {
 }
 
 impl PeerManager {
+    /// Creates the peer manager, which persists the peer db to `peer_db_file` periodically and
+    /// on shutdown if specified.
     // NOTE: Must be run inside a tokio executor.
     pub async fn new(
         cfg: config::Config,
         network_globals: Arc<NetworkGlobals>,
+        peer_db_file: Option<PathBuf>,
     ) -> error::Result<Self> {
         let config::Config {
             heartbeat_interval,
@@ -116,9 +124,26 @@ impl PeerManager {
         // Set up the peer manager heartbeat interval
         let heartbeat = tokio::time::interval(heartbeat_interval);
 
+        // Peers banned before restart, e.g. restored from the persisted peer db, should be banned
+        // in libp2p as well.
+        let events = {
+            let peers = network_globals.peers.read();
+            peers
+                .banned_peers()
+                .filter_map(|peer_id| {
+                    let ips = peers
+                        .peer_info(peer_id)?
+                        .seen_ip_addresses()
+                        .filter(|ip| peers.is_ip_banned(ip))
+                        .collect();
+                    Some(PeerManagerEvent::Banned(*peer_id, ips))
+                })
+                .collect()
+        };
+
         Ok(PeerManager {
             network_globals,
-            events: SmallVec::new(),
+            events,
             inbound_ping_peers: HashSetDelay::new(Duration::from_secs(ping_interval_inbound)),
             outbound_ping_peers: HashSetDelay::new(Duration::from_secs(ping_interval_outbound)),
             status_peers: HashSetDelay::new(Duration::from_secs(status_interval)),
@@ -126,6 +151,8 @@ impl PeerManager {
             heartbeat,
             discovery_enabled,
             metrics_enabled,
+            peer_db_file,
+            peer_db_persisted_at: Instant::now(),
             filters,
         })
     }
@@ -467,6 +494,7 @@ impl PeerManager {
                     Protocol::DataByHash => PeerAction::MidToleranceError,
                     Protocol::AnswerFile => PeerAction::MidToleranceError,
                     Protocol::GetChunks => PeerAction::MidToleranceError,
+                    Protocol::GetShardParity => PeerAction::MidToleranceError,
                 },
             },
             RPCError::SSZDecodeError(_) => PeerAction::Fatal,
@@ -482,6 +510,7 @@ impl PeerManager {
                     Protocol::DataByHash => return,
                     Protocol::AnswerFile => return,
                     Protocol::GetChunks => return,
+                    Protocol::GetShardParity => return,
                 }
             }
             RPCError::StreamTimeout => match direction {
@@ -497,6 +526,7 @@ impl PeerManager {
                     Protocol::DataByHash => PeerAction::MidToleranceError,
                     Protocol::AnswerFile => PeerAction::MidToleranceError,
                     Protocol::GetChunks => PeerAction::MidToleranceError,
+                    Protocol::GetShardParity => PeerAction::MidToleranceError,
                 },
             },
             RPCError::NegotiationTimeout => PeerAction::LowToleranceError,
@@ -1025,6 +1055,23 @@ impl PeerManager {
         // Prune any excess peers back to our target in such a way that incentivises good scores and
         // a uniform distribution of subnets.
         self.prune_excess_peers();
+
+        // Persist the peer db periodically, so that it survives an unclean shutdown.
+        let persist_interval = self.network_globals.peers.read().config().persist_interval;
+        if self.peer_db_persisted_at.elapsed() >= persist_interval {
+            self.persist_peer_db();
+        }
+    }
+
+    /// Persists the peer db to disk if enabled.
+    fn persist_peer_db(&mut self) {
+        self.peer_db_persisted_at = Instant::now();
+        if let Some(path) = &self.peer_db_file {
+            match self.network_globals.peers.read().persist(path) {
+                Ok(num_peers) => debug!(%num_peers, "Persisted peer db"),
+                Err(e) => warn!(error = %e, "Failed to persist peer db"),
+            }
+        }
     }
 
     // Update metrics related to peer scoring.
@@ -1097,6 +1144,12 @@ impl PeerManager {
     }
 }
 
+impl Drop for PeerManager {
+    fn drop(&mut self) {
+        self.persist_peer_db();
+    }
+}
+
 enum ConnectingType {
     /// We are in the process of dialing this peer.
     Dialing,
@@ -1123,7 +1176,9 @@ mod tests {
             ..Default::default()
         };
         let globals = NetworkGlobals::new_test_globals();
-        PeerManager::new(config, Arc::new(globals)).await.unwrap()
+        PeerManager::new(config, Arc::new(globals), None)
+            .await
+            .unwrap()
     }
 
     #[tokio::test]
@@ -8,6 +8,7 @@ use peer_info::{ConnectionDirection, PeerConnectionStatus, PeerInfo};
 use rand::seq::SliceRandom;
 use score::{PeerAction, ReportSource, Score, ScoreState};
 use serde::{Deserialize, Serialize};
+use shared_types::ShardConfig;
 use std::collections::{HashMap, HashSet};
 use std::net::{IpAddr, SocketAddr};
 use std::time::Instant;
@@ -16,9 +17,12 @@ use sync_status::SyncStatus;
 
 pub mod client;
 pub mod peer_info;
+mod persist;
 pub mod score;
 pub mod sync_status;
 
+pub use persist::PEER_DB_FILENAME;
+
 /// We ban an IP if there are more than `BANNED_PEERS_PER_IP_THRESHOLD` banned peers with this IP.
 const BANNED_PEERS_PER_IP_THRESHOLD: usize = 5;
 
@@ -36,6 +40,15 @@ pub struct PeerDBConfig {
     /// The time we allow peers to be in the dialing state in our PeerDb before we revert them to a disconnected state.
     #[serde(deserialize_with = "deserialize_duration")]
     pub dial_timeout: Duration,
+    /// Whether to persist the peer db to the network directory, so that peer scores and bans
+    /// survive restarts.
+    pub persist_enabled: bool,
+    /// The interval to persist the peer db, besides persisting it on shutdown.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub persist_interval: Duration,
+    /// The persisted peer db is ignored on startup if it is older than this.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub persist_max_age: Duration,
 }
 
 impl Default for PeerDBConfig {
@@ -46,6 +59,9 @@ impl Default for PeerDBConfig {
             banned_peers_per_ip_threshold: 5,
             allowed_negative_gossipsub_factor: 0.1,
             dial_timeout: Duration::from_secs(15),
+            persist_enabled: true,
+            persist_interval: Duration::from_secs(300),
+            persist_max_age: Duration::from_secs(7 * 24 * 3600),
         }
     }
 }
@@ -79,6 +95,10 @@ impl PeerDB {
 
     /* Getters */
 
+    pub fn config(&self) -> &PeerDBConfig {
+        &self.config
+    }
+
     /// Gives the score of a peer, or default score if it is unknown.
     pub fn score(&self, peer_id: &PeerId) -> f64 {
         self.peers
@@ -579,6 +599,13 @@ impl PeerDB {
         }
     }
 
+    /// Records the last known shard config of a peer, which is persisted along with the peer db.
+    pub fn update_shard_config(&mut self, peer_id: &PeerId, shard_config: ShardConfig) {
+        if let Some(info) = self.peers.get_mut(peer_id) {
+            info.set_shard_config(shard_config);
+        }
+    }
+
     /// Update min ttl of a peer.
     // VISIBILITY: Only the peer manager can update the min_ttl
     pub(super) fn update_min_ttl(&mut self, peer_id: &PeerId, min_ttl: Instant) {
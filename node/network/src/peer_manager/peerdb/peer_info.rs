@@ -7,6 +7,7 @@ use serde::{
     ser::{SerializeStruct, Serializer},
     Serialize,
 };
+use shared_types::ShardConfig;
 use std::collections::HashSet;
 use std::net::{IpAddr, SocketAddr};
 use std::time::Instant;
@@ -42,6 +43,9 @@ pub struct PeerInfo {
     connection_direction: Option<ConnectionDirection>,
     /// The enr of the peer, if known.
     enr: Option<Enr>,
+    /// The last known shard config announced by the peer.
+    #[serde(skip)]
+    shard_config: Option<ShardConfig>,
 }
 
 impl Default for PeerInfo {
@@ -57,6 +61,7 @@ impl Default for PeerInfo {
             is_trusted: false,
             connection_direction: None,
             enr: None,
+            shard_config: None,
         }
     }
 }
@@ -71,6 +76,26 @@ impl PeerInfo {
         }
     }
 
+    /// Return a PeerInfo struct for a peer restored from the persisted peer db.
+    pub(super) fn restored(
+        score: Score,
+        connection_status: PeerConnectionStatus,
+        listening_addresses: Vec<Multiaddr>,
+        seen_addresses: HashSet<SocketAddr>,
+        enr: Option<Enr>,
+        shard_config: Option<ShardConfig>,
+    ) -> Self {
+        PeerInfo {
+            score,
+            connection_status,
+            listening_addresses,
+            seen_addresses,
+            enr,
+            shard_config,
+            ..Default::default()
+        }
+    }
+
     /// Obtains the client of the peer.
     pub fn client(&self) -> &Client {
         &self.client
@@ -107,6 +132,11 @@ impl PeerInfo {
         self.enr.as_ref()
     }
 
+    /// The last known shard config of the peer.
+    pub fn shard_config(&self) -> Option<ShardConfig> {
+        self.shard_config
+    }
+
     /// Returns the seen addresses of the peer.
     pub fn seen_addresses(&self) -> impl Iterator<Item = &SocketAddr> + '_ {
         self.seen_addresses.iter()
@@ -227,6 +257,11 @@ impl PeerInfo {
         self.enr = Some(enr)
     }
 
+    /// Sets the last known shard config of the peer.
+    pub(super) fn set_shard_config(&mut self, shard_config: ShardConfig) {
+        self.shard_config = Some(shard_config)
+    }
+
     /// Sets the time that the peer is expected to be needed until for an attached validator duty.
     pub(super) fn set_min_ttl(&mut self, min_ttl: Instant) {
         self.min_ttl = Some(min_ttl)
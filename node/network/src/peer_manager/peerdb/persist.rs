@@ -0,0 +1,257 @@
+//! Persistence of the peer db, so that the reputation of peers survives restarts.
+//!
+//! Only the lighthouse part of the score is persisted. When loaded, it decays for the time the
+//! node was offline, and peers are restored in the banned or disconnected state accordingly.
+
+use super::peer_info::{PeerConnectionStatus, PeerInfo};
+use super::score::{RealScore, Score, ScoreState};
+use super::PeerDB;
+use crate::{Enr, Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use shared_types::{timestamp_now, ShardConfig};
+use std::collections::HashSet;
+use std::fs;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// The file name of the persisted peer db in the network directory.
+pub const PEER_DB_FILENAME: &str = "peers.json";
+
+const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct PersistedPeerDB {
+    version: u32,
+    /// Unix timestamp in seconds when the peer db was persisted.
+    saved_at: u32,
+    peers: Vec<PersistedPeer>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersistedPeer {
+    peer_id: String,
+    score: f64,
+    /// The remaining seconds before the score begins to decay, non-zero for banned peers.
+    decay_delay_secs: u64,
+    listening_addresses: Vec<String>,
+    seen_addresses: Vec<SocketAddr>,
+    enr: Option<String>,
+    shard_config: Option<PersistedShardConfig>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersistedShardConfig {
+    shard_id: usize,
+    num_shard: usize,
+}
+
+impl PeerDB {
+    /// Persists all untrusted peers to `path`, and returns the number of persisted peers.
+    pub fn persist(&self, path: &Path) -> Result<usize, String> {
+        let peers: Vec<_> = self
+            .peers
+            .iter()
+            .filter(|(_, info)| !info.is_trusted())
+            .filter_map(|(peer_id, info)| match info.score() {
+                Score::Real(score) => Some(PersistedPeer {
+                    peer_id: peer_id.to_base58(),
+                    score: score.lighthouse_score(),
+                    decay_delay_secs: score.decay_delay().as_secs(),
+                    listening_addresses: info
+                        .listening_addresses()
+                        .iter()
+                        .map(|addr| addr.to_string())
+                        .collect(),
+                    seen_addresses: info.seen_addresses().cloned().collect(),
+                    enr: info.enr().map(|enr| enr.to_base64()),
+                    shard_config: info.shard_config().map(|config| PersistedShardConfig {
+                        shard_id: config.shard_id,
+                        num_shard: config.num_shard,
+                    }),
+                }),
+                Score::Max => None,
+            })
+            .collect();
+        let num_peers = peers.len();
+
+        let db = PersistedPeerDB {
+            version: VERSION,
+            saved_at: timestamp_now(),
+            peers,
+        };
+        let data =
+            serde_json::to_vec(&db).map_err(|e| format!("Failed to serialize peer db: {:?}", e))?;
+
+        directory::atomic_write(path, &data)
+            .map_err(|e| format!("Failed to write {:?}: {:?}", path, e))?;
+
+        Ok(num_peers)
+    }
+
+    /// Restores the peers persisted in `path`, and returns the number of restored peers.
+    ///
+    /// Peers that are already known, e.g. trusted peers, are left untouched. Nothing is restored
+    /// if the file does not exist or is older than `persist_max_age`.
+    pub fn load_persisted(&mut self, path: &Path) -> Result<usize, String> {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(format!("Failed to read {:?}: {:?}", path, e)),
+        };
+        let db: PersistedPeerDB = serde_json::from_slice(&data)
+            .map_err(|e| format!("Failed to deserialize peer db: {:?}", e))?;
+        if db.version != VERSION {
+            return Err(format!("Unsupported peer db version {}", db.version));
+        }
+
+        let elapsed = Duration::from_secs(timestamp_now().saturating_sub(db.saved_at) as u64);
+        if elapsed > self.config.persist_max_age {
+            debug!(?elapsed, "Ignore the outdated persisted peer db");
+            return Ok(0);
+        }
+
+        let now = Instant::now();
+        let mut num_restored = 0;
+        for peer in db.peers {
+            let peer_id = match PeerId::from_str(&peer.peer_id) {
+                Ok(peer_id) => peer_id,
+                Err(e) => {
+                    warn!(peer_id = %peer.peer_id, ?e, "Invalid peer id in persisted peer db");
+                    continue;
+                }
+            };
+            if self.peers.contains_key(&peer_id) {
+                continue;
+            }
+
+            let score = Score::Real(RealScore::restore(
+                peer.score,
+                Duration::from_secs(peer.decay_delay_secs),
+                elapsed,
+            ));
+            let seen_addresses: HashSet<_> = peer.seen_addresses.into_iter().collect();
+            let connection_status = if score.state() == ScoreState::Banned {
+                self.banned_peers_count
+                    .add_banned_peer(seen_addresses.iter().map(|addr| addr.ip()));
+                PeerConnectionStatus::Banned { since: now }
+            } else {
+                self.disconnected_peers += 1;
+                PeerConnectionStatus::Disconnected { since: now }
+            };
+            let listening_addresses = peer
+                .listening_addresses
+                .iter()
+                .filter_map(|addr| Multiaddr::from_str(addr).ok())
+                .collect();
+            let enr = peer.enr.and_then(|enr| Enr::from_str(&enr).ok());
+            let shard_config = peer.shard_config.map(|config| ShardConfig {
+                num_shard: config.num_shard,
+                shard_id: config.shard_id,
+            });
+
+            self.peers.insert(
+                peer_id,
+                PeerInfo::restored(
+                    score,
+                    connection_status,
+                    listening_addresses,
+                    seen_addresses,
+                    enr,
+                    shard_config,
+                ),
+            );
+            num_restored += 1;
+        }
+
+        // The limits may have been lowered since the peer db was persisted. Peers dropped here are
+        // not known by libp2p yet, so there is nothing to unban.
+        let _ = self.shrink_to_fit();
+
+        Ok(num_restored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peer_manager::peerdb::score::{PeerAction, ReportSource};
+    use crate::peer_manager::peerdb::PeerDBConfig;
+
+    #[test]
+    fn test_persist_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PEER_DB_FILENAME);
+
+        let mut pdb = PeerDB::new(PeerDBConfig::default(), vec![]);
+        let good_peer = PeerId::random();
+        let bad_peer = PeerId::random();
+        pdb.connect_ingoing(&good_peer, "/ip4/1.2.3.4/tcp/1234".parse().unwrap(), None);
+        pdb.connect_ingoing(&bad_peer, "/ip4/5.6.7.8/tcp/1234".parse().unwrap(), None);
+        let shard_config = ShardConfig {
+            num_shard: 4,
+            shard_id: 1,
+        };
+        pdb.update_shard_config(&good_peer, shard_config);
+        let _ = pdb.report_peer(
+            &good_peer,
+            PeerAction::HighToleranceError,
+            ReportSource::PeerManager,
+            "test",
+        );
+        let _ = pdb.report_peer(
+            &bad_peer,
+            PeerAction::Fatal,
+            ReportSource::PeerManager,
+            "test",
+        );
+        let _ = pdb.inject_disconnect(&bad_peer);
+        assert!(pdb.ban_status(&bad_peer).is_banned());
+        assert_eq!(pdb.persist(&path).unwrap(), 2);
+
+        let trusted_peer = PeerId::random();
+        let mut pdb = PeerDB::new(PeerDBConfig::default(), vec![trusted_peer]);
+        assert_eq!(pdb.load_persisted(&path).unwrap(), 2);
+        assert_eq!(pdb.peers().count(), 3);
+
+        let info = pdb.peer_info(&good_peer).unwrap();
+        assert!(info.is_disconnected());
+        assert!(info.score().score() < 0.0);
+        assert_eq!(info.shard_config(), Some(shard_config));
+
+        let info = pdb.peer_info(&bad_peer).unwrap();
+        assert!(info.is_banned());
+        assert!(pdb.ban_status(&bad_peer).is_banned());
+
+        // Already known peers are not overwritten.
+        assert_eq!(pdb.load_persisted(&path).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_load_outdated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PEER_DB_FILENAME);
+
+        let mut pdb = PeerDB::new(PeerDBConfig::default(), vec![]);
+        assert_eq!(pdb.load_persisted(&path).unwrap(), 0);
+
+        let db = PersistedPeerDB {
+            version: VERSION,
+            saved_at: timestamp_now() - 30 * 24 * 3600,
+            peers: vec![PersistedPeer {
+                peer_id: PeerId::random().to_base58(),
+                score: -100.0,
+                decay_delay_secs: 0,
+                listening_addresses: vec![],
+                seen_addresses: vec![],
+                enr: None,
+                shard_config: None,
+            }],
+        };
+        fs::write(&path, serde_json::to_vec(&db).unwrap()).unwrap();
+        assert_eq!(pdb.load_persisted(&path).unwrap(), 0);
+        assert_eq!(pdb.peers().count(), 0);
+    }
+}
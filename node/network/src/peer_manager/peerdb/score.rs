@@ -265,6 +265,41 @@ impl RealScore {
     pub fn is_good_gossipsub_peer(&self) -> bool {
         self.gossipsub_score >= 0.0
     }
+
+    /// The lighthouse part of the score, which is the only part that survives a restart.
+    pub(crate) fn lighthouse_score(&self) -> f64 {
+        self.lighthouse_score
+    }
+
+    /// The remaining time before the score begins to decay, which is non-zero for banned peers.
+    pub(crate) fn decay_delay(&self) -> Duration {
+        self.last_updated
+            .checked_duration_since(Instant::now())
+            .unwrap_or_default()
+    }
+
+    /// Restores a score that was persisted `elapsed` ago, applying the decay that would have
+    /// happened in the meantime.
+    pub(crate) fn restore(lighthouse_score: f64, decay_delay: Duration, elapsed: Duration) -> Self {
+        let now = Instant::now();
+        let mut score = RealScore {
+            lighthouse_score: lighthouse_score.clamp(MIN_SCORE, MAX_SCORE),
+            last_updated: now,
+            ..Default::default()
+        };
+
+        match decay_delay.checked_sub(elapsed) {
+            // Still within the ban period, the score does not decay yet.
+            Some(remaining) if !remaining.is_zero() => score.last_updated = now + remaining,
+            _ => {
+                let secs = elapsed.saturating_sub(decay_delay).as_secs();
+                score.lighthouse_score *= (*HALFLIFE_DECAY * secs as f64).exp();
+            }
+        }
+
+        score.recompute_score();
+        score
+    }
 }
 
 #[derive(PartialEq, Clone, Debug, Serialize)]
@@ -386,6 +421,27 @@ mod tests {
         assert_eq!(score.score(), DEFAULT_SCORE + change);
     }
 
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn test_restore() {
+        let ban = Duration::from_secs(3600);
+
+        // Within the ban period the score is kept as is.
+        let score = RealScore::restore(MIN_SCORE, ban, Duration::from_secs(60));
+        assert_eq!(score.score(), MIN_SCORE);
+        assert!(score.decay_delay() > Duration::from_secs(3000));
+
+        // After the ban period the score decays by the elapsed half lives.
+        let elapsed = ban + Duration::from_secs(SCORE_HALFLIFE as u64);
+        let score = RealScore::restore(MIN_SCORE, ban, elapsed);
+        assert!((score.score() - MIN_SCORE / 2.0).abs() < 1e-9);
+        assert_eq!(score.decay_delay(), Duration::ZERO);
+
+        // Out of range scores are capped.
+        let score = RealScore::restore(MAX_SCORE * 2.0, Duration::ZERO, Duration::ZERO);
+        assert_eq!(score.score(), MAX_SCORE);
+    }
+
     #[test]
     #[allow(clippy::float_cmp)]
     fn test_ban_time() {
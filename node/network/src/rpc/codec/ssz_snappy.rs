@@ -52,6 +52,7 @@ impl Encoder<RPCCodedResponse> for SSZSnappyInboundCodec {
                 RPCResponse::Pong(res) => res.data.as_ssz_bytes(),
                 RPCResponse::DataByHash(res) => res.as_ssz_bytes(),
                 RPCResponse::Chunks(res) => res.as_ssz_bytes(),
+                RPCResponse::ShardParity(res) => res.as_ssz_bytes(),
             },
             RPCCodedResponse::Error(_, err) => err.as_ssz_bytes(),
             RPCCodedResponse::StreamTermination(_) => {
@@ -161,6 +162,7 @@ impl Encoder<OutboundRequest> for SSZSnappyOutboundCodec {
             OutboundRequest::DataByHash(req) => req.hashes.as_ssz_bytes(),
             OutboundRequest::AnswerFile(req) => req.as_ssz_bytes(),
             OutboundRequest::GetChunks(req) => req.as_ssz_bytes(),
+            OutboundRequest::GetShardParity(req) => req.as_ssz_bytes(),
         };
         // SSZ encoded bytes should be within `max_packet_size`
         if bytes.len() > self.max_packet_size {
@@ -353,6 +355,9 @@ fn handle_v1_request(
         Protocol::GetChunks => Ok(Some(InboundRequest::GetChunks(
             GetChunksRequest::from_ssz_bytes(decoded_buffer)?,
         ))),
+        Protocol::GetShardParity => Ok(Some(InboundRequest::GetShardParity(
+            GetShardParityRequest::from_ssz_bytes(decoded_buffer)?,
+        ))),
     }
 }
 
@@ -384,6 +389,9 @@ fn handle_v1_response(
         Protocol::GetChunks => Ok(Some(RPCResponse::Chunks(
             ChunkArrayWithProof::from_ssz_bytes(decoded_buffer)?,
         ))),
+        Protocol::GetShardParity => Ok(Some(RPCResponse::ShardParity(
+            ShardParity::from_ssz_bytes(decoded_buffer)?,
+        ))),
     }
 }
 
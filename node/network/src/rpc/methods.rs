@@ -191,6 +191,22 @@ pub struct GetChunksRequest {
     pub merkle_tx_seq: u64,
 }
 
+/// Request the erasure coded parity of a shard for a stripe of a file.
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct GetShardParityRequest {
+    pub tx_id: TxID,
+    pub stripe_index: u64,
+    pub shard_id: usize,
+}
+
+/// The parity segments of a stripe held by the requested shard, concatenated in order.
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct ShardParity {
+    pub tx_id: TxID,
+    pub stripe_index: u64,
+    pub data: Vec<u8>,
+}
+
 /* RPC Handling and Grouping */
 // Collection of enums and structs used by the Codecs to encode/decode RPC messages
 
@@ -207,6 +223,9 @@ pub enum RPCResponse {
 
     /// A response to a GET_CHUNKS request.
     Chunks(ChunkArrayWithProof),
+
+    /// A response to a GET_SHARD_PARITY request.
+    ShardParity(ShardParity),
 }
 
 /// Indicates which response is being terminated by a stream termination response.
@@ -276,6 +295,7 @@ impl RPCCodedResponse {
                 RPCResponse::Pong(_) => false,
                 RPCResponse::DataByHash(_) => true,
                 RPCResponse::Chunks(_) => false,
+                RPCResponse::ShardParity(_) => false,
             },
             RPCCodedResponse::Error(_, _) => true,
             // Stream terminations are part of responses that have chunks
@@ -335,6 +355,14 @@ impl std::fmt::Display for RPCResponse {
                     data.chunks.data.len()
                 )
             }
+            RPCResponse::ShardParity(data) => {
+                write!(
+                    f,
+                    "Shard Parity Response, stripe: {}, data length: {}",
+                    data.stripe_index,
+                    data.data.len()
+                )
+            }
         }
     }
 }
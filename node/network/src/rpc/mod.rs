@@ -22,8 +22,9 @@ pub(crate) use protocol::{InboundRequest, RPCProtocol};
 
 pub use handler::SubstreamId;
 pub use methods::{
-    DataByHashRequest, GetChunksRequest, GoodbyeReason, MaxRequestBlocks, RPCResponseErrorCode,
-    ResponseTermination, StatusMessage, ZgsData, MAX_REQUEST_BLOCKS,
+    DataByHashRequest, GetChunksRequest, GetShardParityRequest, GoodbyeReason, MaxRequestBlocks,
+    RPCResponseErrorCode, ResponseTermination, ShardParity, StatusMessage, ZgsData,
+    MAX_REQUEST_BLOCKS,
 };
 pub(crate) use outbound::OutboundRequest;
 pub use protocol::{max_rpc_size, Protocol, RPCError};
@@ -120,6 +121,7 @@ impl<Id: ReqId> RPC<Id> {
             .n_every(Protocol::DataByHash, 128, Duration::from_secs(10))
             .n_every(Protocol::AnswerFile, 256, Duration::from_secs(10))
             .n_every(Protocol::GetChunks, 4096, Duration::from_secs(10))
+            .n_every(Protocol::GetShardParity, 1024, Duration::from_secs(10))
             .build()
             .expect("Configuration parameters are valid");
         RPC {
@@ -37,6 +37,7 @@ pub enum OutboundRequest {
     DataByHash(DataByHashRequest),
     AnswerFile(ShardedFile),
     GetChunks(GetChunksRequest),
+    GetShardParity(GetShardParityRequest),
 }
 
 impl UpgradeInfo for OutboundRequestContainer {
@@ -84,6 +85,11 @@ impl OutboundRequest {
                 Version::V1,
                 Encoding::SSZSnappy,
             )],
+            OutboundRequest::GetShardParity(_) => vec![ProtocolId::new(
+                Protocol::GetShardParity,
+                Version::V1,
+                Encoding::SSZSnappy,
+            )],
         }
     }
 
@@ -98,6 +104,7 @@ impl OutboundRequest {
             OutboundRequest::DataByHash(req) => req.hashes.len() as u64,
             OutboundRequest::AnswerFile(_) => 0,
             OutboundRequest::GetChunks(_) => 1,
+            OutboundRequest::GetShardParity(_) => 1,
         }
     }
 
@@ -110,6 +117,7 @@ impl OutboundRequest {
             OutboundRequest::DataByHash(_) => Protocol::DataByHash,
             OutboundRequest::AnswerFile(_) => Protocol::AnswerFile,
             OutboundRequest::GetChunks(_) => Protocol::GetChunks,
+            OutboundRequest::GetShardParity(_) => Protocol::GetShardParity,
         }
     }
 
@@ -125,6 +133,7 @@ impl OutboundRequest {
             OutboundRequest::Ping(_) => unreachable!(),
             OutboundRequest::AnswerFile(_) => unreachable!(),
             OutboundRequest::GetChunks(_) => unreachable!(),
+            OutboundRequest::GetShardParity(_) => unreachable!(),
         }
     }
 }
@@ -186,6 +195,9 @@ impl std::fmt::Display for OutboundRequest {
             OutboundRequest::GetChunks(req) => {
                 write!(f, "GetChunks: {:?}", req)
             }
+            OutboundRequest::GetShardParity(req) => {
+                write!(f, "GetShardParity: {:?}", req)
+            }
         }
     }
 }
@@ -8,7 +8,7 @@ use futures::future::BoxFuture;
 use futures::prelude::{AsyncRead, AsyncWrite};
 use futures::{FutureExt, StreamExt};
 use libp2p::core::{InboundUpgrade, ProtocolName, UpgradeInfo};
-use shared_types::{ChunkArray, ChunkArrayWithProof, FlowRangeProof, ShardedFile};
+use shared_types::{ChunkArray, ChunkArrayWithProof, FlowRangeProof, ShardedFile, TxID};
 use ssz::Encode;
 use ssz_types::VariableList;
 use std::io;
@@ -60,6 +60,20 @@ lazy_static! {
     }
     .as_ssz_bytes()
     .len();
+    pub static ref SHARD_PARITY_RESPONSE_MIN: usize = ShardParity {
+        tx_id: TxID::default(),
+        stripe_index: 0,
+        data: vec![],
+    }
+    .as_ssz_bytes()
+    .len();
+    pub static ref SHARD_PARITY_RESPONSE_MAX: usize = ShardParity {
+        tx_id: TxID::default(),
+        stripe_index: 0,
+        data: vec![0u8; MAX_CHUNKS_LENGTH],
+    }
+    .as_ssz_bytes()
+    .len();
 }
 
 // /// The maximum bytes that can be sent across the RPC pre-merge.
@@ -95,6 +109,8 @@ pub enum Protocol {
     AnswerFile,
     /// The Chunk sync protocol.
     GetChunks,
+    /// The erasure coded parity sync protocol.
+    GetShardParity,
 }
 
 /// RPC Versions
@@ -119,6 +135,7 @@ impl std::fmt::Display for Protocol {
             Protocol::DataByHash => "data_by_hash",
             Protocol::AnswerFile => "answer_file",
             Protocol::GetChunks => "get_chunks",
+            Protocol::GetShardParity => "get_shard_parity",
         };
         f.write_str(repr)
     }
@@ -160,6 +177,7 @@ impl UpgradeInfo for RPCProtocol {
             ProtocolId::new(Protocol::DataByHash, Version::V1, Encoding::SSZSnappy),
             ProtocolId::new(Protocol::AnswerFile, Version::V1, Encoding::SSZSnappy),
             ProtocolId::new(Protocol::GetChunks, Version::V1, Encoding::SSZSnappy),
+            ProtocolId::new(Protocol::GetShardParity, Version::V1, Encoding::SSZSnappy),
         ]
     }
 }
@@ -228,6 +246,10 @@ impl ProtocolId {
                 <GetChunksRequest as Encode>::ssz_fixed_len(),
                 <GetChunksRequest as Encode>::ssz_fixed_len(),
             ),
+            Protocol::GetShardParity => RpcLimits::new(
+                <GetShardParityRequest as Encode>::ssz_fixed_len(),
+                <GetShardParityRequest as Encode>::ssz_fixed_len(),
+            ),
         }
     }
 
@@ -253,6 +275,9 @@ impl ProtocolId {
 
             Protocol::AnswerFile => RpcLimits::new(0, 0), // AnswerFile request has no response
             Protocol::GetChunks => RpcLimits::new(*CHUNKS_RESPONSE_MIN, *CHUNKS_RESPONSE_MAX),
+            Protocol::GetShardParity => {
+                RpcLimits::new(*SHARD_PARITY_RESPONSE_MIN, *SHARD_PARITY_RESPONSE_MAX)
+            }
         }
     }
 }
@@ -336,6 +361,7 @@ pub enum InboundRequest {
     DataByHash(DataByHashRequest),
     AnswerFile(ShardedFile),
     GetChunks(GetChunksRequest),
+    GetShardParity(GetShardParityRequest),
 }
 
 impl UpgradeInfo for InboundRequest {
@@ -383,6 +409,11 @@ impl InboundRequest {
                 Version::V1,
                 Encoding::SSZSnappy,
             )],
+            InboundRequest::GetShardParity(_) => vec![ProtocolId::new(
+                Protocol::GetShardParity,
+                Version::V1,
+                Encoding::SSZSnappy,
+            )],
         }
     }
 
@@ -397,6 +428,7 @@ impl InboundRequest {
             InboundRequest::Ping(_) => 1,
             InboundRequest::AnswerFile(_) => 0,
             InboundRequest::GetChunks(_) => 1,
+            InboundRequest::GetShardParity(_) => 1,
         }
     }
 
@@ -409,6 +441,7 @@ impl InboundRequest {
             InboundRequest::DataByHash(_) => Protocol::DataByHash,
             InboundRequest::AnswerFile(_) => Protocol::AnswerFile,
             InboundRequest::GetChunks(_) => Protocol::GetChunks,
+            InboundRequest::GetShardParity(_) => Protocol::GetShardParity,
         }
     }
 
@@ -424,6 +457,7 @@ impl InboundRequest {
             InboundRequest::Ping(_) => unreachable!(),
             InboundRequest::AnswerFile(_) => unreachable!(),
             InboundRequest::GetChunks(_) => unreachable!(),
+            InboundRequest::GetShardParity(_) => unreachable!(),
         }
     }
 }
@@ -547,6 +581,9 @@ impl std::fmt::Display for InboundRequest {
             InboundRequest::GetChunks(req) => {
                 write!(f, "Get Chunks: {:?}", req)
             }
+            InboundRequest::GetShardParity(req) => {
+                write!(f, "Get Shard Parity: {:?}", req)
+            }
         }
     }
 }
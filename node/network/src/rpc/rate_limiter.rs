@@ -88,6 +88,8 @@ pub struct RPCRateLimiter {
     answer_file_rl: Limiter<PeerId>,
     /// GetChunks rate limiter.
     get_chunks_rl: Limiter<PeerId>,
+    /// GetShardParity rate limiter.
+    get_shard_parity_rl: Limiter<PeerId>,
 }
 
 /// Error type for non conformant requests
@@ -113,6 +115,8 @@ pub struct RPCRateLimiterBuilder {
     answer_file_quota: Option<Quota>,
     /// Quota for the GetChunks protocol.
     get_chunks_quota: Option<Quota>,
+    /// Quota for the GetShardParity protocol.
+    get_shard_parity_quota: Option<Quota>,
 }
 
 impl RPCRateLimiterBuilder {
@@ -131,6 +135,7 @@ impl RPCRateLimiterBuilder {
             Protocol::DataByHash => self.data_by_hash_quota = q,
             Protocol::AnswerFile => self.answer_file_quota = q,
             Protocol::GetChunks => self.get_chunks_quota = q,
+            Protocol::GetShardParity => self.get_shard_parity_quota = q,
         }
         self
     }
@@ -160,6 +165,9 @@ impl RPCRateLimiterBuilder {
         let get_chunks_quota = self
             .get_chunks_quota
             .ok_or("GetChunks quota not specified")?;
+        let get_shard_parity_quota = self
+            .get_shard_parity_quota
+            .ok_or("GetShardParity quota not specified")?;
 
         // create the rate limiters
         let ping_rl = Limiter::from_quota(ping_quota)?;
@@ -168,6 +176,7 @@ impl RPCRateLimiterBuilder {
         let data_by_hash_rl = Limiter::from_quota(data_by_hash_quota)?;
         let answer_file_rl = Limiter::from_quota(answer_file_quota)?;
         let get_chunks_rl = Limiter::from_quota(get_chunks_quota)?;
+        let get_shard_parity_rl = Limiter::from_quota(get_shard_parity_quota)?;
 
         // check for peers to prune every 30 seconds, starting in 30 seconds
         let prune_every = tokio::time::Duration::from_secs(30);
@@ -181,6 +190,7 @@ impl RPCRateLimiterBuilder {
             data_by_hash_rl,
             answer_file_rl,
             get_chunks_rl,
+            get_shard_parity_rl,
             init_time: Instant::now(),
         })
     }
@@ -226,6 +236,7 @@ impl RPCRateLimiter {
             Protocol::DataByHash => &mut self.data_by_hash_rl,
             Protocol::AnswerFile => &mut self.answer_file_rl,
             Protocol::GetChunks => &mut self.get_chunks_rl,
+            Protocol::GetShardParity => &mut self.get_shard_parity_rl,
         };
         check(limiter)
     }
@@ -237,6 +248,7 @@ impl RPCRateLimiter {
         self.goodbye_rl.prune(time_since_start);
         self.data_by_hash_rl.prune(time_since_start);
         self.get_chunks_rl.prune(time_since_start);
+        self.get_shard_parity_rl.prune(time_since_start);
     }
 }
 
@@ -290,6 +302,27 @@ impl<Key: Hash + Eq + Clone> Limiter<Key> {
         time_since_start: Duration,
         key: &Key,
         tokens: u64,
+    ) -> Result<(), RateLimitedErr> {
+        self.check(time_since_start, key, tokens)?;
+
+        let time_since_start = time_since_start.as_nanos() as u64;
+        let additional_time = self.t * tokens;
+        let tat = self
+            .tat_per_key
+            .entry(key.clone())
+            .or_insert(time_since_start);
+        // calculate the new TAT
+        *tat = time_since_start.max(*tat) + additional_time;
+        Ok(())
+    }
+
+    /// Checks whether the tokens are allowed for the key like `allows`, but without consuming
+    /// them.
+    pub fn check(
+        &self,
+        time_since_start: Duration,
+        key: &Key,
+        tokens: u64,
     ) -> Result<(), RateLimitedErr> {
         let time_since_start = time_since_start.as_nanos() as u64;
         let tau = self.tau;
@@ -305,10 +338,11 @@ impl<Key: Hash + Eq + Clone> Limiter<Key> {
         // allowed)
         let tat = self
             .tat_per_key
-            .entry(key.clone())
-            .or_insert(time_since_start);
+            .get(key)
+            .copied()
+            .unwrap_or(time_since_start);
         // check how soon could the request be made
-        let earliest_time = (*tat + additional_time).saturating_sub(tau);
+        let earliest_time = (tat + additional_time).saturating_sub(tau);
         // earliest_time is in the future
         if time_since_start < earliest_time {
             Err(RateLimitedErr::TooSoon(Duration::from_nanos(
@@ -316,8 +350,6 @@ impl<Key: Hash + Eq + Clone> Limiter<Key> {
                 earliest_time - time_since_start,
             )))
         } else {
-            // calculate the new TAT
-            *tat = time_since_start.max(*tat) + additional_time;
             Ok(())
         }
     }
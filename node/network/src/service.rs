@@ -22,6 +22,7 @@ use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Duration;
 
+use crate::peer_manager::peerdb::PEER_DB_FILENAME;
 use crate::peer_manager::{MIN_OUTBOUND_ONLY_FACTOR, PEER_EXCESS_FACTOR, PRIORITY_PEER_EXCESS};
 
 pub const NETWORK_KEY_FILENAME: &str = "key";
@@ -87,6 +88,15 @@ impl<AppReqId: ReqId> Service<AppReqId> {
             config.network_id.clone(),
         ));
 
+        // Restore the peer db persisted before restart
+        if config.peer_db.persist_enabled {
+            let peer_db_file = config.network_dir.join(PEER_DB_FILENAME);
+            match network_globals.peers.write().load_persisted(&peer_db_file) {
+                Ok(num_peers) => info!(%num_peers, "Peer db restored"),
+                Err(e) => warn!(error = %e, "Failed to restore peer db"),
+            }
+        }
+
         // try and construct UPnP port mappings if required.
         if let Some(upnp_config) = crate::nat::UPnPConfig::from_config(config) {
             if config.upnp_enabled {
@@ -7,7 +7,7 @@ pub type Enr = discv5::enr::Enr<discv5::enr::CombinedKey>;
 
 pub use globals::NetworkGlobals;
 pub use pubsub::{
-    AnnounceChunks, AnnounceFile, FindChunks, FindFile, HasSignature, PubsubMessage,
-    SignedAnnounceFile, SignedMessage, SnappyTransform, TimedMessage,
+    peer_id_to_public_key, AnnounceChunks, AnnounceFile, FindChunks, FindFile, HasSignature,
+    PubsubMessage, SignedAnnounceFile, SignedMessage, SnappyTransform, TimedMessage, WrappedPeerId,
 };
 pub use topics::{GossipEncoding, GossipKind, GossipTopic};
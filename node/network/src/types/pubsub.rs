@@ -199,6 +199,20 @@ pub trait HasSignature {
     fn verify_signature(&self, public_key: &PublicKey) -> bool;
 }
 
+/// Recovers the public key of a peer from its peer id.
+pub fn peer_id_to_public_key(peer_id: &PeerId) -> Result<PublicKey, String> {
+    // A libp2p peer id byte representation should be 2 length bytes + 4 protobuf bytes + compressed pk bytes
+    // if generated from a PublicKey with Identity multihash.
+    let pk_bytes = &peer_id.to_bytes()[2..];
+
+    PublicKey::from_protobuf_encoding(pk_bytes).map_err(|e| {
+        format!(
+            " Cannot parse libp2p public key public key from peer id: {}",
+            e
+        )
+    })
+}
+
 impl<T: Encode + Decode> HasSignature for SignedMessage<T> {
     fn verify_signature(&self, public_key: &PublicKey) -> bool {
         let raw = self.inner.as_ssz_bytes();
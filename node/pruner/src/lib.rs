@@ -1,15 +1,13 @@
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use chrono::Utc;
 use contract_interface::ChunkLinearReward;
+use contract_wrapper::{ProviderConfig, RpcProvider};
 use ethereum_types::Address;
-use ethers::prelude::{Http, Provider};
-use ethers::providers::{HttpRateLimitRetryPolicy, RetryClient, RetryClientBuilder};
 use ethers_core::types::U256;
 use miner::MinerMessage;
 use rand::Rng;
 use std::cmp::Ordering;
 use std::path::PathBuf;
-use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 use storage::config::{ShardConfig, SHARD_CONFIG_KEY};
@@ -36,8 +34,10 @@ pub struct PrunerConfig {
     pub batch_size: usize,
     pub batch_wait_time: Duration,
 
-    pub rpc_endpoint_url: String,
-    pub reward_address: Address,
+    pub rpc_endpoint_urls: Vec<String>,
+    /// Address of the reward contract to query the first rewardable chunk, which is `None` in
+    /// dev mode so that only data out of the shard range is pruned.
+    pub reward_address: Option<Address>,
 
     pub rate_limit_retries: u32,
     pub timeout_retries: u32,
@@ -60,7 +60,7 @@ pub struct Pruner {
     sender: mpsc::UnboundedSender<PrunerMessage>,
     miner_sender: Option<broadcast::Sender<MinerMessage>>,
 
-    reward_contract: ChunkLinearReward<Arc<Provider<RetryClient<Http>>>>,
+    reward_contract: Option<ChunkLinearReward<Arc<RpcProvider>>>,
 }
 
 impl Pruner {
@@ -77,17 +77,22 @@ impl Pruner {
             .await?
             .unwrap_or((0, 0));
 
-        let provider = Arc::new(Provider::new(
-            RetryClientBuilder::default()
-                .rate_limit_retries(config.rate_limit_retries)
-                .timeout_retries(config.timeout_retries)
-                .initial_backoff(Duration::from_millis(config.initial_backoff))
-                .build(
-                    Http::from_str(&config.rpc_endpoint_url)?,
-                    Box::new(HttpRateLimitRetryPolicy),
-                ),
-        ));
-        let reward_contract = ChunkLinearReward::new(config.reward_address, Arc::new(provider));
+        let reward_contract = match config.reward_address {
+            Some(reward_address) => {
+                let provider = ProviderConfig {
+                    endpoints: config.rpc_endpoint_urls.clone(),
+                    rate_limit_retries: config.rate_limit_retries,
+                    timeout_retries: config.timeout_retries,
+                    initial_backoff: config.initial_backoff,
+                    request_timeout: None,
+                }
+                .make_provider()
+                .map_err(|e| anyhow!(e))?;
+                let provider = Arc::new(provider);
+                Some(ChunkLinearReward::new(reward_address, Arc::new(provider)))
+            }
+            None => None,
+        };
         let (tx, rx) = mpsc::unbounded_channel();
         let pruner = Pruner {
             config,
@@ -118,7 +123,14 @@ impl Pruner {
             }
 
             // Check no reward chunks and prune.
-            match self.reward_contract.first_rewardable_chunk().call().await {
+            let reward_contract = match &self.reward_contract {
+                Some(reward_contract) => reward_contract,
+                None => {
+                    tokio::time::sleep(self.config.check_time).await;
+                    continue;
+                }
+            };
+            match reward_contract.first_rewardable_chunk().call().await {
                 Ok((new_first_rewardable, chain_timestamp)) => {
                     if chain_timestamp > U256::from(i64::MAX as u64) {
                         error!(
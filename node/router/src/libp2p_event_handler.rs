@@ -9,11 +9,11 @@ use network::types::TimedMessage;
 use network::{
     rpc::StatusMessage,
     types::{
-        AnnounceChunks, AnnounceFile, FindChunks, FindFile, HasSignature, SignedAnnounceFile,
-        SignedMessage,
+        peer_id_to_public_key, AnnounceChunks, AnnounceFile, FindChunks, FindFile, HasSignature,
+        SignedAnnounceFile, SignedMessage,
     },
     Keypair, MessageAcceptance, MessageId, NetworkGlobals, NetworkMessage, PeerId, PeerRequestId,
-    PublicKey, PubsubMessage, Request, RequestId, Response,
+    PubsubMessage, Request, RequestId, Response,
 };
 use network::{Multiaddr, NetworkSender, PeerAction, ReportSource};
 use shared_types::{bytes_to_chunks, timestamp_now, NetworkIdentity, ShardedFile, TxID};
@@ -82,19 +82,6 @@ impl PubsubMsgHandleMetrics {
     }
 }
 
-fn peer_id_to_public_key(peer_id: &PeerId) -> Result<PublicKey, String> {
-    // A libp2p peer id byte representation should be 2 length bytes + 4 protobuf bytes + compressed pk bytes
-    // if generated from a PublicKey with Identity multihash.
-    let pk_bytes = &peer_id.to_bytes()[2..];
-
-    PublicKey::from_protobuf_encoding(pk_bytes).map_err(|e| {
-        format!(
-            " Cannot parse libp2p public key public key from peer id: {}",
-            e
-        )
-    })
-}
-
 fn verify_signature(msg: &dyn HasSignature, peer_id: &PeerId, propagation_source: PeerId) -> bool {
     match peer_id_to_public_key(peer_id) {
         Ok(pub_key) => msg.verify_signature(&pub_key),
@@ -198,6 +185,16 @@ impl Libp2pEventHandler {
         });
     }
 
+    /// Caches the shard config of a peer, and records it in the peer db to persist across restarts.
+    fn insert_peer_config(&self, peer_id: PeerId, shard_config: ShardConfig) {
+        self.file_location_cache
+            .insert_peer_config(peer_id, shard_config);
+        self.network_globals
+            .peers
+            .write()
+            .update_shard_config(&peer_id, shard_config.into());
+    }
+
     pub fn send_status(&self, peer_id: PeerId) {
         let shard_config = self.store.get_store().get_shard_config();
         let status_message = StatusMessage {
@@ -254,9 +251,17 @@ impl Libp2pEventHandler {
                 });
                 metrics::LIBP2P_HANDLE_GET_CHUNKS_REQUEST.mark(1);
             }
+            Request::GetShardParity(request) => {
+                self.send_to_sync(SyncMessage::RequestShardParity {
+                    peer_id,
+                    request_id,
+                    request,
+                });
+                metrics::LIBP2P_HANDLE_GET_SHARD_PARITY_REQUEST.mark(1);
+            }
             Request::AnswerFile(file) => match ShardConfig::try_from(file.shard_config) {
                 Ok(v) => {
-                    self.file_location_cache.insert_peer_config(peer_id, v);
+                    self.insert_peer_config(peer_id, v);
 
                     self.send_to_sync(SyncMessage::AnswerFile { peer_id, file });
                 }
@@ -340,6 +345,23 @@ impl Libp2pEventHandler {
                     response,
                 });
             }
+            Response::ShardParity(response) => {
+                let request_id = match request_id {
+                    RequestId::Sync(since, sync_id) => {
+                        metrics::LIBP2P_HANDLE_GET_SHARD_PARITY_RESPONSE.mark(1);
+                        metrics::LIBP2P_HANDLE_GET_SHARD_PARITY_RESPONSE_LATENCY
+                            .update_since(since);
+                        sync_id
+                    }
+                    _ => unreachable!("All ShardParity responses belong to sync"),
+                };
+
+                self.send_to_sync(SyncMessage::ShardParityResponse {
+                    peer_id,
+                    request_id,
+                    response,
+                });
+            }
             Response::DataByHash(_) => {
                 // ignore
             }
@@ -830,6 +852,10 @@ impl Libp2pEventHandler {
         }
 
         // insert message to cache
+        self.network_globals
+            .peers
+            .write()
+            .update_shard_config(&msg.peer_id.clone().into(), announced_shard_config.into());
         self.file_location_cache.insert(msg);
 
         MessageAcceptance::Accept
@@ -857,8 +883,7 @@ impl Libp2pEventHandler {
         };
 
         // insert message to cache
-        self.file_location_cache
-            .insert_peer_config(source, shard_config);
+        self.insert_peer_config(source, shard_config);
 
         // notify sync layer
         self.send_to_sync(SyncMessage::AnnounceShardConfig {
@@ -936,8 +961,7 @@ impl Libp2pEventHandler {
             }
         };
 
-        self.file_location_cache
-            .insert_peer_config(peer_id, peer_shard_config);
+        self.insert_peer_config(peer_id, peer_shard_config);
 
         if !peer_shard_config.intersect(shard_config) {
             info!(%peer_id, ?shard_config, ?status, "Report peer with mismatched shard config");
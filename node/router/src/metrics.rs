@@ -62,6 +62,11 @@ lazy_static::lazy_static! {
     pub static ref LIBP2P_HANDLE_GET_CHUNKS_RESPONSE: Arc<dyn Meter> = register_meter_with_group("router_libp2p_handle_get_chunks_response", "qps");
     pub static ref LIBP2P_HANDLE_GET_CHUNKS_RESPONSE_LATENCY: Arc<dyn Histogram> = Sample::ExpDecay(0.015).register_with_group("router_libp2p_handle_get_chunks_response", "latency", 1024);
 
+    // libp2p_event_handler: get shard parity
+    pub static ref LIBP2P_HANDLE_GET_SHARD_PARITY_REQUEST: Arc<dyn Meter> = register_meter("router_libp2p_handle_get_shard_parity_request");
+    pub static ref LIBP2P_HANDLE_GET_SHARD_PARITY_RESPONSE: Arc<dyn Meter> = register_meter_with_group("router_libp2p_handle_get_shard_parity_response", "qps");
+    pub static ref LIBP2P_HANDLE_GET_SHARD_PARITY_RESPONSE_LATENCY: Arc<dyn Histogram> = Sample::ExpDecay(0.015).register_with_group("router_libp2p_handle_get_shard_parity_response", "latency", 1024);
+
     // libp2p_event_handler: rpc errors
     pub static ref LIBP2P_HANDLE_RESPONSE_ERROR: Arc<dyn Meter> = register_meter_with_group("router_libp2p_handle_response_error", "qps");
     pub static ref LIBP2P_HANDLE_RESPONSE_ERROR_LATENCY: Arc<dyn Histogram> = Sample::ExpDecay(0.015).register_with_group("router_libp2p_handle_response_error", "latency", 1024);
@@ -0,0 +1,320 @@
+//! Authentication for `admin`, `miner` and `dev` namespace RPCs, and file upload of admin file
+//! server.
+//!
+//! Clients should provide a JWT signed with the HS256 shared secret (the same as Ethereum engine
+//! API) in the `Authorization: Bearer <token>` header, and the `role` claim is checked against
+//! the method permissions in config. Methods of other namespaces are not restricted.
+
+use crate::config::AuthConfig;
+use hyper::header::HeaderValue;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Namespaces that require authentication.
+const PROTECTED_NAMESPACES: [&str; 3] = ["admin", "miner", "dev"];
+
+/// JSON-RPC error code for missing or invalid token.
+pub const UNAUTHORIZED_CODE: i32 = -32040;
+/// JSON-RPC error code if the role is not allowed to call the method.
+pub const PERMISSION_DENIED_CODE: i32 = -32041;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AuthError {
+    MissingToken,
+    InvalidToken(String),
+    TokenExpired,
+    UnknownRole(String),
+    PermissionDenied(String),
+}
+
+impl AuthError {
+    /// JSON-RPC error code of the error.
+    pub fn code(&self) -> i32 {
+        match self {
+            AuthError::PermissionDenied(_) => PERMISSION_DENIED_CODE,
+            _ => UNAUTHORIZED_CODE,
+        }
+    }
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::MissingToken => write!(f, "missing bearer token"),
+            AuthError::InvalidToken(e) => write!(f, "invalid token: {}", e),
+            AuthError::TokenExpired => write!(f, "token expired"),
+            AuthError::UnknownRole(role) => write!(f, "unknown role: {}", role),
+            AuthError::PermissionDenied(method) => write!(f, "permission denied: {}", method),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    role: String,
+    iat: Option<u64>,
+    exp: Option<u64>,
+}
+
+pub struct Authenticator {
+    key: DecodingKey,
+    validation: Validation,
+    iat_leeway_secs: u64,
+    roles: HashMap<String, Vec<String>>,
+}
+
+impl Authenticator {
+    pub fn new(config: &AuthConfig) -> Result<Self, String> {
+        let path = config
+            .jwt_secret_path
+            .as_ref()
+            .ok_or_else(|| "jwt_secret_path is required for rpc auth".to_string())?;
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read jwt secret file {:?}: {:?}", path, e))?;
+        let secret = hex::decode(content.trim().trim_start_matches("0x"))
+            .map_err(|e| format!("Invalid hex jwt secret: {:?}", e))?;
+
+        Self::from_secret(&secret, config)
+    }
+
+    pub fn from_secret(secret: &[u8], config: &AuthConfig) -> Result<Self, String> {
+        if secret.len() < 32 {
+            return Err(format!(
+                "jwt secret too short, expected at least 32 bytes, actual = {}",
+                secret.len()
+            ));
+        }
+
+        let mut validation = Validation::new(Algorithm::HS256);
+        // `exp` is optional, in which case `iat` is validated instead
+        validation.required_spec_claims = HashSet::new();
+
+        Ok(Self {
+            key: DecodingKey::from_secret(secret),
+            validation,
+            iat_leeway_secs: config.jwt_iat_leeway_secs,
+            roles: config.roles.clone(),
+        })
+    }
+
+    /// Validates the `Authorization` header, and returns the role of the token.
+    pub fn authenticate(&self, header: Option<&HeaderValue>) -> Result<String, AuthError> {
+        let token = header
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .ok_or(AuthError::MissingToken)?;
+
+        let claims = jsonwebtoken::decode::<Claims>(token, &self.key, &self.validation)
+            .map_err(|e| match e.kind() {
+                jsonwebtoken::errors::ErrorKind::ExpiredSignature => AuthError::TokenExpired,
+                _ => AuthError::InvalidToken(e.to_string()),
+            })?
+            .claims;
+
+        if claims.exp.is_none() {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            match claims.iat {
+                Some(iat) if iat.abs_diff(now) <= self.iat_leeway_secs => {}
+                Some(_) => return Err(AuthError::TokenExpired),
+                None => return Err(AuthError::InvalidToken("exp or iat required".into())),
+            }
+        }
+
+        if !self.roles.contains_key(&claims.role) {
+            return Err(AuthError::UnknownRole(claims.role));
+        }
+
+        Ok(claims.role)
+    }
+
+    /// Checks whether the `Authorization` header permits to call the specified method.
+    pub fn authorize(&self, header: Option<&HeaderValue>, method: &str) -> Result<(), AuthError> {
+        let role = self.authenticate(header)?;
+
+        if self.is_permitted(&role, method) {
+            Ok(())
+        } else {
+            Err(AuthError::PermissionDenied(method.into()))
+        }
+    }
+
+    pub fn is_permitted(&self, role: &str, method: &str) -> bool {
+        match self.roles.get(role) {
+            Some(patterns) => patterns.iter().any(|p| method_matches(p, method)),
+            None => false,
+        }
+    }
+}
+
+/// Returns whether the method belongs to a protected namespace.
+pub fn requires_auth(method: &str) -> bool {
+    match method.split_once('_') {
+        Some((namespace, _)) => PROTECTED_NAMESPACES.contains(&namespace),
+        None => false,
+    }
+}
+
+/// Matches the method name against pattern, which supports a trailing `*` as wildcard.
+fn method_matches(pattern: &str, method: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => method.starts_with(prefix),
+        None => pattern == method,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde::Serialize;
+
+    const SECRET: [u8; 32] = [7u8; 32];
+
+    #[derive(Serialize)]
+    struct TestClaims {
+        role: &'static str,
+        iat: Option<u64>,
+        exp: Option<u64>,
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn authenticator() -> Authenticator {
+        let mut config = AuthConfig::default();
+        config
+            .roles
+            .insert("operator".into(), vec!["admin_*".into(), "miner_*".into()]);
+        config
+            .roles
+            .insert("viewer".into(), vec!["admin_getSyncStatus".into()]);
+        Authenticator::from_secret(&SECRET, &config).unwrap()
+    }
+
+    fn bearer(claims: TestClaims, secret: &[u8]) -> HeaderValue {
+        let token = jsonwebtoken::encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(secret),
+        )
+        .unwrap();
+        HeaderValue::from_str(&format!("Bearer {}", token)).unwrap()
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let auth = authenticator();
+
+        assert_eq!(auth.authenticate(None), Err(AuthError::MissingToken));
+
+        let header = bearer(
+            TestClaims {
+                role: "operator",
+                iat: Some(now()),
+                exp: None,
+            },
+            &SECRET,
+        );
+        assert_eq!(auth.authenticate(Some(&header)), Ok("operator".into()));
+
+        // stale iat without exp
+        let header = bearer(
+            TestClaims {
+                role: "operator",
+                iat: Some(now() - 3600),
+                exp: None,
+            },
+            &SECRET,
+        );
+        assert_eq!(
+            auth.authenticate(Some(&header)),
+            Err(AuthError::TokenExpired)
+        );
+
+        // long-lived token with exp
+        let header = bearer(
+            TestClaims {
+                role: "viewer",
+                iat: Some(now() - 3600),
+                exp: Some(now() + 3600),
+            },
+            &SECRET,
+        );
+        assert_eq!(auth.authenticate(Some(&header)), Ok("viewer".into()));
+
+        // wrong secret
+        let header = bearer(
+            TestClaims {
+                role: "operator",
+                iat: Some(now()),
+                exp: None,
+            },
+            &[8u8; 32],
+        );
+        assert!(matches!(
+            auth.authenticate(Some(&header)),
+            Err(AuthError::InvalidToken(_))
+        ));
+
+        // unknown role
+        let header = bearer(
+            TestClaims {
+                role: "root",
+                iat: Some(now()),
+                exp: None,
+            },
+            &SECRET,
+        );
+        assert_eq!(
+            auth.authenticate(Some(&header)),
+            Err(AuthError::UnknownRole("root".into()))
+        );
+    }
+
+    #[test]
+    fn test_permission() {
+        let auth = authenticator();
+
+        assert!(auth.is_permitted("operator", "admin_shutdown"));
+        assert!(auth.is_permitted("operator", "miner_stop"));
+        assert!(auth.is_permitted("viewer", "admin_getSyncStatus"));
+        assert!(!auth.is_permitted("viewer", "admin_shutdown"));
+        assert!(!auth.is_permitted("viewer", "miner_stop"));
+        assert!(!auth.is_permitted("root", "admin_shutdown"));
+
+        let header = bearer(
+            TestClaims {
+                role: "viewer",
+                iat: Some(now()),
+                exp: None,
+            },
+            &SECRET,
+        );
+        assert_eq!(auth.authorize(Some(&header), "admin_getSyncStatus"), Ok(()));
+        assert_eq!(
+            auth.authorize(Some(&header), "admin_uploadFile"),
+            Err(AuthError::PermissionDenied("admin_uploadFile".into()))
+        );
+        assert_eq!(
+            auth.authorize(None, "admin_getSyncStatus"),
+            Err(AuthError::MissingToken)
+        );
+
+        assert!(requires_auth("admin_shutdown"));
+        assert!(requires_auth("miner_setStartPosition"));
+        assert!(requires_auth("dev_submit"));
+        assert!(!requires_auth("zgs_getStatus"));
+        assert!(!requires_auth("rpc_modules"));
+    }
+}
@@ -1,16 +1,25 @@
-use std::{net::SocketAddr, str::FromStr};
+use std::{collections::HashMap, net::SocketAddr, path::PathBuf, str::FromStr};
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Config {
     pub enabled: bool,
     pub listen_address: SocketAddr,
     pub listen_address_admin: SocketAddr,
+    pub listen_address_file: Option<SocketAddr>,
+    pub listen_address_file_admin: Option<SocketAddr>,
     pub chunks_per_segment: usize,
     pub max_request_body_size: u32,
     pub max_cache_file_size: usize,
+    /// Maximum file size to upload via the admin file server.
+    pub max_upload_file_size: u64,
+    pub listen_address_ws: Option<SocketAddr>,
+    pub max_ws_connections: u32,
+    pub subscription_poll_interval_ms: u64,
+    pub auth: AuthConfig,
+    pub rate_limit: RateLimitConfig,
 }
 
 impl Default for Config {
@@ -19,9 +28,79 @@ impl Default for Config {
             enabled: true,
             listen_address: SocketAddr::from_str("0.0.0.0:5678").unwrap(),
             listen_address_admin: SocketAddr::from_str("127.0.0.1:5679").unwrap(),
+            listen_address_file: None,
+            listen_address_file_admin: None,
             chunks_per_segment: 1024,
             max_request_body_size: 100 * 1024 * 1024, // 100MB
             max_cache_file_size: 10 * 1024 * 1024,    // 10MB
+            max_upload_file_size: 10 * 1024 * 1024 * 1024, // 10GB
+            listen_address_ws: None,
+            max_ws_connections: 100,
+            subscription_poll_interval_ms: 500,
+            auth: AuthConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+        }
+    }
+}
+
+/// Authentication for `admin`, `miner` and `dev` namespace RPCs.
+///
+/// Clients should send a JWT signed with the shared HS256 secret in the `Authorization: Bearer`
+/// header, and the `role` claim determines the allowed methods.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    pub enabled: bool,
+    /// File that contains the hex encoded HS256 secret.
+    pub jwt_secret_path: Option<PathBuf>,
+    /// Maximum allowed difference between `iat` claim and local time, if `exp` claim not provided.
+    pub jwt_iat_leeway_secs: u64,
+    /// Method patterns allowed for each role, e.g. `admin_*`, `miner_stop` or `*`.
+    pub roles: HashMap<String, Vec<String>>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            jwt_secret_path: None,
+            jwt_iat_leeway_secs: 60,
+            roles: HashMap::new(),
+        }
+    }
+}
+
+/// Per client rate limiting for public RPCs, where `0` means no limit.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Period in seconds to fully replenish all quotas below.
+    pub period_secs: u64,
+    pub max_requests: u64,
+    /// Maximum request body bytes of `zgs_uploadSegment*` calls.
+    pub max_upload_bytes: u64,
+    /// Maximum segment bytes of `zgs_downloadSegment*` calls and file server downloads, where
+    /// file downloads are slowed down once exceeded.
+    pub max_download_bytes: u64,
+    /// HTTP header to identify clients by API key instead of IP address.
+    pub api_key_header: String,
+    pub api_keys: Vec<String>,
+    /// Whether to identify clients by the `X-Forwarded-For` header, e.g. behind a load balancer.
+    pub use_forwarded_for: bool,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            period_secs: 60,
+            max_requests: 600,
+            max_upload_bytes: 1024 * 1024 * 1024,   // 1GB
+            max_download_bytes: 1024 * 1024 * 1024, // 1GB
+            api_key_header: "x-api-key".into(),
+            api_keys: vec![],
+            use_forwarded_for: false,
         }
     }
 }
@@ -0,0 +1,12 @@
+use crate::types::Submission;
+use jsonrpsee::core::RpcResult;
+use jsonrpsee::proc_macros::rpc;
+use shared_types::Transaction;
+
+#[rpc(server, client, namespace = "dev")]
+pub trait Rpc {
+    /// Submits a file to the mock Flow contract in dev mode, and returns the transaction which
+    /// will be synced to store soon.
+    #[method(name = "submit")]
+    async fn submit(&self, submission: Submission) -> RpcResult<Transaction>;
+}
@@ -0,0 +1,35 @@
+use super::api::RpcServer;
+use crate::types::Submission;
+use crate::{error, Context};
+use jsonrpsee::core::async_trait;
+use jsonrpsee::core::RpcResult;
+use log_entry_sync::MockFlow;
+use shared_types::Transaction;
+
+pub struct RpcServerImpl {
+    pub ctx: Context,
+}
+
+impl RpcServerImpl {
+    /// Returns the mock Flow contract, which is only available in dev mode.
+    fn mock_flow(&self) -> RpcResult<&MockFlow> {
+        self.ctx.mock_flow.as_deref().ok_or_else(error::not_supported)
+    }
+}
+
+#[async_trait]
+impl RpcServer for RpcServerImpl {
+    async fn submit(&self, submission: Submission) -> RpcResult<Transaction> {
+        info!(length = %submission.length, "dev_submit()");
+
+        let nodes: Vec<_> = submission
+            .nodes
+            .iter()
+            .map(|node| (node.root, node.height))
+            .collect();
+
+        self.mock_flow()?
+            .submit(submission.length, &nodes)
+            .map_err(|e| error::invalid_params("submission", e.to_string()))
+    }
+}
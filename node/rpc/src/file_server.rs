@@ -0,0 +1,559 @@
+//! Plain HTTP server to download or upload file data without JSON-RPC encoding.
+//!
+//! Supported routes:
+//! - `GET /file?root=<data_root>`
+//! - `GET /file?txSeq=<tx_seq>`
+//! - `POST /file?txSeq=<tx_seq>`, only available on admin server, and requires permission of
+//!   `admin_uploadFile` if RPC auth enabled.
+//!
+//! The file is streamed segment by segment for download, and a single `Range` header is
+//! supported. For upload, the raw file data is split into segments along with the merkle
+//! proofs by server, so that clients do not need to compute them. Uploaded data is spooled to a
+//! temporary file segment by segment, and segments are uploaded one by one once the data root is
+//! verified, so the memory usage is bounded for large files.
+//!
+//! Downloads are rate limited along with the public RPCs if configured, where the downloaded
+//! bytes are charged segment by segment, so that the stream is slowed down once throttled.
+
+use crate::auth::AuthError;
+use crate::rate_limit::{ClientKey, RateLimiter};
+use crate::types::{FileSegmentTree, FileSegmentTreeBuilder, SegmentWithProof};
+use crate::zgs::{RpcServer, RpcServerImpl};
+use crate::Context;
+use futures::{Future, Stream};
+use hyper::body::HttpBody;
+use hyper::header::{
+    HeaderValue, ACCEPT_RANGES, AUTHORIZATION, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, RANGE,
+    RETRY_AFTER,
+};
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use shared_types::{bytes_to_chunks, DataRoot, Transaction, CHUNK_SIZE};
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt::Debug;
+use std::net::SocketAddr;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, SeekFrom};
+
+const FILE_PATH: &str = "/file";
+
+/// Method name to check permission for file upload, like the `admin` namespace RPCs.
+const UPLOAD_FILE_METHOD: &str = "admin_uploadFile";
+
+/// Starts the file server on the specified address, and returns the future to drive it.
+///
+/// Note, file upload is only enabled for `admin` server.
+pub fn run_file_server(
+    ctx: Context,
+    addr: SocketAddr,
+    admin: bool,
+) -> Result<impl Future<Output = ()>, Box<dyn Error>> {
+    let make_service = make_service_fn(move |conn: &AddrStream| {
+        let ctx = ctx.clone();
+        let remote_addr = conn.remote_addr();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let ctx = ctx.clone();
+                async move { Ok::<_, Infallible>(handle_request(ctx, admin, remote_addr, req).await) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&addr)?.serve(make_service);
+
+    info!(%addr, %admin, "File server started");
+
+    Ok(async move {
+        if let Err(e) = server.await {
+            error!(reason = %e, "File server terminated");
+        }
+    })
+}
+
+async fn handle_request(
+    ctx: Context,
+    admin: bool,
+    remote_addr: SocketAddr,
+    req: Request<Body>,
+) -> Response<Body> {
+    if req.uri().path() != FILE_PATH {
+        return text_response(StatusCode::NOT_FOUND, "not found");
+    }
+
+    match *req.method() {
+        Method::GET | Method::HEAD => download_file(ctx, remote_addr, req).await,
+        Method::POST if admin => {
+            if let Some(auth) = &ctx.auth {
+                if let Err(e) = auth.authorize(req.headers().get(AUTHORIZATION), UPLOAD_FILE_METHOD)
+                {
+                    debug!(reason = %e, "File upload unauthorized");
+                    let status = match e {
+                        AuthError::PermissionDenied(_) => StatusCode::FORBIDDEN,
+                        _ => StatusCode::UNAUTHORIZED,
+                    };
+                    return text_response(status, e.to_string());
+                }
+            }
+
+            upload_file(ctx, req).await
+        }
+        _ => text_response(StatusCode::METHOD_NOT_ALLOWED, "method not allowed"),
+    }
+}
+
+async fn download_file(
+    ctx: Context,
+    remote_addr: SocketAddr,
+    req: Request<Body>,
+) -> Response<Body> {
+    let tx = match get_finalized_tx(&ctx, req.uri().query().unwrap_or_default()).await {
+        Ok(tx) => tx,
+        Err(resp) => return resp,
+    };
+
+    let range = match req.headers().get(RANGE).map(|v| v.to_str()) {
+        None => None,
+        Some(Ok(value)) => match parse_range(value, tx.size) {
+            Ok(range) => range,
+            Err(()) => {
+                let mut resp =
+                    text_response(StatusCode::RANGE_NOT_SATISFIABLE, "range not satisfiable");
+                resp.headers_mut()
+                    .insert(CONTENT_RANGE, header_value(format!("bytes */{}", tx.size)));
+                return resp;
+            }
+        },
+        // ignore the range header if not a valid string
+        Some(Err(_)) => None,
+    };
+
+    let limiter = ctx
+        .rate_limiter
+        .clone()
+        .map(|rl| (rl.client_key(remote_addr, req.headers()), rl));
+    if let Some((client, rl)) = &limiter {
+        if let Err(e) = rl.check_file_download(client) {
+            debug!(?client, reason = ?e, "File download throttled");
+            let mut resp = text_response(StatusCode::TOO_MANY_REQUESTS, e.message());
+            if let Some(retry_after) = e.retry_after() {
+                let secs = retry_after.as_millis().div_ceil(1000);
+                resp.headers_mut()
+                    .insert(RETRY_AFTER, header_value(secs.to_string()));
+            }
+            return resp;
+        }
+    }
+
+    debug!(tx_seq = %tx.seq, ?range, "Download file via HTTP");
+
+    let mut builder = Response::builder()
+        .header(CONTENT_TYPE, "application/octet-stream")
+        .header(ACCEPT_RANGES, "bytes");
+
+    let range = match range {
+        Some(range) => {
+            builder = builder.status(StatusCode::PARTIAL_CONTENT).header(
+                CONTENT_RANGE,
+                format!("bytes {}-{}/{}", range.start, range.end - 1, tx.size),
+            );
+            range
+        }
+        None => {
+            builder = builder.status(StatusCode::OK);
+            0..tx.size
+        }
+    };
+
+    builder = builder.header(CONTENT_LENGTH, range.end - range.start);
+
+    let body = if req.method() == Method::HEAD {
+        Body::empty()
+    } else {
+        Body::wrap_stream(stream_file(ctx, tx.seq, range, limiter))
+    };
+
+    builder.body(body).unwrap_or_else(internal_error)
+}
+
+async fn get_finalized_tx(ctx: &Context, query: &str) -> Result<Transaction, Response<Body>> {
+    let result = if let Some(value) = query_param(query, "root") {
+        let root = DataRoot::from_str(&value)
+            .map_err(|_| text_response(StatusCode::BAD_REQUEST, "invalid root"))?;
+        ctx.log_store.get_tx_by_data_root(&root, true).await
+    } else if let Some(value) = query_param(query, "txSeq") {
+        let tx_seq = value
+            .parse::<u64>()
+            .map_err(|_| text_response(StatusCode::BAD_REQUEST, "invalid txSeq"))?;
+        ctx.log_store.get_tx_by_seq_number(tx_seq).await
+    } else {
+        return Err(text_response(
+            StatusCode::BAD_REQUEST,
+            "either root or txSeq is required",
+        ));
+    };
+
+    let tx = match result {
+        Ok(Some(tx)) => tx,
+        Ok(None) => return Err(text_response(StatusCode::NOT_FOUND, "file not found")),
+        Err(e) => return Err(internal_error(e)),
+    };
+
+    match ctx.log_store.check_tx_completed(tx.seq).await {
+        Ok(true) => Ok(tx),
+        Ok(false) => Err(text_response(StatusCode::NOT_FOUND, "file not finalized")),
+        Err(e) => Err(internal_error(e)),
+    }
+}
+
+/// Returns the URL-decoded value of the query parameter.
+fn query_param(query: &str, name: &str) -> Option<String> {
+    url::form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+/// Streams file data in the specified byte range. Data is loaded from store segment by segment,
+/// so that the memory usage is bounded for large files. If `limiter` is specified, each segment
+/// waits until the download quota of client allows.
+fn stream_file(
+    ctx: Context,
+    tx_seq: u64,
+    range: Range<u64>,
+    limiter: Option<(ClientKey, Arc<RateLimiter>)>,
+) -> impl Stream<Item = Result<Vec<u8>, String>> {
+    let segment_size = (ctx.config.chunks_per_segment * CHUNK_SIZE) as u64;
+    let end = range.end;
+
+    futures::stream::try_unfold(range.start, move |offset| {
+        let ctx = ctx.clone();
+        let limiter = limiter.clone();
+
+        async move {
+            if offset >= end {
+                return Ok(None);
+            }
+
+            // align with segment boundary for subsequent reads
+            let next_offset = std::cmp::min(end, (offset / segment_size + 1) * segment_size);
+
+            if let Some((client, rl)) = &limiter {
+                while let Err(wait) = rl.consume_file_download(client, next_offset - offset) {
+                    trace!(?client, ?wait, "File download slowed down");
+                    tokio::time::sleep(wait).await;
+                }
+            }
+            let start_index = offset as usize / CHUNK_SIZE;
+            let end_index = (next_offset as usize + CHUNK_SIZE - 1) / CHUNK_SIZE;
+
+            let chunks = ctx
+                .log_store
+                .get_chunks_by_tx_and_index_range(tx_seq, start_index, end_index)
+                .await
+                .map_err(|e| format!("Failed to load chunks: {:?}", e))?
+                .ok_or_else(|| {
+                    warn!(%tx_seq, %start_index, %end_index, "Chunks not available to download");
+                    format!(
+                        "chunks not available, start = {}, end = {}",
+                        start_index, end_index
+                    )
+                })?;
+
+            let skip = offset as usize - start_index * CHUNK_SIZE;
+            let len = (next_offset - offset) as usize;
+
+            Ok(Some((chunks.data[skip..skip + len].to_vec(), next_offset)))
+        }
+    })
+}
+
+async fn upload_file(ctx: Context, req: Request<Body>) -> Response<Body> {
+    let tx_seq = match query_param(req.uri().query().unwrap_or_default(), "txSeq")
+        .map(|v| v.parse::<u64>())
+    {
+        Some(Ok(tx_seq)) => tx_seq,
+        _ => return text_response(StatusCode::BAD_REQUEST, "valid txSeq is required"),
+    };
+
+    let tx = match ctx.log_store.get_tx_by_seq_number(tx_seq).await {
+        Ok(Some(tx)) => tx,
+        Ok(None) => return text_response(StatusCode::NOT_FOUND, "tx not found"),
+        Err(e) => return internal_error(e),
+    };
+
+    if tx.size > ctx.config.max_upload_file_size {
+        return text_response(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "file size exceeds the limit {}",
+                ctx.config.max_upload_file_size
+            ),
+        );
+    }
+
+    info!(%tx_seq, size = %tx.size, "Upload file via HTTP");
+
+    let chunks_per_segment = ctx.config.chunks_per_segment;
+    let mut spooled = match SpooledFile::new(tx.size as usize, chunks_per_segment).await {
+        Ok(spooled) => spooled,
+        Err(e) => return internal_error(e),
+    };
+    let tree = match spooled.read_body(req.into_body()).await {
+        Ok(tree) => tree,
+        Err(msg) => return text_response(StatusCode::BAD_REQUEST, msg),
+    };
+
+    if tree.root() != tx.data_merkle_root {
+        warn!(%tx_seq, expected = %tx.data_merkle_root, actual = %tree.root(), "Data root mismatch");
+        return text_response(StatusCode::BAD_REQUEST, "data root mismatch");
+    }
+
+    // only upload segments within the shard
+    let shard_config = ctx.log_store.get_store().get_shard_config();
+    let tx_start_segment = tx.start_entry_index as usize / chunks_per_segment;
+    let rpc = RpcServerImpl { ctx };
+    for index in 0..spooled.num_segments() {
+        if !shard_config.in_range((tx_start_segment + index) as u64) {
+            continue;
+        }
+
+        let segment = match spooled.read_segment(&tree, index).await {
+            Ok(segment) => segment,
+            Err(e) => return internal_error(e),
+        };
+        if let Err(e) = rpc.upload_segments_by_tx_seq(vec![segment], tx_seq).await {
+            return text_response(StatusCode::BAD_REQUEST, e.to_string());
+        }
+    }
+
+    text_response(StatusCode::OK, "ok")
+}
+
+/// Uploaded file data in a temporary file, where segments are padded to whole chunks.
+struct SpooledFile {
+    file: File,
+    file_size: usize,
+    chunks_per_segment: usize,
+}
+
+impl SpooledFile {
+    async fn new(file_size: usize, chunks_per_segment: usize) -> std::io::Result<Self> {
+        // removed by OS once closed
+        let file = tokio::task::spawn_blocking(tempfile::tempfile).await??;
+
+        Ok(Self {
+            file: File::from_std(file),
+            file_size,
+            chunks_per_segment,
+        })
+    }
+
+    fn num_segments(&self) -> usize {
+        bytes_to_chunks(self.file_size).div_ceil(self.chunks_per_segment)
+    }
+
+    /// Reads the request body, which should be exactly `file_size` bytes, and returns the file
+    /// merkle tree of segments.
+    async fn read_body(&mut self, mut body: Body) -> Result<FileSegmentTree, String> {
+        let mut builder = FileSegmentTreeBuilder::new(self.file_size, self.chunks_per_segment)
+            .map_err(|e| e.to_string())?;
+        let segment_size = self.chunks_per_segment * CHUNK_SIZE;
+        let mut segment = Vec::with_capacity(segment_size);
+        let mut size = 0;
+
+        while let Some(bytes) = body.data().await {
+            let mut bytes = &bytes.map_err(|e| format!("Failed to read data: {}", e))?[..];
+            size += bytes.len();
+            if size > self.file_size {
+                return Err(format!("data size exceeds file size {}", self.file_size));
+            }
+
+            while !bytes.is_empty() {
+                let n = bytes.len().min(segment_size - segment.len());
+                segment.extend_from_slice(&bytes[..n]);
+                bytes = &bytes[n..];
+                if segment.len() == segment_size {
+                    self.write_segment(&mut builder, &mut segment).await?;
+                }
+            }
+        }
+
+        if size != self.file_size {
+            return Err(format!(
+                "data size mismatch, expected = {}, actual = {}",
+                self.file_size, size
+            ));
+        }
+
+        if !segment.is_empty() {
+            // Padding the last chunk with zeros.
+            segment.resize(bytes_to_chunks(segment.len()) * CHUNK_SIZE, 0);
+            self.write_segment(&mut builder, &mut segment).await?;
+        }
+        self.file
+            .flush()
+            .await
+            .map_err(|e| format!("Failed to write temporary file: {}", e))?;
+
+        builder.build().map_err(|e| e.to_string())
+    }
+
+    async fn write_segment(
+        &mut self,
+        builder: &mut FileSegmentTreeBuilder,
+        segment: &mut Vec<u8>,
+    ) -> Result<(), String> {
+        builder.append(segment);
+        self.file
+            .write_all(segment)
+            .await
+            .map_err(|e| format!("Failed to write temporary file: {}", e))?;
+        segment.clear();
+        Ok(())
+    }
+
+    async fn read_segment(
+        &mut self,
+        tree: &FileSegmentTree,
+        index: usize,
+    ) -> std::io::Result<SegmentWithProof> {
+        let segment_size = self.chunks_per_segment * CHUNK_SIZE;
+        let padded_size = bytes_to_chunks(self.file_size) * CHUNK_SIZE;
+        let offset = index * segment_size;
+        let mut data = vec![0u8; segment_size.min(padded_size - offset)];
+        self.file.seek(SeekFrom::Start(offset as u64)).await?;
+        self.file.read_exact(&mut data).await?;
+        Ok(tree.segment(index, data))
+    }
+}
+
+/// Parses the `Range` header against the file size.
+///
+/// Returns `Ok(None)` if the header should be ignored, e.g. malformed or multiple ranges,
+/// in which case the whole file will be returned. Returns `Err` if the range is not
+/// satisfiable.
+fn parse_range(value: &str, size: u64) -> Result<Option<Range<u64>>, ()> {
+    let spec = match value.trim().strip_prefix("bytes=") {
+        Some(spec) if !spec.contains(',') => spec.trim(),
+        _ => return Ok(None),
+    };
+
+    let (start, end) = match spec.split_once('-') {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+        // bytes=start-end
+        (Ok(start), Ok(end)) if start <= end => start..end.saturating_add(1).min(size),
+        // bytes=start-
+        (Ok(start), Err(_)) if end.is_empty() => start..size,
+        // bytes=-suffix
+        (Err(_), Ok(suffix)) if start.is_empty() => {
+            if suffix == 0 {
+                return Err(());
+            }
+            size.saturating_sub(suffix)..size
+        }
+        _ => return Ok(None),
+    };
+
+    if range.start >= size {
+        return Err(());
+    }
+
+    Ok(Some(range))
+}
+
+fn header_value(value: String) -> HeaderValue {
+    HeaderValue::from_str(&value).expect("valid header value")
+}
+
+fn internal_error(e: impl Debug) -> Response<Body> {
+    text_response(StatusCode::INTERNAL_SERVER_ERROR, format!("{:?}", e))
+}
+
+fn text_response(status: StatusCode, msg: impl Into<String>) -> Response<Body> {
+    let mut resp = Response::new(Body::from(msg.into()));
+    *resp.status_mut() = status;
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_range, query_param, SpooledFile};
+    use crate::types::SegmentWithProof;
+    use hyper::Body;
+
+    #[test]
+    fn test_parse_range() {
+        // bounded range
+        assert_eq!(parse_range("bytes=0-99", 1000), Ok(Some(0..100)));
+        assert_eq!(parse_range("bytes=900-1999", 1000), Ok(Some(900..1000)));
+        assert_eq!(parse_range("bytes=5-5", 1000), Ok(Some(5..6)));
+
+        // open range
+        assert_eq!(parse_range("bytes=100-", 1000), Ok(Some(100..1000)));
+
+        // suffix range
+        assert_eq!(parse_range("bytes=-100", 1000), Ok(Some(900..1000)));
+        assert_eq!(parse_range("bytes=-2000", 1000), Ok(Some(0..1000)));
+
+        // ignored
+        assert_eq!(parse_range("items=0-99", 1000), Ok(None));
+        assert_eq!(parse_range("bytes=0-9,20-29", 1000), Ok(None));
+        assert_eq!(parse_range("bytes=9-0", 1000), Ok(None));
+        assert_eq!(parse_range("bytes=abc", 1000), Ok(None));
+
+        // not satisfiable
+        assert_eq!(parse_range("bytes=1000-", 1000), Err(()));
+        assert_eq!(parse_range("bytes=1000-2000", 1000), Err(()));
+        assert_eq!(parse_range("bytes=-0", 1000), Err(()));
+    }
+
+    #[test]
+    fn test_query_param() {
+        let query = "txSeq=%31%32&root=0x%41b&name=a+b";
+        assert_eq!(query_param(query, "txSeq").as_deref(), Some("12"));
+        assert_eq!(query_param(query, "root").as_deref(), Some("0xAb"));
+        assert_eq!(query_param(query, "name").as_deref(), Some("a b"));
+        assert_eq!(query_param(query, "size"), None);
+    }
+
+    #[tokio::test]
+    async fn test_spooled_file() {
+        let chunks_per_segment = 4;
+        let data: Vec<u8> = (0..5000).map(|i| (i % 251) as u8).collect();
+        let (root, segments) = SegmentWithProof::split_file(&data, chunks_per_segment).unwrap();
+
+        // body pieces are not aligned with segments
+        let pieces: Vec<Result<_, std::io::Error>> =
+            data.chunks(700).map(|piece| Ok(piece.to_vec())).collect();
+        let mut spooled = SpooledFile::new(data.len(), chunks_per_segment)
+            .await
+            .unwrap();
+        let tree = spooled
+            .read_body(Body::wrap_stream(futures::stream::iter(pieces)))
+            .await
+            .unwrap();
+        assert_eq!(tree.root(), root);
+        assert_eq!(spooled.num_segments(), segments.len());
+        for expected in segments {
+            let segment = spooled.read_segment(&tree, expected.index).await.unwrap();
+            assert_eq!(segment.data, expected.data);
+            assert_eq!(segment.proof, expected.proof);
+        }
+
+        // data size mismatch
+        for file_size in [data.len() - 1, data.len() + 1] {
+            let mut spooled = SpooledFile::new(file_size, chunks_per_segment)
+                .await
+                .unwrap();
+            assert!(spooled.read_body(Body::from(data.clone())).await.is_err());
+        }
+    }
+}
@@ -0,0 +1,531 @@
+//! HTTP and WebSocket server in front of JSON-RPC methods, to authenticate and rate limit calls.
+//!
+//! Middleware of jsonrpsee 0.14 could neither access HTTP headers and remote address nor reject
+//! calls, so requests are checked here and then dispatched to the RPC methods directly. Each
+//! call of a batch request is checked separately, and calls of a batch are executed concurrently.
+//! Notifications, i.e. calls without `id`, are executed without any output as JSON-RPC 2.0
+//! requires.
+//!
+//! For WebSocket, the concurrent requests and queued outputs of a connection are bounded, and the
+//! connection is closed if the client could not keep up with the outputs.
+
+use crate::auth::{requires_auth, Authenticator};
+use crate::middleware;
+use crate::rate_limit::{ClientKey, RateLimiter};
+use futures::channel::mpsc::{self, UnboundedReceiver};
+use futures::future::join_all;
+use futures::io::{BufReader, BufWriter};
+use futures::{Future, StreamExt};
+use hyper::body::HttpBody;
+use hyper::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use jsonrpsee::core::middleware::Middleware;
+use jsonrpsee::core::server::rpc_module::Methods;
+use jsonrpsee::types::error::ErrorCode;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use soketto::connection::Error as WsError;
+use soketto::handshake::http::{is_upgrade_request, Server as WsHandshake};
+use std::convert::Infallible;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::{oneshot, Semaphore};
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+/// Maximum number of responses and subscription notifications queued for a WebSocket connection.
+const MAX_WS_PENDING_OUTPUTS: usize = 1024;
+
+/// Maximum number of concurrent requests of a WebSocket connection. Further messages are not read
+/// until any request completed.
+const MAX_WS_CONCURRENT_REQUESTS: usize = 16;
+
+pub struct Gateway {
+    pub methods: Methods,
+    /// Authentication for `admin`, `miner` and `dev` namespace RPCs.
+    pub auth: Option<Arc<Authenticator>>,
+    /// Rate limiting for RPCs that do not require authentication.
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    pub max_request_body_size: u32,
+    pub metrics: middleware::Metrics,
+    /// Serves WebSocket connections instead of HTTP requests if specified, which limits the
+    /// number of concurrent connections.
+    pub ws_connections: Option<Arc<Semaphore>>,
+}
+
+/// Starts the gateway server on the specified address, and returns the future to drive it.
+pub fn run_gateway(
+    addr: SocketAddr,
+    gateway: Gateway,
+) -> Result<impl Future<Output = ()>, Box<dyn Error>> {
+    let gateway = Arc::new(gateway);
+
+    let make_service = make_service_fn(move |conn: &AddrStream| {
+        let gateway = gateway.clone();
+        let remote_addr = conn.remote_addr();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let gateway = gateway.clone();
+                async move { Ok::<_, Infallible>(gateway.handle_request(remote_addr, req).await) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&addr)?.serve(make_service);
+
+    info!(%addr, "RPC gateway server started");
+
+    Ok(async move {
+        if let Err(e) = server.await {
+            error!(reason = %e, "RPC gateway server terminated");
+        }
+    })
+}
+
+impl Gateway {
+    async fn handle_request(
+        self: Arc<Self>,
+        remote_addr: SocketAddr,
+        req: Request<Body>,
+    ) -> Response<Body> {
+        if let Some(connections) = self.ws_connections.clone() {
+            return self.upgrade(connections, remote_addr, req);
+        }
+
+        if req.method() != Method::POST {
+            return response(StatusCode::METHOD_NOT_ALLOWED, "method not allowed".into());
+        }
+
+        let header = req.headers().get(AUTHORIZATION).cloned();
+        let client = self
+            .rate_limiter
+            .as_ref()
+            .map(|rl| rl.client_key(remote_addr, req.headers()));
+
+        let body = match read_body(req.into_body(), self.max_request_body_size as usize).await {
+            Ok(body) => body,
+            Err(status) => return response(status, status.to_string()),
+        };
+
+        // subscriptions are closed once the receivers dropped, since HTTP could not notify
+        let (output, _) = self
+            .handle_calls(client.as_ref(), header.as_ref(), &body)
+            .await;
+
+        response(StatusCode::OK, output.unwrap_or_default())
+    }
+
+    /// Handles a single or batch JSON-RPC request, and returns the output, which is `None` if
+    /// all calls are notifications, along with the receivers of subscription notifications.
+    async fn handle_calls(
+        &self,
+        client: Option<&ClientKey>,
+        header: Option<&HeaderValue>,
+        body: &[u8],
+    ) -> (Option<String>, Vec<UnboundedReceiver<String>>) {
+        let (calls, batch) = match serde_json::from_slice::<Value>(body) {
+            Ok(Value::Array(calls)) if !calls.is_empty() => (calls, true),
+            Ok(call @ Value::Object(_)) => (vec![call], false),
+            _ => {
+                let err = error_response(Value::Null, ErrorCode::ParseError.code(), "Parse error");
+                return (Some(err.to_string()), vec![]);
+            }
+        };
+
+        let results = join_all(
+            calls
+                .iter()
+                .map(|call| self.handle_call(client, header, call)),
+        )
+        .await;
+
+        let mut outputs = Vec::with_capacity(results.len());
+        let mut notifications = vec![];
+        for (output, rx) in results {
+            outputs.extend(output);
+            notifications.extend(rx);
+        }
+
+        let output = match outputs.len() {
+            0 => None,
+            _ if batch => Some(format!("[{}]", outputs.join(","))),
+            _ => outputs.pop(),
+        };
+
+        (output, notifications)
+    }
+
+    /// Handles a single JSON-RPC call, and returns the output, which is `None` for notification,
+    /// along with the receiver of subscription notifications.
+    async fn handle_call(
+        &self,
+        client: Option<&ClientKey>,
+        header: Option<&HeaderValue>,
+        call: &Value,
+    ) -> (Option<String>, Option<UnboundedReceiver<String>>) {
+        if !call.is_object() {
+            let err = error_response(
+                Value::Null,
+                ErrorCode::InvalidRequest.code(),
+                "Invalid request",
+            );
+            return (Some(err.to_string()), None);
+        }
+
+        let method = call
+            .get("method")
+            .and_then(|m| m.as_str())
+            .unwrap_or_default();
+        let id = call.get("id").cloned();
+        let payload = call.to_string();
+
+        if requires_auth(method) {
+            if let Some(auth) = &self.auth {
+                if let Err(e) = auth.authorize(header, method) {
+                    debug!(%method, reason = %e, "RPC call unauthorized");
+                    let output = id.map(|id| error_response(id, e.code(), &e.to_string()));
+                    return (output.map(|err| err.to_string()), None);
+                }
+            }
+        } else if let (Some(rl), Some(client)) = (&self.rate_limiter, client) {
+            if let Err(e) = rl.check(client, method, call.get("params"), payload.len()) {
+                debug!(%method, ?client, reason = ?e, "RPC call throttled");
+                let output = id.map(|id| {
+                    let mut err = error_response(id, e.code(), e.message());
+                    if let Some(retry_after) = e.retry_after() {
+                        err["error"]["data"] =
+                            json!({ "retryAfterMs": retry_after.as_millis() as u64 });
+                    }
+                    err.to_string()
+                });
+                return (output, None);
+            }
+        }
+
+        match id {
+            Some(_) => {
+                let (output, rx) = self.execute(method, payload).await;
+                (Some(output), rx)
+            }
+            None => {
+                // jsonrpsee requires `id` to execute the call, and the output is dropped
+                let mut call = call.clone();
+                call["id"] = Value::Null;
+                self.execute(method, call.to_string()).await;
+                (None, None)
+            }
+        }
+    }
+
+    async fn execute(
+        &self,
+        method: &str,
+        call: String,
+    ) -> (String, Option<UnboundedReceiver<String>>) {
+        let started_at = self.metrics.on_request();
+        self.metrics.on_call(method);
+
+        let (output, rx, success) = match self.methods.raw_json_request(&call).await {
+            Ok((output, rx)) => {
+                let success = serde_json::from_str::<Output>(&output)
+                    .is_ok_and(|output| output.error.is_none());
+                (output, Some(rx), success)
+            }
+            Err(e) => {
+                let err =
+                    error_response(Value::Null, ErrorCode::InternalError.code(), &e.to_string());
+                (err.to_string(), None, false)
+            }
+        };
+
+        self.metrics.on_result(method, success, started_at);
+
+        (output, rx)
+    }
+
+    /// Accepts the WebSocket upgrade request, and serves the connection in background.
+    fn upgrade(
+        self: Arc<Self>,
+        connections: Arc<Semaphore>,
+        remote_addr: SocketAddr,
+        req: Request<Body>,
+    ) -> Response<Body> {
+        if !is_upgrade_request(&req) {
+            return response(StatusCode::BAD_REQUEST, "websocket required".into());
+        }
+
+        let permit = match connections.try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                return response(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "too many connections".into(),
+                )
+            }
+        };
+
+        let mut handshake = WsHandshake::new();
+        let resp = match handshake.receive_request(&req) {
+            Ok(resp) => resp,
+            Err(e) => return response(StatusCode::BAD_REQUEST, e.to_string()),
+        };
+
+        let header = req.headers().get(AUTHORIZATION).cloned();
+        let client = self
+            .rate_limiter
+            .as_ref()
+            .map(|rl| rl.client_key(remote_addr, req.headers()));
+
+        tokio::spawn(async move {
+            if let Err(e) = self.serve_ws(handshake, req, client, header).await {
+                debug!(%remote_addr, reason = %e, "WebSocket connection terminated");
+            }
+
+            drop(permit);
+        });
+
+        resp.map(|()| Body::empty())
+    }
+
+    async fn serve_ws(
+        self: Arc<Self>,
+        handshake: WsHandshake,
+        req: Request<Body>,
+        client: Option<ClientKey>,
+        header: Option<HeaderValue>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let stream = hyper::upgrade::on(req).await?;
+        let mut builder = handshake.into_builder(BufReader::new(BufWriter::new(stream.compat())));
+        builder.set_max_message_size(self.max_request_body_size as usize);
+        let (mut sender, mut receiver) = builder.finish();
+
+        // responses and subscription notifications to send
+        let (mut output_send, mut output_recv) = mpsc::channel::<String>(MAX_WS_PENDING_OUTPUTS);
+        let (sender_done_send, mut sender_done) = oneshot::channel::<()>();
+
+        tokio::spawn(async move {
+            while let Some(output) = output_recv.next().await {
+                if let Err(e) = send_ws_message(&mut sender, output).await {
+                    debug!(reason = %e, "Failed to send WebSocket message");
+                    break;
+                }
+            }
+
+            let _ = sender.close().await;
+            drop(sender_done_send);
+        });
+
+        let requests = Arc::new(Semaphore::new(MAX_WS_CONCURRENT_REQUESTS));
+        let mut data = Vec::new();
+
+        let result = loop {
+            data.clear();
+
+            let received = tokio::select! {
+                received = receiver.receive_data(&mut data) => received,
+                // e.g. the client could not keep up with the outputs
+                _ = &mut sender_done => break Err("WebSocket sender stopped".into()),
+            };
+
+            match received {
+                Ok(_) => {}
+                Err(WsError::Closed) => break Ok(()),
+                Err(WsError::MessageTooLarge { .. }) => {
+                    let err = error_response(
+                        Value::Null,
+                        ErrorCode::OversizedRequest.code(),
+                        "Request too large",
+                    );
+                    send_output(&mut output_send, err.to_string());
+                    continue;
+                }
+                Err(e) => break Err(e.into()),
+            }
+
+            let permit = requests
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("semaphore never closed");
+            let gateway = self.clone();
+            let (client, header, mut output_send) =
+                (client.clone(), header.clone(), output_send.clone());
+            let body = std::mem::take(&mut data);
+
+            tokio::spawn(async move {
+                let (output, notifications) = gateway
+                    .handle_calls(client.as_ref(), header.as_ref(), &body)
+                    .await;
+                drop(permit);
+
+                // notifications are sent after the subscription response
+                if let Some(output) = output {
+                    if !send_output(&mut output_send, output) {
+                        return;
+                    }
+                }
+
+                for mut rx in notifications {
+                    let mut output_send = output_send.clone();
+                    tokio::spawn(async move {
+                        while let Some(notification) = rx.next().await {
+                            if !send_output(&mut output_send, notification) {
+                                break;
+                            }
+                        }
+                    });
+                }
+            });
+        };
+
+        // stop sending, and subscriptions will be closed once the forwarding failed
+        output_send.close_channel();
+
+        result
+    }
+}
+
+/// Queues the output to send over WebSocket. If the queue is full, i.e. the client could not keep
+/// up with the outputs, the queue is closed so that the connection will be closed, and returns
+/// `false`.
+fn send_output(output_send: &mut mpsc::Sender<String>, output: String) -> bool {
+    match output_send.try_send(output) {
+        Ok(()) => true,
+        Err(e) => {
+            if e.is_full() {
+                debug!("WebSocket client could not keep up, closing the connection");
+            }
+            output_send.close_channel();
+            false
+        }
+    }
+}
+
+async fn send_ws_message<T>(sender: &mut soketto::Sender<T>, message: String) -> Result<(), WsError>
+where
+    T: futures::AsyncRead + futures::AsyncWrite + Unpin,
+{
+    sender.send_text_owned(message).await?;
+    sender.flush().await
+}
+
+/// Response of a JSON-RPC call, which is only decoded to check whether the call succeeded.
+#[derive(Deserialize)]
+struct Output {
+    error: Option<IgnoredAny>,
+}
+
+async fn read_body(mut body: Body, limit: usize) -> Result<Vec<u8>, StatusCode> {
+    let mut data = Vec::new();
+
+    while let Some(bytes) = body.data().await {
+        let bytes = bytes.map_err(|_| StatusCode::BAD_REQUEST)?;
+        if data.len() + bytes.len() > limit {
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        data.extend_from_slice(&bytes);
+    }
+
+    Ok(data)
+}
+
+fn error_response(id: Value, code: i32, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "error": { "code": code, "message": message },
+        "id": id,
+    })
+}
+
+fn response(status: StatusCode, body: String) -> Response<Body> {
+    let mut resp = Response::new(Body::from(body));
+    *resp.status_mut() = status;
+    resp.headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpsee::RpcModule;
+    use std::time::Duration;
+    use tokio::sync::Barrier;
+
+    fn new_gateway() -> Gateway {
+        let mut module = RpcModule::new(Arc::new(Barrier::new(2)));
+        module
+            .register_method("echo", |params, _| Ok(params.one::<u64>()?))
+            .unwrap();
+        module
+            .register_async_method("wait", |params, barrier| async move {
+                barrier.wait().await;
+                Ok(params.one::<u64>()?)
+            })
+            .unwrap();
+
+        Gateway {
+            methods: module.into(),
+            auth: None,
+            rate_limiter: None,
+            max_request_body_size: 1024,
+            metrics: Default::default(),
+            ws_connections: None,
+        }
+    }
+
+    async fn handle(gateway: &Gateway, body: Value) -> Option<Value> {
+        let (output, _) = gateway
+            .handle_calls(None, None, body.to_string().as_bytes())
+            .await;
+        output.map(|output| serde_json::from_str(&output).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_notification() {
+        let gateway = new_gateway();
+
+        let call = json!({"jsonrpc": "2.0", "method": "echo", "params": [1]});
+        assert_eq!(handle(&gateway, call.clone()).await, None);
+        assert_eq!(handle(&gateway, json!([call.clone(), call])).await, None);
+
+        let batch = json!([
+            {"jsonrpc": "2.0", "method": "echo", "params": [1], "id": 1},
+            {"jsonrpc": "2.0", "method": "echo", "params": [2]},
+            {"jsonrpc": "2.0", "method": "echo", "params": [3], "id": 3},
+        ]);
+        let output = handle(&gateway, batch).await.unwrap();
+        assert_eq!(
+            output,
+            json!([
+                {"jsonrpc": "2.0", "result": 1, "id": 1},
+                {"jsonrpc": "2.0", "result": 3, "id": 3},
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_concurrent() {
+        let gateway = new_gateway();
+
+        // calls wait for each other, so would never complete if executed sequentially
+        let batch = json!([
+            {"jsonrpc": "2.0", "method": "wait", "params": [1], "id": 1},
+            {"jsonrpc": "2.0", "method": "wait", "params": [2], "id": 2},
+        ]);
+        let output = tokio::time::timeout(Duration::from_secs(5), handle(&gateway, batch))
+            .await
+            .expect("batch calls executed sequentially")
+            .unwrap();
+        assert_eq!(
+            output,
+            json!([
+                {"jsonrpc": "2.0", "result": 1, "id": 1},
+                {"jsonrpc": "2.0", "result": 2, "id": 2},
+            ])
+        );
+    }
+}
@@ -4,10 +4,16 @@ extern crate tracing;
 extern crate miner as zgs_miner;
 
 mod admin;
+mod auth;
 mod config;
+mod dev;
 mod error;
+mod file_server;
+mod gateway;
 mod middleware;
 mod miner;
+mod rate_limit;
+mod subscription;
 pub mod types;
 mod zgs;
 
@@ -16,21 +22,33 @@ use admin::RpcServer as AdminRpcServer;
 use chunk_pool::MemoryChunkPool;
 use file_location_cache::FileLocationCache;
 use futures::channel::mpsc::Sender;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use gateway::Gateway;
+use jsonrpsee::core::server::rpc_module::Methods;
 use jsonrpsee::core::RpcResult;
-use jsonrpsee::http_server::{HttpServerBuilder, HttpServerHandle};
+use jsonrpsee::http_server::HttpServerBuilder;
+use log_entry_sync::MockFlow;
 use network::{NetworkGlobals, NetworkMessage, NetworkSender};
 use std::error::Error;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use storage_async::Store;
 use sync::{SyncRequest, SyncResponse, SyncSender};
 use task_executor::ShutdownReason;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, Semaphore};
+use types::SubscriptionEvent;
 use zgs::RpcServer as ZgsRpcServer;
-use zgs_miner::MinerMessage;
+use zgs_miner::{MinerMessage, MinerStatusHandle};
 
 pub use admin::RpcClient as ZgsAdminRpcClient;
+pub use auth::Authenticator;
 pub use config::Config as RPCConfig;
+pub use dev::RpcClient as ZgsDevRpcClient;
+pub use file_server::run_file_server;
 pub use miner::RpcClient as ZgsMinerRpcClient;
+pub use rate_limit::RateLimiter;
+pub use subscription::EventNotifier;
 pub use zgs::RpcClient as ZgsRPCClient;
 
 /// A wrapper around all the items required to spawn the HTTP server.
@@ -47,6 +65,16 @@ pub struct Context {
     pub log_store: Arc<Store>,
     pub shutdown_sender: Sender<ShutdownReason>,
     pub mine_service_sender: Option<broadcast::Sender<MinerMessage>>,
+    /// Live status of the miner, which is available along with `mine_service_sender`.
+    pub mine_status: Option<MinerStatusHandle>,
+    /// Events for `zgs_subscribe`, which is `None` if the WebSocket server is disabled.
+    pub event_send: Option<broadcast::Sender<SubscriptionEvent>>,
+    /// Mock Flow contract for `dev_submit`, which is only available in dev mode.
+    pub mock_flow: Option<Arc<MockFlow>>,
+    /// Authentication for admin RPCs and file upload, which is `None` if disabled.
+    pub auth: Option<Arc<Authenticator>>,
+    /// Rate limiting for public RPCs and file download, which is `None` if disabled.
+    pub rate_limiter: Option<Arc<RateLimiter>>,
 }
 
 impl Context {
@@ -64,9 +92,12 @@ impl Context {
     }
 }
 
+/// Future to drive an RPC server.
+pub type ServerHandle = BoxFuture<'static, ()>;
+
 pub async fn run_server(
     ctx: Context,
-) -> Result<(HttpServerHandle, Option<HttpServerHandle>), Box<dyn Error>> {
+) -> Result<(ServerHandle, Option<ServerHandle>), Box<dyn Error>> {
     let handles = if ctx.config.listen_address.port() != ctx.config.listen_address_admin.port() {
         run_server_public_private(ctx).await?
     } else {
@@ -84,31 +115,65 @@ fn server_builder(ctx: Context) -> HttpServerBuilder<middleware::Metrics> {
         .set_middleware(middleware::Metrics::default())
 }
 
+/// Starts the HTTP server for the specified RPCs. If `auth` or `rate_limiter` is configured,
+/// requests are checked by the gateway server before dispatched to RPCs.
+async fn start_server(
+    ctx: &Context,
+    addr: SocketAddr,
+    methods: impl Into<Methods>,
+    auth: Option<Arc<Authenticator>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+) -> Result<ServerHandle, Box<dyn Error>> {
+    if auth.is_none() && rate_limiter.is_none() {
+        return Ok(server_builder(ctx.clone())
+            .build(addr)
+            .await?
+            .start(methods)?
+            .boxed());
+    }
+
+    let gateway = Gateway {
+        methods: methods.into(),
+        auth,
+        rate_limiter,
+        max_request_body_size: ctx.config.max_request_body_size,
+        metrics: middleware::Metrics::default(),
+        ws_connections: None,
+    };
+
+    Ok(gateway::run_gateway(addr, gateway)?.boxed())
+}
+
 /// Run a single RPC server for all namespace RPCs.
-async fn run_server_all(ctx: Context) -> Result<HttpServerHandle, Box<dyn Error>> {
+async fn run_server_all(ctx: Context) -> Result<ServerHandle, Box<dyn Error>> {
     // public rpc
     let mut zgs = (zgs::RpcServerImpl { ctx: ctx.clone() }).into_rpc();
 
     // admin rpc
-    let admin = (admin::RpcServerImpl { ctx: ctx.clone() }).into_rpc();
-    zgs.merge(admin)?;
+    let mut admin = (admin::RpcServerImpl { ctx: ctx.clone() }).into_rpc();
 
     // mine rpc if configured
     if ctx.mine_service_sender.is_some() {
         let mine = (miner::RpcServerImpl { ctx: ctx.clone() }).into_rpc();
-        zgs.merge(mine)?;
+        admin.merge(mine)?;
+    }
+
+    // dev rpc in dev mode
+    if ctx.mock_flow.is_some() {
+        let dev = (dev::RpcServerImpl { ctx: ctx.clone() }).into_rpc();
+        admin.merge(dev)?;
     }
 
-    Ok(server_builder(ctx.clone())
-        .build(ctx.config.listen_address)
-        .await?
-        .start(zgs)?)
+    zgs.merge(admin)?;
+
+    let (auth, rate_limiter) = (ctx.auth.clone(), ctx.rate_limiter.clone());
+    start_server(&ctx, ctx.config.listen_address, zgs, auth, rate_limiter).await
 }
 
 /// Run 2 RPC servers (public & private) for different namespace RPCs.
 async fn run_server_public_private(
     ctx: Context,
-) -> Result<(HttpServerHandle, Option<HttpServerHandle>), Box<dyn Error>> {
+) -> Result<(ServerHandle, Option<ServerHandle>), Box<dyn Error>> {
     // public rpc
     let zgs = (zgs::RpcServerImpl { ctx: ctx.clone() }).into_rpc();
 
@@ -121,15 +186,44 @@ async fn run_server_public_private(
         admin.merge(mine)?;
     }
 
-    let handle_public = server_builder(ctx.clone())
-        .build(ctx.config.listen_address)
-        .await?
-        .start(zgs)?;
+    // dev rpc in dev mode
+    if ctx.mock_flow.is_some() {
+        let dev = (dev::RpcServerImpl { ctx: ctx.clone() }).into_rpc();
+        admin.merge(dev)?;
+    }
 
-    let handle_private = server_builder(ctx.clone())
-        .build(ctx.config.listen_address_admin)
-        .await?
-        .start(admin)?;
+    let rate_limiter = ctx.rate_limiter.clone();
+    let handle_public =
+        start_server(&ctx, ctx.config.listen_address, zgs, None, rate_limiter).await?;
+    let auth = ctx.auth.clone();
+    let handle_private =
+        start_server(&ctx, ctx.config.listen_address_admin, admin, auth, None).await?;
 
     Ok((handle_public, Some(handle_private)))
 }
+
+/// Run the WebSocket server for public RPCs and `zgs_subscribe`, which is served by the gateway
+/// server so that calls are rate limited the same as HTTP.
+pub async fn run_ws_server(
+    ctx: Context,
+    listen_address: SocketAddr,
+) -> Result<ServerHandle, Box<dyn Error>> {
+    let zgs = (zgs::RpcServerImpl { ctx: ctx.clone() }).into_rpc();
+
+    let gateway = Gateway {
+        methods: zgs.into(),
+        auth: None,
+        rate_limiter: ctx.rate_limiter.clone(),
+        max_request_body_size: ctx.config.max_request_body_size,
+        metrics: middleware::Metrics::default(),
+        ws_connections: Some(Arc::new(Semaphore::new(
+            ctx.config.max_ws_connections as usize,
+        ))),
+    };
+
+    let handle = gateway::run_gateway(listen_address, gateway)?.boxed();
+
+    info!("WebSocket server started");
+
+    Ok(handle)
+}
@@ -1,5 +1,6 @@
 use jsonrpsee::core::RpcResult;
 use jsonrpsee::proc_macros::rpc;
+use miner::{MinerStatus, SubmissionRecord};
 
 #[rpc(server, client, namespace = "miner")]
 pub trait Rpc {
@@ -11,4 +12,13 @@ pub trait Rpc {
 
     #[method(name = "setStartPosition")]
     async fn set_start_position(&self, index: u64) -> RpcResult<bool>;
+
+    /// Returns the current puzzle, recall range, hash rate, found answers, submission outcomes
+    /// and sealing progress, along with the reason if mining is stopped.
+    #[method(name = "getStatus")]
+    async fn get_status(&self) -> RpcResult<MinerStatus>;
+
+    /// Returns the latest submitted answers with receipts, ordered from the latest.
+    #[method(name = "getSubmissions")]
+    async fn get_submissions(&self, limit: Option<usize>) -> RpcResult<Vec<SubmissionRecord>>;
 }
@@ -1,9 +1,9 @@
 use super::api::RpcServer;
-use crate::Context;
+use crate::{error, Context};
 use futures::prelude::*;
 use jsonrpsee::core::async_trait;
 use jsonrpsee::core::{Error, RpcResult};
-use miner::MinerMessage;
+use miner::{load_submission_history, MinerMessage, MinerStatus, SubmissionRecord};
 use tokio::sync::broadcast;
 
 pub struct RpcServerImpl {
@@ -46,4 +46,22 @@ impl RpcServer for RpcServerImpl {
             .is_ok();
         Ok(success)
     }
+
+    async fn get_status(&self) -> RpcResult<MinerStatus> {
+        debug!("mine_getStatus()");
+
+        Ok(match &self.ctx.mine_status {
+            Some(status) => status.get(),
+            None => MinerStatus::stopped("miner is not started"),
+        })
+    }
+
+    async fn get_submissions(&self, limit: Option<usize>) -> RpcResult<Vec<SubmissionRecord>> {
+        debug!("mine_getSubmissions({:?})", limit);
+
+        let history = load_submission_history(&self.ctx.log_store, limit)
+            .await
+            .map_err(error::internal_error)?;
+        Ok(history.into_iter().rev().collect())
+    }
 }
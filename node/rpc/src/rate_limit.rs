@@ -0,0 +1,458 @@
+//! Per client rate limiting for public RPCs.
+//!
+//! Clients are identified by a configured API key header, or the remote IP address otherwise.
+//! Besides the number of calls, the bytes of uploaded and downloaded segments are limited
+//! separately, all with the GCRA limiter of p2p network RPCs. The limits are shared by the HTTP
+//! and WebSocket RPC servers, and file download of the file server, which is charged segment by
+//! segment while streaming.
+
+use crate::config::{Config, RateLimitConfig};
+use hyper::header::HeaderMap;
+use network::rpc::rate_limiter::{Limiter, Quota, RateLimitedErr};
+use parking_lot::Mutex;
+use serde_json::Value;
+use shared_types::CHUNK_SIZE;
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+/// JSON-RPC error code if too many calls are made.
+pub const RATE_LIMITED_CODE: i32 = -32005;
+/// JSON-RPC error code if too many bytes are uploaded or downloaded.
+pub const QUOTA_EXCEEDED_CODE: i32 = -32006;
+
+/// Byte quotas are counted in KiB, so that the replenish interval of a token is long enough.
+const BYTES_PER_TOKEN: u64 = 1024;
+
+const PRUNE_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum ClientKey {
+    Ip(IpAddr),
+    ApiKey(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Throttled {
+    /// Too many calls, and could retry after the specified duration.
+    Requests(Duration),
+    /// Too many bytes uploaded, and could retry after the specified duration.
+    Upload(Duration),
+    /// Too many bytes downloaded, and could retry after the specified duration.
+    Download(Duration),
+    /// The call exceeds the whole byte quota, and would never be allowed.
+    TooLarge,
+}
+
+impl Throttled {
+    pub fn code(&self) -> i32 {
+        match self {
+            Throttled::Requests(_) => RATE_LIMITED_CODE,
+            _ => QUOTA_EXCEEDED_CODE,
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            Throttled::Requests(_) => "Rate limit exceeded",
+            Throttled::Upload(_) => "Upload quota exceeded",
+            Throttled::Download(_) => "Download quota exceeded",
+            Throttled::TooLarge => "Request exceeds quota",
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Throttled::Requests(d) | Throttled::Upload(d) | Throttled::Download(d) => Some(*d),
+            Throttled::TooLarge => None,
+        }
+    }
+}
+
+enum Direction {
+    Upload,
+    Download,
+}
+
+struct Limiters {
+    requests: Option<Limiter<ClientKey>>,
+    upload: Option<Limiter<ClientKey>>,
+    download: Option<Limiter<ClientKey>>,
+    last_prune: Instant,
+}
+
+pub struct RateLimiter {
+    init_time: Instant,
+    limiters: Mutex<Limiters>,
+    api_key_header: String,
+    api_keys: HashSet<String>,
+    use_forwarded_for: bool,
+    chunks_per_segment: usize,
+    /// Burst of the download quota in tokens, which caps the charge of a streamed segment.
+    max_download_tokens: u64,
+}
+
+impl RateLimiter {
+    pub fn new(config: &Config) -> Result<Self, String> {
+        let rl: &RateLimitConfig = &config.rate_limit;
+        let period = Duration::from_secs(rl.period_secs);
+
+        let limiter = |max_tokens: u64, name: &str| {
+            if max_tokens == 0 {
+                return Ok(None);
+            }
+
+            Limiter::from_quota(Quota::n_every(max_tokens, period))
+                .map(Some)
+                .map_err(|e| format!("Invalid {} quota: {}", name, e))
+        };
+
+        Ok(Self {
+            init_time: Instant::now(),
+            limiters: Mutex::new(Limiters {
+                requests: limiter(rl.max_requests, "request")?,
+                upload: limiter(to_tokens(rl.max_upload_bytes), "upload")?,
+                download: limiter(to_tokens(rl.max_download_bytes), "download")?,
+                last_prune: Instant::now(),
+            }),
+            api_key_header: rl.api_key_header.to_lowercase(),
+            api_keys: rl.api_keys.iter().cloned().collect(),
+            use_forwarded_for: rl.use_forwarded_for,
+            chunks_per_segment: config.chunks_per_segment,
+            max_download_tokens: to_tokens(rl.max_download_bytes),
+        })
+    }
+
+    /// Identifies the client by API key if provided and configured, or by IP address.
+    pub fn client_key(&self, remote_addr: SocketAddr, headers: &HeaderMap) -> ClientKey {
+        if let Some(key) = headers
+            .get(self.api_key_header.as_str())
+            .and_then(|v| v.to_str().ok())
+        {
+            // unknown keys are ignored, otherwise clients could bypass the limit by random keys
+            if self.api_keys.contains(key) {
+                return ClientKey::ApiKey(key.to_string());
+            }
+        }
+
+        // only the last hop is appended by the trusted proxy, and others could be forged by client
+        if self.use_forwarded_for {
+            if let Some(ip) = headers
+                .get("x-forwarded-for")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.rsplit(',').next())
+                .and_then(|v| v.trim().parse::<IpAddr>().ok())
+            {
+                return ClientKey::Ip(ip);
+            }
+        }
+
+        ClientKey::Ip(remote_addr.ip())
+    }
+
+    /// Checks whether the call is allowed for the client, where `call_size` is the size of
+    /// serialized JSON-RPC call.
+    pub fn check(
+        &self,
+        key: &ClientKey,
+        method: &str,
+        params: Option<&Value>,
+        call_size: usize,
+    ) -> Result<(), Throttled> {
+        if method.starts_with("zgs_uploadSegment") {
+            self.consume(key, Some((Direction::Upload, call_size as u64)))
+        } else if method.starts_with("zgs_downloadSegment") {
+            let bytes = self.download_size(method, params);
+            self.consume(key, Some((Direction::Download, bytes)))
+        } else {
+            self.consume(key, None)
+        }
+    }
+
+    /// Checks whether a file download request is allowed for the client, which is counted as a
+    /// call. The downloaded bytes are charged by [`Self::consume_file_download`] while streaming.
+    pub fn check_file_download(&self, key: &ClientKey) -> Result<(), Throttled> {
+        self.consume(key, None)
+    }
+
+    /// Consumes the bytes of a streamed file segment for the client, or returns how long to wait
+    /// before retrying. The charge is capped by the burst of download quota, so that the stream
+    /// of a large file is slowed down instead of refused.
+    pub fn consume_file_download(&self, key: &ClientKey, bytes: u64) -> Result<(), Duration> {
+        let now = self.init_time.elapsed();
+        let tokens = to_tokens(bytes).min(self.max_download_tokens);
+        match self.limiters.lock().download.as_mut() {
+            Some(limiter) => match limiter.allows(now, key, tokens) {
+                Err(RateLimitedErr::TooSoon(wait)) => Err(wait),
+                // never too large since capped by the burst
+                Err(RateLimitedErr::TooLarge) | Ok(()) => Ok(()),
+            },
+            None => Ok(()),
+        }
+    }
+
+    /// Consumes a call and the optional bytes for the client. Tokens are consumed only if all
+    /// limits allow, so that a throttled call is not counted against any other limit.
+    fn consume(&self, key: &ClientKey, bytes: Option<(Direction, u64)>) -> Result<(), Throttled> {
+        let now = self.init_time.elapsed();
+        let mut guard = self.limiters.lock();
+        let limiters = &mut *guard;
+
+        if limiters.last_prune.elapsed() >= PRUNE_INTERVAL {
+            limiters.last_prune = Instant::now();
+            for limiter in [
+                &mut limiters.requests,
+                &mut limiters.upload,
+                &mut limiters.download,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                limiter.prune(now);
+            }
+        }
+
+        let bytes_limiter = match bytes {
+            Some((Direction::Upload, bytes)) => limiters.upload.as_mut().map(|limiter| {
+                (
+                    limiter,
+                    to_tokens(bytes),
+                    Throttled::Upload as fn(Duration) -> Throttled,
+                )
+            }),
+            Some((Direction::Download, bytes)) => limiters.download.as_mut().map(|limiter| {
+                (
+                    limiter,
+                    to_tokens(bytes),
+                    Throttled::Download as fn(Duration) -> Throttled,
+                )
+            }),
+            None => None,
+        };
+
+        if let Some(limiter) = &limiters.requests {
+            limiter
+                .check(now, key, 1)
+                .map_err(|e| throttled(e, Throttled::Requests))?;
+        }
+
+        if let Some((limiter, tokens, f)) = &bytes_limiter {
+            limiter
+                .check(now, key, *tokens)
+                .map_err(|e| throttled(e, f))?;
+        }
+
+        // both checked above, so never fails
+        if let Some(limiter) = limiters.requests.as_mut() {
+            limiter
+                .allows(now, key, 1)
+                .map_err(|e| throttled(e, Throttled::Requests))?;
+        }
+
+        if let Some((limiter, tokens, f)) = bytes_limiter {
+            limiter
+                .allows(now, key, tokens)
+                .map_err(|e| throttled(e, f))?;
+        }
+
+        Ok(())
+    }
+
+    /// Estimates the downloaded bytes before the call is executed, so that data is not loaded
+    /// from store for throttled clients.
+    fn download_size(&self, method: &str, params: Option<&Value>) -> u64 {
+        let segment_size = (self.chunks_per_segment * CHUNK_SIZE) as u64;
+
+        if method.starts_with("zgs_downloadSegmentWithProof") {
+            return segment_size;
+        }
+
+        // downloadSegment(root_or_seq, start_index, end_index)
+        let (start, end) = match params {
+            Some(Value::Array(values)) if values.len() == 3 => {
+                (values[1].as_u64(), values[2].as_u64())
+            }
+            Some(Value::Object(values)) => (
+                values.get("start_index").and_then(|v| v.as_u64()),
+                values.get("end_index").and_then(|v| v.as_u64()),
+            ),
+            _ => (None, None),
+        };
+
+        match (start, end) {
+            (Some(start), Some(end)) if start < end => {
+                // invalid range will be rejected by RPC without loading data
+                std::cmp::min(end - start, self.chunks_per_segment as u64) * CHUNK_SIZE as u64
+            }
+            _ => 0,
+        }
+    }
+}
+
+fn to_tokens(bytes: u64) -> u64 {
+    (bytes + BYTES_PER_TOKEN - 1) / BYTES_PER_TOKEN
+}
+
+fn throttled(e: RateLimitedErr, f: impl FnOnce(Duration) -> Throttled) -> Throttled {
+    match e {
+        RateLimitedErr::TooLarge => Throttled::TooLarge,
+        RateLimitedErr::TooSoon(d) => f(d),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rate_limiter() -> RateLimiter {
+        let mut config = Config::default();
+        config.rate_limit.enabled = true;
+        config.rate_limit.period_secs = 60;
+        config.rate_limit.max_requests = 2;
+        config.rate_limit.max_upload_bytes = 1024 * 1024;
+        config.rate_limit.max_download_bytes = 512 * 1024;
+        config.rate_limit.api_keys = vec!["secret".into()];
+        RateLimiter::new(&config).unwrap()
+    }
+
+    #[test]
+    fn test_client_key() {
+        let rl = rate_limiter();
+        let remote: SocketAddr = "10.0.0.1:1234".parse().unwrap();
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(rl.client_key(remote, &headers), ClientKey::Ip(remote.ip()));
+
+        headers.insert("x-api-key", "unknown".parse().unwrap());
+        assert_eq!(rl.client_key(remote, &headers), ClientKey::Ip(remote.ip()));
+
+        headers.insert("x-api-key", "secret".parse().unwrap());
+        assert_eq!(
+            rl.client_key(remote, &headers),
+            ClientKey::ApiKey("secret".into())
+        );
+
+        // forwarded header is not trusted by default
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "1.2.3.4, 10.0.0.2".parse().unwrap());
+        assert_eq!(rl.client_key(remote, &headers), ClientKey::Ip(remote.ip()));
+
+        // only the hop appended by proxy is trusted
+        let mut config = Config::default();
+        config.rate_limit.use_forwarded_for = true;
+        let rl = RateLimiter::new(&config).unwrap();
+        assert_eq!(
+            rl.client_key(remote, &headers),
+            ClientKey::Ip("10.0.0.2".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_request_limit() {
+        let rl = rate_limiter();
+        let a = ClientKey::Ip("10.0.0.1".parse().unwrap());
+        let b = ClientKey::Ip("10.0.0.2".parse().unwrap());
+
+        assert_eq!(rl.check(&a, "zgs_getStatus", None, 0), Ok(()));
+        assert_eq!(rl.check(&a, "zgs_getStatus", None, 0), Ok(()));
+        assert!(matches!(
+            rl.check(&a, "zgs_getStatus", None, 0),
+            Err(Throttled::Requests(_))
+        ));
+        assert_eq!(rl.check(&b, "zgs_getStatus", None, 0), Ok(()));
+    }
+
+    #[test]
+    fn test_byte_quota() {
+        let mut config = Config::default();
+        config.rate_limit.enabled = true;
+        config.rate_limit.period_secs = 60;
+        config.rate_limit.max_requests = 0;
+        config.rate_limit.max_upload_bytes = 1024 * 1024;
+        config.rate_limit.max_download_bytes = 512 * 1024;
+        let rl = RateLimiter::new(&config).unwrap();
+        let key = ClientKey::Ip("10.0.0.1".parse().unwrap());
+
+        // 2 segments of 256 KiB exhaust the download quota
+        let params = json!([1, 0, 1024]);
+        assert_eq!(
+            rl.check(&key, "zgs_downloadSegmentByTxSeq", Some(&params), 0),
+            Ok(())
+        );
+        let params = json!({"tx_seq": 1, "index": 1});
+        assert_eq!(
+            rl.check(
+                &key,
+                "zgs_downloadSegmentWithProofByTxSeq",
+                Some(&params),
+                0
+            ),
+            Ok(())
+        );
+        assert!(matches!(
+            rl.check(
+                &key,
+                "zgs_downloadSegmentWithProofByTxSeq",
+                Some(&params),
+                0
+            ),
+            Err(Throttled::Download(_))
+        ));
+
+        // other methods are not limited
+        assert_eq!(rl.check(&key, "zgs_getStatus", None, 0), Ok(()));
+
+        // upload quota is independent of download quota
+        assert_eq!(
+            rl.check(&key, "zgs_uploadSegments", None, 1024 * 1024),
+            Ok(())
+        );
+        assert!(matches!(
+            rl.check(&key, "zgs_uploadSegments", None, 1024),
+            Err(Throttled::Upload(_))
+        ));
+        assert_eq!(
+            rl.check(&key, "zgs_uploadSegments", None, 2 * 1024 * 1024),
+            Err(Throttled::TooLarge)
+        );
+    }
+
+    #[test]
+    fn test_throttled_not_counted() {
+        let rl = rate_limiter();
+        let key = ClientKey::Ip("10.0.0.1".parse().unwrap());
+
+        // upload exceeding quota does not consume a call
+        assert_eq!(
+            rl.check(&key, "zgs_uploadSegments", None, 2 * 1024 * 1024),
+            Err(Throttled::TooLarge)
+        );
+        assert_eq!(rl.check_file_download(&key), Ok(()));
+        assert_eq!(rl.check(&key, "zgs_getStatus", None, 0), Ok(()));
+        assert!(matches!(
+            rl.check(&key, "zgs_uploadSegments", None, 1024),
+            Err(Throttled::Requests(_))
+        ));
+    }
+
+    #[test]
+    fn test_file_download_larger_than_burst() {
+        let rl = rate_limiter();
+        let key = ClientKey::Ip("10.0.0.1".parse().unwrap());
+
+        // file of 2 MiB is larger than the 512 KiB burst, but the request is allowed
+        assert_eq!(rl.check_file_download(&key), Ok(()));
+
+        // segments of 256 KiB are streamed until the burst is exhausted, then slowed down
+        assert_eq!(rl.consume_file_download(&key, 256 * 1024), Ok(()));
+        assert_eq!(rl.consume_file_download(&key, 256 * 1024), Ok(()));
+        let wait = rl.consume_file_download(&key, 256 * 1024).unwrap_err();
+        assert!(wait > Duration::ZERO && wait <= Duration::from_secs(60));
+
+        // a single segment larger than the burst is capped instead of refused
+        let other = ClientKey::Ip("10.0.0.2".parse().unwrap());
+        assert_eq!(rl.consume_file_download(&other, 1024 * 1024), Ok(()));
+        assert!(rl.consume_file_download(&other, 1024).is_err());
+    }
+}
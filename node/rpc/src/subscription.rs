@@ -0,0 +1,301 @@
+//! Events for `zgs_subscribe` subscribers.
+//!
+//! New and reverted transactions are forwarded from the log sync events, and finalized files
+//! from the store notifications. The store does not emit events for pruned files and the log
+//! sync progress, so they are detected by a single task polling the store periodically,
+//! instead of by every client.
+//!
+//! Events dropped due to lagging are reported to subscribers as a `Lagged` event.
+
+use crate::types::SubscriptionEvent;
+use log_entry_sync::LogSyncEvent;
+use std::sync::Arc;
+use std::time::Duration;
+use storage::H256;
+use storage_async::Store;
+use task_executor::TaskExecutor;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+const EVENT_CHANNEL_CAPACITY: usize = 4096;
+
+pub struct EventNotifier {
+    store: Arc<Store>,
+    event_send: broadcast::Sender<SubscriptionEvent>,
+    finalized_recv: broadcast::Receiver<u64>,
+    /// The first transaction not pruned yet.
+    next_unpruned_tx_seq: u64,
+    sync_progress: Option<(u64, H256)>,
+}
+
+impl EventNotifier {
+    /// Spawns a task to notify events, and returns the sender to subscribe events from.
+    pub async fn spawn(
+        executor: &TaskExecutor,
+        store: Arc<Store>,
+        log_sync_recv: broadcast::Receiver<LogSyncEvent>,
+        poll_interval: Duration,
+    ) -> Result<broadcast::Sender<SubscriptionEvent>, String> {
+        let (event_send, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        let notifier = EventNotifier::new(store, event_send.clone()).await?;
+
+        executor.spawn(
+            notifier.run(log_sync_recv, poll_interval),
+            "rpc_event_notifier",
+        );
+
+        Ok(event_send)
+    }
+
+    async fn new(
+        store: Arc<Store>,
+        event_send: broadcast::Sender<SubscriptionEvent>,
+    ) -> Result<Self, String> {
+        let finalized_recv = store.get_store().subscribe_finalized();
+        let next_unpruned_tx_seq = first_unpruned_tx_seq(&store)
+            .await
+            .map_err(|e| format!("Failed to find the first unpruned tx: {:?}", e))?;
+
+        Ok(EventNotifier {
+            store,
+            event_send,
+            finalized_recv,
+            next_unpruned_tx_seq,
+            sync_progress: None,
+        })
+    }
+
+    async fn run(
+        mut self,
+        mut log_sync_recv: broadcast::Receiver<LogSyncEvent>,
+        poll_interval: Duration,
+    ) {
+        info!("Start to notify subscription events");
+
+        let mut interval = tokio::time::interval(poll_interval);
+
+        loop {
+            tokio::select! {
+                event = log_sync_recv.recv() => match event {
+                    Ok(event) => self.on_log_sync_event(event),
+                    Err(RecvError::Closed) => {
+                        // program terminated
+                        info!("Completed to notify subscription events");
+                        return;
+                    }
+                    Err(RecvError::Lagged(lagged)) => {
+                        error!(%lagged, "Lagged messages: (Lagged)");
+                        self.notify(SubscriptionEvent::Lagged { skipped: lagged });
+                    }
+                },
+
+                tx_seq = self.finalized_recv.recv() => match tx_seq {
+                    Ok(tx_seq) => self.notify(SubscriptionEvent::Finalized { tx_seq }),
+                    Err(RecvError::Closed) => {
+                        // program terminated
+                        info!("Completed to notify subscription events");
+                        return;
+                    }
+                    Err(RecvError::Lagged(lagged)) => {
+                        error!(%lagged, "Lagged finalized txs: (Lagged)");
+                        self.notify(SubscriptionEvent::Lagged { skipped: lagged });
+                    }
+                },
+
+                _ = interval.tick() => {
+                    if let Err(e) = self.poll().await {
+                        warn!(?e, "Failed to poll subscription events");
+                    }
+                }
+            }
+        }
+    }
+
+    fn notify(&self, event: SubscriptionEvent) {
+        // fails only if there are no subscribers
+        let _ = self.event_send.send(event);
+    }
+
+    fn on_log_sync_event(&mut self, event: LogSyncEvent) {
+        match event {
+            LogSyncEvent::ReorgDetected { .. } => {}
+            LogSyncEvent::Reverted { tx_seq } => {
+                self.next_unpruned_tx_seq = self.next_unpruned_tx_seq.min(tx_seq);
+                self.notify(SubscriptionEvent::Reverted { tx_seq });
+            }
+            LogSyncEvent::TxSynced { tx } => {
+                self.notify(SubscriptionEvent::from(&tx));
+            }
+        }
+    }
+
+    async fn poll(&mut self) -> storage::error::Result<()> {
+        // pruned files, which are always pruned in sequence
+        let next_tx_seq = self.store.get_store().next_tx_seq();
+        while self.next_unpruned_tx_seq < next_tx_seq
+            && self
+                .store
+                .check_tx_pruned(self.next_unpruned_tx_seq)
+                .await?
+        {
+            self.notify(SubscriptionEvent::Pruned {
+                tx_seq: self.next_unpruned_tx_seq,
+            });
+            self.next_unpruned_tx_seq += 1;
+        }
+
+        // log sync progress
+        let sync_progress = self.store.get_store().get_sync_progress()?;
+        if sync_progress.is_some() && sync_progress != self.sync_progress {
+            let (log_sync_height, log_sync_block) = sync_progress.unwrap_or_default();
+            self.notify(SubscriptionEvent::SyncProgress {
+                log_sync_height,
+                log_sync_block,
+                next_tx_seq,
+            });
+            self.sync_progress = sync_progress;
+        }
+
+        Ok(())
+    }
+}
+
+/// Binary searches the first transaction not pruned, since transactions are pruned in sequence.
+async fn first_unpruned_tx_seq(store: &Store) -> storage::error::Result<u64> {
+    let (mut low, mut high) = (0, store.get_store().next_tx_seq());
+
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if store.check_tx_pruned(mid).await? {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    Ok(low)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared_types::{ChunkArray, Transaction, CHUNK_SIZE};
+    use storage::log_store::log_manager::{
+        sub_merkle_tree, tx_subtree_root_list_padded, LogConfig,
+    };
+    use storage::log_store::{LogStoreChunkWrite, LogStoreRead, LogStoreWrite};
+    use storage::LogManager;
+    use task_executor::test_utils::TestRuntime;
+
+    /// Puts a tx of a single chunk, and returns the tx along with its data.
+    fn put_tx(store: &LogManager, seq: u64) -> (Transaction, Vec<u8>) {
+        let data = vec![seq as u8 + 1; CHUNK_SIZE];
+        let tx = Transaction {
+            stream_ids: vec![],
+            size: data.len() as u64,
+            data_merkle_root: sub_merkle_tree(&data).unwrap().root().into(),
+            seq,
+            data: vec![],
+            start_entry_index: store.get_context().unwrap().1,
+            merkle_nodes: tx_subtree_root_list_padded(&data),
+        };
+        store.put_tx(tx.clone()).unwrap();
+        (tx, data)
+    }
+
+    /// Spawns a notifier on an in-memory store, and returns the store along with the receiver
+    /// of subscription events.
+    async fn spawn_notifier(
+        runtime: &TestRuntime,
+        log_sync_recv: broadcast::Receiver<LogSyncEvent>,
+    ) -> (Arc<LogManager>, broadcast::Receiver<SubscriptionEvent>) {
+        let store = Arc::new(LogManager::memorydb(LogConfig::default()).unwrap());
+        let async_store = Arc::new(Store::new(store.clone(), runtime.task_executor.clone()));
+        let (event_send, event_recv) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        let notifier = EventNotifier::new(async_store, event_send).await.unwrap();
+        tokio::spawn(notifier.run(log_sync_recv, Duration::from_millis(10)));
+        (store, event_recv)
+    }
+
+    async fn next_event(
+        event_recv: &mut broadcast::Receiver<SubscriptionEvent>,
+    ) -> SubscriptionEvent {
+        tokio::time::timeout(Duration::from_secs(5), event_recv.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_events() {
+        let runtime = TestRuntime::default();
+        let (log_sync_send, log_sync_recv) = broadcast::channel(16);
+        let (store, mut event_recv) = spawn_notifier(&runtime, log_sync_recv).await;
+
+        let (tx, data) = put_tx(&store, 0);
+        log_sync_send
+            .send(LogSyncEvent::TxSynced { tx: tx.clone() })
+            .unwrap();
+        assert_eq!(
+            next_event(&mut event_recv).await,
+            SubscriptionEvent::from(&tx)
+        );
+
+        let chunks = ChunkArray {
+            data,
+            start_index: 0,
+        };
+        store.put_chunks(0, chunks).unwrap();
+        store.finalize_tx(0).unwrap();
+        assert_eq!(
+            next_event(&mut event_recv).await,
+            SubscriptionEvent::Finalized { tx_seq: 0 }
+        );
+
+        store.prune_tx(0).unwrap();
+        assert_eq!(
+            next_event(&mut event_recv).await,
+            SubscriptionEvent::Pruned { tx_seq: 0 }
+        );
+
+        store
+            .put_sync_progress((5, H256::repeat_byte(1), None))
+            .unwrap();
+        assert_eq!(
+            next_event(&mut event_recv).await,
+            SubscriptionEvent::SyncProgress {
+                log_sync_height: 5,
+                log_sync_block: H256::repeat_byte(1),
+                next_tx_seq: 1,
+            }
+        );
+
+        log_sync_send
+            .send(LogSyncEvent::Reverted { tx_seq: 0 })
+            .unwrap();
+        assert_eq!(
+            next_event(&mut event_recv).await,
+            SubscriptionEvent::Reverted { tx_seq: 0 }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lagged_log_sync_events() {
+        let runtime = TestRuntime::default();
+        let (log_sync_send, log_sync_recv) = broadcast::channel(1);
+        for tx_seq in 1..=3 {
+            log_sync_send
+                .send(LogSyncEvent::Reverted { tx_seq })
+                .unwrap();
+        }
+        let (_store, mut event_recv) = spawn_notifier(&runtime, log_sync_recv).await;
+
+        assert_eq!(
+            next_event(&mut event_recv).await,
+            SubscriptionEvent::Lagged { skipped: 2 }
+        );
+        assert_eq!(
+            next_event(&mut event_recv).await,
+            SubscriptionEvent::Reverted { tx_seq: 3 }
+        );
+    }
+}
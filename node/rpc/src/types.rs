@@ -7,8 +7,8 @@ use merkle_tree::RawLeafSha3Algorithm;
 use network::Multiaddr;
 use serde::{Deserialize, Serialize};
 use shared_types::{
-    compute_padded_chunk_size, compute_segment_size, DataRoot, FileProof, NetworkIdentity,
-    Transaction, CHUNK_SIZE,
+    bytes_to_chunks, compute_padded_chunk_size, compute_segment_merkle_root, compute_segment_size,
+    DataRoot, FileProof, NetworkIdentity, Transaction, CHUNK_SIZE,
 };
 use std::collections::HashSet;
 use std::hash::Hasher;
@@ -29,10 +29,84 @@ pub struct Status {
     pub connected_peers: usize,
     pub log_sync_height: u64,
     pub log_sync_block: H256,
+    /// Finalized block number of log sync, at or below which the synced txs will not be
+    /// reverted by chain reorg. `None` if `log_sync_finality_tag` is not configured.
+    pub log_sync_finality: Option<u64>,
     pub next_tx_seq: u64,
     pub network_identity: NetworkIdentity,
 }
 
+/// Topic to subscribe via `zgs_subscribe`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SubscriptionTopic {
+    /// New transactions synced from blockchain.
+    NewTxs,
+    /// Files finalized in storage.
+    Finalized,
+    /// Transactions reverted due to chain reorg.
+    Reverted,
+    /// Files pruned from storage.
+    Pruned,
+    /// Progress of log sync from blockchain.
+    SyncProgress,
+}
+
+/// Event pushed to subscribers of `zgs_subscribe`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum SubscriptionEvent {
+    #[serde(rename_all = "camelCase")]
+    NewTx {
+        tx_seq: u64,
+        data_root: DataRoot,
+        size: u64,
+        start_entry_index: u64,
+    },
+    #[serde(rename_all = "camelCase")]
+    Finalized { tx_seq: u64 },
+    /// All transactions since `tx_seq` are reverted.
+    #[serde(rename_all = "camelCase")]
+    Reverted { tx_seq: u64 },
+    #[serde(rename_all = "camelCase")]
+    Pruned { tx_seq: u64 },
+    #[serde(rename_all = "camelCase")]
+    SyncProgress {
+        log_sync_height: u64,
+        log_sync_block: H256,
+        next_tx_seq: u64,
+    },
+    /// `skipped` events are dropped since the subscriber or the node lagged behind, so the
+    /// subscriber should catch up via RPCs.
+    #[serde(rename_all = "camelCase")]
+    Lagged { skipped: u64 },
+}
+
+impl SubscriptionEvent {
+    /// Returns the topic of the event, or `None` if the event is sent to all subscribers.
+    pub fn topic(&self) -> Option<SubscriptionTopic> {
+        match self {
+            SubscriptionEvent::NewTx { .. } => Some(SubscriptionTopic::NewTxs),
+            SubscriptionEvent::Finalized { .. } => Some(SubscriptionTopic::Finalized),
+            SubscriptionEvent::Reverted { .. } => Some(SubscriptionTopic::Reverted),
+            SubscriptionEvent::Pruned { .. } => Some(SubscriptionTopic::Pruned),
+            SubscriptionEvent::SyncProgress { .. } => Some(SubscriptionTopic::SyncProgress),
+            SubscriptionEvent::Lagged { .. } => None,
+        }
+    }
+}
+
+impl From<&Transaction> for SubscriptionEvent {
+    fn from(tx: &Transaction) -> Self {
+        SubscriptionEvent::NewTx {
+            tx_seq: tx.seq,
+            data_root: tx.data_merkle_root,
+            size: tx.size,
+            start_entry_index: tx.start_entry_index,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct NetworkInfo {
@@ -57,6 +131,115 @@ pub struct FileInfo {
     pub pruned: bool,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileStatus {
+    #[serde(flatten)]
+    pub info: FileInfo,
+    /// Chunk ranges `[start, end)` of file that are available in local storage, or `None` if
+    /// `need_available` is false.
+    pub available_ranges: Option<Vec<(usize, usize)>>,
+}
+
+/// Segments of a file that are stored by this node, so that clients could download a sharded
+/// or partially synced file from several nodes in parallel.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileAvailability {
+    pub tx_seq: u64,
+    pub chunks_per_segment: usize,
+    pub num_segments: usize,
+    pub available_seg_num: usize,
+    /// Bit `i % 8` of byte `i / 8` is set if segment `i` is available.
+    #[serde(with = "base64")]
+    pub bitmap: Vec<u8>,
+    /// Run-length list of available segments `[start, end)`.
+    pub ranges: Vec<(usize, usize)>,
+}
+
+impl FileAvailability {
+    /// Builds the segment availability from the available flow entry ranges, which should be
+    /// ordered and not overlapped. Only segments within the shard are available.
+    pub fn new(
+        tx: &Transaction,
+        entry_ranges: &[(u64, u64)],
+        shard_config: &ShardConfig,
+        chunks_per_segment: usize,
+    ) -> Self {
+        let num_chunks = bytes_to_chunks(tx.size as usize);
+        let num_segments = (num_chunks + chunks_per_segment - 1) / chunks_per_segment;
+        let tx_start_segment = tx.start_entry_index as usize / chunks_per_segment;
+
+        let mut bitmap = vec![0u8; (num_segments + 7) / 8];
+        let mut ranges: Vec<(usize, usize)> = vec![];
+        let mut available_seg_num = 0;
+
+        for index in 0..num_segments {
+            if !shard_config.in_range((tx_start_segment + index) as u64) {
+                continue;
+            }
+
+            let start = tx.start_entry_index + (index * chunks_per_segment) as u64;
+            let end = tx.start_entry_index
+                + std::cmp::min((index + 1) * chunks_per_segment, num_chunks) as u64;
+
+            // find the last range that starts at or before the segment
+            let pos = entry_ranges.partition_point(|(s, _)| *s <= start);
+            if pos == 0 || entry_ranges[pos - 1].1 < end {
+                continue;
+            }
+
+            bitmap[index / 8] |= 1 << (index % 8);
+            available_seg_num += 1;
+            match ranges.last_mut() {
+                Some(last) if last.1 == index => last.1 = index + 1,
+                _ => ranges.push((index, index + 1)),
+            }
+        }
+
+        Self {
+            tx_seq: tx.seq,
+            chunks_per_segment,
+            num_segments,
+            available_seg_num,
+            bitmap,
+            ranges,
+        }
+    }
+
+    /// Returns the chunk ranges `[start, end)` of the available segments in a file of
+    /// `num_chunks` chunks.
+    pub fn chunk_ranges(&self, num_chunks: usize) -> Vec<(usize, usize)> {
+        self.ranges
+            .iter()
+            .map(|(start, end)| {
+                (
+                    start * self.chunks_per_segment,
+                    std::cmp::min(end * self.chunks_per_segment, num_chunks),
+                )
+            })
+            .collect()
+    }
+}
+
+/// File submission to the mock Flow contract in dev mode, which is the same as the `submit`
+/// method of Flow contract.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Submission {
+    /// File size in bytes.
+    pub length: u64,
+    pub nodes: Vec<SubmissionNode>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmissionNode {
+    pub root: DataRoot,
+    /// Height of the subtree, starting from 0 for a single chunk.
+    pub height: usize,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Segment(#[serde(with = "base64")] pub Vec<u8>);
 
@@ -107,6 +290,36 @@ impl SegmentWithProof {
         }
     }
 
+    /// Splits file data into segments along with the file merkle proofs, and returns the
+    /// file merkle root and segments.
+    pub fn split_file(
+        data: &[u8],
+        chunks_per_segment: usize,
+    ) -> RpcResult<(DataRoot, Vec<SegmentWithProof>)> {
+        let mut builder = FileSegmentTreeBuilder::new(data.len(), chunks_per_segment)?;
+
+        // Padding the last chunk with zeros.
+        let segment_size = chunks_per_segment * CHUNK_SIZE;
+        let segments: Vec<Vec<u8>> = data
+            .chunks(segment_size)
+            .map(|seg| {
+                let mut seg = seg.to_vec();
+                seg.resize(bytes_to_chunks(seg.len()) * CHUNK_SIZE, 0);
+                builder.append(&seg);
+                seg
+            })
+            .collect();
+
+        let tree = builder.build()?;
+        let segments = segments
+            .into_iter()
+            .enumerate()
+            .map(|(index, data)| tree.segment(index, data))
+            .collect();
+
+        Ok((tree.root(), segments))
+    }
+
     fn validate_data_size_and_index(
         &self,
         file_size: usize,
@@ -264,6 +477,107 @@ impl SegmentWithProof {
     }
 }
 
+/// Builds the [`FileSegmentTree`] from the segments appended in order, so that the segment data
+/// need not be held in memory at the same time.
+pub struct FileSegmentTreeBuilder {
+    file_size: usize,
+    chunks_per_segment: usize,
+    num_segments: usize,
+    segments_for_proof: usize,
+    last_segment_size: usize,
+    segment_roots: Vec<[u8; 32]>,
+}
+
+impl FileSegmentTreeBuilder {
+    pub fn new(file_size: usize, chunks_per_segment: usize) -> RpcResult<Self> {
+        let (num_segments, _) =
+            SegmentWithProof::split_file_into_segments(file_size, chunks_per_segment)?;
+        let (chunks, _) = compute_padded_chunk_size(file_size);
+        let (segments_for_proof, last_segment_size) =
+            compute_segment_size(chunks, chunks_per_segment);
+
+        Ok(Self {
+            file_size,
+            chunks_per_segment,
+            num_segments,
+            segments_for_proof,
+            last_segment_size,
+            segment_roots: Vec::with_capacity(segments_for_proof),
+        })
+    }
+
+    /// Appends the next segment, whose last chunk is padded with zeros.
+    pub fn append(&mut self, segment: &[u8]) {
+        let root = self.segment_root(self.segment_roots.len(), segment);
+        self.segment_roots.push(root);
+    }
+
+    pub fn build(mut self) -> RpcResult<FileSegmentTree> {
+        if self.segment_roots.len() != self.num_segments {
+            return Err(error::invalid_params(
+                "data",
+                format!(
+                    "segments mismatch, expected = {}, actual = {}",
+                    self.num_segments,
+                    self.segment_roots.len()
+                ),
+            ));
+        }
+
+        // Segments in the rear padding range are all zeros.
+        for index in self.num_segments..self.segments_for_proof {
+            let root = self.segment_root(index, &[]);
+            self.segment_roots.push(root);
+        }
+
+        let tree = MerkleTree::<_, RawLeafSha3Algorithm>::new(self.segment_roots);
+        Ok(FileSegmentTree {
+            root: DataRoot::from(tree.root()),
+            tree,
+            file_size: self.file_size,
+        })
+    }
+
+    fn segment_root(&self, index: usize, segment: &[u8]) -> [u8; 32] {
+        let seg_chunks = if index == self.segments_for_proof - 1 {
+            self.last_segment_size
+        } else {
+            self.chunks_per_segment
+        };
+
+        compute_segment_merkle_root(segment, seg_chunks)
+    }
+}
+
+/// File merkle tree whose leaves are segment roots, to generate the proofs of segments.
+pub struct FileSegmentTree {
+    tree: MerkleTree<[u8; 32], RawLeafSha3Algorithm>,
+    root: DataRoot,
+    file_size: usize,
+}
+
+impl FileSegmentTree {
+    pub fn root(&self) -> DataRoot {
+        self.root
+    }
+
+    /// Returns the segment at `index` along with its proof, where `data` should be the same as
+    /// the appended one.
+    pub fn segment(&self, index: usize, data: Vec<u8>) -> SegmentWithProof {
+        let proof = self.tree.gen_proof(index);
+        SegmentWithProof {
+            root: self.root,
+            data,
+            index,
+            proof: FileProof::new(
+                proof.lemma().iter().map(|h| H256::from(*h)).collect(),
+                proof.path().to_vec(),
+            ),
+            file_size: self.file_size,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PeerInfo {
@@ -379,7 +693,11 @@ mod base64 {
 
 #[cfg(test)]
 mod tests {
-    use super::Segment;
+    use super::{
+        FileAvailability, Segment, SegmentWithProof, SubscriptionEvent, SubscriptionTopic,
+    };
+    use shared_types::{Transaction, CHUNK_SIZE};
+    use storage::config::ShardConfig;
 
     #[test]
     fn test_segment_serde() {
@@ -390,4 +708,120 @@ mod tests {
         let seg2: Segment = serde_json::from_str("\"aGVsbG8sIHdvcmxk\"").unwrap();
         assert_eq!(String::from_utf8(seg2.0).unwrap().as_str(), "hello, world");
     }
+
+    #[test]
+    fn test_split_file() {
+        let chunks_per_segment = 4;
+
+        for file_size in [1, 100, 1024, 1025, 4096, 5000, 10 * 1024 + 1, 33 * 1024] {
+            let data: Vec<u8> = (0..file_size).map(|i| (i % 251) as u8).collect();
+            let (root, segments) = SegmentWithProof::split_file(&data, chunks_per_segment).unwrap();
+
+            let (num_segments, _) =
+                SegmentWithProof::split_file_into_segments(file_size, chunks_per_segment).unwrap();
+            assert_eq!(segments.len(), num_segments);
+
+            let mut merged = vec![];
+            for (index, segment) in segments.into_iter().enumerate() {
+                assert_eq!(segment.root, root);
+                assert_eq!(segment.index, index);
+                assert_eq!(segment.data.len() % CHUNK_SIZE, 0);
+                segment.validate(chunks_per_segment).unwrap();
+                merged.extend_from_slice(&segment.data);
+            }
+
+            assert_eq!(&merged[..file_size], &data[..]);
+        }
+
+        assert!(SegmentWithProof::split_file(&[], chunks_per_segment).is_err());
+    }
+
+    #[test]
+    fn test_subscription_serde() {
+        let topic: SubscriptionTopic = serde_json::from_str("\"syncProgress\"").unwrap();
+        assert_eq!(topic, SubscriptionTopic::SyncProgress);
+
+        let event = SubscriptionEvent::Finalized { tx_seq: 3 };
+        assert_eq!(event.topic(), Some(SubscriptionTopic::Finalized));
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"type":"finalized","txSeq":3}"#
+        );
+
+        let event = SubscriptionEvent::Lagged { skipped: 2 };
+        assert_eq!(event.topic(), None);
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"type":"lagged","skipped":2}"#
+        );
+    }
+
+    fn tx(start_entry_index: u64, num_chunks: u64, seq: u64) -> Transaction {
+        Transaction {
+            stream_ids: vec![],
+            data: vec![],
+            data_merkle_root: Default::default(),
+            merkle_nodes: vec![],
+            start_entry_index,
+            size: num_chunks * CHUNK_SIZE as u64 - 1,
+            seq,
+        }
+    }
+
+    #[test]
+    fn test_file_availability() {
+        // 10 segments of 4 chunks, and the last segment has 2 chunks
+        let tx = tx(64, 38, 7);
+        let shard = ShardConfig::default();
+
+        // segment 2 is partially available, and the last segment is available
+        let ranges = [(64, 72), (76, 80), (88, 102)];
+        let availability = FileAvailability::new(&tx, &ranges, &shard, 4);
+        assert_eq!(availability.tx_seq, 7);
+        assert_eq!(availability.num_segments, 10);
+        assert_eq!(availability.available_seg_num, 7);
+        assert_eq!(availability.ranges, vec![(0, 2), (3, 4), (6, 10)]);
+        assert_eq!(availability.bitmap, vec![0b1100_1011, 0b11]);
+        assert_eq!(
+            availability.chunk_ranges(38),
+            vec![(0, 8), (12, 16), (24, 38)]
+        );
+
+        // only even segments of flow in shard 0
+        let shard = ShardConfig::new(0, 2).unwrap();
+        let availability = FileAvailability::new(&tx, &[(64, 102)], &shard, 4);
+        assert_eq!(availability.available_seg_num, 5);
+        assert_eq!(
+            availability.ranges,
+            vec![(0, 1), (2, 3), (4, 5), (6, 7), (8, 9)]
+        );
+        assert_eq!(availability.bitmap, vec![0b0101_0101, 0b01]);
+        assert_eq!(
+            availability.chunk_ranges(38),
+            vec![(0, 4), (8, 12), (16, 20), (24, 28), (32, 36)]
+        );
+
+        // nothing available
+        let availability = FileAvailability::new(&tx, &[], &shard, 4);
+        assert_eq!(availability.available_seg_num, 0);
+        assert!(availability.ranges.is_empty());
+        assert!(availability.chunk_ranges(38).is_empty());
+    }
+
+    #[test]
+    fn test_file_availability_chunk_ranges() {
+        // all segments merged without sharding
+        let shard = ShardConfig::new(0, 1).unwrap();
+        let availability = FileAvailability::new(&tx(0, 10, 0), &[(0, 10)], &shard, 4);
+        assert_eq!(availability.chunk_ranges(10), vec![(0, 10)]);
+
+        // file starts from flow segment 1, and only even segments of flow in shard 0
+        let shard = ShardConfig::new(0, 2).unwrap();
+        let availability = FileAvailability::new(&tx(4, 18, 0), &[(4, 22)], &shard, 4);
+        assert_eq!(availability.chunk_ranges(18), vec![(4, 8), (12, 16)]);
+
+        // small file in other shard
+        let availability = FileAvailability::new(&tx(4, 2, 0), &[(4, 6)], &shard, 4);
+        assert!(availability.chunk_ranges(2).is_empty());
+    }
 }
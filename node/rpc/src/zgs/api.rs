@@ -1,4 +1,7 @@
-use crate::types::{FileInfo, Segment, SegmentWithProof, Status};
+use crate::types::{
+    FileAvailability, FileInfo, FileStatus, Segment, SegmentWithProof, Status, SubscriptionEvent,
+    SubscriptionTopic,
+};
 use jsonrpsee::core::RpcResult;
 use jsonrpsee::proc_macros::rpc;
 use shared_types::{DataRoot, FlowProof, TxSeqOrRoot};
@@ -72,6 +75,22 @@ pub trait Rpc {
     #[method(name = "getFileInfoByTxSeq")]
     async fn get_file_info_by_tx_seq(&self, tx_seq: u64) -> RpcResult<Option<FileInfo>>;
 
+    /// Returns the file status for each of the specified tx seq or data root in the same order,
+    /// or `None` if the file not found.
+    ///
+    /// If `need_available` is true, data roots are resolved to the finalized files and the
+    /// available chunk ranges are returned. Otherwise, the available ranges are skipped.
+    #[method(name = "getFileStatusBatch")]
+    async fn get_file_status_batch(
+        &self,
+        tx_seq_or_roots: Vec<TxSeqOrRoot>,
+        need_available: bool,
+    ) -> RpcResult<Vec<Option<FileStatus>>>;
+
+    /// Returns the segments of file that are stored by this node.
+    #[method(name = "getFileAvailability")]
+    async fn get_file_availability(&self, tx_seq: u64) -> RpcResult<Option<FileAvailability>>;
+
     #[method(name = "getShardConfig")]
     async fn get_shard_config(&self) -> RpcResult<ShardConfig>;
 
@@ -84,4 +103,8 @@ pub trait Rpc {
 
     #[method(name = "getFlowContext")]
     async fn get_flow_context(&self) -> RpcResult<(H256, u64)>;
+
+    /// Subscribes to events of the given topic, which is only available on the WebSocket server.
+    #[subscription(name = "subscribe" => "subscription", unsubscribe = "unsubscribe", item = SubscriptionEvent)]
+    fn subscribe(&self, topic: SubscriptionTopic);
 }
@@ -1,15 +1,22 @@
 use super::api::RpcServer;
 use crate::error;
-use crate::types::{FileInfo, Segment, SegmentWithProof, Status};
+use crate::types::{
+    FileInfo, FileStatus, Segment, SegmentWithProof, Status, SubscriptionEvent, SubscriptionTopic,
+};
 use crate::Context;
 use chunk_pool::{FileID, SegmentInfo};
 use jsonrpsee::core::async_trait;
 use jsonrpsee::core::RpcResult;
-use shared_types::{DataRoot, FlowProof, Transaction, TxSeqOrRoot, CHUNK_SIZE};
+use jsonrpsee::PendingSubscription;
+use shared_types::{bytes_to_chunks, DataRoot, FlowProof, Transaction, TxSeqOrRoot, CHUNK_SIZE};
 use std::fmt::{Debug, Formatter, Result};
 use storage::config::ShardConfig;
 use storage::log_store::tx_store::TxStatus;
 use storage::{try_option, H256};
+use tokio::sync::broadcast::error::RecvError;
+
+/// Maximum number of files to query in a single `zgs_getFileStatusBatch` call.
+const MAX_FILE_STATUS_BATCH_SIZE: usize = 1000;
 
 pub struct RpcServerImpl {
     pub ctx: Context,
@@ -26,12 +33,18 @@ impl RpcServer for RpcServerImpl {
             .get_sync_progress()?
             .unwrap_or_default();
 
+        let log_sync_finality = self
+            .ctx
+            .log_store
+            .get_store()
+            .get_log_finalized_block_number()?;
         let next_tx_seq = self.ctx.log_store.get_store().next_tx_seq();
 
         Ok(Status {
             connected_peers: self.ctx.network_globals.connected_peers(),
             log_sync_height: sync_progress.0,
             log_sync_block: sync_progress.1,
+            log_sync_finality,
             next_tx_seq,
             network_identity: self.ctx.network_globals.network_id(),
         })
@@ -198,6 +211,64 @@ impl RpcServer for RpcServerImpl {
         Ok(Some(self.get_file_info_by_tx(tx).await?))
     }
 
+    async fn get_file_status_batch(
+        &self,
+        tx_seq_or_roots: Vec<TxSeqOrRoot>,
+        need_available: bool,
+    ) -> RpcResult<Vec<Option<FileStatus>>> {
+        debug!(num = %tx_seq_or_roots.len(), "zgs_getFileStatusBatch");
+
+        if tx_seq_or_roots.len() > MAX_FILE_STATUS_BATCH_SIZE {
+            return Err(error::invalid_params(
+                "tx_seq_or_roots",
+                format!("exceeds maximum batch size {}", MAX_FILE_STATUS_BATCH_SIZE),
+            ));
+        }
+
+        let txs = self
+            .ctx
+            .log_store
+            .get_txs_with_status(tx_seq_or_roots, need_available)
+            .await?;
+
+        let mut result = Vec::with_capacity(txs.len());
+        for maybe_tx in txs {
+            let (tx, status, entry_ranges) = match maybe_tx {
+                Some(v) => v,
+                None => {
+                    result.push(None);
+                    continue;
+                }
+            };
+
+            let available_ranges = need_available.then(|| {
+                self.file_availability(&tx, &entry_ranges)
+                    .chunk_ranges(bytes_to_chunks(tx.size as usize))
+            });
+
+            result.push(Some(FileStatus {
+                info: self.get_file_info_by_tx_status(tx, status).await?,
+                available_ranges,
+            }));
+        }
+
+        Ok(result)
+    }
+
+    async fn get_file_availability(&self, tx_seq: u64) -> RpcResult<Option<FileAvailability>> {
+        debug!(%tx_seq, "zgs_getFileAvailability");
+
+        let (tx, _, entry_ranges) = try_option!(self
+            .ctx
+            .log_store
+            .get_txs_with_status(vec![TxSeqOrRoot::TxSeq(tx_seq)], true)
+            .await?
+            .pop()
+            .flatten());
+
+        Ok(Some(self.file_availability(&tx, &entry_ranges)))
+    }
+
     async fn get_shard_config(&self) -> RpcResult<ShardConfig> {
         debug!("zgs_getShardConfig");
         let shard_config = self.ctx.log_store.get_store().get_shard_config();
@@ -221,6 +292,49 @@ impl RpcServer for RpcServerImpl {
     async fn get_flow_context(&self) -> RpcResult<(H256, u64)> {
         Ok(self.ctx.log_store.get_context().await?)
     }
+
+    fn subscribe(&self, pending: PendingSubscription, topic: SubscriptionTopic) {
+        info!(?topic, "zgs_subscribe");
+
+        // rejected if dropped, e.g. subscribed via HTTP
+        let mut event_recv = match &self.ctx.event_send {
+            Some(event_send) => event_send.subscribe(),
+            None => return,
+        };
+
+        let mut sink = match pending.accept() {
+            Some(sink) => sink,
+            None => return,
+        };
+
+        tokio::spawn(async move {
+            loop {
+                let event = match event_recv.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Closed) => return,
+                    Err(RecvError::Lagged(lagged)) => {
+                        // subscriber should catch up via RPCs
+                        warn!(%lagged, ?topic, "Subscriber lagged behind");
+                        SubscriptionEvent::Lagged { skipped: lagged }
+                    }
+                };
+
+                if event.topic().is_some_and(|t| t != topic) {
+                    continue;
+                }
+
+                match sink.send(&event) {
+                    Ok(true) => {}
+                    // unsubscribed or connection closed
+                    Ok(false) => return,
+                    Err(e) => {
+                        warn!(?e, ?topic, "Failed to send subscription event");
+                        return;
+                    }
+                }
+            }
+        });
+    }
 }
 
 impl RpcServerImpl {
@@ -264,7 +378,26 @@ impl RpcServerImpl {
     }
 
     async fn get_file_info_by_tx(&self, tx: Transaction) -> RpcResult<FileInfo> {
-        let (finalized, pruned) = match self.ctx.log_store.get_store().get_tx_status(tx.seq)? {
+        let status = self.ctx.log_store.get_store().get_tx_status(tx.seq)?;
+        self.get_file_info_by_tx_status(tx, status).await
+    }
+
+    fn file_availability(&self, tx: &Transaction, entry_ranges: &[(u64, u64)]) -> FileAvailability {
+        let shard_config = self.ctx.log_store.get_store().get_shard_config();
+        FileAvailability::new(
+            tx,
+            entry_ranges,
+            &shard_config,
+            self.ctx.config.chunks_per_segment,
+        )
+    }
+
+    async fn get_file_info_by_tx_status(
+        &self,
+        tx: Transaction,
+        status: Option<TxStatus>,
+    ) -> RpcResult<FileInfo> {
+        let (finalized, pruned) = match status {
             Some(TxStatus::Finalized) => (true, false),
             Some(TxStatus::Pruned) => (false, true),
             None => (false, false),
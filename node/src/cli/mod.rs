@@ -1,4 +1,4 @@
-use clap::{arg, command, Command};
+use clap::{arg, command, value_parser, Command};
 
 pub fn cli_app() -> Command {
     command!()
@@ -10,6 +10,65 @@ pub fn cli_app() -> Command {
         )
         .arg(arg!(--"db-max-num-chunks" [NUM] "Sets the max number of chunks to store in db (Default: None)"))
         .arg(arg!(--"network-enr-address" [URL] "Sets the network ENR address (Default: None)"))
+        .subcommand(
+            Command::new("export")
+                .about("Exports flow_db and data_db to a snapshot archive, the node must be stopped")
+                .arg(arg!(<FILE> "Snapshot archive file to create")),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Imports a snapshot archive into an empty db, the node must be stopped")
+                .arg(arg!(<FILE> "Snapshot archive file to import")),
+        )
+        .subcommand(
+            Command::new("export-logs")
+                .about("Exports the Submit events of the Flow contract up to the finalized block to a log archive")
+                .arg(arg!(<FILE> "Log archive file to create")),
+        )
+        .subcommand(
+            Command::new("mine-simulate")
+                .about("Mines a synthetic mine context offline to measure the mining performance")
+                .arg(
+                    arg!(--"flow-length" <SECTORS> "Number of sectors in the synthetic flow")
+                        .value_parser(value_parser!(u64))
+                        .default_value("1073741824"),
+                )
+                .arg(
+                    arg!(--difficulty <NUM> "Difficulty of the PoRA target")
+                        .value_parser(value_parser!(u64).range(1..))
+                        .default_value("1"),
+                )
+                .arg(
+                    arg!(--"duration-secs" <SECS> "How long to mine")
+                        .value_parser(value_parser!(u64))
+                        .default_value("60"),
+                )
+                .arg(
+                    arg!(--"sealed-ratio" <RATIO> "Ratio of sealed data in the synthetic flow")
+                        .value_parser(value_parser!(f64))
+                        .default_value("1.0"),
+                )
+                .arg(
+                    arg!(--"load-latency-ms" <MS> "Simulated latency to load data of the synthetic flow")
+                        .value_parser(value_parser!(u64))
+                        .default_value("0"),
+                )
+                .arg(
+                    arg!(--"iter-batch" <NUM> "Number of nonces to mine per thread in a batch, overriding mine_iter_batch_size")
+                        .value_parser(value_parser!(u64).range(1..)),
+                )
+                .arg(
+                    arg!(--"epoch-secs" <SECS> "Duration of a mining epoch to estimate the answers per epoch")
+                        .value_parser(value_parser!(u64))
+                        .default_value("60"),
+                )
+                .arg(arg!(--"use-store" "Mines the data of the local db instead, the node must be stopped")),
+        )
+        .subcommand(
+            Command::new("fsck")
+                .about("Checks the integrity of the db, the node must be stopped")
+                .arg(arg!(--repair "Removes corrupted data and rebuilds the merkle tree")),
+        )
         .allow_external_subcommands(true)
         .version(zgs_version::VERSION)
 }
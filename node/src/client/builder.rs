@@ -1,8 +1,8 @@
 use super::{Client, RuntimeContext};
 use chunk_pool::{Config as ChunkPoolConfig, MemoryChunkPool};
 use file_location_cache::FileLocationCache;
-use log_entry_sync::{LogSyncConfig, LogSyncEvent, LogSyncManager};
-use miner::{MineService, MinerConfig, MinerMessage, ShardConfig};
+use log_entry_sync::{LogSyncConfig, LogSyncEvent, LogSyncManager, MockFlow};
+use miner::{MineService, MinerConfig, MinerMessage, MinerStatusHandle, ShardConfig};
 use network::{
     self, new_network_channel, Keypair, NetworkConfig, NetworkGlobals, NetworkReceiver,
     NetworkSender, RequestId, Service as LibP2PService,
@@ -11,6 +11,8 @@ use pruner::{get_shard_config, Pruner, PrunerConfig, PrunerMessage};
 use router::RouterService;
 use rpc::RPCConfig;
 use std::sync::Arc;
+use std::time::Duration;
+use storage::config::DbBackend;
 use storage::log_store::log_manager::LogConfig;
 use storage::log_store::Store;
 use storage::{LogManager, StorageConfig};
@@ -41,11 +43,14 @@ struct SyncComponents {
 
 struct MinerComponents {
     send: broadcast::Sender<MinerMessage>,
+    status: MinerStatusHandle,
 }
 
 struct LogSyncComponents {
     send: broadcast::Sender<LogSyncEvent>,
     catch_up_end_recv: Option<oneshot::Receiver<()>>,
+    // note: this is only available in dev mode
+    mock_flow: Option<Arc<MockFlow>>,
 }
 
 struct PrunerComponents {
@@ -127,10 +132,80 @@ impl ClientBuilder {
         Ok(self)
     }
 
-    pub fn with_file_location_cache(mut self, config: file_location_cache::Config) -> Self {
-        let file_location_cache = FileLocationCache::new(config);
-        self.file_location_cache = Some(Arc::new(file_location_cache));
-        self
+    /// Initializes RocksDB storage with entry batches stored in segment files.
+    pub fn with_segment_store(mut self, config: &StorageConfig) -> Result<Self, String> {
+        let store = Arc::new(
+            LogManager::segment(
+                config.log_config.clone(),
+                config.db_dir.join("flow_db"),
+                config.db_dir.join("data_db"),
+                config.db_dir.join("entry_segments"),
+                config.segment_config,
+            )
+            .map_err(|e| format!("Unable to start segment store: {:?}", e))?,
+        );
+
+        self.store = Some(store.clone());
+
+        if let Some(ctx) = self.runtime_context.as_ref() {
+            self.async_store = Some(Arc::new(storage_async::Store::new(
+                store,
+                ctx.executor.clone(),
+            )));
+        }
+
+        Ok(self)
+    }
+
+    /// Initializes storage with the configured backend.
+    pub fn with_store(self, config: &StorageConfig) -> Result<Self, String> {
+        match config.backend {
+            DbBackend::RocksDB => self.with_rocksdb_store(config),
+            DbBackend::Memory => self.with_memory_store(),
+            DbBackend::Segment => self.with_segment_store(config),
+        }
+    }
+
+    /// Returns the async store, which requires the runtime context and store.
+    pub fn async_store(&self) -> Option<Arc<storage_async::Store>> {
+        self.async_store.clone()
+    }
+
+    pub fn with_file_location_cache(
+        mut self,
+        config: file_location_cache::Config,
+    ) -> Result<Self, String> {
+        let executor = require!("file_location_cache", self, runtime_context)
+            .clone()
+            .executor;
+        let persist_interval = Duration::from_secs(config.persist_interval_secs.max(1));
+        let persistent = config.persist_path.is_some();
+        let file_location_cache = Arc::new(FileLocationCache::new(config));
+
+        // persist periodically, and the cache persists itself once dropped on shutdown
+        if persistent {
+            let cache = Arc::downgrade(&file_location_cache);
+            executor.spawn(
+                async move {
+                    let mut interval = tokio::time::interval(persist_interval);
+                    interval.tick().await;
+                    loop {
+                        interval.tick().await;
+                        let cache = match cache.upgrade() {
+                            Some(cache) => cache,
+                            None => break,
+                        };
+                        if let Err(e) = cache.persist() {
+                            warn!(error = %e, "Failed to persist file location cache");
+                        }
+                    }
+                },
+                "file_location_cache_persist",
+            );
+        }
+
+        self.file_location_cache = Some(file_location_cache);
+        Ok(self)
     }
 
     /// Starts the networking stack.
@@ -140,8 +215,9 @@ impl ClientBuilder {
         let file_location_cache = require!("network", self, file_location_cache).clone();
 
         // only dial to peers that shard config matched
+        let peer_config_cache = file_location_cache.clone();
         config.peer_manager.filters.dial_peer_filter = Some(Arc::new(move |peer_id| {
-            match file_location_cache.get_peer_config(peer_id) {
+            match peer_config_cache.get_peer_config(peer_id) {
                 Some(v) => store.get_shard_config().intersect(&v),
                 None => true,
             }
@@ -159,6 +235,16 @@ impl ClientBuilder {
                 .await
                 .map_err(|e| format!("Failed to start network service: {:?}", e))?;
 
+        // restore the shard configs of peers in the persisted peer db
+        for (peer_id, info) in globals.peers.read().peers() {
+            if let Some(shard_config) = info
+                .shard_config()
+                .and_then(|v| ShardConfig::try_from(v).ok())
+            {
+                file_location_cache.insert_peer_config(*peer_id, shard_config);
+            }
+        }
+
         self.network = Some(NetworkComponents {
             send,
             globals,
@@ -205,8 +291,8 @@ impl ClientBuilder {
             let network_send = require!("miner", self, network).send.clone();
             let store = require!("miner", self, async_store).clone();
 
-            let send = MineService::spawn(executor, network_send, config, store).await?;
-            self.miner = Some(MinerComponents { send });
+            let (send, status) = MineService::spawn(executor, network_send, config, store).await?;
+            self.miner = Some(MinerComponents { send, status });
         }
 
         Ok(self)
@@ -286,8 +372,33 @@ impl ClientBuilder {
         let file_location_cache = require!("rpc", self, file_location_cache).clone();
         let chunk_pool = require!("rpc", self, chunk_pool).chunk_pool.clone();
 
+        let event_send = match rpc_config.listen_address_ws {
+            Some(_) => Some(
+                rpc::EventNotifier::spawn(
+                    &executor,
+                    async_store.clone(),
+                    require!("rpc", self, log_sync).send.subscribe(),
+                    Duration::from_millis(rpc_config.subscription_poll_interval_ms),
+                )
+                .await?,
+            ),
+            None => None,
+        };
+
+        let auth = if rpc_config.auth.enabled {
+            Some(Arc::new(rpc::Authenticator::new(&rpc_config.auth)?))
+        } else {
+            None
+        };
+
+        let rate_limiter = if rpc_config.rate_limit.enabled {
+            Some(Arc::new(rpc::RateLimiter::new(&rpc_config)?))
+        } else {
+            None
+        };
+
         let ctx = rpc::Context {
-            config: rpc_config,
+            config: rpc_config.clone(),
             file_location_cache,
             network_globals: require!("rpc", self, network).globals.clone(),
             network_send,
@@ -296,6 +407,36 @@ impl ClientBuilder {
             chunk_pool,
             shutdown_sender: executor.shutdown_sender(),
             mine_service_sender: mine_send,
+            mine_status: self.miner.as_ref().map(|x| x.status.clone()),
+            event_send,
+            mock_flow: require!("rpc", self, log_sync).mock_flow.clone(),
+            auth,
+            rate_limiter,
+        };
+
+        let maybe_file_server = match rpc_config.listen_address_file {
+            Some(addr) => Some(
+                rpc::run_file_server(ctx.clone(), addr, false)
+                    .map_err(|e| format!("Unable to start HTTP file server: {:?}", e))?,
+            ),
+            None => None,
+        };
+
+        let maybe_admin_file_server = match rpc_config.listen_address_file_admin {
+            Some(addr) => Some(
+                rpc::run_file_server(ctx.clone(), addr, true)
+                    .map_err(|e| format!("Unable to start HTTP admin file server: {:?}", e))?,
+            ),
+            None => None,
+        };
+
+        let maybe_ws_server = match rpc_config.listen_address_ws {
+            Some(addr) => Some(
+                rpc::run_ws_server(ctx.clone(), addr)
+                    .await
+                    .map_err(|e| format!("Unable to start WebSocket RPC server: {:?}", e))?,
+            ),
+            None => None,
         };
 
         let (rpc_handle, maybe_admin_rpc_handle) = rpc::run_server(ctx)
@@ -306,6 +447,15 @@ impl ClientBuilder {
         if let Some(admin_rpc_handle) = maybe_admin_rpc_handle {
             executor.spawn(admin_rpc_handle, "rpc_admin");
         }
+        if let Some(file_server) = maybe_file_server {
+            executor.spawn(file_server, "rpc_file");
+        }
+        if let Some(admin_file_server) = maybe_admin_file_server {
+            executor.spawn(admin_file_server, "rpc_file_admin");
+        }
+        if let Some(ws_server) = maybe_ws_server {
+            executor.spawn(ws_server, "rpc_ws");
+        }
 
         Ok(self)
     }
@@ -336,13 +486,23 @@ impl ClientBuilder {
     pub async fn with_log_sync(mut self, config: LogSyncConfig) -> Result<Self, String> {
         let executor = require!("log_sync", self, runtime_context).clone().executor;
         let store = require!("log_sync", self, store).clone();
-        let (send, catch_up_end_recv) = LogSyncManager::spawn(config, executor, store)
-            .await
-            .map_err(|e| e.to_string())?;
+        let (send, catch_up_end_recv, mock_flow) = if config.dev_mode {
+            let (send, catch_up_end_recv, mock_flow) =
+                LogSyncManager::spawn_dev(config, executor, store)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            (send, catch_up_end_recv, Some(mock_flow))
+        } else {
+            let (send, catch_up_end_recv) = LogSyncManager::spawn(config, executor, store)
+                .await
+                .map_err(|e| e.to_string())?;
+            (send, catch_up_end_recv, None)
+        };
 
         self.log_sync = Some(LogSyncComponents {
             send,
             catch_up_end_recv: Some(catch_up_end_recv),
+            mock_flow,
         });
         Ok(self)
     }
@@ -1,10 +1,11 @@
 #![allow(clippy::field_reassign_with_default)]
 
 use crate::ZgsConfig;
+use contract_wrapper::ProviderConfig;
 use ethereum_types::H256;
-use ethers::prelude::{Http, Middleware, Provider};
+use ethers::prelude::{BlockNumber, Middleware};
 use log_entry_sync::{CacheConfig, ContractAddress, LogSyncConfig};
-use miner::MinerConfig;
+use miner::{MineEngineConfig, MinerConfig};
 use network::{EnrExt, NetworkConfig};
 use pruner::PrunerConfig;
 use shared_types::{NetworkIdentity, ProtocolVersion};
@@ -13,8 +14,13 @@ use std::sync::Arc;
 use std::time::Duration;
 use storage::config::ShardConfig;
 use storage::log_store::log_manager::LogConfig;
+use storage::log_store::parity::ParityConfig;
+use storage::segment_db::SegmentConfig;
 use storage::StorageConfig;
 
+/// Chain id of the network identity in dev mode, which is the same as local dev chains.
+const DEV_CHAIN_ID: u64 = 1337;
+
 impl ZgsConfig {
     pub async fn network_config(&self) -> Result<NetworkConfig, String> {
         let mut network_config = NetworkConfig::default();
@@ -28,17 +34,24 @@ impl ZgsConfig {
         network_config.libp2p_port = self.network_libp2p_port;
         network_config.disable_discovery = self.network_disable_discovery;
         network_config.discovery_port = self.network_discovery_port;
-        let flow_address = self
-            .log_contract_address
-            .parse::<ContractAddress>()
-            .map_err(|e| format!("Unable to parse log_contract_address: {:?}", e))?;
-        let provider = Provider::<Http>::try_from(&self.blockchain_rpc_endpoint)
-            .map_err(|e| format!("Can not parse blockchain endpoint: {:?}", e))?;
-        let chain_id = provider
-            .get_chainid()
-            .await
-            .map_err(|e| format!("Unable to get chain id: {:?}", e))?
-            .as_u64();
+        let flow_address = self.flow_address()?;
+        let chain_id = if self.dev_mode {
+            DEV_CHAIN_ID
+        } else {
+            let provider = ProviderConfig {
+                endpoints: self.blockchain_rpc_endpoints(),
+                rate_limit_retries: self.rate_limit_retries,
+                timeout_retries: self.timeout_retries,
+                initial_backoff: self.initial_backoff,
+                request_timeout: Some(Duration::from_secs(self.blockchain_rpc_timeout_secs)),
+            }
+            .make_provider()?;
+            provider
+                .get_chainid()
+                .await
+                .map_err(|e| format!("Unable to get chain id: {:?}", e))?
+                .as_u64()
+        };
         let local_network_id = NetworkIdentity {
             chain_id,
             flow_address,
@@ -115,25 +128,36 @@ impl ZgsConfig {
     pub fn storage_config(&self) -> Result<StorageConfig, String> {
         let mut log_config = LogConfig::default();
         log_config.flow.merkle_node_cache_capacity = self.merkle_node_cache_capacity;
+        if self.db_parity_enabled {
+            let parity = ParityConfig {
+                data_shards: self.db_parity_data_shards,
+                parity_shards: self.db_parity_shards,
+            };
+            parity.validate()?;
+            log_config.flow.parity = Some(parity);
+        }
+        let segment_config = SegmentConfig {
+            max_file_size: self.db_segment_file_size,
+            ..Default::default()
+        };
         Ok(StorageConfig {
             db_dir: self.db_dir.clone().into(),
             log_config,
+            backend: self.db_backend.parse()?,
+            segment_config,
         })
     }
 
     pub fn log_sync_config(&self) -> Result<LogSyncConfig, String> {
-        let contract_address = self
-            .log_contract_address
-            .parse::<ContractAddress>()
-            .map_err(|e| format!("Unable to parse log_contract_address: {:?}", e))?;
+        let contract_address = self.flow_address()?;
         let cache_config = CacheConfig {
             // 100 MB.
             max_data_size: self.max_cache_data_size,
             // This should be enough if we have about one Zgs tx per block.
             tx_seq_ttl: self.cache_tx_seq_ttl,
         };
-        Ok(LogSyncConfig::new(
-            self.blockchain_rpc_endpoint.clone(),
+        let mut config = LogSyncConfig::new(
+            self.blockchain_rpc_endpoints(),
             contract_address,
             self.log_sync_start_block_number,
             self.confirmation_block_count,
@@ -148,18 +172,30 @@ impl ZgsConfig {
             self.watch_loop_wait_time_ms,
             self.force_log_sync_from_start_block_number,
             Duration::from_secs(self.blockchain_rpc_timeout_secs),
-        ))
+        );
+        config.subscription_endpoint = self.blockchain_subscription_endpoint.clone();
+        config.quorum = self.blockchain_rpc_quorum;
+        config.archive_path = self.log_sync_archive_path.clone();
+        config.finality_tag = match self.log_sync_finality_tag.as_deref() {
+            None => None,
+            Some("finalized") => Some(BlockNumber::Finalized),
+            Some("safe") => Some(BlockNumber::Safe),
+            Some(tag) => return Err(format!("Invalid log_sync_finality_tag: {}", tag)),
+        };
+        config.dev_mode = self.dev_mode;
+        Ok(config)
     }
 
     pub fn mine_config(&self) -> Result<Option<MinerConfig>, String> {
-        let flow_address = self
-            .log_contract_address
-            .parse::<ContractAddress>()
-            .map_err(|e| format!("Unable to parse log_contract_address: {:?}", e))?;
-        let mine_address = self
-            .mine_contract_address
-            .parse::<ContractAddress>()
-            .map_err(|e| format!("Unable to parse mine_address: {:?}", e))?;
+        let flow_address = self.flow_address()?;
+        // The PoraMine contract is not used in dev mode, where the puzzle is made locally.
+        let mine_address = if self.dev_mode && self.mine_contract_address.is_empty() {
+            ContractAddress::zero()
+        } else {
+            self.mine_contract_address
+                .parse::<ContractAddress>()
+                .map_err(|e| format!("Unable to parse mine_address: {:?}", e))?
+        };
 
         let miner_id = if let Some(ref miner_id) = self.miner_id {
             Some(
@@ -188,7 +224,7 @@ impl ZgsConfig {
         Ok(MinerConfig::new(
             miner_id,
             miner_key,
-            self.blockchain_rpc_endpoint.clone(),
+            self.blockchain_rpc_endpoints(),
             mine_address,
             flow_address,
             cpu_percentage,
@@ -199,9 +235,21 @@ impl ZgsConfig {
             self.timeout_retries,
             self.initial_backoff,
             self.submission_config,
+            self.miner_skip_unprofitable,
+            self.miner_seal_threads,
+            self.mine_engine_config(),
+            self.dev_mode,
         ))
     }
 
+    pub fn mine_engine_config(&self) -> MineEngineConfig {
+        MineEngineConfig {
+            threads: self.miner_threads,
+            cpu_affinity: self.miner_cpu_affinity.clone(),
+            hasher: self.miner_hasher.clone(),
+        }
+    }
+
     pub fn chunk_pool_config(&self) -> Result<chunk_pool::Config, String> {
         Ok(chunk_pool::Config {
             write_window_size: self.chunk_pool_write_window_size,
@@ -228,10 +276,16 @@ impl ZgsConfig {
     pub fn pruner_config(&self) -> Result<Option<PrunerConfig>, String> {
         if let Some(max_num_sectors) = self.db_max_num_sectors {
             let shard_config = self.shard_config()?;
-            let reward_address = self
-                .reward_contract_address
-                .parse::<ContractAddress>()
-                .map_err(|e| format!("Unable to parse reward_contract_address: {:?}", e))?;
+            // No reward is distributed in dev mode, so only prune data out of the shard range.
+            let reward_address = if self.dev_mode {
+                None
+            } else {
+                Some(
+                    self.reward_contract_address
+                        .parse::<ContractAddress>()
+                        .map_err(|e| format!("Unable to parse reward_contract_address: {:?}", e))?,
+                )
+            };
             Ok(Some(PrunerConfig {
                 shard_config,
                 db_path: self.db_dir.clone().into(),
@@ -239,7 +293,7 @@ impl ZgsConfig {
                 check_time: Duration::from_secs(self.prune_check_time_s),
                 batch_size: self.prune_batch_size,
                 batch_wait_time: Duration::from_millis(self.prune_batch_wait_time_ms),
-                rpc_endpoint_url: self.blockchain_rpc_endpoint.clone(),
+                rpc_endpoint_urls: self.blockchain_rpc_endpoints(),
                 reward_address,
                 rate_limit_retries: self.rate_limit_retries,
                 timeout_retries: self.timeout_retries,
@@ -253,4 +307,22 @@ impl ZgsConfig {
     pub fn shard_config(&self) -> Result<ShardConfig, String> {
         self.shard_position.clone().try_into()
     }
+
+    /// Returns the blockchain RPC endpoints in the order of preference.
+    fn blockchain_rpc_endpoints(&self) -> Vec<String> {
+        let mut endpoints = vec![self.blockchain_rpc_endpoint.clone()];
+        endpoints.extend(self.blockchain_rpc_fallback_endpoints.iter().cloned());
+        endpoints
+    }
+
+    /// Returns the Flow contract address, which is optional in dev mode.
+    fn flow_address(&self) -> Result<ContractAddress, String> {
+        if self.dev_mode && self.log_contract_address.is_empty() {
+            return Ok(ContractAddress::zero());
+        }
+
+        self.log_contract_address
+            .parse::<ContractAddress>()
+            .map_err(|e| format!("Unable to parse log_contract_address: {:?}", e))
+    }
 }
@@ -32,11 +32,17 @@ build_config! {
     (discv5_disable_enr_network_id, (bool), false)
 
     // log sync
+    (dev_mode, (bool), false)
     (blockchain_rpc_endpoint, (String), "http://127.0.0.1:8545".to_string())
+    (blockchain_rpc_fallback_endpoints, (Vec<String>), vec![])
+    (blockchain_rpc_quorum, (usize), 1)
+    (blockchain_subscription_endpoint, (Option<String>), None)
     (log_contract_address, (String), "".to_string())
     (log_sync_start_block_number, (u64), 0)
     (force_log_sync_from_start_block_number, (bool), false)
+    (log_sync_archive_path, (Option<String>), None)
     (confirmation_block_count, (u64), 3)
+    (log_sync_finality_tag, (Option<String>), None)
     (log_page_size, (u64), 999)
     (max_cache_data_size, (usize), 100 * 1024 * 1024) // 100 MB
     (cache_tx_seq_ttl, (usize), 500)
@@ -60,7 +66,12 @@ build_config! {
 
     // db
     (db_dir, (String), "db".to_string())
+    (db_backend, (String), "rocksdb".to_string())
+    (db_segment_file_size, (u64), 256 * 1024 * 1024)
     (db_max_num_sectors, (Option<usize>), None)
+    (db_parity_enabled, (bool), false)
+    (db_parity_data_shards, (usize), 8)
+    (db_parity_shards, (usize), 2)
     (prune_check_time_s, (u64), 60)
     (prune_batch_size, (usize), 16 * 1024)
     (prune_batch_wait_time_ms, (u64), 1000)
@@ -76,6 +87,11 @@ build_config! {
     (miner_key, (Option<String>), None)
     (miner_cpu_percentage, (u64), 100)
     (mine_iter_batch_size, (usize), 100)
+    (miner_threads, (usize), 1)
+    (miner_cpu_affinity, (Vec<usize>), vec![])
+    (miner_hasher, (String), "auto".to_string())
+    (miner_skip_unprofitable, (bool), false)
+    (miner_seal_threads, (usize), 1)
     (reward_contract_address, (String), "".to_string())
     (shard_position, (Option<String>), None)
 
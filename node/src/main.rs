@@ -7,12 +7,25 @@ mod config;
 mod log;
 
 use crate::config::ZgsConfig;
+use clap::ArgMatches;
 use client::{Client, ClientBuilder, RuntimeContext};
+use ethereum_types::U256;
+use miner::simulator::{GeneratedLoader, SimulationReport, SimulatorConfig};
+use miner::PoraLoader;
 use std::error::Error;
+use std::sync::Arc;
+use std::time::Duration;
 
 async fn start_node(context: RuntimeContext, config: ZgsConfig) -> Result<Client, String> {
+    let builder = ClientBuilder::default()
+        .with_runtime_context(context)
+        .with_store(&config.storage_config()?)?;
+    build_node(builder, config).await
+}
+
+/// Starts the services of a node on the store of `builder`.
+async fn build_node(builder: ClientBuilder, config: ZgsConfig) -> Result<Client, String> {
     let network_config = config.network_config().await?;
-    let storage_config = config.storage_config()?;
     let log_sync_config = config.log_sync_config()?;
     let chunk_pool_config = config.chunk_pool_config()?;
     let miner_config = config.mine_config()?;
@@ -20,14 +33,12 @@ async fn start_node(context: RuntimeContext, config: ZgsConfig) -> Result<Client
     let pruner_config = config.pruner_config()?;
     let shard_config = config.shard_config()?;
 
-    ClientBuilder::default()
-        .with_runtime_context(context)
-        .with_rocksdb_store(&storage_config)?
+    builder
         .with_shard(shard_config)
         .await?
         .with_log_sync(log_sync_config)
         .await?
-        .with_file_location_cache(config.file_location_cache)
+        .with_file_location_cache(config.file_location_cache)?
         .with_network(network_config)
         .await?
         .with_chunk_pool(chunk_pool_config)
@@ -44,6 +55,55 @@ async fn start_node(context: RuntimeContext, config: ZgsConfig) -> Result<Client
         .build()
 }
 
+async fn simulate_mining(
+    context: RuntimeContext,
+    config: &ZgsConfig,
+    args: &ArgMatches,
+) -> Result<SimulationReport, String> {
+    let arg = |name| *args.get_one::<u64>(name).expect("defaulted by clap");
+
+    let (loader, flow_length): (Arc<dyn PoraLoader>, u64) = if args.get_flag("use-store") {
+        let store = ClientBuilder::default()
+            .with_runtime_context(context)
+            .with_store(&config.storage_config()?)?
+            .async_store()
+            .expect("initialized with runtime context");
+        let (_, flow_length) = store
+            .get_context()
+            .await
+            .map_err(|e| format!("Failed to get flow context: {:?}", e))?;
+        (store, flow_length)
+    } else {
+        let sealed_ratio = *args
+            .get_one::<f64>("sealed-ratio")
+            .expect("defaulted by clap");
+        let latency = Duration::from_millis(arg("load-latency-ms"));
+        (
+            Arc::new(GeneratedLoader::new(sealed_ratio, latency)),
+            arg("flow-length"),
+        )
+    };
+
+    let simulator_config = SimulatorConfig {
+        flow_length,
+        pora_target: U256::MAX / arg("difficulty"),
+        shard_config: config.shard_config()?,
+        engine_config: config.mine_engine_config(),
+        iter_batch: args
+            .get_one::<u64>("iter-batch")
+            .map_or(config.mine_iter_batch_size, |batch| *batch as usize),
+        cpu_percentage: config.miner_cpu_percentage,
+        duration: Duration::from_secs(arg("duration-secs")),
+        epoch_duration: Duration::from_secs(arg("epoch-secs")),
+    };
+    info!(
+        flow_length,
+        duration = ?simulator_config.duration,
+        "Simulating mining"
+    );
+    miner::simulator::simulate(&simulator_config, loader).await
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     // Only allow 64-bit targets for compilation, since there are many
     // type conversions between `usize` and `u64`, or even use `usize`
@@ -72,6 +132,86 @@ fn main() -> Result<(), Box<dyn Error>> {
         executor.clone(),
     );
 
+    // offline database maintenance
+    match matches.subcommand() {
+        Some((name @ ("export" | "import"), sub_matches)) => {
+            let storage_config = config.storage_config()?;
+            let file = sub_matches
+                .get_one::<String>("FILE")
+                .expect("required by clap");
+            let info = if name == "export" {
+                storage::snapshot::export_to_file(&storage_config, file)
+            } else {
+                storage::snapshot::import_from_file(&storage_config, file)
+            }
+            .map_err(|e| format!("Failed to {} snapshot: {:?}", name, e))?;
+            info!(
+                flow_root = ?info.flow_root,
+                flow_length = info.flow_length,
+                num_records = info.num_records,
+                "Snapshot {}ed",
+                name
+            );
+            return Ok(());
+        }
+        Some(("export-logs", sub_matches)) => {
+            let log_sync_config = config.log_sync_config()?;
+            let file = sub_matches
+                .get_one::<String>("FILE")
+                .expect("required by clap");
+            let info = environment
+                .runtime()
+                .block_on(log_entry_sync::export_log_archive(&log_sync_config, file))
+                .map_err(|e| format!("Failed to export log archive: {:?}", e))?;
+            info!(
+                start_block = info.start_block,
+                end_block = info.end_block,
+                end_block_hash = ?info.end_block_hash,
+                num_logs = info.num_logs,
+                "Log archive exported"
+            );
+            return Ok(());
+        }
+        Some(("mine-simulate", sub_matches)) => {
+            let report = environment.runtime().block_on(simulate_mining(
+                context.clone(),
+                &config,
+                sub_matches,
+            ))?;
+            info!(%report, "Mining simulated");
+            return Ok(());
+        }
+        Some(("fsck", sub_matches)) => {
+            let storage_config = config.storage_config()?;
+            let repair = sub_matches.get_flag("repair");
+            let report = storage::log_store::fsck::fsck_db(&storage_config, repair)
+                .map_err(|e| format!("Failed to check db: {:?}", e))?;
+            for (seq, reason) in &report.bad_txs {
+                warn!(seq, %reason, "Corrupted transaction");
+            }
+            for (index, reason) in &report.bad_batches {
+                warn!(index, %reason, "Corrupted entry batch");
+            }
+            for (layer, position) in &report.bad_nodes {
+                warn!(layer, position, "Corrupted merkle node");
+            }
+            info!(
+                num_txs = report.num_txs,
+                num_batches = report.num_batches,
+                num_nodes = report.num_nodes,
+                clean = report.is_clean(),
+                recovered_batches = ?report.recovered_batches,
+                unfinalized_txs = ?report.unfinalized_txs,
+                "Db checked"
+            );
+            if !report.is_clean() && !repair {
+                return Err("db is corrupted, run with --repair to fix it".into());
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+
     // start services
     executor.clone().spawn(
         async move {
@@ -107,3 +247,137 @@ fn main() -> Result<(), Box<dyn Error>> {
         task_executor::ShutdownReason::Failure(msg) => Err(msg.to_string().into()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RawConfiguration;
+    use ethereum_types::H256;
+    use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
+    use rpc::types::{SegmentWithProof, Submission, SubmissionNode};
+    use rpc::{ZgsDevRpcClient, ZgsMinerRpcClient, ZgsRPCClient};
+    use shared_types::TxSeqOrRoot;
+    use std::net::{SocketAddr, TcpListener};
+    use std::time::Instant;
+    use storage::log_store::log_manager::tx_subtree_root_list_padded;
+
+    const TIMEOUT: Duration = Duration::from_secs(120);
+
+    fn unused_addr() -> SocketAddr {
+        TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap()
+    }
+
+    fn rpc_client(addr: SocketAddr) -> HttpClient {
+        HttpClientBuilder::default()
+            .build(format!("http://{}", addr))
+            .unwrap()
+    }
+
+    /// Polls `f` until it returns `Some`, or panics on timeout.
+    async fn wait_for<T, F, Fut>(what: &str, mut f: F) -> T
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Option<T>>,
+    {
+        let timer = Instant::now();
+        loop {
+            if let Some(value) = f().await {
+                return value;
+            }
+            assert!(timer.elapsed() < TIMEOUT, "timeout to wait for {}", what);
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    }
+
+    #[test]
+    fn test_dev_mode_node() {
+        let network_dir = tempfile::tempdir().unwrap();
+        let mut config = ZgsConfig {
+            raw_conf: RawConfiguration {
+                dev_mode: true,
+                network_dir: network_dir.path().to_str().unwrap().to_string(),
+                network_listen_address: "127.0.0.1".to_string(),
+                network_libp2p_port: unused_addr().port(),
+                network_disable_discovery: true,
+                miner_key: Some(format!("{:?}", H256::repeat_byte(1))),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        config.rpc.listen_address = unused_addr();
+        config.rpc.listen_address_admin = unused_addr();
+        let public = rpc_client(config.rpc.listen_address);
+        let admin = rpc_client(config.rpc.listen_address_admin);
+
+        let mut environment = client::EnvironmentBuilder::new()
+            .multi_threaded_tokio_runtime()
+            .unwrap()
+            .build()
+            .unwrap();
+        let context = environment.core_context();
+        environment.runtime().block_on(async {
+            let builder = ClientBuilder::default()
+                .with_runtime_context(context)
+                .with_memory_store()
+                .unwrap();
+            let _client = build_node(builder, config).await.unwrap();
+
+            // a file of one segment
+            let data: Vec<u8> = (0..256 * 1024).map(|i| (i % 251) as u8).collect();
+            let submission = || Submission {
+                length: data.len() as u64,
+                nodes: tx_subtree_root_list_padded(&data)
+                    .into_iter()
+                    .map(|(depth, root)| SubmissionNode {
+                        root,
+                        height: depth - 1,
+                    })
+                    .collect(),
+            };
+
+            // dev RPCs are only served along with admin RPCs
+            assert!(ZgsDevRpcClient::submit(&public, submission())
+                .await
+                .is_err());
+            let tx = ZgsDevRpcClient::submit(&admin, submission()).await.unwrap();
+            assert_eq!(tx.seq, 0);
+
+            wait_for("tx synced", || async {
+                public
+                    .get_file_info_by_tx_seq(tx.seq)
+                    .await
+                    .unwrap()
+                    .map(|_| ())
+            })
+            .await;
+
+            let (root, segments) = SegmentWithProof::split_file(&data, 1024).unwrap();
+            assert_eq!(root, tx.data_merkle_root);
+            public.upload_segments(segments).await.unwrap();
+            wait_for("file finalized", || async {
+                public
+                    .check_file_finalized(TxSeqOrRoot::TxSeq(tx.seq))
+                    .await
+                    .unwrap()
+                    .filter(|finalized| *finalized)
+            })
+            .await;
+
+            // the stub mine context is made from the flow in store
+            let (_, flow_length) = public.get_flow_context().await.unwrap();
+            let status = wait_for("mine answer", || async {
+                let status = ZgsMinerRpcClient::get_status(&admin).await.unwrap();
+                (!status.last_answers.is_empty()).then_some(status)
+            })
+            .await;
+            let puzzle = status.puzzle.expect("puzzle is reported with answers");
+            assert_eq!(puzzle.flow_length, flow_length.into());
+            assert!(status.sealing.sealed_seals > 0);
+        });
+
+        environment.fire_signal();
+        environment.shutdown_on_idle();
+    }
+}
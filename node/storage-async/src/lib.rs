@@ -3,7 +3,8 @@ extern crate tracing;
 
 use anyhow::bail;
 use shared_types::{
-    Chunk, ChunkArray, ChunkArrayWithProof, DataRoot, FlowProof, FlowRangeProof, Transaction,
+    bytes_to_chunks, Chunk, ChunkArray, ChunkArrayWithProof, DataRoot, FlowProof, FlowRangeProof,
+    Transaction, TxSeqOrRoot,
 };
 use ssz::{Decode, Encode};
 use std::sync::Arc;
@@ -12,7 +13,8 @@ use task_executor::TaskExecutor;
 use tokio::sync::oneshot;
 
 pub use storage::config::ShardConfig;
-use storage::log_store::config::ConfigurableExt;
+use storage::log_store::config::{ConfigTx, ConfigurableExt};
+use storage::log_store::tx_store::TxStatus;
 use storage::log_store::{MineLoadChunk, SealAnswer, SealTask};
 
 /// The name of the worker tokio tasks.
@@ -58,6 +60,11 @@ impl Store {
     delegate!(fn finalize_tx_with_hash(tx_seq: u64, tx_hash: H256) -> Result<bool>);
     delegate!(fn get_proof_at_root(root: Option<DataRoot>, index: u64, length: u64) -> Result<FlowRangeProof>);
     delegate!(fn get_context() -> Result<(DataRoot, u64)>);
+    delegate!(fn get_num_seal_tasks() -> Result<u64>);
+    delegate!(fn get_num_sealed_seals() -> Result<u64>);
+    delegate!(fn get_shard_parity(tx_seq: u64, stripe_index: u64) -> Result<Option<Vec<u8>>>);
+    delegate!(fn get_available_entry_ranges(index_start: u64, index_end: u64) -> Result<Vec<(u64, u64)>>);
+    delegate!(fn put_shard_parity(tx_seq: u64, stripe_index: u64, parity: Vec<u8>) -> Result<()>);
 
     pub async fn get_tx_seq_by_data_root(
         &self,
@@ -79,6 +86,45 @@ impl Store {
             .await
     }
 
+    /// Returns the transaction along with its status and available flow entry ranges for each of
+    /// the specified tx seq or data root in a single storage task, or `None` if the transaction
+    /// not found.
+    ///
+    /// If `need_available` is false, data roots are resolved to the first transaction even if it
+    /// is not finalized, and the available entry ranges are not computed.
+    #[allow(clippy::type_complexity)]
+    pub async fn get_txs_with_status(
+        &self,
+        ids: Vec<TxSeqOrRoot>,
+        need_available: bool,
+    ) -> Result<Vec<Option<(Transaction, Option<TxStatus>, Vec<(u64, u64)>)>>> {
+        self.spawn(move |store| {
+            let mut result = Vec::with_capacity(ids.len());
+
+            for id in ids {
+                let maybe_tx = match id {
+                    TxSeqOrRoot::TxSeq(seq) => store.get_tx_by_seq_number(seq)?,
+                    TxSeqOrRoot::Root(root) => store.get_tx_by_data_root(&root, need_available)?,
+                };
+
+                result.push(match maybe_tx {
+                    Some(tx) => {
+                        let status = store.get_tx_status(tx.seq)?;
+                        let entry_ranges = match need_available {
+                            true => available_entry_ranges(store, &tx, &status)?,
+                            false => vec![],
+                        };
+                        Some((tx, status, entry_ranges))
+                    }
+                    None => None,
+                });
+            }
+
+            Ok(result)
+        })
+        .await
+    }
+
     pub async fn get_config_decoded<K: AsRef<[u8]> + Send + Sync, T: Decode + Send + 'static>(
         &self,
         key: &K,
@@ -103,11 +149,17 @@ impl Store {
             .await
     }
 
+    pub async fn exec_configs(&self, tx: ConfigTx, dest: &str) -> anyhow::Result<()> {
+        let dest = dest.to_string();
+        self.spawn(move |store| store.exec_configs(tx, &dest)).await
+    }
+
     pub async fn pull_seal_chunk(
         &self,
         seal_index_max: usize,
+        max_seals: usize,
     ) -> anyhow::Result<Option<Vec<SealTask>>> {
-        self.spawn(move |store| store.pull_seal_chunk(seal_index_max))
+        self.spawn(move |store| store.pull_seal_chunk(seal_index_max, max_seals))
             .await
     }
 
@@ -169,3 +221,22 @@ impl Store {
         self.store.as_ref()
     }
 }
+
+/// Returns the flow entry ranges of the transaction that are stored locally, which are ordered
+/// and not overlapped.
+fn available_entry_ranges(
+    store: &dyn LogStore,
+    tx: &Transaction,
+    status: &Option<TxStatus>,
+) -> Result<Vec<(u64, u64)>> {
+    let start = tx.start_entry_index;
+    let end = start + bytes_to_chunks(tx.size as usize) as u64;
+
+    match status {
+        // all data within shard is stored once finalized
+        Some(TxStatus::Finalized) => Ok(vec![(start, end)]),
+        Some(TxStatus::Pruned) => Ok(vec![]),
+        None if start == end => Ok(vec![]),
+        None => store.get_available_entry_ranges(start, end),
+    }
+}
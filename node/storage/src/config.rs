@@ -1,4 +1,5 @@
 use crate::log_store::log_manager::LogConfig;
+use crate::segment_db::SegmentConfig;
 use serde::{Deserialize, Serialize};
 use ssz_derive::{Decode, Encode};
 use std::{cell::RefCell, path::PathBuf, rc::Rc, str::FromStr};
@@ -9,6 +10,38 @@ pub const SHARD_CONFIG_KEY: &str = "shard_config";
 pub struct Config {
     pub db_dir: PathBuf,
     pub log_config: LogConfig,
+    pub backend: DbBackend,
+    pub segment_config: SegmentConfig,
+}
+
+/// The engine used to persist the log store.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DbBackend {
+    /// All columns are stored in RocksDB.
+    #[default]
+    RocksDB,
+    /// All columns are kept in memory, which is only for test purpose.
+    Memory,
+    /// Entry batches are appended to segment files, and the other columns are
+    /// stored in RocksDB.
+    Segment,
+}
+
+impl FromStr for DbBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "rocksdb" => Ok(Self::RocksDB),
+            "memory" => Ok(Self::Memory),
+            "segment" => Ok(Self::Segment),
+            _ => Err(format!(
+                "Unknown db backend {:?}, expected one of rocksdb, memory, segment",
+                s
+            )),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Decode, Encode, Serialize, Deserialize, Eq, PartialEq)]
@@ -3,6 +3,8 @@ use kvdb::KeyValueDB;
 pub mod config;
 pub mod error;
 pub mod log_store;
+pub mod segment_db;
+pub mod snapshot;
 
 pub use config::Config as StorageConfig;
 pub use log_store::log_manager::LogManager;
@@ -48,7 +50,12 @@ impl ZgsKeyValueDB for Database {
 }
 
 impl ZgsKeyValueDB for InMemory {
-    fn num_keys(&self, _col: u32) -> std::io::Result<u64> {
-        todo!("not used")
+    fn num_keys(&self, col: u32) -> std::io::Result<u64> {
+        let mut num_keys = 0;
+        for kv in self.iter(col) {
+            kv?;
+            num_keys += 1;
+        }
+        Ok(num_keys)
     }
 }
@@ -2,9 +2,10 @@ use crate::config::ShardConfig;
 use crate::error::Error;
 use crate::log_store::load_chunk::EntryBatch;
 use crate::log_store::log_manager::{
-    bytes_to_entries, COL_ENTRY_BATCH, COL_FLOW_MPT_NODES, COL_PAD_DATA_LIST,
-    COL_PAD_DATA_SYNC_HEIGH, PORA_CHUNK_SIZE,
+    bytes_to_entries, COL_ENTRY_BATCH, COL_FLOW_MPT_NODES, COL_MISC, COL_PAD_DATA_LIST,
+    COL_PAD_DATA_SYNC_HEIGH, COL_SEAL_TASK, PORA_CHUNK_SIZE,
 };
+use crate::log_store::parity::{EntryBatchParity, ParityConfig};
 use crate::log_store::seal_task_manager::SealTaskManager;
 use crate::log_store::{
     metrics, FlowRead, FlowSeal, FlowWrite, MineLoadChunk, SealAnswer, SealTask,
@@ -12,7 +13,7 @@ use crate::log_store::{
 use crate::{try_option, ZgsKeyValueDB};
 use any::Any;
 use anyhow::{anyhow, bail, Result};
-use append_merkle::{MerkleTreeRead, NodeDatabase, NodeTransaction};
+use append_merkle::{HashElement, MerkleTreeRead, NodeDatabase, NodeTransaction};
 use itertools::Itertools;
 use kvdb::DBTransaction;
 use parking_lot::RwLock;
@@ -24,7 +25,7 @@ use std::fmt::Debug;
 use std::sync::Arc;
 use std::time::Instant;
 use std::{any, cmp};
-use tracing::{debug, error, trace};
+use tracing::{debug, error, info, trace, warn};
 use zgs_spec::{BYTES_PER_SECTOR, SEALS_PER_LOAD, SECTORS_PER_LOAD, SECTORS_PER_SEAL};
 
 pub struct FlowStore {
@@ -35,13 +36,19 @@ pub struct FlowStore {
 }
 
 impl FlowStore {
-    pub fn new(flow_db: Arc<FlowDBStore>, data_db: Arc<FlowDBStore>, config: FlowConfig) -> Self {
-        Self {
+    pub fn new(
+        flow_db: Arc<FlowDBStore>,
+        data_db: Arc<FlowDBStore>,
+        config: FlowConfig,
+    ) -> Result<Self> {
+        let (seal_tasks, num_sealed) = data_db.get_seal_state()?;
+        let seal_manager = SealTaskManager::with_tasks(seal_tasks, num_sealed);
+        Ok(Self {
             flow_db,
             data_db,
-            seal_manager: Default::default(),
+            seal_manager,
             config,
-        }
+        })
     }
 
     pub fn insert_subtree_list_for_batch(
@@ -56,7 +63,7 @@ impl FlowStore {
             .unwrap_or_else(|| EntryBatch::new(batch_index as u64));
         batch.set_subtree_list(subtree_list);
         self.data_db
-            .put_entry_raw(vec![(batch_index as u64, batch)])?;
+            .put_entry_raw(DBTransaction::new(), vec![(batch_index as u64, batch)])?;
         metrics::INSERT_SUBTREE_LIST.update_since(start_time);
         Ok(())
     }
@@ -66,26 +73,65 @@ impl FlowStore {
             .data_db
             .get_entry_batch(batch_index as u64)?
             .ok_or_else(|| anyhow!("batch missing, index={}", batch_index))?;
-        let merkle = batch.to_merkle_tree(batch_index == 0)?.ok_or_else(|| {
+        let mut merkle = batch.to_merkle_tree(batch_index == 0)?.ok_or_else(|| {
             anyhow!(
                 "batch data incomplete for building a merkle tree, index={}",
                 batch_index
             )
         })?;
+        if self.data_db.has_parity() && !self.check_batch_root(batch_index, &merkle.root())? {
+            warn!(batch_index, "Entry batch mismatches the flow merkle tree");
+            if let Some(recovered) = self
+                .data_db
+                .recover_entry_batch(batch_index as u64)?
+                .and_then(|batch| batch.to_merkle_tree(batch_index == 0).ok().flatten())
+            {
+                merkle = recovered;
+            }
+        }
         merkle.gen_proof(sector_index)
     }
 
+    /// Whether the root of a complete batch matches the leaf of the flow merkle tree.
+    fn check_batch_root(&self, batch_index: usize, root: &DataRoot) -> Result<bool> {
+        if batch_index >= self.flow_db.get_layer_size(0)?.unwrap_or(0) {
+            return Ok(true);
+        }
+        Ok(match self.flow_db.get_node(0, batch_index)? {
+            Some(leaf) => leaf.is_null() || leaf == *root,
+            None => true,
+        })
+    }
+
     pub fn delete_batch_list(&self, batch_list: &[u64]) -> Result<()> {
-        self.seal_manager.delete_batch_list(batch_list);
-        self.data_db.delete_batch_list(batch_list)
+        let mut to_seal_set = self.seal_manager.to_seal_set.write();
+        let seal_indices = batch_list.iter().flat_map(|&batch_index| {
+            batch_index as usize * SEALS_PER_LOAD..(batch_index as usize + 1) * SEALS_PER_LOAD
+        });
+        let mut tx = DBTransaction::new();
+        delete_seal_tasks(&mut tx, seal_indices.clone());
+        let num_sealed =
+            self.data_db
+                .delete_batch_list(tx, batch_list, self.seal_manager.num_sealed())?;
+
+        self.seal_manager.set_num_sealed(num_sealed);
+        for seal_index in seal_indices {
+            to_seal_set.remove(&seal_index);
+        }
+        Ok(())
     }
 }
 
+/// The number of sealed seals in the data db, in `COL_MISC`.
+pub(crate) const NUM_SEALED_KEY: &[u8] = b"num_sealed_seals";
+
 #[derive(Clone, Debug)]
 pub struct FlowConfig {
     pub batch_size: usize,
     pub merkle_node_cache_capacity: usize,
     pub shard_config: Arc<RwLock<ShardConfig>>,
+    /// Reed-Solomon parity of the entry batches, which is disabled if `None`.
+    pub parity: Option<ParityConfig>,
 }
 
 impl Default for FlowConfig {
@@ -95,6 +141,7 @@ impl Default for FlowConfig {
             // Each node takes (8+8+32=)48 Bytes, so the default value is 1.5 GB memory size.
             merkle_node_cache_capacity: 32 * 1024 * 1024,
             shard_config: Default::default(),
+            parity: None,
         }
     }
 }
@@ -176,6 +223,43 @@ impl FlowRead for FlowStore {
         Ok(entry_list)
     }
 
+    fn get_available_ranges(&self, index_start: u64, index_end: u64) -> Result<Vec<(u64, u64)>> {
+        if index_end <= index_start {
+            bail!(
+                "invalid entry index: start={} end={}",
+                index_start,
+                index_end
+            );
+        }
+        let mut ranges: Vec<(u64, u64)> = Vec::new();
+        for (start_entry_index, end_entry_index) in
+            batch_iter(index_start, index_end, self.config.batch_size)
+        {
+            let chunk_index = start_entry_index / self.config.batch_size as u64;
+            let batch_start = chunk_index * self.config.batch_size as u64;
+
+            let batch = match self.data_db.get_entry_batch(chunk_index)? {
+                Some(batch) => batch,
+                None => continue,
+            };
+            for (start_sector, length_sector) in batch.available_range_entries() {
+                let start = cmp::max(batch_start + start_sector as u64, start_entry_index);
+                let end = cmp::min(
+                    batch_start + (start_sector + length_sector) as u64,
+                    end_entry_index,
+                );
+                if start >= end {
+                    continue;
+                }
+                match ranges.last_mut() {
+                    Some(last) if last.1 == start => last.1 = end,
+                    _ => ranges.push((start, end)),
+                }
+            }
+        }
+        Ok(ranges)
+    }
+
     fn load_sealed_data(&self, chunk_index: u64) -> Result<Option<MineLoadChunk>> {
         let batch = try_option!(self.data_db.get_entry_batch(chunk_index)?);
         let mut mine_chunk = MineLoadChunk::default();
@@ -226,6 +310,7 @@ impl FlowWrite for FlowStore {
             bail!("append_entries: invalid data size, len={}", data.data.len());
         }
         let mut batch_list = Vec::new();
+        let mut new_seal_tasks = Vec::new();
         for (start_entry_index, end_entry_index) in batch_iter(
             data.start_index,
             data.start_index + bytes_to_entries(data.data.len() as u64),
@@ -252,28 +337,45 @@ impl FlowWrite for FlowStore {
                 chunk.data,
             )?;
             if self.seal_manager.seal_worker_available() {
-                completed_seals.into_iter().for_each(|x| {
-                    to_seal_set.insert(
-                        chunk_index as usize * SEALS_PER_LOAD + x as usize,
-                        self.seal_manager.to_seal_version(),
-                    );
-                });
+                new_seal_tasks.extend(
+                    completed_seals
+                        .into_iter()
+                        .map(|x| chunk_index as usize * SEALS_PER_LOAD + x as usize),
+                );
             }
 
             batch_list.push((chunk_index, batch));
         }
 
         metrics::APPEND_ENTRIES.update_since(start_time);
-        self.data_db.put_entry_batch_list(batch_list)
+        // The seal tasks are persisted in the same transaction as their data.
+        let mut tx = DBTransaction::new();
+        put_seal_tasks(&mut tx, new_seal_tasks.iter().copied());
+        let completed_batches = self.data_db.put_entry_batch_list(tx, batch_list)?;
+        for seal_index in new_seal_tasks {
+            to_seal_set.insert(seal_index, self.seal_manager.to_seal_version());
+        }
+        Ok(completed_batches)
     }
 
     fn truncate(&self, start_index: u64) -> crate::error::Result<()> {
         let mut to_seal_set = self.seal_manager.to_seal_set.write();
-        let to_reseal = self.data_db.truncate(start_index, self.config.batch_size)?;
+        let first_truncated_seal = start_index as usize / SECTORS_PER_SEAL;
+        let mut tx = DBTransaction::new();
+        delete_seal_tasks(
+            &mut tx,
+            to_seal_set.range(first_truncated_seal..).map(|(&x, _)| x),
+        );
+        let (to_reseal, num_sealed) = self.data_db.truncate(
+            tx,
+            start_index,
+            self.config.batch_size,
+            self.seal_manager.num_sealed(),
+        )?;
 
-        to_seal_set.split_off(&(start_index as usize / SECTORS_PER_SEAL));
+        to_seal_set.split_off(&first_truncated_seal);
+        self.seal_manager.set_num_sealed(num_sealed);
         let new_seal_version = self.seal_manager.inc_seal_version();
-
         to_reseal.into_iter().for_each(|x| {
             to_seal_set.insert(x, new_seal_version);
         });
@@ -294,28 +396,32 @@ impl FlowWrite for FlowStore {
 }
 
 impl FlowSeal for FlowStore {
-    fn pull_seal_chunk(&self, seal_index_max: usize) -> Result<Option<Vec<SealTask>>> {
+    fn pull_seal_chunk(
+        &self,
+        seal_index_max: usize,
+        max_seals: usize,
+    ) -> Result<Option<Vec<SealTask>>> {
         let to_seal_set = self.seal_manager.to_seal_set.read();
         self.seal_manager.update_pull_time();
 
-        let mut to_seal_iter = to_seal_set.iter();
-        let (&first_index, &first_version) = try_option!(to_seal_iter.next());
-        if first_index >= seal_index_max {
-            return Ok(None);
-        }
-
         let mut tasks = Vec::with_capacity(SEALS_PER_LOAD);
+        let mut batch: Option<(usize, EntryBatch)> = None;
+
+        for (&seal_index, &version) in to_seal_set.range(..seal_index_max) {
+            let load_index = seal_index / SEALS_PER_LOAD;
+            // Pull the whole load chunks, whose seals share the same entry batch.
+            if batch.as_ref().map(|(index, _)| *index) != Some(load_index) {
+                if tasks.len() >= max_seals {
+                    break;
+                }
+                let batch_data = self
+                    .data_db
+                    .get_entry_batch(load_index as u64)?
+                    .expect("Lost data chunk in to_seal_set");
+                batch = Some((load_index, batch_data));
+            }
+            let (_, batch_data) = batch.as_ref().expect("loaded above");
 
-        let batch_data = self
-            .data_db
-            .get_entry_batch((first_index / SEALS_PER_LOAD) as u64)?
-            .expect("Lost data chunk in to_seal_set");
-
-        for (&seal_index, &version) in
-            std::iter::once((&first_index, &first_version)).chain(to_seal_iter.filter(|(&x, _)| {
-                first_index / SEALS_PER_LOAD == x / SEALS_PER_LOAD && x < seal_index_max
-            }))
-        {
             let seal_index_local = seal_index % SEALS_PER_LOAD;
             let non_sealed_data = batch_data
                 .get_non_sealed_data(seal_index_local as u16)
@@ -327,9 +433,20 @@ impl FlowSeal for FlowStore {
             })
         }
 
+        if tasks.is_empty() {
+            return Ok(None);
+        }
         Ok(Some(tasks))
     }
 
+    fn get_num_seal_tasks(&self) -> u64 {
+        self.seal_manager.to_seal_set.read().len() as u64
+    }
+
+    fn get_num_sealed_seals(&self) -> u64 {
+        self.seal_manager.num_sealed()
+    }
+
     fn submit_seal_result(&self, answers: Vec<SealAnswer>) -> Result<()> {
         let mut to_seal_set = self.seal_manager.to_seal_set.write();
         let is_consistent = |answer: &SealAnswer| {
@@ -358,12 +475,17 @@ impl FlowSeal for FlowStore {
 
         debug!("Seal chunks: indices = {:?}", removed_seal_index);
 
+        // The seal tasks are removed in the same transaction as the sealed data is written.
+        let num_sealed = self.seal_manager.num_sealed() + removed_seal_index.len() as u64;
+        let mut tx = DBTransaction::new();
+        delete_seal_tasks(&mut tx, removed_seal_index.iter().copied());
+        put_num_sealed(&mut tx, num_sealed);
+        self.data_db.put_entry_raw(tx, updated_chunk)?;
+
+        self.seal_manager.set_num_sealed(num_sealed);
         for idx in removed_seal_index.into_iter() {
             to_seal_set.remove(&idx);
         }
-
-        self.data_db.put_entry_raw(updated_chunk)?;
-
         Ok(())
     }
 }
@@ -376,74 +498,133 @@ pub struct PadPair {
 
 pub struct FlowDBStore {
     kvdb: Arc<dyn ZgsKeyValueDB>,
+    parity: Option<EntryBatchParity>,
 }
 
 impl FlowDBStore {
     pub fn new(kvdb: Arc<dyn ZgsKeyValueDB>) -> Self {
-        Self { kvdb }
+        Self { kvdb, parity: None }
+    }
+
+    /// Protects the entry batches with Reed-Solomon parity, which is grouped within the
+    /// local shard of `shard_config`.
+    pub fn with_parity(
+        kvdb: Arc<dyn ZgsKeyValueDB>,
+        config: ParityConfig,
+        shard_config: Arc<RwLock<ShardConfig>>,
+    ) -> Result<Self> {
+        Ok(Self {
+            kvdb,
+            parity: Some(EntryBatchParity::new(config, shard_config)?),
+        })
+    }
+
+    pub fn has_parity(&self) -> bool {
+        self.parity.is_some()
     }
 
+    /// Writes the entry batches, together with the other updates in `tx`.
     fn put_entry_batch_list(
         &self,
+        tx: DBTransaction,
         batch_list: Vec<(u64, EntryBatch)>,
     ) -> Result<Vec<(u64, DataRoot)>> {
         let start_time = Instant::now();
         let mut completed_batches = Vec::new();
-        let mut tx = self.kvdb.transaction();
+        let mut raw_batch_list = Vec::with_capacity(batch_list.len());
         for (batch_index, batch) in batch_list {
-            tx.put(
-                COL_ENTRY_BATCH,
-                &batch_index.to_be_bytes(),
-                &batch.as_ssz_bytes(),
-            );
             if let Some(root) = batch.build_root(batch_index == 0)? {
                 trace!("complete batch: index={}", batch_index);
                 completed_batches.push((batch_index, root));
             }
+            raw_batch_list.push((batch_index, Some(batch.as_ssz_bytes())));
         }
-        self.kvdb.write(tx)?;
+        self.write_entry_batches(tx, raw_batch_list)?;
         metrics::PUT_ENTRY_BATCH_LIST.update_since(start_time);
         Ok(completed_batches)
     }
 
-    fn put_entry_raw(&self, batch_list: Vec<(u64, EntryBatch)>) -> Result<()> {
-        let mut tx = self.kvdb.transaction();
-        for (batch_index, batch) in batch_list {
-            tx.put(
-                COL_ENTRY_BATCH,
-                &batch_index.to_be_bytes(),
-                &batch.as_ssz_bytes(),
-            );
+    fn put_entry_raw(&self, tx: DBTransaction, batch_list: Vec<(u64, EntryBatch)>) -> Result<()> {
+        self.write_entry_batches(
+            tx,
+            batch_list
+                .into_iter()
+                .map(|(batch_index, batch)| (batch_index, Some(batch.as_ssz_bytes())))
+                .collect(),
+        )
+    }
+
+    /// Writes the raw entry batches, where `None` deletes the batch, in one transaction with
+    /// the other updates in `tx`.
+    fn write_entry_batches(
+        &self,
+        mut tx: DBTransaction,
+        batch_list: Vec<(u64, Option<Vec<u8>>)>,
+    ) -> Result<()> {
+        if let Some(parity) = &self.parity {
+            return parity.write(self.kvdb.as_ref(), tx, batch_list);
+        }
+        for (batch_index, value) in batch_list {
+            match value {
+                Some(value) => tx.put_vec(COL_ENTRY_BATCH, &batch_index.to_be_bytes(), value),
+                None => tx.delete(COL_ENTRY_BATCH, &batch_index.to_be_bytes()),
+            }
         }
         self.kvdb.write(tx)?;
         Ok(())
     }
 
+    /// Return the entry batch, which is reconstructed from the parity if it is lost,
+    /// corrupted or cannot be decoded.
     fn get_entry_batch(&self, batch_index: u64) -> Result<Option<EntryBatch>> {
-        let raw = try_option!(self.kvdb.get(COL_ENTRY_BATCH, &batch_index.to_be_bytes())?);
-        Ok(Some(EntryBatch::from_ssz_bytes(&raw).map_err(Error::from)?))
+        let raw = try_option!(match &self.parity {
+            Some(parity) => parity.get(self.kvdb.as_ref(), batch_index)?,
+            None => self.kvdb.get(COL_ENTRY_BATCH, &batch_index.to_be_bytes())?,
+        });
+        match EntryBatch::from_ssz_bytes(&raw) {
+            Ok(batch) => Ok(Some(batch)),
+            Err(e) => match self.recover_entry_batch(batch_index)? {
+                Some(batch) => Ok(Some(batch)),
+                None => Err(Error::from(e).into()),
+            },
+        }
     }
 
-    fn truncate(&self, start_index: u64, batch_size: usize) -> crate::error::Result<Vec<usize>> {
-        let mut tx = self.kvdb.transaction();
+    /// Reconstruct the entry batch from the parity regardless of the stored value, and
+    /// return it if it differs from the stored one.
+    pub(crate) fn recover_entry_batch(&self, batch_index: u64) -> Result<Option<EntryBatch>> {
+        let parity = try_option!(&self.parity);
+        let raw = try_option!(parity.recover(self.kvdb.as_ref(), batch_index)?);
+        Ok(EntryBatch::from_ssz_bytes(&raw).ok())
+    }
+
+    /// Truncates the entry batches from `start_index`, together with the other updates in `tx`.
+    /// Return the seals to reseal, which are persisted as seal tasks, and the number of sealed
+    /// seals left from `num_sealed`.
+    fn truncate(
+        &self,
+        mut tx: DBTransaction,
+        start_index: u64,
+        batch_size: usize,
+        mut num_sealed: u64,
+    ) -> crate::error::Result<(Vec<usize>, u64)> {
+        let mut batch_list = Vec::new();
         let mut start_batch_index = start_index / batch_size as u64;
         let first_batch_offset = start_index as usize % batch_size;
         let mut index_to_reseal = Vec::new();
         if first_batch_offset != 0 {
             if let Some(mut first_batch) = self.get_entry_batch(start_batch_index)? {
+                num_sealed = num_sealed.saturating_sub(first_batch.num_sealed() as u64);
                 index_to_reseal = first_batch
                     .truncate(first_batch_offset)
                     .into_iter()
                     .map(|x| start_batch_index as usize * SEALS_PER_LOAD + x as usize)
                     .collect();
+                num_sealed += first_batch.num_sealed() as u64;
                 if !first_batch.is_empty() {
-                    tx.put(
-                        COL_ENTRY_BATCH,
-                        &start_batch_index.to_be_bytes(),
-                        &first_batch.as_ssz_bytes(),
-                    );
+                    batch_list.push((start_batch_index, Some(first_batch.as_ssz_bytes())));
                 } else {
-                    tx.delete(COL_ENTRY_BATCH, &start_batch_index.to_be_bytes());
+                    batch_list.push((start_batch_index, None));
                 }
             }
 
@@ -452,29 +633,105 @@ impl FlowDBStore {
         // TODO: `kvdb` and `kvdb-rocksdb` does not support `seek_to_last` yet.
         // We'll need to fork it or use another wrapper for a better performance in this.
         let end = match self.kvdb.iter(COL_ENTRY_BATCH).last() {
-            Some(Ok((k, _))) => decode_batch_index(k.as_ref())?,
+            Some(Ok((k, _))) => Some(decode_batch_index(k.as_ref())? as u64),
             Some(Err(e)) => {
                 error!("truncate db error: e={:?}", e);
                 return Err(e.into());
             }
-            None => {
-                // The db has no data, so we can just return;
-                return Ok(index_to_reseal);
-            }
+            // The db has no data.
+            None => None,
         };
-        for batch_index in start_batch_index as usize..=end {
-            tx.delete(COL_ENTRY_BATCH, &batch_index.to_be_bytes());
+        if let Some(end) = end {
+            for batch_index in start_batch_index..=end {
+                num_sealed = num_sealed.saturating_sub(self.num_sealed_in_batch(batch_index)?);
+                batch_list.push((batch_index, None));
+            }
+        }
+        put_seal_tasks(&mut tx, index_to_reseal.iter().copied());
+        put_num_sealed(&mut tx, num_sealed);
+        self.write_entry_batches(tx, batch_list)?;
+        Ok((index_to_reseal, num_sealed))
+    }
+
+    /// Deletes the entry batches, together with the other updates in `tx`. Return the number
+    /// of sealed seals left from `num_sealed`.
+    pub(crate) fn delete_batch_list(
+        &self,
+        mut tx: DBTransaction,
+        batch_list: &[u64],
+        mut num_sealed: u64,
+    ) -> Result<u64> {
+        for &batch_index in batch_list {
+            num_sealed = num_sealed.saturating_sub(self.num_sealed_in_batch(batch_index)?);
+        }
+        put_num_sealed(&mut tx, num_sealed);
+        self.write_entry_batches(tx, batch_list.iter().map(|&i| (i, None)).collect())?;
+        Ok(num_sealed)
+    }
+
+    fn num_sealed_in_batch(&self, batch_index: u64) -> Result<u64> {
+        Ok(self
+            .get_entry_batch(batch_index)?
+            .map_or(0, |batch| batch.num_sealed() as u64))
+    }
+
+    /// Return the seal tasks and the number of sealed seals. A db created before they are
+    /// persisted has no number of sealed seals, so they are rebuilt from the entry batches once.
+    fn get_seal_state(&self) -> Result<(Vec<usize>, u64)> {
+        match self.kvdb.get(COL_MISC, NUM_SEALED_KEY)? {
+            Some(value) => {
+                let num_sealed = u64::from_be_bytes(
+                    value
+                        .as_slice()
+                        .try_into()
+                        .map_err(|e| anyhow!("{:?}", e))?,
+                );
+                Ok((self.get_seal_tasks()?, num_sealed))
+            }
+            None => self.rebuild_seal_state(),
+        }
+    }
+
+    /// Scans the entry batches to persist the seals with complete data as seal tasks, and the
+    /// number of sealed seals.
+    fn rebuild_seal_state(&self) -> Result<(Vec<usize>, u64)> {
+        info!("Rebuild seal tasks from the entry batches");
+        let mut seal_tasks = Vec::new();
+        let mut num_sealed = 0;
+        for kv in self.kvdb.iter(COL_ENTRY_BATCH) {
+            let (key, _) = kv?;
+            let batch_index = decode_batch_index(key.as_ref())?;
+            let batch = match self.get_entry_batch(batch_index as u64)? {
+                Some(batch) => batch,
+                None => continue,
+            };
+            num_sealed += batch.num_sealed() as u64;
+            seal_tasks.extend(
+                batch
+                    .unsealed_seal_indices()
+                    .into_iter()
+                    .map(|x| batch_index * SEALS_PER_LOAD + x as usize),
+            );
         }
+
+        let mut tx = DBTransaction::new();
+        put_seal_tasks(&mut tx, seal_tasks.iter().copied());
+        put_num_sealed(&mut tx, num_sealed);
         self.kvdb.write(tx)?;
-        Ok(index_to_reseal)
+        info!(
+            num_seal_tasks = seal_tasks.len(),
+            num_sealed, "Seal tasks rebuilt"
+        );
+        Ok((seal_tasks, num_sealed))
     }
 
-    fn delete_batch_list(&self, batch_list: &[u64]) -> Result<()> {
-        let mut tx = self.kvdb.transaction();
-        for i in batch_list {
-            tx.delete(COL_ENTRY_BATCH, &i.to_be_bytes());
+    fn get_seal_tasks(&self) -> Result<Vec<usize>> {
+        let mut seal_indices = Vec::new();
+        for kv in self.kvdb.iter(COL_SEAL_TASK) {
+            let (key, _) = kv?;
+            seal_indices.push(try_decode_usize(key.as_ref())?);
         }
-        Ok(self.kvdb.write(tx)?)
+        Ok(seal_indices)
     }
 
     fn put_pad_data(&self, data_sizes: &[PadPair], tx_seq: u64) -> Result<()> {
@@ -527,6 +784,23 @@ pub enum BatchRoot {
     Multiple((usize, DataRoot)),
 }
 
+/// Persists the seal tasks, so that sealing resumes after restart.
+fn put_seal_tasks(tx: &mut DBTransaction, seal_indices: impl IntoIterator<Item = usize>) {
+    for seal_index in seal_indices {
+        tx.put(COL_SEAL_TASK, &seal_index.to_be_bytes(), &[]);
+    }
+}
+
+fn delete_seal_tasks(tx: &mut DBTransaction, seal_indices: impl IntoIterator<Item = usize>) {
+    for seal_index in seal_indices {
+        tx.delete(COL_SEAL_TASK, &seal_index.to_be_bytes());
+    }
+}
+
+fn put_num_sealed(tx: &mut DBTransaction, num_sealed: u64) {
+    tx.put(COL_MISC, NUM_SEALED_KEY, &num_sealed.to_be_bytes());
+}
+
 /// Return the batch boundaries `(batch_start_index, batch_end_index)` given the index range.
 pub fn batch_iter(start: u64, end: u64, batch_size: usize) -> Vec<(u64, u64)> {
     let mut list = Vec::new();
@@ -0,0 +1,409 @@
+//! Offline integrity check of the log store.
+//!
+//! The check walks the transactions, the entry batches with their seal info and the
+//! persisted layers of the flow merkle tree, and reports every inconsistency found.
+//! With `repair` enabled, the corrupted entry batches are reconstructed from the parity
+//! if it is enabled, or removed otherwise. The transactions whose data is lost are marked
+//! as not finalized so that auto sync will fetch them again, and the merkle layers are
+//! rebuilt from the leaves.
+
+use crate::config::{ShardConfig, SHARD_CONFIG_KEY};
+use crate::log_store::flow_store::{FlowConfig, FlowDBStore, FlowStore};
+use crate::log_store::load_chunk::EntryBatch;
+use crate::log_store::log_manager::{
+    bytes_to_entries, data_to_merkle_leaves, open_databases, split_nodes, FileMerkleTree,
+    COL_ENTRY_BATCH, COL_MISC, PORA_CHUNK_SIZE,
+};
+use crate::log_store::tx_store::{TransactionStore, TxStatus};
+use crate::log_store::FlowRead;
+use crate::{StorageConfig, ZgsKeyValueDB};
+use anyhow::{anyhow, Result};
+use append_merkle::{Algorithm, HashElement, NodeDatabase, Sha3Algorithm, ZERO_HASHES};
+use merkle_light::merkle::log2_pow2;
+use parking_lot::RwLock;
+use shared_types::{DataRoot, Transaction};
+use ssz::Decode;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+#[derive(Debug, Default)]
+pub struct FsckReport {
+    pub num_txs: u64,
+    pub num_batches: u64,
+    pub num_nodes: u64,
+    /// Corrupted transactions and the reasons, indexed by tx seq.
+    pub bad_txs: BTreeMap<u64, String>,
+    /// Corrupted entry batches and the reasons, indexed by batch index.
+    pub bad_batches: BTreeMap<u64, String>,
+    /// Merkle nodes `(layer, position)` which are missing or mismatch their children.
+    pub bad_nodes: Vec<(usize, usize)>,
+    /// Corrupted entry batches reconstructed from the parity by repair.
+    pub recovered_batches: Vec<u64>,
+    /// Transactions marked as not finalized by repair.
+    pub unfinalized_txs: Vec<u64>,
+}
+
+impl FsckReport {
+    pub fn is_clean(&self) -> bool {
+        self.bad_txs.is_empty() && self.bad_batches.is_empty() && self.bad_nodes.is_empty()
+    }
+}
+
+/// Checks the log store in `config.db_dir`.
+pub fn fsck_db(config: &StorageConfig, repair: bool) -> Result<FsckReport> {
+    let (flow_db, data_db) = open_databases(config)?;
+    fsck(flow_db, data_db, config.log_config.flow.clone(), repair)
+}
+
+pub fn fsck(
+    flow_db: Arc<dyn ZgsKeyValueDB>,
+    data_db: Arc<dyn ZgsKeyValueDB>,
+    config: FlowConfig,
+    repair: bool,
+) -> Result<FsckReport> {
+    let tx_store = TransactionStore::new(flow_db.clone(), data_db.clone())?;
+    let shard_config = match data_db.get(COL_MISC, SHARD_CONFIG_KEY.as_bytes())? {
+        Some(v) => ShardConfig::from_ssz_bytes(&v).map_err(|e| anyhow!("{:?}", e))?,
+        None => ShardConfig::default(),
+    };
+    // The parity groups depend on the shard config of the node.
+    let config = FlowConfig {
+        shard_config: Arc::new(RwLock::new(shard_config)),
+        ..config
+    };
+    let flow_db_store = Arc::new(FlowDBStore::new(flow_db));
+    let data_db_store = Arc::new(FlowDBStore::new(data_db.clone()));
+    let flow_store = FlowStore::new(flow_db_store.clone(), data_db_store, config.clone())?;
+    let mut report = FsckReport::default();
+
+    // Transactions, ordered by the flow position.
+    let mut tx_ranges = Vec::new();
+    for seq in 0..tx_store.next_tx_seq() {
+        let tx = match tx_store.get_tx_by_seq_number(seq) {
+            Ok(Some(tx)) => tx,
+            Ok(None) => {
+                report.bad_txs.insert(seq, "tx missing".into());
+                continue;
+            }
+            Err(e) => {
+                report
+                    .bad_txs
+                    .insert(seq, format!("tx undecodable: {:?}", e));
+                continue;
+            }
+        };
+        report.num_txs += 1;
+        if let Err(reason) = check_tx(&tx_store, &tx, seq) {
+            report.bad_txs.insert(seq, reason);
+            continue;
+        }
+        tx_ranges.push((
+            tx.start_entry_index,
+            tx.start_entry_index + tx.num_entries() as u64,
+            seq,
+        ));
+    }
+    info!(num_txs = report.num_txs, "Transactions checked");
+
+    // Entry batches and their seal info, against the leaves of the flow merkle tree.
+    let leaves = flow_db_store.get_layer_size(0)?.unwrap_or(0);
+    let batch_size = config.batch_size as u64;
+    for kv in data_db.iter(COL_ENTRY_BATCH) {
+        let (key, value) = kv?;
+        let batch_index = match <[u8; 8]>::try_from(key.as_ref()) {
+            Ok(bytes) => u64::from_be_bytes(bytes),
+            Err(_) => {
+                warn!(?key, "Unexpected key of entry batch");
+                continue;
+            }
+        };
+        report.num_batches += 1;
+        if let Err(reason) = check_entry_batch(&flow_db_store, batch_index, &value, leaves) {
+            report.bad_batches.insert(batch_index, reason);
+        }
+    }
+    info!(num_batches = report.num_batches, "Entry batches checked");
+
+    // Data of the finalized transactions. The tx data cannot be recovered with sharding,
+    // so the check relies on the entry batches in that case.
+    if shard_config.num_shard == 1 {
+        for &(_, _, seq) in &tx_ranges {
+            if !matches!(tx_store.get_tx_status(seq)?, Some(TxStatus::Finalized)) {
+                continue;
+            }
+            let tx = tx_store.get_tx_by_seq_number(seq)?.expect("checked");
+            if let Err(reason) = check_tx_data(&flow_store, &tx) {
+                report.bad_txs.insert(seq, reason);
+            }
+        }
+        info!("Finalized transaction data checked");
+    }
+
+    // Persisted layers of the flow merkle tree.
+    let rebuilt_nodes = check_merkle_layers(&flow_db_store, &mut report)?;
+    info!(num_nodes = report.num_nodes, "Merkle nodes checked");
+
+    if repair && !report.is_clean() {
+        // The check above reads the raw batches, while the repair keeps the parity updated.
+        let repair_store = match config.parity {
+            Some(parity) => {
+                FlowDBStore::with_parity(data_db.clone(), parity, config.shard_config.clone())?
+            }
+            None => FlowDBStore::new(data_db.clone()),
+        };
+        let mut unfinalized = report.bad_txs.keys().copied().collect::<Vec<_>>();
+        for &batch_index in report.bad_batches.keys() {
+            if repair_store.recover_entry_batch(batch_index)?.is_some() {
+                report.recovered_batches.push(batch_index);
+                continue;
+            }
+            let (start, end) = (batch_index * batch_size, (batch_index + 1) * batch_size);
+            // `tx_ranges` is sorted by the start position.
+            let first = tx_ranges.partition_point(|&(_, tx_end, _)| tx_end <= start);
+            for &(tx_start, _, seq) in &tx_ranges[first..] {
+                if tx_start >= end {
+                    break;
+                }
+                unfinalized.push(seq);
+            }
+            repair_store.delete_batch_list(&[batch_index])?;
+        }
+        unfinalized.sort_unstable();
+        unfinalized.dedup();
+        for &seq in &unfinalized {
+            if matches!(tx_store.get_tx_status(seq), Ok(Some(TxStatus::Finalized))) {
+                tx_store.unfinalize_tx(seq)?;
+                report.unfinalized_txs.push(seq);
+            }
+        }
+
+        if !rebuilt_nodes.is_empty() {
+            let mut tx = flow_db_store.start_transaction();
+            for ((layer, pos), node) in &rebuilt_nodes {
+                tx.save_node(*layer, *pos, node);
+            }
+            flow_db_store.commit(tx)?;
+        }
+        info!(
+            recovered_batches = report.recovered_batches.len(),
+            removed_batches = report.bad_batches.len() - report.recovered_batches.len(),
+            unfinalized_txs = report.unfinalized_txs.len(),
+            rebuilt_nodes = rebuilt_nodes.len(),
+            "Log store repaired"
+        );
+    }
+
+    Ok(report)
+}
+
+fn check_tx(
+    tx_store: &TransactionStore,
+    tx: &Transaction,
+    seq: u64,
+) -> std::result::Result<(), String> {
+    if tx.seq != seq {
+        return Err(format!("tx seq mismatch: stored={}", tx.seq));
+    }
+    let subtree_sizes = tx
+        .merkle_nodes
+        .iter()
+        .map(|&(depth, _)| Transaction::num_entries_of_node(depth))
+        .collect::<Vec<_>>();
+    if subtree_sizes != split_nodes(tx.size as usize) {
+        return Err(format!(
+            "merkle nodes do not match tx size: size={} nodes={:?}",
+            tx.size, subtree_sizes
+        ));
+    }
+    match tx_store.get_tx_seq_list_by_data_root(&tx.data_merkle_root) {
+        Ok(seq_list) if seq_list.contains(&seq) => Ok(()),
+        Ok(_) => Err("tx missing in data root index".into()),
+        Err(e) => Err(format!("data root index undecodable: {:?}", e)),
+    }
+}
+
+fn check_entry_batch(
+    flow_db_store: &FlowDBStore,
+    batch_index: u64,
+    value: &[u8],
+    leaves: usize,
+) -> std::result::Result<(), String> {
+    let batch = EntryBatch::from_ssz_bytes(value)
+        .map_err(|e| format!("entry batch undecodable: {:?}", e))?;
+    let invalid_seals = batch.invalid_seal_indices();
+    if !invalid_seals.is_empty() {
+        return Err(format!(
+            "invalid seal info: seal_indices={:?}",
+            invalid_seals
+        ));
+    }
+    let root = match batch.build_root(batch_index == 0) {
+        Ok(Some(root)) => root,
+        // Incomplete batch, which is not a leaf yet.
+        Ok(None) => return Ok(()),
+        Err(e) => return Err(format!("failed to build root: {:?}", e)),
+    };
+    if batch_index as usize >= leaves {
+        return Ok(());
+    }
+    match flow_db_store.get_node(0, batch_index as usize) {
+        Ok(Some(leaf)) if leaf.is_null() || leaf == root => Ok(()),
+        Ok(Some(leaf)) => Err(format!("root mismatch: leaf={:?} data={:?}", leaf, root)),
+        // The leaf is only known with the subtree roots.
+        Ok(None) => Ok(()),
+        Err(e) => Err(format!("failed to read leaf: {:?}", e)),
+    }
+}
+
+/// Recompute the subtree roots of the tx data and compare them with `tx.merkle_nodes`.
+fn check_tx_data(flow_store: &FlowStore, tx: &Transaction) -> std::result::Result<(), String> {
+    let data_entries = bytes_to_entries(tx.size);
+    let mut start = 0u64;
+    for (i, &(depth, root)) in tx.merkle_nodes.iter().enumerate() {
+        let end = start + Transaction::num_entries_of_node(depth) as u64;
+        let mut leaves = Vec::with_capacity((end - start) as usize);
+        let mut index = start;
+        while index < end.min(data_entries) {
+            let batch_end = (index + PORA_CHUNK_SIZE as u64).min(end.min(data_entries));
+            let chunks = match flow_store.get_entries(
+                tx.start_entry_index + index,
+                tx.start_entry_index + batch_end,
+            ) {
+                Ok(Some(chunks)) => chunks,
+                Ok(None) => return Err(format!("data missing at entry {}", index)),
+                Err(e) => return Err(format!("failed to read entry {}: {:?}", index, e)),
+            };
+            let chunk_leaves = data_to_merkle_leaves(&chunks.data)
+                .map_err(|e| format!("failed to compute leaves: {:?}", e))?;
+            leaves.extend(chunk_leaves.into_iter().map(|h| h.0));
+            index = batch_end;
+        }
+        // Padding entries are all zeros.
+        leaves.resize((end - start) as usize, ZERO_HASHES[0].0);
+        let computed: DataRoot = FileMerkleTree::new(leaves).root().into();
+        if computed != root {
+            return Err(format!(
+                "subtree root mismatch: index={} stored={:?} computed={:?}",
+                i, root, computed
+            ));
+        }
+        start = end;
+    }
+    Ok(())
+}
+
+/// Check every node above the leaves with its children, and return the recomputed
+/// values of the bad nodes.
+fn check_merkle_layers(
+    flow_db_store: &FlowDBStore,
+    report: &mut FsckReport,
+) -> Result<BTreeMap<(usize, usize), DataRoot>> {
+    let leaf_height = log2_pow2(PORA_CHUNK_SIZE);
+    let mut rebuilt = BTreeMap::new();
+    let mut children = Vec::new();
+    let mut layer_size = flow_db_store.get_layer_size(0)?.unwrap_or(0);
+    for pos in 0..layer_size {
+        children.push(flow_db_store.get_node(0, pos)?);
+    }
+    report.num_nodes += layer_size as u64;
+
+    let mut layer = 1;
+    while let Some(size) = flow_db_store.get_layer_size(layer)? {
+        if size != (layer_size + 1) / 2 {
+            warn!(
+                layer,
+                size,
+                child_layer_size = layer_size,
+                "Unexpected layer size"
+            );
+        }
+        let mut nodes = Vec::with_capacity(size);
+        for pos in 0..size {
+            let stored = flow_db_store.get_node(layer, pos)?;
+            let left = children.get(2 * pos).cloned().flatten();
+            let right = children.get(2 * pos + 1).cloned().flatten();
+            let expected = match (left, right) {
+                (Some(l), _) if l.is_null() => None,
+                (_, Some(r)) if r.is_null() => None,
+                (Some(l), Some(r)) => Some(Sha3Algorithm::parent(&l, &r)),
+                (Some(l), None) if 2 * pos + 1 >= layer_size => {
+                    Some(Sha3Algorithm::parent_single(&l, layer - 1 + leaf_height))
+                }
+                _ => None,
+            };
+            let node = match (stored, expected) {
+                (Some(s), Some(e)) if !s.is_null() && s != e => {
+                    report.bad_nodes.push((layer, pos));
+                    rebuilt.insert((layer, pos), e);
+                    Some(e)
+                }
+                (None, Some(e)) => {
+                    report.bad_nodes.push((layer, pos));
+                    rebuilt.insert((layer, pos), e);
+                    Some(e)
+                }
+                (stored, _) => stored,
+            };
+            nodes.push(node);
+        }
+        report.num_nodes += size as u64;
+        children = nodes;
+        layer_size = size;
+        layer += 1;
+    }
+    Ok(rebuilt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_store::log_manager::{LogConfig, COL_NUM};
+    use crate::log_store::test_utils::put_tx;
+    use crate::LogManager;
+
+    fn memorydb() -> Arc<dyn ZgsKeyValueDB> {
+        Arc::new(kvdb_memorydb::create(COL_NUM))
+    }
+
+    #[test]
+    fn test_fsck_repair() {
+        let (flow_db, data_db) = (memorydb(), memorydb());
+        let mut store =
+            LogManager::new(flow_db.clone(), data_db.clone(), LogConfig::default()).unwrap();
+        put_tx(&mut store, 0x1000, 0);
+        put_tx(&mut store, 0x1000, 1);
+        drop(store);
+        let config = FlowConfig::default();
+
+        let report = fsck(flow_db.clone(), data_db.clone(), config.clone(), false).unwrap();
+        assert!(report.is_clean(), "{:?}", report);
+        assert_eq!(report.num_txs, 2);
+
+        // Corrupt the data of the last entry batch of tx 1.
+        let batch_index = data_db
+            .iter(COL_ENTRY_BATCH)
+            .last()
+            .unwrap()
+            .unwrap()
+            .0
+            .to_vec();
+        let mut value = data_db.get(COL_ENTRY_BATCH, &batch_index).unwrap().unwrap();
+        *value.last_mut().unwrap() ^= 1;
+        data_db.put(COL_ENTRY_BATCH, &batch_index, &value).unwrap();
+        // Corrupt a merkle node.
+        let flow_db_store = FlowDBStore::new(flow_db.clone());
+        let mut tx = flow_db_store.start_transaction();
+        tx.save_node(1, 0, &DataRoot::repeat_byte(7));
+        flow_db_store.commit(tx).unwrap();
+
+        let report = fsck(flow_db.clone(), data_db.clone(), config.clone(), true).unwrap();
+        assert_eq!(report.bad_batches.len(), 1);
+        assert!(report.bad_txs.contains_key(&1));
+        assert_eq!(report.bad_nodes, vec![(1, 0)]);
+        assert_eq!(report.unfinalized_txs, vec![1]);
+
+        let report = fsck(flow_db, data_db, config, false).unwrap();
+        assert!(report.is_clean(), "{:?}", report);
+    }
+}
@@ -41,6 +41,16 @@ impl EntryBatch {
     pub fn is_empty(&self) -> bool {
         self.data.is_empty()
     }
+
+    /// Return the number of sealed seals in this batch.
+    pub fn num_sealed(&self) -> usize {
+        self.seal.num_sealed()
+    }
+
+    /// Return the `(start_sector, length_sector)` list of available data in this batch.
+    pub fn available_range_entries(&self) -> Vec<(usize, usize)> {
+        self.data.available_range_entries()
+    }
 }
 
 impl EntryBatch {
@@ -209,6 +219,34 @@ impl EntryBatch {
         ))
     }
 
+    /// Return the seal indices which are marked as sealed but cannot be unsealed,
+    /// because the seal context or the data is missing.
+    pub fn invalid_seal_indices(&self) -> Vec<u16> {
+        (0..SEALS_PER_LOAD as u16)
+            .filter(|&seal_index| {
+                self.seal.is_sealed(seal_index)
+                    && (self.seal.get_seal_context_digest(seal_index).is_none()
+                        || self
+                            .data
+                            .get(seal_index as usize * BYTES_PER_SEAL, BYTES_PER_SEAL)
+                            .is_none())
+            })
+            .collect()
+    }
+
+    /// Return the seal indices whose data is complete but not sealed yet.
+    pub fn unsealed_seal_indices(&self) -> Vec<u16> {
+        (0..SEALS_PER_LOAD as u16)
+            .filter(|&seal_index| {
+                !self.seal.is_sealed(seal_index)
+                    && self
+                        .data
+                        .get(seal_index as usize * BYTES_PER_SEAL, BYTES_PER_SEAL)
+                        .is_some()
+            })
+            .collect()
+    }
+
     pub fn submit_seal_result(&mut self, answer: SealAnswer) -> Result<()> {
         let local_seal_index = answer.seal_index as usize % SEALS_PER_LOAD;
         assert!(
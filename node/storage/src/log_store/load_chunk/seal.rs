@@ -46,6 +46,10 @@ impl SealInfo {
         self.bitmap.get(seal_index as usize)
     }
 
+    pub fn num_sealed(&self) -> usize {
+        self.bitmap.len()
+    }
+
     pub fn mark_sealed(&mut self, seal_index: u16) {
         self.bitmap.set(seal_index as usize, true);
     }
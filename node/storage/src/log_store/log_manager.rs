@@ -1,4 +1,4 @@
-use crate::config::ShardConfig;
+use crate::config::{DbBackend, ShardConfig};
 use crate::log_store::flow_store::{
     batch_iter_sharded, FlowConfig, FlowDBStore, FlowStore, PadPair,
 };
@@ -7,7 +7,8 @@ use crate::log_store::{
     FlowRead, FlowSeal, FlowWrite, LogStoreChunkRead, LogStoreChunkWrite, LogStoreRead,
     LogStoreWrite, MineLoadChunk, SealAnswer, SealTask,
 };
-use crate::{try_option, ZgsKeyValueDB};
+use crate::segment_db::{has_segment_data, SegmentConfig, SegmentDB};
+use crate::{try_option, StorageConfig, ZgsKeyValueDB};
 use anyhow::{anyhow, bail, Result};
 use append_merkle::{Algorithm, MerkleTreeRead, Sha3Algorithm};
 use ethereum_types::H256;
@@ -24,9 +25,10 @@ use shared_types::{
 };
 use std::cmp::Ordering;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
 
 use tracing::{debug, error, info, instrument, trace, warn};
 
@@ -46,7 +48,10 @@ pub const COL_FLOW_MPT_NODES: u32 = 5; // flow db
 pub const COL_BLOCK_PROGRESS: u32 = 6; // flow db
 pub const COL_PAD_DATA_LIST: u32 = 7; // flow db
 pub const COL_PAD_DATA_SYNC_HEIGH: u32 = 8; // data db
-pub const COL_NUM: u32 = 9;
+pub const COL_ENTRY_BATCH_PARITY: u32 = 9; // data db
+pub const COL_SHARD_PARITY: u32 = 10; // data db
+pub const COL_SEAL_TASK: u32 = 11; // data db
+pub const COL_NUM: u32 = 12;
 
 pub const DATA_DB_KEY: &str = "data_db";
 pub const FLOW_DB_KEY: &str = "flow_db";
@@ -362,6 +367,8 @@ impl LogStoreWrite for LogManager {
     }
 
     fn prune_tx(&self, tx_seq: u64) -> crate::error::Result<()> {
+        self.data_db
+            .delete_with_prefix(COL_SHARD_PARITY, &tx_seq.to_be_bytes())?;
         self.tx_store.prune_tx(tx_seq)
     }
 
@@ -373,6 +380,10 @@ impl LogStoreWrite for LogManager {
         self.tx_store.put_log_latest_block_number(block_number)
     }
 
+    fn put_log_finalized_block_number(&self, block_number: u64) -> Result<()> {
+        self.tx_store.put_log_finalized_block_number(block_number)
+    }
+
     /// Return the reverted Transactions in order.
     /// `tx_seq == u64::MAX` is a special case for reverting all transactions.
     fn revert_to(&self, tx_seq: u64) -> Result<Vec<Transaction>> {
@@ -390,7 +401,20 @@ impl LogStoreWrite for LogManager {
             + merkle.last_chunk_merkle.leaves() as u64;
         self.flow_store.truncate(start_index)?;
         let start = if tx_seq != u64::MAX { tx_seq + 1 } else { 0 };
-        self.tx_store.remove_tx_after(start)
+        let reverted = self.tx_store.remove_tx_after(start)?;
+        for tx in &reverted {
+            self.data_db
+                .delete_with_prefix(COL_SHARD_PARITY, &tx.seq.to_be_bytes())?;
+        }
+        Ok(reverted)
+    }
+
+    fn put_shard_parity(&self, tx_seq: u64, stripe_index: u64, parity: Vec<u8>) -> Result<()> {
+        Ok(self.data_db.put(
+            COL_SHARD_PARITY,
+            &shard_parity_key(tx_seq, stripe_index),
+            &parity,
+        )?)
     }
 
     fn validate_and_insert_range_proof(
@@ -635,6 +659,10 @@ impl LogStoreRead for LogManager {
         self.tx_store.get_log_latest_block_number()
     }
 
+    fn get_log_finalized_block_number(&self) -> Result<Option<u64>> {
+        self.tx_store.get_log_finalized_block_number()
+    }
+
     fn get_block_hash_by_number(&self, block_number: u64) -> Result<Option<(H256, Option<u64>)>> {
         self.tx_store.get_block_hash_by_number(block_number)
     }
@@ -673,8 +701,20 @@ impl LogStoreRead for LogManager {
         self.tx_store.check_tx_pruned(tx_seq)
     }
 
-    fn pull_seal_chunk(&self, seal_index_max: usize) -> Result<Option<Vec<SealTask>>> {
-        self.flow_store.pull_seal_chunk(seal_index_max)
+    fn pull_seal_chunk(
+        &self,
+        seal_index_max: usize,
+        max_seals: usize,
+    ) -> Result<Option<Vec<SealTask>>> {
+        self.flow_store.pull_seal_chunk(seal_index_max, max_seals)
+    }
+
+    fn get_num_seal_tasks(&self) -> Result<u64> {
+        Ok(self.flow_store.get_num_seal_tasks())
+    }
+
+    fn get_num_sealed_seals(&self) -> Result<u64> {
+        Ok(self.flow_store.get_num_sealed_seals())
     }
 
     fn get_num_entries(&self) -> Result<u64> {
@@ -688,6 +728,76 @@ impl LogStoreRead for LogManager {
     fn get_shard_config(&self) -> ShardConfig {
         self.flow_store.get_shard_config()
     }
+
+    fn get_shard_parity(&self, tx_seq: u64, stripe_index: u64) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .data_db
+            .get(COL_SHARD_PARITY, &shard_parity_key(tx_seq, stripe_index))?)
+    }
+
+    fn get_available_entry_ranges(
+        &self,
+        index_start: u64,
+        index_end: u64,
+    ) -> Result<Vec<(u64, u64)>> {
+        self.flow_store.get_available_ranges(index_start, index_end)
+    }
+
+    fn subscribe_finalized(&self) -> broadcast::Receiver<u64> {
+        self.tx_store.subscribe_finalized()
+    }
+}
+
+/// Opens the underlying databases of the log store with the configured backend.
+pub fn open_databases(
+    config: &StorageConfig,
+) -> Result<(Arc<dyn ZgsKeyValueDB>, Arc<dyn ZgsKeyValueDB>)> {
+    let flow_path = config.db_dir.join("flow_db");
+    let data_path = config.db_dir.join("data_db");
+    match config.backend {
+        DbBackend::RocksDB => open_rocksdb(flow_path, data_path, None),
+        DbBackend::Segment => open_rocksdb(
+            flow_path,
+            data_path,
+            Some((config.db_dir.join("entry_segments"), config.segment_config)),
+        ),
+        DbBackend::Memory => bail!("no persistent database for the memory backend"),
+    }
+}
+
+/// Opens the RocksDB databases, and stores the entry batches of the data database in segment
+/// files if `segment` is given.
+fn open_rocksdb(
+    flow_path: impl AsRef<Path>,
+    data_path: impl AsRef<Path>,
+    segment: Option<(PathBuf, SegmentConfig)>,
+) -> Result<(Arc<dyn ZgsKeyValueDB>, Arc<dyn ZgsKeyValueDB>)> {
+    let mut db_config = DatabaseConfig::with_columns(COL_NUM);
+    db_config.enable_statistics = true;
+    let flow_db: Arc<dyn ZgsKeyValueDB> = Arc::new(Database::open(&db_config, flow_path)?);
+    let data_db: Arc<dyn ZgsKeyValueDB> = Arc::new(Database::open(&db_config, data_path)?);
+    let data_db: Arc<dyn ZgsKeyValueDB> = match segment {
+        Some((segment_path, segment_config)) => Arc::new(SegmentDB::open(
+            data_db,
+            COL_ENTRY_BATCH,
+            segment_path,
+            segment_config,
+        )?),
+        None => {
+            ensure_no_segment_data(&*data_db)?;
+            data_db
+        }
+    };
+    Ok((flow_db, data_db))
+}
+
+/// Entry batches written with the segment backend are not visible in RocksDB, so the
+/// node must not switch back to RocksDB once any are written.
+fn ensure_no_segment_data(data_db: &dyn ZgsKeyValueDB) -> Result<()> {
+    if has_segment_data(data_db, COL_ENTRY_BATCH)? {
+        bail!("entry batches are stored in segment files, db_backend must be \"segment\"");
+    }
+    Ok(())
 }
 
 impl LogManager {
@@ -696,10 +806,21 @@ impl LogManager {
         flow_path: impl AsRef<Path>,
         data_path: impl AsRef<Path>,
     ) -> Result<Self> {
-        let mut db_config = DatabaseConfig::with_columns(COL_NUM);
-        db_config.enable_statistics = true;
-        let flow_db_source = Arc::new(Database::open(&db_config, flow_path)?);
-        let data_db_source = Arc::new(Database::open(&db_config, data_path)?);
+        let (flow_db_source, data_db_source) = open_rocksdb(flow_path, data_path, None)?;
+        Self::new(flow_db_source, data_db_source, config)
+    }
+
+    /// Stores entry batches in append-only segment files under `segment_path`, and
+    /// the other columns in RocksDB.
+    pub fn segment(
+        config: LogConfig,
+        flow_path: impl AsRef<Path>,
+        data_path: impl AsRef<Path>,
+        segment_path: impl AsRef<Path>,
+        segment_config: SegmentConfig,
+    ) -> Result<Self> {
+        let segment = Some((segment_path.as_ref().to_path_buf(), segment_config));
+        let (flow_db_source, data_db_source) = open_rocksdb(flow_path, data_path, segment)?;
         Self::new(flow_db_source, data_db_source, config)
     }
 
@@ -709,19 +830,28 @@ impl LogManager {
         Self::new(flow_db, data_db, config)
     }
 
-    fn new(
+    /// Creates a log store with custom backends, which should support all the columns
+    /// up to `COL_NUM`.
+    pub fn new(
         flow_db_source: Arc<dyn ZgsKeyValueDB>,
         data_db_source: Arc<dyn ZgsKeyValueDB>,
         config: LogConfig,
     ) -> Result<Self> {
         let tx_store = TransactionStore::new(flow_db_source.clone(), data_db_source.clone())?;
         let flow_db = Arc::new(FlowDBStore::new(flow_db_source.clone()));
-        let data_db = Arc::new(FlowDBStore::new(data_db_source.clone()));
+        let data_db = Arc::new(match config.flow.parity {
+            Some(parity) => FlowDBStore::with_parity(
+                data_db_source.clone(),
+                parity,
+                config.flow.shard_config.clone(),
+            )?,
+            None => FlowDBStore::new(data_db_source.clone()),
+        });
         let flow_store = Arc::new(FlowStore::new(
             flow_db.clone(),
             data_db.clone(),
             config.flow.clone(),
-        ));
+        )?);
         // If the last tx `put_tx` does not complete, we will revert it in `pora_chunks_merkle`
         // first and call `put_tx` later.
         let next_tx_seq = tx_store.next_tx_seq();
@@ -1384,3 +1514,9 @@ pub fn sector_to_segment(sector_index: u64) -> usize {
 pub fn segment_to_sector(segment_index: usize) -> usize {
     segment_index * PORA_CHUNK_SIZE
 }
+
+fn shard_parity_key(tx_seq: u64, stripe_index: u64) -> Vec<u8> {
+    let mut key = tx_seq.to_be_bytes().to_vec();
+    key.extend_from_slice(&stripe_index.to_be_bytes());
+    key
+}
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use metrics::{register_timer, Gauge, GaugeUsize, Timer};
+use metrics::{register_timer, Counter, CounterUsize, Gauge, GaugeUsize, Timer};
 
 lazy_static::lazy_static! {
     pub static ref PUT_TX: Arc<dyn Timer> = register_timer("log_store_put_tx");
@@ -40,4 +40,10 @@ lazy_static::lazy_static! {
     pub static ref DATA_TO_MERKLE_LEAVES_SIZE: Arc<dyn Gauge<usize>> = GaugeUsize::register("log_store_data_to_merkle_leaves_size");
 
     pub static ref TX_BY_SEQ_NUMBER: Arc<dyn Timer> = register_timer("log_store_tx_store_get_tx_by_seq_number");
+
+    pub static ref ENTRY_BATCH_RECONSTRUCTED: Arc<dyn Counter<usize>> =
+        CounterUsize::register("log_store_flow_store_entry_batch_reconstructed");
+
+    pub static ref ENTRY_BATCH_UNRECOVERABLE: Arc<dyn Counter<usize>> =
+        CounterUsize::register("log_store_flow_store_entry_batch_unrecoverable");
 }
@@ -6,6 +6,7 @@ use shared_types::{
     Chunk, ChunkArray, ChunkArrayWithProof, ChunkWithProof, DataRoot, FlowProof, FlowRangeProof,
     Transaction,
 };
+use tokio::sync::broadcast;
 use zgs_spec::{BYTES_PER_SEAL, SEALS_PER_LOAD};
 
 use crate::error::Result;
@@ -14,11 +15,15 @@ use self::tx_store::{BlockHashAndSubmissionIndex, TxStatus};
 
 pub mod config;
 mod flow_store;
+pub mod fsck;
 pub mod load_chunk;
 pub mod log_manager;
 mod metrics;
+pub mod parity;
 mod seal_task_manager;
 #[cfg(test)]
+pub(crate) mod test_utils;
+#[cfg(test)]
 mod tests;
 pub mod tx_store;
 
@@ -78,6 +83,10 @@ pub trait LogStoreRead: LogStoreChunkRead {
 
     fn get_log_latest_block_number(&self) -> Result<Option<u64>>;
 
+    /// Returns the finalized block number of the chain, at or below which the synced
+    /// transactions will not be reverted by chain reorg.
+    fn get_log_finalized_block_number(&self) -> Result<Option<u64>>;
+
     fn get_block_hash_by_number(&self, block_number: u64) -> Result<Option<(H256, Option<u64>)>>;
 
     fn get_block_hashes(&self) -> Result<Vec<(u64, BlockHashAndSubmissionIndex)>>;
@@ -94,13 +103,37 @@ pub trait LogStoreRead: LogStoreChunkRead {
     /// Return flow root and length.
     fn get_context(&self) -> Result<(DataRoot, u64)>;
 
-    fn pull_seal_chunk(&self, seal_index_max: usize) -> Result<Option<Vec<SealTask>>>;
+    fn pull_seal_chunk(
+        &self,
+        seal_index_max: usize,
+        max_seals: usize,
+    ) -> Result<Option<Vec<SealTask>>>;
+
+    /// Return the number of seals waiting to seal.
+    fn get_num_seal_tasks(&self) -> Result<u64>;
+
+    /// Return the number of sealed seals in the store.
+    fn get_num_sealed_seals(&self) -> Result<u64>;
 
     fn get_num_entries(&self) -> Result<u64>;
 
     fn load_sealed_data(&self, chunk_index: u64) -> Result<Option<MineLoadChunk>>;
 
     fn get_shard_config(&self) -> ShardConfig;
+
+    /// Get the erasure coded parity of a file stripe that is held by this node.
+    fn get_shard_parity(&self, tx_seq: u64, stripe_index: u64) -> Result<Option<Vec<u8>>>;
+
+    /// Return the `[start, end)` ranges of flow entries that are stored locally in the given
+    /// range.
+    fn get_available_entry_ranges(
+        &self,
+        index_start: u64,
+        index_end: u64,
+    ) -> Result<Vec<(u64, u64)>>;
+
+    /// Subscribe to the sequence numbers of transactions once they are finalized.
+    fn subscribe_finalized(&self) -> broadcast::Receiver<u64>;
 }
 
 pub trait LogStoreChunkRead {
@@ -156,6 +189,9 @@ pub trait LogStoreWrite: LogStoreChunkWrite {
     /// Store the latest block number which has log
     fn put_log_latest_block_number(&self, block_number: u64) -> Result<()>;
 
+    /// Store the finalized block number, which is synced already.
+    fn put_log_finalized_block_number(&self, block_number: u64) -> Result<()>;
+
     /// Revert the log state to a given tx seq.
     /// This is needed when transactions are reverted because of chain reorg.
     ///
@@ -176,6 +212,10 @@ pub trait LogStoreWrite: LogStoreChunkWrite {
     fn submit_seal_result(&self, answers: Vec<SealAnswer>) -> Result<()>;
 
     fn start_padding(&self, executor: &task_executor::TaskExecutor);
+
+    /// Store the erasure coded parity of a file stripe that is held by this node.
+    /// The parity is removed together with the tx when it is pruned or reverted.
+    fn put_shard_parity(&self, tx_seq: u64, stripe_index: u64, parity: Vec<u8>) -> Result<()>;
 }
 
 pub trait LogStoreChunkWrite {
@@ -229,6 +269,11 @@ pub trait FlowRead {
     /// For simplicity, `index_start` and `index_end` must be at the batch boundaries.
     fn get_available_entries(&self, index_start: u64, index_end: u64) -> Result<Vec<ChunkArray>>;
 
+    /// Return the `[start, end)` ranges of available entries in the given range, without
+    /// loading the data. The returned ranges are in order and they will not overlap or be
+    /// adjacent.
+    fn get_available_ranges(&self, index_start: u64, index_end: u64) -> Result<Vec<(u64, u64)>>;
+
     fn load_sealed_data(&self, chunk_index: u64) -> Result<Option<MineLoadChunk>>;
 
     // An estimation of the number of entries in the flow db.
@@ -284,9 +329,19 @@ pub struct SealAnswer {
 }
 
 pub trait FlowSeal {
-    /// Pull a seal chunk ready for sealing
+    /// Pull seal chunks ready for sealing, until at least `max_seals` seals are pulled
     /// Return the global index (in sector) and the data
-    fn pull_seal_chunk(&self, seal_index_max: usize) -> Result<Option<Vec<SealTask>>>;
+    fn pull_seal_chunk(
+        &self,
+        seal_index_max: usize,
+        max_seals: usize,
+    ) -> Result<Option<Vec<SealTask>>>;
+
+    /// Return the number of seals waiting to seal
+    fn get_num_seal_tasks(&self) -> u64;
+
+    /// Return the number of sealed seals in the store
+    fn get_num_sealed_seals(&self) -> u64;
 
     /// Submit sealing result
 
@@ -0,0 +1,647 @@
+//! Local Reed-Solomon parity over groups of entry batches.
+//!
+//! The entry batches in the local shard are grouped by `data_shards` in the order of their
+//! indices, and `parity_shards` parity shards are computed over the ssz encoded batches of
+//! each group, zero padded to the same length. Up to `parity_shards` lost or corrupted
+//! batches of a group can be reconstructed.
+//!
+//! Each group has a metadata record with the length and the crc32 checksum of every member,
+//! which are verified on reads. Parity is updated incrementally by applying the difference
+//! between the old and the new value of the written batches, so a write only reads the old
+//! batch and the parity shards of its group.
+//!
+//! Keys in `COL_ENTRY_BATCH_PARITY`: `group_index (8)` for the metadata, and
+//! `group_index (8) | parity_index (1)` for the parity shards.
+
+use crate::config::ShardConfig;
+use crate::log_store::log_manager::{COL_ENTRY_BATCH, COL_ENTRY_BATCH_PARITY};
+use crate::log_store::metrics;
+use crate::ZgsKeyValueDB;
+use anyhow::{anyhow, Result};
+use kvdb::DBTransaction;
+use parking_lot::{Mutex, RwLock};
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use ssz::{Decode, Encode};
+use ssz_derive::{Decode as DeriveDecode, Encode as DeriveEncode};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParityConfig {
+    /// Number of entry batches in a parity group.
+    pub data_shards: usize,
+    /// Number of parity shards of a group, which is the max number of batches that can be
+    /// reconstructed in the group.
+    pub parity_shards: usize,
+}
+
+impl Default for ParityConfig {
+    fn default() -> Self {
+        Self {
+            data_shards: 8,
+            parity_shards: 2,
+        }
+    }
+}
+
+impl ParityConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.data_shards == 0 || self.parity_shards == 0 {
+            return Err(format!(
+                "Invalid parity config: data_shards={} parity_shards={}, both should be positive",
+                self.data_shards, self.parity_shards
+            ));
+        }
+        if self.data_shards + self.parity_shards > 256 {
+            return Err(format!(
+                "Invalid parity config: data_shards={} parity_shards={}, the total should not exceed 256",
+                self.data_shards, self.parity_shards
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, DeriveEncode, DeriveDecode)]
+struct GroupMeta {
+    data_shards: u32,
+    parity_shards: u32,
+    /// The shard config when the group was built, which decides the members of the group.
+    num_shard: u64,
+    shard_id: u64,
+    /// Length of the parity shards, which is never less than the length of any member.
+    shard_size: u64,
+    lens: Vec<u64>,
+    checksums: Vec<u32>,
+}
+
+impl GroupMeta {
+    /// Whether the stored `value` of the member at `position` matches the metadata.
+    fn matches(&self, position: usize, value: Option<&[u8]>) -> bool {
+        match value {
+            None => self.lens[position] == 0,
+            Some(value) => {
+                value.len() as u64 == self.lens[position]
+                    && crc32fast::hash(value) == self.checksums[position]
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.lens.iter().all(|&len| len == 0)
+    }
+}
+
+pub(crate) struct EntryBatchParity {
+    config: ParityConfig,
+    codec: ReedSolomon,
+    shard_config: Arc<RwLock<ShardConfig>>,
+    /// Serializes the updates of the entry batches and their parity.
+    update_lock: Mutex<()>,
+}
+
+impl EntryBatchParity {
+    pub fn new(config: ParityConfig, shard_config: Arc<RwLock<ShardConfig>>) -> Result<Self> {
+        config.validate().map_err(|e| anyhow!(e))?;
+        let codec = ReedSolomon::new(config.data_shards, config.parity_shards)
+            .map_err(|e| anyhow!("failed to create parity codec: {:?}", e))?;
+        Ok(Self {
+            config,
+            codec,
+            shard_config,
+            update_lock: Mutex::new(()),
+        })
+    }
+
+    /// Returns the raw entry batch, which is reconstructed from the parity if the stored
+    /// value is missing or corrupted.
+    pub fn get(&self, kvdb: &dyn ZgsKeyValueDB, batch_index: u64) -> Result<Option<Vec<u8>>> {
+        let raw = kvdb.get(COL_ENTRY_BATCH, &batch_index.to_be_bytes())?;
+        let (group_index, position) = match self.locate(batch_index) {
+            Some(location) => location,
+            None => return Ok(raw),
+        };
+        // A batch not known by the parity is left as it is.
+        match self.get_meta(kvdb, group_index)? {
+            Some(meta)
+                if self.is_usable(&meta)
+                    && meta.lens[position] != 0
+                    && !meta.matches(position, raw.as_deref()) => {}
+            _ => return Ok(raw),
+        }
+
+        // Check again with the lock held, in case the batch has been updated concurrently.
+        let _guard = self.update_lock.lock();
+        let raw = kvdb.get(COL_ENTRY_BATCH, &batch_index.to_be_bytes())?;
+        let meta = match self.get_meta(kvdb, group_index)? {
+            Some(meta) if self.is_usable(&meta) => meta,
+            _ => return Ok(raw),
+        };
+        if meta.matches(position, raw.as_deref()) || meta.lens[position] == 0 {
+            return Ok(raw);
+        }
+        warn!(batch_index, "Entry batch mismatches its checksum");
+        Ok(self
+            .recover_locked(kvdb, batch_index, group_index, position, &meta)?
+            .or(raw))
+    }
+
+    /// Reconstructs the entry batch from the other members and the parity of its group,
+    /// regardless of the stored value. The reconstructed batch is written back and returned
+    /// if it differs from the stored one.
+    pub fn recover(&self, kvdb: &dyn ZgsKeyValueDB, batch_index: u64) -> Result<Option<Vec<u8>>> {
+        let (group_index, position) = match self.locate(batch_index) {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+        let _guard = self.update_lock.lock();
+        match self.get_meta(kvdb, group_index)? {
+            Some(meta) if self.is_usable(&meta) && meta.lens[position] != 0 => {
+                self.recover_locked(kvdb, batch_index, group_index, position, &meta)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Writes the entry batches together with the parity of their groups and the other updates
+    /// in `tx`. A `None` value deletes the batch.
+    pub fn write(
+        &self,
+        kvdb: &dyn ZgsKeyValueDB,
+        mut tx: DBTransaction,
+        batch_list: Vec<(u64, Option<Vec<u8>>)>,
+    ) -> Result<()> {
+        let _guard = self.update_lock.lock();
+        let mut groups: BTreeMap<u64, BTreeMap<usize, Vec<u8>>> = BTreeMap::new();
+        for (batch_index, value) in batch_list {
+            match &value {
+                Some(value) => tx.put(COL_ENTRY_BATCH, &batch_index.to_be_bytes(), value),
+                None => tx.delete(COL_ENTRY_BATCH, &batch_index.to_be_bytes()),
+            }
+            if let Some((group_index, position)) = self.locate(batch_index) {
+                groups
+                    .entry(group_index)
+                    .or_default()
+                    .insert(position, value.unwrap_or_default());
+            }
+        }
+        for (group_index, updates) in groups {
+            self.update_group(kvdb, &mut tx, group_index, updates)?;
+        }
+        kvdb.write(tx)?;
+        Ok(())
+    }
+
+    /// Returns `(group_index, position)` of a batch with the current shard config, or `None`
+    /// if the batch is not in the local shard.
+    fn locate(&self, batch_index: u64) -> Option<(u64, usize)> {
+        let shard_config = *self.shard_config.read();
+        if !shard_config.in_range(batch_index) {
+            return None;
+        }
+        let local_index = batch_index / shard_config.num_shard as u64;
+        let data_shards = self.config.data_shards as u64;
+        Some((
+            local_index / data_shards,
+            (local_index % data_shards) as usize,
+        ))
+    }
+
+    fn member_index(&self, group_index: u64, position: usize) -> u64 {
+        let shard_config = *self.shard_config.read();
+        (group_index * self.config.data_shards as u64 + position as u64)
+            * shard_config.num_shard as u64
+            + shard_config.shard_id as u64
+    }
+
+    /// Whether the group is built with the current grouping, so that it can be used to
+    /// reconstruct its members.
+    fn is_usable(&self, meta: &GroupMeta) -> bool {
+        let shard_config = *self.shard_config.read();
+        meta.data_shards as usize == self.config.data_shards
+            && meta.num_shard == shard_config.num_shard as u64
+            && meta.shard_id == shard_config.shard_id as u64
+    }
+
+    fn get_meta(&self, kvdb: &dyn ZgsKeyValueDB, group_index: u64) -> Result<Option<GroupMeta>> {
+        match kvdb.get(COL_ENTRY_BATCH_PARITY, &group_index.to_be_bytes())? {
+            Some(raw) => match GroupMeta::from_ssz_bytes(&raw) {
+                Ok(meta)
+                    if meta.lens.len() == meta.data_shards as usize
+                        && meta.checksums.len() == meta.data_shards as usize =>
+                {
+                    Ok(Some(meta))
+                }
+                _ => {
+                    warn!(group_index, "Invalid parity group metadata");
+                    Ok(None)
+                }
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn get_parity(
+        &self,
+        kvdb: &dyn ZgsKeyValueDB,
+        group_index: u64,
+        meta: &GroupMeta,
+    ) -> Result<Vec<Option<Vec<u8>>>> {
+        (0..meta.parity_shards as usize)
+            .map(|parity_index| {
+                Ok(kvdb
+                    .get(
+                        COL_ENTRY_BATCH_PARITY,
+                        &parity_key(group_index, parity_index),
+                    )?
+                    .filter(|shard| shard.len() as u64 == meta.shard_size))
+            })
+            .collect()
+    }
+
+    /// Must be called with `update_lock` held.
+    fn recover_locked(
+        &self,
+        kvdb: &dyn ZgsKeyValueDB,
+        batch_index: u64,
+        group_index: u64,
+        position: usize,
+        meta: &GroupMeta,
+    ) -> Result<Option<Vec<u8>>> {
+        let value = match self.reconstruct(kvdb, group_index, meta, Some(position))? {
+            Some(mut members) => members.swap_remove(position),
+            None => {
+                metrics::ENTRY_BATCH_UNRECOVERABLE.inc(1);
+                warn!(batch_index, "Failed to reconstruct entry batch from parity");
+                return Ok(None);
+            }
+        };
+        let key = batch_index.to_be_bytes();
+        if kvdb.get(COL_ENTRY_BATCH, &key)?.as_deref() == Some(value.as_slice()) {
+            return Ok(None);
+        }
+        kvdb.put(COL_ENTRY_BATCH, &key, &value)?;
+        metrics::ENTRY_BATCH_RECONSTRUCTED.inc(1);
+        debug!(batch_index, "Entry batch reconstructed from parity");
+        Ok(Some(value))
+    }
+
+    /// Reconstructs all the members of a group, where the member at `erased` and the ones
+    /// mismatching the metadata are treated as lost. Returns `None` if too many shards are
+    /// lost, or the parity is corrupted.
+    fn reconstruct(
+        &self,
+        kvdb: &dyn ZgsKeyValueDB,
+        group_index: u64,
+        meta: &GroupMeta,
+        erased: Option<usize>,
+    ) -> Result<Option<Vec<Vec<u8>>>> {
+        let shard_size = meta.shard_size as usize;
+        let mut shards = Vec::with_capacity((meta.data_shards + meta.parity_shards) as usize);
+        for position in 0..meta.data_shards as usize {
+            if erased == Some(position) {
+                shards.push(None);
+                continue;
+            }
+            let raw = kvdb.get(
+                COL_ENTRY_BATCH,
+                &self.member_index(group_index, position).to_be_bytes(),
+            )?;
+            if meta.matches(position, raw.as_deref()) {
+                let mut shard = raw.unwrap_or_default();
+                shard.resize(shard_size, 0);
+                shards.push(Some(shard));
+            } else {
+                shards.push(None);
+            }
+        }
+        shards.extend(self.get_parity(kvdb, group_index, meta)?);
+
+        let other_codec;
+        let codec = if meta.parity_shards as usize == self.config.parity_shards {
+            &self.codec
+        } else {
+            other_codec =
+                match ReedSolomon::new(meta.data_shards as usize, meta.parity_shards as usize) {
+                    Ok(codec) => codec,
+                    Err(e) => {
+                        warn!(group_index, ?e, "Invalid parity group metadata");
+                        return Ok(None);
+                    }
+                };
+            &other_codec
+        };
+        if let Err(e) = codec.reconstruct_data(&mut shards) {
+            debug!(group_index, ?e, "Failed to reconstruct parity group");
+            return Ok(None);
+        }
+
+        let mut members = Vec::with_capacity(meta.data_shards as usize);
+        for (position, shard) in shards
+            .into_iter()
+            .take(meta.data_shards as usize)
+            .enumerate()
+        {
+            let mut value = shard.expect("reconstructed");
+            value.truncate(meta.lens[position] as usize);
+            if !meta.matches(position, Some(&value)) {
+                // The parity itself is corrupted.
+                return Ok(None);
+            }
+            members.push(value);
+        }
+        Ok(Some(members))
+    }
+
+    /// Must be called with `update_lock` held.
+    fn update_group(
+        &self,
+        kvdb: &dyn ZgsKeyValueDB,
+        tx: &mut DBTransaction,
+        group_index: u64,
+        updates: BTreeMap<usize, Vec<u8>>,
+    ) -> Result<()> {
+        let meta = self.get_meta(kvdb, group_index)?;
+        let old_parity_shards = meta.as_ref().map_or(0, |meta| meta.parity_shards as usize);
+        if let Some(meta) = meta.filter(|meta| {
+            self.is_usable(meta) && meta.parity_shards as usize == self.config.parity_shards
+        }) {
+            let parity = self.get_parity(kvdb, group_index, &meta)?;
+            if parity.iter().all(Option::is_some) {
+                if let Some(old_values) = self.old_values(kvdb, group_index, &meta, &updates)? {
+                    let parity = parity.into_iter().map(Option::unwrap).collect();
+                    return self.apply_updates(tx, group_index, meta, parity, old_values, updates);
+                }
+            }
+            warn!(group_index, "Parity group is unrecoverable, rebuild it");
+        }
+        self.rebuild_group(kvdb, tx, group_index, old_parity_shards, updates)
+    }
+
+    /// Returns the values of the updated members which the parity is computed with.
+    fn old_values(
+        &self,
+        kvdb: &dyn ZgsKeyValueDB,
+        group_index: u64,
+        meta: &GroupMeta,
+        updates: &BTreeMap<usize, Vec<u8>>,
+    ) -> Result<Option<Vec<Vec<u8>>>> {
+        let mut old_values = Vec::with_capacity(updates.len());
+        for &position in updates.keys() {
+            let raw = kvdb.get(
+                COL_ENTRY_BATCH,
+                &self.member_index(group_index, position).to_be_bytes(),
+            )?;
+            if !meta.matches(position, raw.as_deref()) {
+                let members = match self.reconstruct(kvdb, group_index, meta, None)? {
+                    Some(members) => members,
+                    None => return Ok(None),
+                };
+                return Ok(Some(
+                    updates
+                        .keys()
+                        .map(|&position| members[position].clone())
+                        .collect(),
+                ));
+            }
+            old_values.push(raw.unwrap_or_default());
+        }
+        Ok(Some(old_values))
+    }
+
+    fn apply_updates(
+        &self,
+        tx: &mut DBTransaction,
+        group_index: u64,
+        mut meta: GroupMeta,
+        mut parity: Vec<Vec<u8>>,
+        old_values: Vec<Vec<u8>>,
+        updates: BTreeMap<usize, Vec<u8>>,
+    ) -> Result<()> {
+        let shard_size = updates
+            .values()
+            .map(|value| value.len() as u64)
+            .fold(meta.shard_size, u64::max) as usize;
+        for shard in parity.iter_mut() {
+            shard.resize(shard_size, 0);
+        }
+        // The code is linear, so the parity is updated with the contribution of the
+        // difference between the old and the new value.
+        let mut contribution = vec![vec![0u8; shard_size]; parity.len()];
+        for ((position, value), old_value) in updates.into_iter().zip(old_values) {
+            let mut delta = old_value;
+            delta.resize(shard_size, 0);
+            xor_into(&mut delta, &value);
+            if delta.iter().any(|&b| b != 0) {
+                self.codec
+                    .encode_single_sep(position, &delta, &mut contribution)
+                    .map_err(|e| anyhow!("failed to encode parity: {:?}", e))?;
+                for (shard, c) in parity.iter_mut().zip(contribution.iter_mut()) {
+                    xor_into(shard, c);
+                    c.fill(0);
+                }
+            }
+            meta.lens[position] = value.len() as u64;
+            meta.checksums[position] = crc32fast::hash(&value);
+        }
+        meta.shard_size = shard_size as u64;
+        put_group(tx, group_index, &meta, &parity);
+        Ok(())
+    }
+
+    /// Computes the parity of a group from scratch with the stored members.
+    fn rebuild_group(
+        &self,
+        kvdb: &dyn ZgsKeyValueDB,
+        tx: &mut DBTransaction,
+        group_index: u64,
+        old_parity_shards: usize,
+        mut updates: BTreeMap<usize, Vec<u8>>,
+    ) -> Result<()> {
+        let mut values = Vec::with_capacity(self.config.data_shards);
+        for position in 0..self.config.data_shards {
+            let value = match updates.remove(&position) {
+                Some(value) => value,
+                None => kvdb
+                    .get(
+                        COL_ENTRY_BATCH,
+                        &self.member_index(group_index, position).to_be_bytes(),
+                    )?
+                    .unwrap_or_default(),
+            };
+            values.push(value);
+        }
+        let shard_config = *self.shard_config.read();
+        let meta = GroupMeta {
+            data_shards: self.config.data_shards as u32,
+            parity_shards: self.config.parity_shards as u32,
+            num_shard: shard_config.num_shard as u64,
+            shard_id: shard_config.shard_id as u64,
+            // Shards cannot be empty.
+            shard_size: values.iter().map(Vec::len).max().unwrap_or(0).max(1) as u64,
+            lens: values.iter().map(|value| value.len() as u64).collect(),
+            checksums: values.iter().map(|value| crc32fast::hash(value)).collect(),
+        };
+        for value in values.iter_mut() {
+            value.resize(meta.shard_size as usize, 0);
+        }
+        let mut parity = vec![vec![0u8; meta.shard_size as usize]; self.config.parity_shards];
+        self.codec
+            .encode_sep(&values, &mut parity)
+            .map_err(|e| anyhow!("failed to encode parity: {:?}", e))?;
+
+        for parity_index in self.config.parity_shards..old_parity_shards {
+            tx.delete(
+                COL_ENTRY_BATCH_PARITY,
+                &parity_key(group_index, parity_index),
+            );
+        }
+        put_group(tx, group_index, &meta, &parity);
+        Ok(())
+    }
+}
+
+fn put_group(tx: &mut DBTransaction, group_index: u64, meta: &GroupMeta, parity: &[Vec<u8>]) {
+    if meta.is_empty() {
+        tx.delete(COL_ENTRY_BATCH_PARITY, &group_index.to_be_bytes());
+        for parity_index in 0..parity.len() {
+            tx.delete(
+                COL_ENTRY_BATCH_PARITY,
+                &parity_key(group_index, parity_index),
+            );
+        }
+        return;
+    }
+    tx.put(
+        COL_ENTRY_BATCH_PARITY,
+        &group_index.to_be_bytes(),
+        &meta.as_ssz_bytes(),
+    );
+    for (parity_index, shard) in parity.iter().enumerate() {
+        tx.put(
+            COL_ENTRY_BATCH_PARITY,
+            &parity_key(group_index, parity_index),
+            shard,
+        );
+    }
+}
+
+fn parity_key(group_index: u64, parity_index: usize) -> Vec<u8> {
+    let mut key = group_index.to_be_bytes().to_vec();
+    key.push(parity_index as u8);
+    key
+}
+
+fn xor_into(dst: &mut [u8], src: &[u8]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d ^= s;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_store::flow_store::FlowConfig;
+    use crate::log_store::log_manager::{LogConfig, COL_NUM};
+    use crate::log_store::test_utils::put_tx;
+    use crate::log_store::{LogStoreChunkRead, LogStoreWrite};
+    use crate::LogManager;
+
+    #[test]
+    fn test_reconstruct_entry_batch() {
+        let flow_db: Arc<dyn ZgsKeyValueDB> = Arc::new(kvdb_memorydb::create(COL_NUM));
+        let data_db: Arc<dyn ZgsKeyValueDB> = Arc::new(kvdb_memorydb::create(COL_NUM));
+        let parity_config = ParityConfig {
+            data_shards: 4,
+            parity_shards: 2,
+        };
+        let config = LogConfig {
+            flow: FlowConfig {
+                parity: Some(parity_config),
+                ..Default::default()
+            },
+        };
+        let mut store = LogManager::new(flow_db, data_db.clone(), config).unwrap();
+        put_tx(&mut store, 0x1800, 0);
+        put_tx(&mut store, 0x300, 1);
+        let expected = store
+            .get_chunks_by_tx_and_index_range(0, 0, 0x1800)
+            .unwrap()
+            .unwrap();
+
+        // Parity updated incrementally is the same as the one computed from scratch, except
+        // for the zero padding.
+        let parity = EntryBatchParity::new(parity_config, Default::default()).unwrap();
+        let stored_parity = |group_index: u64| -> Vec<Vec<u8>> {
+            let meta = parity
+                .get_meta(data_db.as_ref(), group_index)
+                .unwrap()
+                .unwrap();
+            parity
+                .get_parity(data_db.as_ref(), group_index, &meta)
+                .unwrap()
+                .into_iter()
+                .map(Option::unwrap)
+                .collect()
+        };
+        let incremental = stored_parity(1);
+        let mut tx = data_db.transaction();
+        parity
+            .rebuild_group(data_db.as_ref(), &mut tx, 1, 0, BTreeMap::new())
+            .unwrap();
+        data_db.write(tx).unwrap();
+        for (incremental, rebuilt) in incremental.iter().zip(stored_parity(1)) {
+            assert_eq!(&incremental[..rebuilt.len()], rebuilt.as_slice());
+            assert!(incremental[rebuilt.len()..].iter().all(|&b| b == 0));
+        }
+
+        // Corrupt one batch and remove another in the same group.
+        let mut value = data_db
+            .get(COL_ENTRY_BATCH, &5u64.to_be_bytes())
+            .unwrap()
+            .unwrap();
+        *value.last_mut().unwrap() ^= 1;
+        data_db
+            .put(COL_ENTRY_BATCH, &5u64.to_be_bytes(), &value)
+            .unwrap();
+        data_db
+            .delete(COL_ENTRY_BATCH, &6u64.to_be_bytes())
+            .unwrap();
+        let chunks = store
+            .get_chunks_by_tx_and_index_range(0, 0, 0x1800)
+            .unwrap()
+            .unwrap();
+        assert_eq!(chunks.data, expected.data);
+        // The reconstructed batches are written back.
+        let meta = parity.get_meta(data_db.as_ref(), 1).unwrap().unwrap();
+        for (position, batch_index) in [(1, 5u64), (2, 6)] {
+            let raw = data_db
+                .get(COL_ENTRY_BATCH, &batch_index.to_be_bytes())
+                .unwrap();
+            assert!(meta.matches(position, raw.as_deref()));
+        }
+
+        // Parity is kept consistent after the last batch of the group is truncated.
+        store.revert_to(0).unwrap();
+        data_db
+            .delete(COL_ENTRY_BATCH, &9u64.to_be_bytes())
+            .unwrap();
+        let chunks = store
+            .get_chunks_by_tx_and_index_range(0, 0, 0x1800)
+            .unwrap()
+            .unwrap();
+        assert_eq!(chunks.data, expected.data);
+
+        // Too many lost batches in a group.
+        for batch_index in 4u64..7 {
+            data_db
+                .delete(COL_ENTRY_BATCH, &batch_index.to_be_bytes())
+                .unwrap();
+        }
+        assert!(store
+            .get_chunks_by_tx_and_index_range(0, 0, 0x1800)
+            .unwrap()
+            .is_none());
+    }
+}
@@ -5,15 +5,18 @@ use std::{
 };
 
 use parking_lot::RwLock;
-use zgs_spec::SEALS_PER_LOAD;
 
 pub struct SealTaskManager {
-    // TODO(kevin): This is an in-memory cache for recording which chunks are ready for sealing. It should be persisted on disk.
+    // This is an in-memory cache for recording which chunks are ready for sealing, which is
+    // persisted in `COL_SEAL_TASK` by the flow store.
     pub to_seal_set: RwLock<BTreeMap<usize, u64>>,
     // Data sealing is an asynchronized process.
     // The sealing service uses the version number to distinguish if revert happens during sealing.
     to_seal_version: AtomicU64,
     last_pull_time: AtomicU64,
+    // The number of sealed seals in the store, which is persisted by the flow store together
+    // with the entry batches.
+    num_sealed: AtomicU64,
 }
 
 impl Default for SealTaskManager {
@@ -22,6 +25,7 @@ impl Default for SealTaskManager {
             to_seal_set: Default::default(),
             to_seal_version: Default::default(),
             last_pull_time: AtomicU64::new(current_timestamp()),
+            num_sealed: Default::default(),
         }
     }
 }
@@ -36,15 +40,25 @@ fn current_timestamp() -> u64 {
 const SEAL_TASK_PULL_TIMEOUT_SECONDS: u64 = 300;
 
 impl SealTaskManager {
-    pub fn delete_batch_list(&self, batch_list: &[u64]) {
-        let mut to_seal_set = self.to_seal_set.write();
-        for batch_index in batch_list {
-            for seal_index in (*batch_index as usize) * SEALS_PER_LOAD
-                ..(*batch_index as usize + 1) * SEALS_PER_LOAD
-            {
-                to_seal_set.remove(&seal_index);
-            }
-        }
+    /// Creates a manager with the seal tasks and the number of sealed seals persisted before
+    /// restart.
+    pub fn with_tasks(seal_indices: Vec<usize>, num_sealed: u64) -> Self {
+        let manager = Self::default();
+        manager.set_num_sealed(num_sealed);
+        let version = manager.to_seal_version();
+        manager
+            .to_seal_set
+            .write()
+            .extend(seal_indices.into_iter().map(|x| (x, version)));
+        manager
+    }
+
+    pub fn num_sealed(&self) -> u64 {
+        self.num_sealed.load(Ordering::Relaxed)
+    }
+
+    pub fn set_num_sealed(&self, num_sealed: u64) {
+        self.num_sealed.store(num_sealed, Ordering::Relaxed)
     }
 
     /// Record the latest timestamp that the miner thread pull seal tasks from the seal status.
@@ -0,0 +1,52 @@
+//! Fixtures shared by the tests of the log store and the tools built on it.
+
+use crate::log_store::log_manager::{
+    sub_merkle_tree, tx_subtree_root_list_padded, LogManager, PORA_CHUNK_SIZE,
+};
+use crate::log_store::{LogStoreChunkWrite, LogStoreRead, LogStoreWrite};
+use shared_types::{ChunkArray, Transaction, CHUNK_SIZE};
+use std::cmp;
+
+/// Puts a finalized tx of `chunk_count` chunks.
+pub(crate) fn put_tx(store: &mut LogManager, chunk_count: usize, seq: u64) {
+    let (tx, data) = put_tx_without_data(store, chunk_count, seq);
+    for start_index in (0..chunk_count).step_by(PORA_CHUNK_SIZE) {
+        let end = cmp::min((start_index + PORA_CHUNK_SIZE) * CHUNK_SIZE, data.len());
+        let chunk_array = ChunkArray {
+            data: data[start_index * CHUNK_SIZE..end].to_vec(),
+            start_index: start_index as u64,
+        };
+        store.put_chunks(tx.seq, chunk_array.clone()).unwrap();
+    }
+    store.finalize_tx(tx.seq).unwrap();
+}
+
+/// Puts a tx without chunks, and returns the tx along with its data.
+pub(crate) fn put_tx_without_data(
+    store: &mut LogManager,
+    chunk_count: usize,
+    seq: u64,
+) -> (Transaction, Vec<u8>) {
+    let data_size = CHUNK_SIZE * chunk_count;
+    let mut data = vec![0u8; data_size];
+    for i in 0..chunk_count {
+        data[i * CHUNK_SIZE..(i * CHUNK_SIZE + 8)].copy_from_slice(&(seq + 1).to_be_bytes());
+    }
+    let tx_merkle = sub_merkle_tree(&data).unwrap();
+    let merkle_nodes = tx_subtree_root_list_padded(&data);
+    let flow_len = store.get_context().unwrap().1;
+    let first_subtree_size = 1 << (merkle_nodes.first().unwrap().0 - 1);
+    let start_entry_index = ((flow_len - 1) / first_subtree_size + 1) * first_subtree_size;
+    let tx = Transaction {
+        stream_ids: vec![],
+        size: data_size as u64,
+        data_merkle_root: tx_merkle.root().into(),
+        seq,
+        data: vec![],
+        start_entry_index,
+        // TODO: This can come from `tx_merkle`.
+        merkle_nodes,
+    };
+    store.put_tx(tx.clone()).unwrap();
+    (tx, data)
+}
@@ -1,13 +1,19 @@
+use crate::log_store::flow_store::NUM_SEALED_KEY;
 use crate::log_store::log_manager::{
     data_to_merkle_leaves, sub_merkle_tree, tx_subtree_root_list_padded, LogConfig, LogManager,
-    PORA_CHUNK_SIZE,
+    COL_MISC, COL_NUM, COL_SEAL_TASK, PORA_CHUNK_SIZE,
+};
+use crate::log_store::test_utils::{put_tx, put_tx_without_data};
+use crate::log_store::{
+    LogStoreChunkRead, LogStoreChunkWrite, LogStoreRead, LogStoreWrite, SealAnswer,
 };
-use crate::log_store::{LogStoreChunkRead, LogStoreChunkWrite, LogStoreRead, LogStoreWrite};
 use append_merkle::{Algorithm, AppendMerkleTree, MerkleTreeRead, Sha3Algorithm};
 use ethereum_types::H256;
+use kvdb::KeyValueDB;
 use rand::random;
 use shared_types::{compute_padded_chunk_size, ChunkArray, Transaction, CHUNK_SIZE};
 use std::cmp;
+use std::sync::Arc;
 
 #[test]
 fn test_put_get() {
@@ -158,6 +164,162 @@ fn test_revert() {
     put_tx(&mut store, 1, 2);
 }
 
+#[test]
+fn test_shard_parity() {
+    let mut store = create_store();
+    put_tx(&mut store, 1, 0);
+    put_tx(&mut store, 1, 1);
+    store.put_shard_parity(0, 0, vec![1; 8]).unwrap();
+    store.put_shard_parity(1, 0, vec![2; 8]).unwrap();
+    store.put_shard_parity(1, 3, vec![3; 8]).unwrap();
+    assert_eq!(store.get_shard_parity(1, 3).unwrap(), Some(vec![3; 8]));
+    assert_eq!(store.get_shard_parity(1, 1).unwrap(), None);
+
+    // Parity of reverted txs is removed.
+    store.revert_to(0).unwrap();
+    assert_eq!(store.get_shard_parity(0, 0).unwrap(), Some(vec![1; 8]));
+    assert_eq!(store.get_shard_parity(1, 0).unwrap(), None);
+    assert_eq!(store.get_shard_parity(1, 3).unwrap(), None);
+
+    // Parity of pruned txs is removed.
+    store.prune_tx(0).unwrap();
+    assert_eq!(store.get_shard_parity(0, 0).unwrap(), None);
+}
+
+#[test]
+fn test_available_entry_ranges() {
+    let mut store = create_store();
+    let chunk_count = PORA_CHUNK_SIZE * 3;
+    let (tx, data) = put_tx_without_data(&mut store, chunk_count, 0);
+    let start = tx.start_entry_index;
+    let end = start + chunk_count as u64;
+    assert!(store
+        .get_available_entry_ranges(start, end)
+        .unwrap()
+        .is_empty());
+
+    // Put the first and the last batch.
+    for start_index in [0, PORA_CHUNK_SIZE * 2] {
+        let chunk_array = ChunkArray {
+            data: data[start_index * CHUNK_SIZE..(start_index + PORA_CHUNK_SIZE) * CHUNK_SIZE]
+                .to_vec(),
+            start_index: start_index as u64,
+        };
+        store.put_chunks(tx.seq, chunk_array).unwrap();
+    }
+    let p = PORA_CHUNK_SIZE as u64;
+    assert_eq!(
+        store.get_available_entry_ranges(start, end).unwrap(),
+        vec![(start, start + p), (start + 2 * p, end)]
+    );
+    // Ranges are clipped by the query range.
+    assert_eq!(
+        store
+            .get_available_entry_ranges(start + 10, start + 2 * p + 10)
+            .unwrap(),
+        vec![(start + 10, start + p), (start + 2 * p, start + 2 * p + 10)]
+    );
+
+    // Put the middle batch, and all ranges are merged.
+    let chunk_array = ChunkArray {
+        data: data[PORA_CHUNK_SIZE * CHUNK_SIZE..PORA_CHUNK_SIZE * 2 * CHUNK_SIZE].to_vec(),
+        start_index: PORA_CHUNK_SIZE as u64,
+    };
+    store.put_chunks(tx.seq, chunk_array).unwrap();
+    assert_eq!(
+        store.get_available_entry_ranges(start, end).unwrap(),
+        vec![(start, end)]
+    );
+}
+
+#[test]
+fn test_seal_tasks_persisted() {
+    let flow_db = Arc::new(kvdb_memorydb::create(COL_NUM));
+    let data_db = Arc::new(kvdb_memorydb::create(COL_NUM));
+    let open = || LogManager::new(flow_db.clone(), data_db.clone(), LogConfig::default()).unwrap();
+
+    let mut store = open();
+    put_tx(&mut store, PORA_CHUNK_SIZE * 3, 0);
+    let num_tasks = store.get_num_seal_tasks().unwrap();
+    assert!(num_tasks > 0);
+
+    // Seal the first load chunk.
+    let answers: Vec<_> = store
+        .pull_seal_chunk(usize::MAX, 1)
+        .unwrap()
+        .unwrap()
+        .into_iter()
+        .map(|task| SealAnswer {
+            seal_index: task.seal_index,
+            version: task.version,
+            sealed_data: task.non_sealed_data,
+            miner_id: H256::zero(),
+            seal_context: H256::zero(),
+            context_end_seal: 0,
+        })
+        .collect();
+    let num_sealed = answers.len() as u64;
+    store.submit_seal_result(answers).unwrap();
+    assert_eq!(store.get_num_seal_tasks().unwrap(), num_tasks - num_sealed);
+    assert_eq!(store.get_num_sealed_seals().unwrap(), num_sealed);
+
+    // The remaining tasks are resumed after restart.
+    drop(store);
+    let store = open();
+    assert_eq!(store.get_num_seal_tasks().unwrap(), num_tasks - num_sealed);
+    assert_eq!(store.get_num_sealed_seals().unwrap(), num_sealed);
+
+    // The tasks of reverted data are removed.
+    store.revert_to(0u64.wrapping_sub(1)).unwrap();
+    let num_reverted_tasks = store.get_num_seal_tasks().unwrap();
+    assert!(num_reverted_tasks < num_tasks - num_sealed);
+    assert_eq!(store.get_num_sealed_seals().unwrap(), 0);
+    drop(store);
+    let store = open();
+    assert_eq!(store.get_num_seal_tasks().unwrap(), num_reverted_tasks);
+    assert_eq!(store.get_num_sealed_seals().unwrap(), 0);
+}
+
+#[test]
+fn test_seal_tasks_rebuilt() {
+    let flow_db = Arc::new(kvdb_memorydb::create(COL_NUM));
+    let data_db = Arc::new(kvdb_memorydb::create(COL_NUM));
+    let open = || LogManager::new(flow_db.clone(), data_db.clone(), LogConfig::default()).unwrap();
+
+    let mut store = open();
+    put_tx(&mut store, PORA_CHUNK_SIZE * 3, 0);
+    let num_tasks = store.get_num_seal_tasks().unwrap();
+    let answers: Vec<_> = store
+        .pull_seal_chunk(usize::MAX, 1)
+        .unwrap()
+        .unwrap()
+        .into_iter()
+        .map(|task| SealAnswer {
+            seal_index: task.seal_index,
+            version: task.version,
+            sealed_data: task.non_sealed_data,
+            miner_id: H256::zero(),
+            seal_context: H256::zero(),
+            context_end_seal: 0,
+        })
+        .collect();
+    let num_sealed = answers.len() as u64;
+    store.submit_seal_result(answers).unwrap();
+    drop(store);
+
+    // A db created before the seal tasks are persisted.
+    let mut tx = data_db.transaction();
+    tx.delete_prefix(COL_SEAL_TASK, &[]);
+    tx.delete(COL_MISC, NUM_SEALED_KEY);
+    data_db.write(tx).unwrap();
+
+    for _ in 0..2 {
+        let store = open();
+        assert_eq!(store.get_num_seal_tasks().unwrap(), num_tasks - num_sealed);
+        assert_eq!(store.get_num_sealed_seals().unwrap(), num_sealed);
+    }
+}
+
 #[test]
 fn test_put_tx() {
     for i in 0..12 {
@@ -171,36 +333,3 @@ fn create_store() -> LogManager {
     let config = LogConfig::default();
     LogManager::memorydb(config).unwrap()
 }
-
-fn put_tx(store: &mut LogManager, chunk_count: usize, seq: u64) {
-    let data_size = CHUNK_SIZE * chunk_count;
-    let mut data = vec![0u8; data_size];
-    for i in 0..chunk_count {
-        data[i * CHUNK_SIZE..(i * CHUNK_SIZE + 8)].copy_from_slice(&(seq + 1).to_be_bytes());
-    }
-    let tx_merkle = sub_merkle_tree(&data).unwrap();
-    let merkle_nodes = tx_subtree_root_list_padded(&data);
-    let flow_len = store.get_context().unwrap().1;
-    let first_subtree_size = 1 << (merkle_nodes.first().unwrap().0 - 1);
-    let start_entry_index = ((flow_len - 1) / first_subtree_size + 1) * first_subtree_size;
-    let tx = Transaction {
-        stream_ids: vec![],
-        size: data_size as u64,
-        data_merkle_root: tx_merkle.root().into(),
-        seq,
-        data: vec![],
-        start_entry_index,
-        // TODO: This can come from `tx_merkle`.
-        merkle_nodes,
-    };
-    store.put_tx(tx.clone()).unwrap();
-    for start_index in (0..chunk_count).step_by(PORA_CHUNK_SIZE) {
-        let end = cmp::min((start_index + PORA_CHUNK_SIZE) * CHUNK_SIZE, data.len());
-        let chunk_array = ChunkArray {
-            data: data[start_index * CHUNK_SIZE..end].to_vec(),
-            start_index: start_index as u64,
-        };
-        store.put_chunks(tx.seq, chunk_array.clone()).unwrap();
-    }
-    store.finalize_tx(tx.seq).unwrap();
-}
@@ -17,11 +17,14 @@ use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
+use tokio::sync::broadcast;
 use tracing::{error, instrument};
 
 const LOG_SYNC_PROGRESS_KEY: &str = "log_sync_progress";
 const NEXT_TX_KEY: &str = "next_tx_seq";
 const LOG_LATEST_BLOCK_NUMBER_KEY: &str = "log_latest_block_number_key";
+const LOG_FINALIZED_BLOCK_NUMBER_KEY: &str = "log_finalized_block_number";
+const FINALIZED_CHANNEL_CAPACITY: usize = 4096;
 
 #[derive(Debug)]
 pub enum TxStatus {
@@ -61,6 +64,8 @@ pub struct TransactionStore {
     data_kvdb: Arc<dyn ZgsKeyValueDB>,
     /// This is always updated before writing the database to ensure no intermediate states.
     next_tx_seq: AtomicU64,
+    /// Notifies the sequence numbers of finalized transactions.
+    finalized_send: broadcast::Sender<u64>,
 }
 
 impl TransactionStore {
@@ -72,10 +77,12 @@ impl TransactionStore {
             .get(COL_TX, NEXT_TX_KEY.as_bytes())?
             .map(|a| decode_tx_seq(&a))
             .unwrap_or(Ok(0))?;
+        let (finalized_send, _) = broadcast::channel(FINALIZED_CHANNEL_CAPACITY);
         Ok(Self {
             flow_kvdb,
             data_kvdb,
             next_tx_seq: AtomicU64::new(next_tx_seq),
+            finalized_send,
         })
     }
 
@@ -190,11 +197,18 @@ impl TransactionStore {
 
     #[instrument(skip(self))]
     pub fn finalize_tx(&self, tx_seq: u64) -> Result<()> {
-        Ok(self.data_kvdb.put(
+        self.data_kvdb.put(
             COL_TX_COMPLETED,
             &tx_seq.to_be_bytes(),
             &[TxStatus::Finalized.into()],
-        )?)
+        )?;
+        // fails only if there are no subscribers
+        let _ = self.finalized_send.send(tx_seq);
+        Ok(())
+    }
+
+    pub fn subscribe_finalized(&self) -> broadcast::Receiver<u64> {
+        self.finalized_send.subscribe()
     }
 
     #[instrument(skip(self))]
@@ -206,6 +220,14 @@ impl TransactionStore {
         )?)
     }
 
+    /// Remove the status of a tx, so its data will be synced again.
+    #[instrument(skip(self))]
+    pub fn unfinalize_tx(&self, tx_seq: u64) -> Result<()> {
+        Ok(self
+            .data_kvdb
+            .delete(COL_TX_COMPLETED, &tx_seq.to_be_bytes())?)
+    }
+
     pub fn get_tx_status(&self, tx_seq: u64) -> Result<Option<TxStatus>> {
         let value = try_option!(self
             .data_kvdb
@@ -280,6 +302,25 @@ impl TransactionStore {
         ))
     }
 
+    #[instrument(skip(self))]
+    pub fn put_log_finalized_block_number(&self, block_number: u64) -> Result<()> {
+        Ok(self.flow_kvdb.put(
+            COL_MISC,
+            LOG_FINALIZED_BLOCK_NUMBER_KEY.as_bytes(),
+            &block_number.as_ssz_bytes(),
+        )?)
+    }
+
+    #[instrument(skip(self))]
+    pub fn get_log_finalized_block_number(&self) -> Result<Option<u64>> {
+        Ok(Some(
+            <u64>::from_ssz_bytes(&try_option!(self
+                .flow_kvdb
+                .get(COL_MISC, LOG_FINALIZED_BLOCK_NUMBER_KEY.as_bytes())?))
+            .map_err(Error::from)?,
+        ))
+    }
+
     pub fn get_block_hash_by_number(
         &self,
         block_number: u64,
@@ -0,0 +1,976 @@
+//! An append-only, log-structured key-value store for a single column.
+//!
+//! Values of the managed column are appended to segment files under a directory,
+//! and an in-memory index maps every live key to its latest record. All the other
+//! columns are forwarded to the wrapped database. This is mainly used for
+//! `COL_ENTRY_BATCH`, whose bulk data would otherwise be rewritten over and over by
+//! the compaction of an LSM engine.
+//!
+//! Record layout: `crc32 (4) | op (1) | key_len (4) | value_len (4) | key | value`,
+//! where the checksum covers everything after itself.
+//!
+//! A transaction is committed by syncing its records to the segment files first, and
+//! then writing the other columns together with the end position of the records to
+//! the wrapped database in one batch. Records after the committed position are the
+//! leftover of an interrupted write, and are truncated on open.
+
+use crate::ZgsKeyValueDB;
+use kvdb::{DBKeyValue, DBOp, DBTransaction, DBValue, KeyValueDB};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+const SEGMENT_FILE_EXTENSION: &str = "seg";
+const HEADER_SIZE: usize = 13;
+const OP_PUT: u8 = 0;
+const OP_DELETE: u8 = 1;
+/// The key of the committed position in the managed column of the wrapped database.
+const COMMIT_KEY: &[u8] = b"segment_db_commit";
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SegmentConfig {
+    /// A new segment file is started once the active one reaches this size in bytes.
+    pub max_file_size: u64,
+    /// A sealed segment is compacted once the ratio of its dead bytes reaches this value.
+    pub compaction_ratio: f64,
+}
+
+impl Default for SegmentConfig {
+    fn default() -> Self {
+        Self {
+            max_file_size: 256 * 1024 * 1024,
+            compaction_ratio: 0.5,
+        }
+    }
+}
+
+pub struct SegmentDB {
+    inner: Arc<dyn ZgsKeyValueDB>,
+    col: u32,
+    dir: PathBuf,
+    config: SegmentConfig,
+    state: RwLock<State>,
+    /// Serializes the writes, which append records without holding `state`.
+    write_lock: Mutex<()>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct RecordLocation {
+    segment: u64,
+    offset: u64,
+    len: u64,
+}
+
+/// The end of the records committed by the last write.
+#[derive(Clone, Copy, Debug)]
+struct CommitPosition {
+    segment: u64,
+    offset: u64,
+}
+
+struct Segment {
+    file: Arc<File>,
+    size: u64,
+    dead: u64,
+}
+
+struct State {
+    index: BTreeMap<Vec<u8>, RecordLocation>,
+    segments: BTreeMap<u64, Segment>,
+    active: u64,
+}
+
+struct Record {
+    op: u8,
+    key: Vec<u8>,
+    value: Vec<u8>,
+    offset: u64,
+    len: u64,
+}
+
+/// Returns whether `db` has ever been wrapped by a `SegmentDB` managing column `col`,
+/// in which case the data of the column lives in the segment files.
+pub fn has_segment_data(db: &dyn ZgsKeyValueDB, col: u32) -> io::Result<bool> {
+    Ok(db.get(col, COMMIT_KEY)?.is_some())
+}
+
+impl SegmentDB {
+    /// Opens the segment files in `dir` and rebuilds the index of column `col`.
+    ///
+    /// The column must not hold any data in `inner`, otherwise the existing data
+    /// would be hidden after switching the backend.
+    pub fn open(
+        inner: Arc<dyn ZgsKeyValueDB>,
+        col: u32,
+        dir: impl AsRef<Path>,
+        config: SegmentConfig,
+    ) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(SEGMENT_FILE_EXTENSION) {
+                continue;
+            }
+            if let Some(id) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse::<u64>().ok())
+            {
+                ids.push(id);
+            }
+        }
+        ids.sort_unstable();
+
+        if inner
+            .iter(col)
+            .any(|kv| kv.map_or(true, |(key, _)| &key[..] != COMMIT_KEY))
+        {
+            return Err(io::Error::other(format!(
+                "column {} already has data in the inner database, cannot switch to segment files",
+                col
+            )));
+        }
+        let commit = match inner.get(col, COMMIT_KEY)? {
+            Some(value) => Some(decode_commit(&value)?),
+            None => None,
+        };
+        if let Some(commit) = commit {
+            if !ids.contains(&commit.segment) {
+                return Err(corrupted(format!(
+                    "committed segment {} is missing in {:?}",
+                    commit.segment, dir
+                )));
+            }
+        }
+
+        let mut state = State {
+            index: BTreeMap::new(),
+            segments: BTreeMap::new(),
+            active: 0,
+        };
+        for (i, id) in ids.iter().enumerate() {
+            let path = segment_path(&dir, *id);
+            // The records after the committed position, including whole segments, are
+            // not visible to anyone before the crash.
+            let (limit, is_tail) = match commit {
+                Some(commit) if *id > commit.segment => {
+                    warn!("remove uncommitted segment {:?}", path);
+                    fs::remove_file(&path)?;
+                    continue;
+                }
+                Some(commit) => (
+                    (*id == commit.segment).then_some(commit.offset),
+                    *id == commit.segment,
+                ),
+                None => (None, i + 1 == ids.len()),
+            };
+
+            let file = OpenOptions::new().read(true).write(true).open(&path)?;
+            let file_len = file.metadata()?.len();
+            state.segments.insert(
+                *id,
+                Segment {
+                    file: Arc::new(file.try_clone()?),
+                    size: 0,
+                    dead: 0,
+                },
+            );
+
+            let mut reader = RecordReader::new(file);
+            let mut valid_len = 0;
+            while let Some(record) = reader.next_record()? {
+                if record.offset + record.len > limit.unwrap_or(u64::MAX) {
+                    break;
+                }
+                valid_len = record.offset + record.len;
+                state.apply(*id, &record);
+            }
+            if valid_len < limit.unwrap_or(0) || (valid_len < file_len && !is_tail) {
+                return Err(corrupted(format!(
+                    "segment {:?} is corrupted at offset {}",
+                    path, valid_len
+                )));
+            }
+            if valid_len < file_len {
+                warn!(
+                    "truncate torn or uncommitted tail of segment {:?} from {} to {}",
+                    path, file_len, valid_len
+                );
+                state.segments[id].file.set_len(valid_len)?;
+            }
+            if let Some(segment) = state.segments.get_mut(id) {
+                segment.size = valid_len;
+            }
+        }
+        if state.segments.is_empty() {
+            let file = create_segment_file(&dir, 0)?;
+            state.segments.insert(
+                0,
+                Segment {
+                    file: Arc::new(file),
+                    size: 0,
+                    dead: 0,
+                },
+            );
+        }
+        state.active = *state
+            .segments
+            .last_key_value()
+            .expect("segments not empty")
+            .0;
+        debug!(
+            "segment db opened: dir={:?} segments={} keys={}",
+            dir,
+            state.segments.len(),
+            state.index.len()
+        );
+
+        Ok(Self {
+            inner,
+            col,
+            dir,
+            config,
+            state: RwLock::new(state),
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    fn appender(&self) -> io::Result<Appender<'_>> {
+        let state = self.state.read();
+        let active = &state.segments[&state.active];
+        Appender::new(
+            &self.dir,
+            self.config.max_file_size,
+            StagedSegment {
+                id: state.active,
+                file: active.file.clone(),
+                size: active.size,
+            },
+        )
+    }
+
+    /// Syncs the appended records, then writes `others` together with the new
+    /// committed position, and finally makes the records visible to the readers.
+    fn commit(&self, appender: Appender<'_>, mut others: DBTransaction) -> io::Result<()> {
+        if appender.records.is_empty() {
+            if !others.ops.is_empty() {
+                self.inner.write(others)?;
+            }
+            return Ok(());
+        }
+        let (segments, records) = appender.sync()?;
+        let last = segments.last().expect("active segment exists");
+        others.put(
+            self.col,
+            COMMIT_KEY,
+            &encode_commit(CommitPosition {
+                segment: last.id,
+                offset: last.size,
+            }),
+        );
+        self.inner.write(others)?;
+
+        let mut state = self.state.write();
+        state.active = last.id;
+        for staged in segments {
+            state
+                .segments
+                .entry(staged.id)
+                .or_insert(Segment {
+                    file: staged.file,
+                    size: 0,
+                    dead: 0,
+                })
+                .size = staged.size;
+        }
+        for (id, record) in &records {
+            state.apply(*id, record);
+        }
+        Ok(())
+    }
+
+    fn maybe_compact(&self) -> io::Result<()> {
+        let candidates: Vec<u64> = {
+            let state = self.state.read();
+            state
+                .segments
+                .iter()
+                .filter(|(id, segment)| {
+                    **id != state.active
+                        && segment.dead as f64 >= segment.size as f64 * self.config.compaction_ratio
+                })
+                .map(|(id, _)| *id)
+                .collect()
+        };
+        for id in candidates {
+            self.compact(id)?;
+        }
+        Ok(())
+    }
+
+    /// Moves the live records of segment `id` to the active segment and removes it.
+    fn compact(&self, id: u64) -> io::Result<()> {
+        let mut appender = self.appender()?;
+        {
+            let state = self.state.read();
+            let is_oldest = state.segments.keys().next() == Some(&id);
+            let segment = &state.segments[&id];
+            let mut file = segment.file.try_clone()?;
+            file.seek(SeekFrom::Start(0))?;
+            let mut reader = RecordReader::new(file);
+            while let Some(record) = reader.next_record()? {
+                if record.offset + record.len > segment.size {
+                    break;
+                }
+                let location = RecordLocation {
+                    segment: id,
+                    offset: record.offset,
+                    len: record.len,
+                };
+                match record.op {
+                    OP_PUT if state.index.get(&record.key) == Some(&location) => {
+                        appender.append(OP_PUT, &record.key, &record.value)?;
+                    }
+                    // A tombstone is only needed while an older segment may still hold
+                    // a put of the same key.
+                    OP_DELETE if !is_oldest && !state.index.contains_key(&record.key) => {
+                        appender.append(OP_DELETE, &record.key, &[])?;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let moved = appender.records.len();
+        // The moved records must be committed before the old segment is removed.
+        self.commit(appender, DBTransaction::new())?;
+        self.state.write().segments.remove(&id);
+        fs::remove_file(segment_path(&self.dir, id))?;
+        debug!("segment {} compacted, {} records moved", id, moved);
+        Ok(())
+    }
+}
+
+struct StagedSegment {
+    id: u64,
+    file: Arc<File>,
+    size: u64,
+}
+
+/// Appends the records of a write after the end of the active segment. The records
+/// are not visible to the readers until they are committed.
+struct Appender<'a> {
+    dir: &'a Path,
+    max_file_size: u64,
+    /// The segments written by this appender, starting with the active segment.
+    segments: Vec<StagedSegment>,
+    writer: BufWriter<File>,
+    records: Vec<(u64, Record)>,
+    /// Whether the keys of the appended records are live after this write.
+    live: BTreeMap<Vec<u8>, bool>,
+}
+
+impl<'a> Appender<'a> {
+    fn new(dir: &'a Path, max_file_size: u64, active: StagedSegment) -> io::Result<Self> {
+        Ok(Self {
+            dir,
+            max_file_size,
+            writer: segment_writer(&active)?,
+            segments: vec![active],
+            records: Vec::new(),
+            live: BTreeMap::new(),
+        })
+    }
+
+    fn append(&mut self, op: u8, key: &[u8], value: &[u8]) -> io::Result<()> {
+        let last = self.segments.last().expect("active segment exists");
+        if last.size >= self.max_file_size {
+            let id = last.id + 1;
+            self.writer.flush()?;
+            let segment = StagedSegment {
+                id,
+                file: Arc::new(create_segment_file(self.dir, id)?),
+                size: 0,
+            };
+            self.writer = segment_writer(&segment)?;
+            self.segments.push(segment);
+        }
+
+        let record = encode_record(op, key, value);
+        self.writer.write_all(&record)?;
+        let segment = self.segments.last_mut().expect("active segment exists");
+        self.records.push((
+            segment.id,
+            Record {
+                op,
+                key: key.to_vec(),
+                value: vec![],
+                offset: segment.size,
+                len: record.len() as u64,
+            },
+        ));
+        segment.size += record.len() as u64;
+        self.live.insert(key.to_vec(), op == OP_PUT);
+        Ok(())
+    }
+
+    fn is_live(&self, state: &State, key: &[u8]) -> bool {
+        match self.live.get(key) {
+            Some(live) => *live,
+            None => state.index.contains_key(key),
+        }
+    }
+
+    fn live_keys_with_prefix(&self, state: &State, prefix: &[u8]) -> Vec<Vec<u8>> {
+        let keys: BTreeSet<&Vec<u8>> = keys_with_prefix(&state.index, prefix)
+            .chain(keys_with_prefix(&self.live, prefix))
+            .collect();
+        keys.into_iter()
+            .filter(|key| self.is_live(state, key))
+            .cloned()
+            .collect()
+    }
+
+    /// Flushes the appended records and syncs them to the disk.
+    fn sync(mut self) -> io::Result<(Vec<StagedSegment>, Vec<(u64, Record)>)> {
+        self.writer.flush()?;
+        for segment in &self.segments {
+            segment.file.sync_data()?;
+        }
+        if self.segments.len() > 1 {
+            sync_dir(self.dir)?;
+        }
+        Ok((self.segments, self.records))
+    }
+}
+
+impl State {
+    /// Updates the index and the dead byte counters with a record in segment `id`.
+    fn apply(&mut self, id: u64, record: &Record) {
+        let old = match record.op {
+            OP_PUT => self.index.insert(
+                record.key.clone(),
+                RecordLocation {
+                    segment: id,
+                    offset: record.offset,
+                    len: record.len,
+                },
+            ),
+            _ => {
+                if let Some(segment) = self.segments.get_mut(&id) {
+                    segment.dead += record.len;
+                }
+                self.index.remove(&record.key)
+            }
+        };
+        if let Some(old) = old {
+            if let Some(segment) = self.segments.get_mut(&old.segment) {
+                segment.dead += old.len;
+            }
+        }
+    }
+
+    fn read_value(&self, key: &[u8]) -> io::Result<Option<DBValue>> {
+        let location = match self.index.get(key) {
+            Some(location) => *location,
+            None => return Ok(None),
+        };
+        let segment = self
+            .segments
+            .get(&location.segment)
+            .ok_or_else(|| corrupted(format!("segment {} missing", location.segment)))?;
+        let mut buf = vec![0u8; location.len as usize];
+        read_exact_at(&segment.file, &mut buf, location.offset)?;
+        match decode_record(&buf) {
+            Some((OP_PUT, k, v)) if k == key => Ok(Some(v.to_vec())),
+            _ => Err(corrupted(format!(
+                "invalid record in segment {} at offset {}",
+                location.segment, location.offset
+            ))),
+        }
+    }
+}
+
+fn keys_with_prefix<'a, V>(
+    map: &'a BTreeMap<Vec<u8>, V>,
+    prefix: &'a [u8],
+) -> impl Iterator<Item = &'a Vec<u8>> {
+    map.range::<[u8], _>((Bound::Included(prefix), Bound::Unbounded))
+        .take_while(move |(k, _)| k.starts_with(prefix))
+        .map(|(k, _)| k)
+}
+
+impl KeyValueDB for SegmentDB {
+    fn get(&self, col: u32, key: &[u8]) -> io::Result<Option<DBValue>> {
+        if col != self.col {
+            return self.inner.get(col, key);
+        }
+        self.state.read().read_value(key)
+    }
+
+    fn get_by_prefix(&self, col: u32, prefix: &[u8]) -> io::Result<Option<DBValue>> {
+        if col != self.col {
+            return self.inner.get_by_prefix(col, prefix);
+        }
+        let state = self.state.read();
+        match keys_with_prefix(&state.index, prefix).next() {
+            Some(key) => state.read_value(key),
+            None => Ok(None),
+        }
+    }
+
+    fn write(&self, transaction: DBTransaction) -> io::Result<()> {
+        let _write_guard = self.write_lock.lock();
+        let mut appender = self.appender()?;
+        let mut others = DBTransaction::new();
+        {
+            let state = self.state.read();
+            for op in transaction.ops {
+                match op {
+                    DBOp::Insert { col, key, value } if col == self.col => {
+                        appender.append(OP_PUT, &key, &value)?;
+                    }
+                    DBOp::Delete { col, key } if col == self.col => {
+                        if appender.is_live(&state, &key) {
+                            appender.append(OP_DELETE, &key, &[])?;
+                        }
+                    }
+                    DBOp::DeletePrefix { col, prefix } if col == self.col => {
+                        for key in appender.live_keys_with_prefix(&state, &prefix) {
+                            appender.append(OP_DELETE, &key, &[])?;
+                        }
+                    }
+                    op => others.ops.push(op),
+                }
+            }
+        }
+        self.commit(appender, others)?;
+        self.maybe_compact()
+    }
+
+    fn iter<'a>(&'a self, col: u32) -> Box<dyn Iterator<Item = io::Result<DBKeyValue>> + 'a> {
+        if col != self.col {
+            return self.inner.iter(col);
+        }
+        self.iter_with_prefix(col, &[])
+    }
+
+    fn iter_with_prefix<'a>(
+        &'a self,
+        col: u32,
+        prefix: &'a [u8],
+    ) -> Box<dyn Iterator<Item = io::Result<DBKeyValue>> + 'a> {
+        if col != self.col {
+            return self.inner.iter_with_prefix(col, prefix);
+        }
+        // Values are loaded lazily, as the column may be too large to fit in memory.
+        let keys: Vec<Vec<u8>> = keys_with_prefix(&self.state.read().index, prefix)
+            .cloned()
+            .collect();
+        Box::new(keys.into_iter().filter_map(move |key| {
+            match self.state.read().read_value(&key) {
+                Ok(Some(value)) => Some(Ok((key.into(), value))),
+                // Deleted after the iterator is created.
+                Ok(None) => None,
+                Err(e) => Some(Err(e)),
+            }
+        }))
+    }
+
+    fn io_stats(&self, kind: kvdb::IoStatsKind) -> kvdb::IoStats {
+        self.inner.io_stats(kind)
+    }
+}
+
+impl ZgsKeyValueDB for SegmentDB {
+    fn num_keys(&self, col: u32) -> io::Result<u64> {
+        if col != self.col {
+            return self.inner.num_keys(col);
+        }
+        Ok(self.state.read().index.len() as u64)
+    }
+}
+
+struct RecordReader {
+    reader: BufReader<File>,
+    offset: u64,
+}
+
+impl RecordReader {
+    fn new(file: File) -> Self {
+        Self {
+            reader: BufReader::new(file),
+            offset: 0,
+        }
+    }
+
+    /// Returns `None` at the end of the file or at the first torn or corrupted record.
+    fn next_record(&mut self) -> io::Result<Option<Record>> {
+        let mut header = [0u8; HEADER_SIZE];
+        if !read_full(&mut self.reader, &mut header)? {
+            return Ok(None);
+        }
+        let key_len = u32::from_be_bytes(header[5..9].try_into().unwrap()) as usize;
+        let value_len = u32::from_be_bytes(header[9..13].try_into().unwrap()) as usize;
+        let mut buf = vec![0u8; HEADER_SIZE + key_len + value_len];
+        buf[..HEADER_SIZE].copy_from_slice(&header);
+        if !read_full(&mut self.reader, &mut buf[HEADER_SIZE..])? {
+            return Ok(None);
+        }
+        let (op, key, value) = match decode_record(&buf) {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        let record = Record {
+            op,
+            key: key.to_vec(),
+            value: value.to_vec(),
+            offset: self.offset,
+            len: buf.len() as u64,
+        };
+        self.offset += record.len;
+        Ok(Some(record))
+    }
+}
+
+/// Returns `false` if the reader reaches the end of file before `buf` is filled.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<bool> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+fn encode_record(op: u8, key: &[u8], value: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(HEADER_SIZE + key.len() + value.len());
+    record.extend_from_slice(&[0u8; 4]);
+    record.push(op);
+    record.extend_from_slice(&(key.len() as u32).to_be_bytes());
+    record.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    record.extend_from_slice(key);
+    record.extend_from_slice(value);
+    let checksum = crc32fast::hash(&record[4..]);
+    record[..4].copy_from_slice(&checksum.to_be_bytes());
+    record
+}
+
+fn decode_record(record: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    if record.len() < HEADER_SIZE {
+        return None;
+    }
+    let checksum = u32::from_be_bytes(record[..4].try_into().unwrap());
+    let key_len = u32::from_be_bytes(record[5..9].try_into().unwrap()) as usize;
+    let value_len = u32::from_be_bytes(record[9..13].try_into().unwrap()) as usize;
+    if record.len() != HEADER_SIZE + key_len + value_len
+        || crc32fast::hash(&record[4..]) != checksum
+    {
+        return None;
+    }
+    let (key, value) = record[HEADER_SIZE..].split_at(key_len);
+    Some((record[4], key, value))
+}
+
+fn segment_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{:016}.{}", id, SEGMENT_FILE_EXTENSION))
+}
+
+/// Creates the file of a new segment. An existing file with the same id can only be
+/// left by a write that failed to commit, so it is overwritten.
+fn create_segment_file(dir: &Path, id: u64) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(segment_path(dir, id))
+}
+
+fn segment_writer(segment: &StagedSegment) -> io::Result<BufWriter<File>> {
+    let mut file = segment.file.try_clone()?;
+    file.seek(SeekFrom::Start(segment.size))?;
+    Ok(BufWriter::new(file))
+}
+
+fn encode_commit(commit: CommitPosition) -> Vec<u8> {
+    let mut value = commit.segment.to_be_bytes().to_vec();
+    value.extend_from_slice(&commit.offset.to_be_bytes());
+    value
+}
+
+fn decode_commit(value: &[u8]) -> io::Result<CommitPosition> {
+    if value.len() != 16 {
+        return Err(corrupted(format!("invalid commit position {:?}", value)));
+    }
+    Ok(CommitPosition {
+        segment: u64::from_be_bytes(value[..8].try_into().unwrap()),
+        offset: u64::from_be_bytes(value[8..].try_into().unwrap()),
+    })
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Makes the creation of the files in `dir` durable.
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    // Directories cannot be opened as files on Windows.
+    if cfg!(unix) {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+fn corrupted(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    const COL_DATA: u32 = 0;
+    const COL_OTHER: u32 = 1;
+
+    fn open(inner: &Arc<kvdb_memorydb::InMemory>, dir: &Path, max_file_size: u64) -> SegmentDB {
+        let config = SegmentConfig {
+            max_file_size,
+            compaction_ratio: 0.5,
+        };
+        SegmentDB::open(inner.clone(), COL_DATA, dir, config).unwrap()
+    }
+
+    #[test]
+    fn test_put_get_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = Arc::new(kvdb_memorydb::create(2));
+        let db = open(&inner, dir.path(), 1024);
+
+        db.put(COL_DATA, b"a1", b"v1").unwrap();
+        db.put(COL_DATA, b"a2", b"v2").unwrap();
+        db.put(COL_DATA, b"b1", b"v3").unwrap();
+        db.put(COL_OTHER, b"a1", b"other").unwrap();
+        db.put(COL_DATA, b"a1", b"v4").unwrap();
+
+        assert_eq!(db.get(COL_DATA, b"a1").unwrap(), Some(b"v4".to_vec()));
+        assert_eq!(db.get(COL_OTHER, b"a1").unwrap(), Some(b"other".to_vec()));
+        assert_eq!(inner.get(COL_DATA, b"a1").unwrap(), None);
+        assert_eq!(db.num_keys(COL_DATA).unwrap(), 3);
+        assert_eq!(
+            db.get_by_prefix(COL_DATA, b"b").unwrap(),
+            Some(b"v3".to_vec())
+        );
+
+        db.delete_with_prefix(COL_DATA, b"a").unwrap();
+        assert_eq!(db.get(COL_DATA, b"a2").unwrap(), None);
+        let keys: Vec<Vec<u8>> = db.iter(COL_DATA).map(|kv| kv.unwrap().0.to_vec()).collect();
+        assert_eq!(keys, vec![b"b1".to_vec()]);
+        assert_eq!(db.num_keys(COL_DATA).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_reopen_and_truncate_torn_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = Arc::new(kvdb_memorydb::create(2));
+        {
+            let db = open(&inner, dir.path(), 1024);
+            for i in 0u64..100 {
+                db.put(COL_DATA, &i.to_be_bytes(), &[i as u8; 40]).unwrap();
+            }
+            db.delete(COL_DATA, &5u64.to_be_bytes()).unwrap();
+        }
+
+        // Simulate a crash in the middle of appending a record.
+        let last = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .max()
+            .unwrap();
+        let mut file = OpenOptions::new().append(true).open(&last).unwrap();
+        file.write_all(&encode_record(OP_PUT, b"torn", &[1; 10])[..15])
+            .unwrap();
+
+        let db = open(&inner, dir.path(), 1024);
+        assert_eq!(db.num_keys(COL_DATA).unwrap(), 99);
+        assert_eq!(db.get(COL_DATA, &5u64.to_be_bytes()).unwrap(), None);
+        assert_eq!(
+            db.get(COL_DATA, &7u64.to_be_bytes()).unwrap(),
+            Some(vec![7; 40])
+        );
+        db.put(COL_DATA, b"new", b"value").unwrap();
+        drop(db);
+
+        let db = open(&inner, dir.path(), 1024);
+        assert_eq!(db.get(COL_DATA, b"new").unwrap(), Some(b"value".to_vec()));
+        assert_eq!(db.get(COL_DATA, b"torn").unwrap(), None);
+    }
+
+    #[test]
+    fn test_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = Arc::new(kvdb_memorydb::create(2));
+        let db = open(&inner, dir.path(), 512);
+        for round in 0..10u8 {
+            for i in 0u64..20 {
+                db.put(COL_DATA, &i.to_be_bytes(), &[round; 64]).unwrap();
+            }
+        }
+        for i in 0u64..10 {
+            db.delete(COL_DATA, &i.to_be_bytes()).unwrap();
+        }
+
+        // Overwritten segments are removed, so the disk usage stays bounded.
+        let state = db.state.read();
+        let total: u64 = state.segments.values().map(|s| s.size).sum();
+        let dead: u64 = state.segments.values().map(|s| s.dead).sum();
+        assert!(total - dead <= 10 * (HEADER_SIZE as u64 + 8 + 64) + 512);
+        assert!(state.segments.len() < 20);
+        drop(state);
+
+        let check = |db: &SegmentDB| {
+            assert_eq!(db.num_keys(COL_DATA).unwrap(), 10);
+            for i in 0u64..20 {
+                let expected = if i < 10 { None } else { Some(vec![9; 64]) };
+                assert_eq!(db.get(COL_DATA, &i.to_be_bytes()).unwrap(), expected);
+            }
+        };
+        check(&db);
+        drop(db);
+        check(&open(&inner, dir.path(), 512));
+    }
+
+    /// Forwards to an in-memory database, and fails the writes on demand.
+    struct FailingDB {
+        inner: kvdb_memorydb::InMemory,
+        fail: AtomicBool,
+    }
+
+    impl KeyValueDB for FailingDB {
+        fn get(&self, col: u32, key: &[u8]) -> io::Result<Option<DBValue>> {
+            self.inner.get(col, key)
+        }
+
+        fn get_by_prefix(&self, col: u32, prefix: &[u8]) -> io::Result<Option<DBValue>> {
+            self.inner.get_by_prefix(col, prefix)
+        }
+
+        fn write(&self, transaction: DBTransaction) -> io::Result<()> {
+            if self.fail.load(Ordering::SeqCst) {
+                return Err(io::Error::other("write failed"));
+            }
+            self.inner.write(transaction)
+        }
+
+        fn iter<'a>(&'a self, col: u32) -> Box<dyn Iterator<Item = io::Result<DBKeyValue>> + 'a> {
+            self.inner.iter(col)
+        }
+
+        fn iter_with_prefix<'a>(
+            &'a self,
+            col: u32,
+            prefix: &'a [u8],
+        ) -> Box<dyn Iterator<Item = io::Result<DBKeyValue>> + 'a> {
+            self.inner.iter_with_prefix(col, prefix)
+        }
+    }
+
+    impl ZgsKeyValueDB for FailingDB {
+        fn num_keys(&self, col: u32) -> io::Result<u64> {
+            self.inner.num_keys(col)
+        }
+    }
+
+    #[test]
+    fn test_write_is_atomic() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = Arc::new(FailingDB {
+            inner: kvdb_memorydb::create(2),
+            fail: AtomicBool::new(false),
+        });
+        let open = || {
+            let config = SegmentConfig {
+                max_file_size: 256,
+                compaction_ratio: 0.5,
+            };
+            SegmentDB::open(inner.clone(), COL_DATA, dir.path(), config).unwrap()
+        };
+        let db = open();
+        db.put(COL_DATA, b"committed", b"v1").unwrap();
+        assert!(has_segment_data(&*inner, COL_DATA).unwrap());
+
+        // Neither half of a transaction is visible if the other columns fail to write,
+        // even though its records have been appended to the segment files.
+        inner.fail.store(true, Ordering::SeqCst);
+        let mut tx = DBTransaction::new();
+        for i in 0u64..10 {
+            tx.put(COL_DATA, &i.to_be_bytes(), &[1; 64]);
+        }
+        tx.delete(COL_DATA, b"committed");
+        tx.put(COL_OTHER, b"other", b"v2");
+        assert!(db.write(tx).is_err());
+        assert_eq!(db.get(COL_DATA, &0u64.to_be_bytes()).unwrap(), None);
+        assert_eq!(
+            db.get(COL_DATA, b"committed").unwrap(),
+            Some(b"v1".to_vec())
+        );
+        assert_eq!(db.get(COL_OTHER, b"other").unwrap(), None);
+        drop(db);
+
+        inner.fail.store(false, Ordering::SeqCst);
+        let db = open();
+        assert_eq!(db.num_keys(COL_DATA).unwrap(), 1);
+        assert_eq!(
+            db.get(COL_DATA, b"committed").unwrap(),
+            Some(b"v1".to_vec())
+        );
+        let mut tx = DBTransaction::new();
+        tx.put(COL_DATA, b"key", b"v3");
+        tx.delete(COL_DATA, b"key");
+        tx.put(COL_DATA, b"prefix1", b"v4");
+        tx.delete_prefix(COL_DATA, b"prefix");
+        tx.put(COL_OTHER, b"other", b"v5");
+        db.write(tx).unwrap();
+        assert_eq!(db.get(COL_DATA, b"key").unwrap(), None);
+        assert_eq!(db.get(COL_DATA, b"prefix1").unwrap(), None);
+        drop(db);
+
+        let db = open();
+        assert_eq!(db.num_keys(COL_DATA).unwrap(), 1);
+        assert_eq!(db.get(COL_OTHER, b"other").unwrap(), Some(b"v5".to_vec()));
+    }
+
+    #[test]
+    fn test_refuse_existing_column_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = Arc::new(kvdb_memorydb::create(2));
+        inner.put(COL_DATA, b"k", b"v").unwrap();
+        assert!(SegmentDB::open(inner, COL_DATA, dir.path(), SegmentConfig::default()).is_err());
+    }
+}
@@ -0,0 +1,449 @@
+//! Offline snapshot of the log store.
+//!
+//! A snapshot archive contains all the columns of `flow_db` and `data_db`, so that a
+//! new node can be bootstrapped without replaying the chain log and syncing every
+//! file from peers. Both functions must run while the node is stopped.
+//!
+//! Archive layout:
+//! - header: `magic (8) | version (4) | flow root (32) | flow length (8)`
+//! - records: `tag = 1 (1) | db (1) | col (4) | key_len (4) | value_len (4) | key | value`
+//! - trailer: `tag = 0 (1) | num_records (8) | crc32 (4)`, where the checksum covers
+//!   everything before itself.
+
+use crate::config::Config as StorageConfig;
+use crate::log_store::log_manager::{open_databases, LogConfig, COL_NUM};
+use crate::log_store::LogStoreRead;
+use crate::{LogManager, ZgsKeyValueDB};
+use anyhow::{anyhow, bail, Result};
+use kvdb::DBTransaction;
+use shared_types::DataRoot;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+const MAGIC: &[u8; 8] = b"ZGSSNAP\0";
+const VERSION: u32 = 1;
+const TAG_END: u8 = 0;
+const TAG_RECORD: u8 = 1;
+const DB_FLOW: u8 = 0;
+const DB_DATA: u8 = 1;
+/// Flush the imported records to db once the pending batch reaches this size in bytes.
+const IMPORT_BATCH_BYTES: usize = 64 * 1024 * 1024;
+const PROGRESS_LOG_INTERVAL: u64 = 100_000;
+/// Directories of the log store in `db_dir`, which are replaced by the imported ones.
+const DB_DIRS: [&str; 3] = ["flow_db", "data_db", "entry_segments"];
+/// Directory in `db_dir` to import into, which replaces [`DB_DIRS`] once verified.
+const IMPORT_DIR: &str = "snapshot_import";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SnapshotInfo {
+    pub flow_root: DataRoot,
+    pub flow_length: u64,
+    pub num_records: u64,
+}
+
+/// Exports the log store in `config.db_dir` to the archive file `path`.
+pub fn export_to_file(config: &StorageConfig, path: impl AsRef<Path>) -> Result<SnapshotInfo> {
+    let (flow_db, data_db) = open_databases(config)?;
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path.as_ref())
+        .map_err(|e| anyhow!("failed to create {:?}: {:?}", path.as_ref(), e))?;
+    let mut writer = BufWriter::new(file);
+    let info = export(flow_db, data_db, config.log_config.clone(), &mut writer)?;
+    writer.into_inner()?.sync_all()?;
+    Ok(info)
+}
+
+/// Imports the archive file `path` into the empty log store in `config.db_dir`.
+///
+/// The archive is imported into a temporary directory, which replaces the log store only after
+/// the archive is verified, so a corrupted archive leaves the log store untouched.
+pub fn import_from_file(config: &StorageConfig, path: impl AsRef<Path>) -> Result<SnapshotInfo> {
+    {
+        let (flow_db, data_db) = open_databases(config)?;
+        ensure_empty(&flow_db, &data_db)?;
+    }
+    let file = File::open(path.as_ref())
+        .map_err(|e| anyhow!("failed to open {:?}: {:?}", path.as_ref(), e))?;
+
+    let import_dir = config.db_dir.join(IMPORT_DIR);
+    if import_dir.exists() {
+        // left by an interrupted import
+        fs::remove_dir_all(&import_dir)?;
+    }
+    let import_config = StorageConfig {
+        db_dir: import_dir.clone(),
+        ..config.clone()
+    };
+    // the databases are closed once `import` returns
+    let info = match open_databases(&import_config).and_then(|(flow_db, data_db)| {
+        import(
+            flow_db,
+            data_db,
+            config.log_config.clone(),
+            BufReader::new(file),
+        )
+    }) {
+        Ok(info) => info,
+        Err(e) => {
+            if let Err(e) = fs::remove_dir_all(&import_dir) {
+                warn!(?e, ?import_dir, "Failed to remove the imported databases");
+            }
+            return Err(e);
+        }
+    };
+
+    for name in DB_DIRS {
+        let (from, to) = (import_dir.join(name), config.db_dir.join(name));
+        if from.exists() {
+            if to.exists() {
+                fs::remove_dir_all(&to)?;
+            }
+            fs::rename(&from, &to)?;
+        }
+    }
+    fs::remove_dir_all(&import_dir)?;
+    Ok(info)
+}
+
+pub fn export(
+    flow_db: Arc<dyn ZgsKeyValueDB>,
+    data_db: Arc<dyn ZgsKeyValueDB>,
+    log_config: LogConfig,
+    writer: impl Write,
+) -> Result<SnapshotInfo> {
+    let (flow_root, flow_length) =
+        LogManager::new(flow_db.clone(), data_db.clone(), log_config)?.get_context()?;
+    info!(?flow_root, flow_length, "Start to export snapshot");
+
+    let mut writer = ChecksumWriter::new(writer);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_be_bytes())?;
+    writer.write_all(flow_root.as_bytes())?;
+    writer.write_all(&flow_length.to_be_bytes())?;
+
+    let mut num_records = 0;
+    for (db_id, db) in [(DB_FLOW, &flow_db), (DB_DATA, &data_db)] {
+        for col in 0..COL_NUM {
+            for kv in db.iter(col) {
+                let (key, value) = kv?;
+                writer.write_all(&[TAG_RECORD, db_id])?;
+                writer.write_all(&col.to_be_bytes())?;
+                writer.write_all(&(key.len() as u32).to_be_bytes())?;
+                writer.write_all(&(value.len() as u32).to_be_bytes())?;
+                writer.write_all(&key)?;
+                writer.write_all(&value)?;
+                num_records += 1;
+                if num_records % PROGRESS_LOG_INTERVAL == 0 {
+                    info!(num_records, "Exporting snapshot");
+                }
+            }
+        }
+    }
+
+    writer.write_all(&[TAG_END])?;
+    writer.write_all(&num_records.to_be_bytes())?;
+    let checksum = writer.hasher.clone().finalize();
+    writer.inner.write_all(&checksum.to_be_bytes())?;
+    writer.inner.flush()?;
+
+    info!(num_records, "Snapshot exported");
+    Ok(SnapshotInfo {
+        flow_root,
+        flow_length,
+        num_records,
+    })
+}
+
+/// Imports the archive into the empty databases, which are left partially written if it fails.
+pub fn import(
+    flow_db: Arc<dyn ZgsKeyValueDB>,
+    data_db: Arc<dyn ZgsKeyValueDB>,
+    log_config: LogConfig,
+    reader: impl Read,
+) -> Result<SnapshotInfo> {
+    ensure_empty(&flow_db, &data_db)?;
+
+    let mut reader = ChecksumReader::new(reader);
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        bail!("not a snapshot archive");
+    }
+    let version = read_u32(&mut reader)?;
+    if version != VERSION {
+        bail!("unsupported snapshot version {}", version);
+    }
+    let mut flow_root = DataRoot::zero();
+    reader.read_exact(flow_root.as_bytes_mut())?;
+    let flow_length = read_u64(&mut reader)?;
+    info!(?flow_root, flow_length, "Start to import snapshot");
+
+    let mut batches = [DBTransaction::new(), DBTransaction::new()];
+    let mut pending_bytes = 0;
+    let mut num_records = 0;
+    loop {
+        let mut tag = [0u8; 2];
+        reader.read_exact(&mut tag[..1])?;
+        match tag[0] {
+            TAG_RECORD => {}
+            TAG_END => break,
+            t => bail!("invalid record tag {}", t),
+        }
+        reader.read_exact(&mut tag[1..])?;
+        let db_id = tag[1];
+        if db_id != DB_FLOW && db_id != DB_DATA {
+            bail!("invalid db id {}", db_id);
+        }
+        let col = read_u32(&mut reader)?;
+        if col >= COL_NUM {
+            bail!("invalid column {}", col);
+        }
+        let key_len = read_u32(&mut reader)?;
+        let value_len = read_u32(&mut reader)?;
+        let key = read_vec(&mut reader, key_len)?;
+        let value = read_vec(&mut reader, value_len)?;
+
+        batches[db_id as usize].put_vec(col, &key, value);
+        pending_bytes += key.len() + value.len();
+        num_records += 1;
+        if pending_bytes >= IMPORT_BATCH_BYTES {
+            flush(&flow_db, &data_db, &mut batches)?;
+            pending_bytes = 0;
+        }
+        if num_records % PROGRESS_LOG_INTERVAL == 0 {
+            info!(num_records, "Importing snapshot");
+        }
+    }
+
+    let expected_records = read_u64(&mut reader)?;
+    let checksum = reader.hasher.clone().finalize();
+    let mut expected_checksum = [0u8; 4];
+    reader.inner.read_exact(&mut expected_checksum)?;
+    if expected_records != num_records || u32::from_be_bytes(expected_checksum) != checksum {
+        bail!("snapshot archive is corrupted");
+    }
+    flush(&flow_db, &data_db, &mut batches)?;
+
+    let context = LogManager::new(flow_db, data_db, log_config)?.get_context()?;
+    if context != (flow_root, flow_length) {
+        bail!(
+            "flow context mismatch after import: expected=({:?}, {}) actual=({:?}, {})",
+            flow_root,
+            flow_length,
+            context.0,
+            context.1
+        );
+    }
+
+    info!(num_records, "Snapshot imported");
+    Ok(SnapshotInfo {
+        flow_root,
+        flow_length,
+        num_records,
+    })
+}
+
+fn ensure_empty(flow_db: &Arc<dyn ZgsKeyValueDB>, data_db: &Arc<dyn ZgsKeyValueDB>) -> Result<()> {
+    for db in [flow_db, data_db] {
+        for col in 0..COL_NUM {
+            if db.iter(col).next().is_some() {
+                bail!("db is not empty, column {} has data", col);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn flush(
+    flow_db: &Arc<dyn ZgsKeyValueDB>,
+    data_db: &Arc<dyn ZgsKeyValueDB>,
+    batches: &mut [DBTransaction; 2],
+) -> Result<()> {
+    flow_db.write(std::mem::take(&mut batches[DB_FLOW as usize]))?;
+    data_db.write(std::mem::take(&mut batches[DB_DATA as usize]))?;
+    Ok(())
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+/// Reads `len` bytes, where the buffer grows with the bytes read rather than being allocated
+/// upfront for a length that may be corrupted.
+fn read_vec(reader: &mut impl Read, len: u32) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    reader.take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len as usize {
+        bail!("snapshot archive is truncated");
+    }
+    Ok(buf)
+}
+
+struct ChecksumWriter<W> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct ChecksumReader<R> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+}
+
+impl<R: Read> ChecksumReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+        }
+    }
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DbBackend;
+    use crate::log_store::test_utils::put_tx;
+
+    fn memorydb() -> Arc<dyn ZgsKeyValueDB> {
+        Arc::new(kvdb_memorydb::create(COL_NUM))
+    }
+
+    fn export_to_vec() -> (Vec<u8>, SnapshotInfo) {
+        let (flow_db, data_db) = (memorydb(), memorydb());
+        let mut store =
+            LogManager::new(flow_db.clone(), data_db.clone(), LogConfig::default()).unwrap();
+        put_tx(&mut store, 0x1000, 0);
+        put_tx(&mut store, 0x30, 1);
+        drop(store);
+
+        let mut archive = vec![];
+        let info = export(flow_db, data_db, LogConfig::default(), &mut archive).unwrap();
+        (archive, info)
+    }
+
+    #[test]
+    fn test_export_import() {
+        let (mut archive, exported) = export_to_vec();
+
+        let imported = import(
+            memorydb(),
+            memorydb(),
+            LogConfig::default(),
+            archive.as_slice(),
+        )
+        .unwrap();
+        assert_eq!(imported, exported);
+
+        // Corrupted archive.
+        let len = archive.len();
+        archive[len / 2] ^= 1;
+        assert!(import(
+            memorydb(),
+            memorydb(),
+            LogConfig::default(),
+            archive.as_slice()
+        )
+        .is_err());
+
+        // Not empty.
+        let flow_db = memorydb();
+        flow_db.put(0, b"key", b"value").unwrap();
+        assert!(import(
+            flow_db,
+            memorydb(),
+            LogConfig::default(),
+            archive.as_slice()
+        )
+        .is_err());
+
+        // Truncated in a record, whose length is not allocated upfront.
+        let header_len = MAGIC.len() + 4 + 32 + 8;
+        let mut truncated = archive[..header_len].to_vec();
+        truncated.extend_from_slice(&[TAG_RECORD, DB_FLOW, 0, 0, 0, 0]);
+        truncated.extend_from_slice(&u32::MAX.to_be_bytes());
+        truncated.extend_from_slice(&u32::MAX.to_be_bytes());
+        assert!(import(
+            memorydb(),
+            memorydb(),
+            LogConfig::default(),
+            truncated.as_slice()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_import_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = StorageConfig {
+            db_dir: dir.path().join("db"),
+            log_config: LogConfig::default(),
+            backend: DbBackend::RocksDB,
+            segment_config: Default::default(),
+        };
+        let (mut archive, exported) = export_to_vec();
+        let path = dir.path().join("snapshot");
+
+        // The log store is untouched by a corrupted archive.
+        let len = archive.len();
+        archive[len - 1] ^= 1;
+        fs::write(&path, &archive).unwrap();
+        assert!(import_from_file(&config, &path).is_err());
+        assert!(!config.db_dir.join(IMPORT_DIR).exists());
+        let (flow_db, data_db) = open_databases(&config).unwrap();
+        ensure_empty(&flow_db, &data_db).unwrap();
+        drop((flow_db, data_db));
+
+        archive[len - 1] ^= 1;
+        fs::write(&path, &archive).unwrap();
+        assert_eq!(import_from_file(&config, &path).unwrap(), exported);
+        assert!(!config.db_dir.join(IMPORT_DIR).exists());
+        let (flow_db, data_db) = open_databases(&config).unwrap();
+        let store = LogManager::new(flow_db, data_db, LogConfig::default()).unwrap();
+        assert_eq!(
+            store.get_context().unwrap(),
+            (exported.flow_root, exported.flow_length)
+        );
+    }
+}
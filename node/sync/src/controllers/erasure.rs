@@ -0,0 +1,582 @@
+//! Network level erasure coding of files across shards.
+//!
+//! When enabled, every `num_shard` consecutive segments in the flow form a stripe, in which the
+//! segment at position `i` is held by the nodes of shard `i`. Besides its data segment, the
+//! holder of shard `i` keeps `parity_per_shard` Reed-Solomon parity segments of each stripe,
+//! which is the least number so that the segments of any `min_shards` holders are enough to
+//! rebuild the whole stripe. Parity is computed per file, and the segments of a stripe out of
+//! the file are taken as zeros.
+//!
+//! Parity can not be proved by itself, so a rebuilt segment is only accepted along with a flow
+//! merkle proof, which is derived from the rebuilt stripe and the proof of another data segment
+//! in the stripe.
+
+use crate::Config;
+use append_merkle::{Algorithm, AppendMerkleTree, MerkleTreeRead, Proof, Sha3Algorithm};
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use shared_types::{compute_padded_chunk_size, FlowRangeProof, CHUNK_SIZE};
+use std::cmp;
+use storage::log_store::log_manager::{data_to_merkle_leaves, sector_to_segment, PORA_CHUNK_SIZE};
+use storage::H256;
+
+/// Size of a segment in bytes.
+pub const SEGMENT_SIZE: usize = PORA_CHUNK_SIZE * CHUNK_SIZE;
+
+/// Height of a segment in the flow merkle tree, whose leaves are chunks.
+const SEGMENT_HEIGHT: usize = PORA_CHUNK_SIZE.trailing_zeros() as usize;
+
+/// Maximum number of parity segments held by a shard, so that they fit in one RPC response.
+const MAX_PARITY_PER_SHARD: usize = 32;
+
+pub struct ErasureCoder {
+    num_shard: usize,
+    min_shards: usize,
+    parity_per_shard: usize,
+    codec: ReedSolomon,
+}
+
+impl ErasureCoder {
+    pub fn new(num_shard: usize, min_shards: usize) -> Result<Self, String> {
+        if num_shard < 2 || !num_shard.is_power_of_two() {
+            return Err(format!(
+                "Invalid erasure coding shard number {}, should be a power of two and at least 2",
+                num_shard
+            ));
+        }
+
+        if min_shards == 0 || min_shards >= num_shard {
+            return Err(format!(
+                "Invalid erasure coding min shards {}, expected [1, {})",
+                min_shards, num_shard
+            ));
+        }
+
+        let parity_per_shard = (num_shard - 1) / min_shards;
+        if parity_per_shard > MAX_PARITY_PER_SHARD || num_shard * (parity_per_shard + 1) > 256 {
+            return Err(format!(
+                "Too many parity segments for erasure coding {} of {} shards",
+                min_shards, num_shard
+            ));
+        }
+
+        let codec = ReedSolomon::new(num_shard, num_shard * parity_per_shard)
+            .map_err(|e| format!("Failed to create erasure codec: {:?}", e))?;
+
+        Ok(Self {
+            num_shard,
+            min_shards,
+            parity_per_shard,
+            codec,
+        })
+    }
+
+    /// Returns the erasure coder configured in `config`, or `None` if erasure coding is disabled.
+    pub fn from_config(config: &Config) -> Result<Option<Self>, String> {
+        if config.erasure_coding_num_shard == 0 {
+            return Ok(None);
+        }
+
+        Self::new(
+            config.erasure_coding_num_shard,
+            config.erasure_coding_min_shards,
+        )
+        .map(Some)
+    }
+
+    pub fn num_shard(&self) -> usize {
+        self.num_shard
+    }
+
+    pub fn min_shards(&self) -> usize {
+        self.min_shards
+    }
+
+    pub fn parity_per_shard(&self) -> usize {
+        self.parity_per_shard
+    }
+
+    /// Computes the parity segments of the stripe `data`, and returns the concatenated parity
+    /// held by `shard_id`.
+    pub fn encode(&self, data: &[Vec<u8>], shard_id: usize) -> Result<Vec<u8>, String> {
+        if shard_id >= self.num_shard {
+            return Err(format!("Invalid shard id {}", shard_id));
+        }
+
+        let mut parity = vec![vec![0u8; SEGMENT_SIZE]; self.num_shard * self.parity_per_shard];
+        self.codec
+            .encode_sep(data, &mut parity)
+            .map_err(|e| format!("Failed to encode stripe: {:?}", e))?;
+
+        let start = shard_id * self.parity_per_shard;
+        Ok(parity[start..start + self.parity_per_shard].concat())
+    }
+}
+
+/// Position of a file in the stripes of the flow.
+#[derive(Clone, Copy, Debug)]
+pub struct FileStripes {
+    num_shard: u64,
+    /// Index of the first file segment in the flow.
+    start_segment: u64,
+    /// Index of the first file chunk in the flow.
+    start_chunk: u64,
+    num_chunks: u64,
+    /// Number of chunks of the file in the flow, including the zeros padded after the file.
+    padded_chunks: u64,
+}
+
+impl FileStripes {
+    pub fn new(num_shard: usize, tx_start_chunk_in_flow: u64, num_chunks: u64) -> Self {
+        let (padded_chunks, _) = compute_padded_chunk_size(num_chunks as usize * CHUNK_SIZE);
+        Self {
+            num_shard: num_shard as u64,
+            start_segment: sector_to_segment(tx_start_chunk_in_flow) as u64,
+            start_chunk: tx_start_chunk_in_flow,
+            num_chunks,
+            padded_chunks: padded_chunks as u64,
+        }
+    }
+
+    pub fn num_segments(&self) -> u64 {
+        (self.num_chunks - 1) / PORA_CHUNK_SIZE as u64 + 1
+    }
+
+    /// Returns the index of the stripe which contains the file chunk `chunk`.
+    pub fn stripe_of_chunk(&self, chunk: u64) -> u64 {
+        (self.start_segment + chunk / PORA_CHUNK_SIZE as u64) / self.num_shard
+    }
+
+    /// Returns the index of the segment at `position` of `stripe` in the flow.
+    pub fn flow_segment(&self, stripe: u64, position: usize) -> u64 {
+        stripe * self.num_shard + position as u64
+    }
+
+    /// Returns the file segment at `position` of `stripe`, or `None` if out of the file.
+    pub fn file_segment(&self, stripe: u64, position: usize) -> Option<u64> {
+        let segment = self.flow_segment(stripe, position);
+        if segment < self.start_segment || segment >= self.start_segment + self.num_segments() {
+            None
+        } else {
+            Some(segment - self.start_segment)
+        }
+    }
+
+    /// Whether the segment at `position` of `stripe` lies entirely in the flow entries of the
+    /// file, so that its flow merkle root can be computed from the stripe.
+    pub fn in_file_entries(&self, stripe: u64, position: usize) -> bool {
+        let start = self.flow_segment(stripe, position) * PORA_CHUNK_SIZE as u64;
+        start >= self.start_chunk
+            && start + PORA_CHUNK_SIZE as u64 <= self.start_chunk + self.padded_chunks
+    }
+
+    /// Returns the position of the file segment `segment` in its stripe.
+    pub fn position(&self, segment: u64) -> usize {
+        ((self.start_segment + segment) % self.num_shard) as usize
+    }
+
+    /// Returns the file chunk range `[start, end)` of the file segment `segment`.
+    pub fn chunk_range(&self, segment: u64) -> (u64, u64) {
+        let start = segment * PORA_CHUNK_SIZE as u64;
+        (
+            start,
+            cmp::min(start + PORA_CHUNK_SIZE as u64, self.num_chunks),
+        )
+    }
+
+    /// Returns the first file chunk of `stripe`, which is `num_chunks` if `stripe` is after
+    /// the file.
+    pub fn first_chunk(&self, stripe: u64) -> u64 {
+        let segment = cmp::max(self.flow_segment(stripe, 0), self.start_segment);
+        cmp::min(
+            (segment - self.start_segment) * PORA_CHUNK_SIZE as u64,
+            self.num_chunks,
+        )
+    }
+}
+
+/// The segments of a stripe collected from peers.
+pub struct Stripe {
+    index: u64,
+    num_shard: usize,
+    parity_per_shard: usize,
+    /// Data segments followed by the parity segments, `None` if not available yet.
+    shards: Vec<Option<Vec<u8>>>,
+    /// Flow merkle proofs of the downloaded data segments.
+    proofs: Vec<Option<FlowRangeProof>>,
+}
+
+impl Stripe {
+    pub fn new(coder: &ErasureCoder, layout: &FileStripes, index: u64) -> Self {
+        let num_shard = coder.num_shard;
+        let mut shards = vec![None; num_shard * (coder.parity_per_shard + 1)];
+        for (position, shard) in shards.iter_mut().take(num_shard).enumerate() {
+            if layout.file_segment(index, position).is_none() {
+                *shard = Some(vec![0u8; SEGMENT_SIZE]);
+            }
+        }
+
+        Self {
+            index,
+            num_shard,
+            parity_per_shard: coder.parity_per_shard,
+            shards,
+            proofs: vec![None; num_shard],
+        }
+    }
+
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    pub fn has_data(&self, position: usize) -> bool {
+        self.shards[position].is_some()
+    }
+
+    pub fn has_parity(&self, position: usize) -> bool {
+        self.shards[self.num_shard + position * self.parity_per_shard].is_some()
+    }
+
+    /// Whether all the data segments of the stripe are available without decoding.
+    pub fn has_all_data(&self) -> bool {
+        self.shards[..self.num_shard]
+            .iter()
+            .all(|shard| shard.is_some())
+    }
+
+    /// Sets the data segment at `position` downloaded along with its flow merkle `proof`.
+    pub fn set_data(&mut self, position: usize, mut data: Vec<u8>, proof: FlowRangeProof) {
+        data.resize(SEGMENT_SIZE, 0);
+        self.shards[position] = Some(data);
+        self.proofs[position] = Some(proof);
+    }
+
+    /// Sets the concatenated parity segments held by the shard at `position`.
+    pub fn set_parity(&mut self, position: usize, parity: &[u8]) -> Result<(), String> {
+        if parity.len() != self.parity_per_shard * SEGMENT_SIZE {
+            return Err(format!("Invalid parity length {}", parity.len()));
+        }
+
+        let start = self.num_shard + position * self.parity_per_shard;
+        for (shard, segment) in self.shards[start..start + self.parity_per_shard]
+            .iter_mut()
+            .zip(parity.chunks_exact(SEGMENT_SIZE))
+        {
+            *shard = Some(segment.to_vec());
+        }
+
+        Ok(())
+    }
+
+    /// Returns the concatenated parity segments held by the shard at `position`.
+    pub fn parity(&self, position: usize) -> Option<Vec<u8>> {
+        let start = self.num_shard + position * self.parity_per_shard;
+        self.shards[start..start + self.parity_per_shard]
+            .iter()
+            .cloned()
+            .collect::<Option<Vec<_>>>()
+            .map(|segments| segments.concat())
+    }
+
+    /// Drops all the parity segments, e.g. when the stripe rebuilt from them is invalid.
+    pub fn clear_parity(&mut self) {
+        for shard in self.shards[self.num_shard..].iter_mut() {
+            *shard = None;
+        }
+    }
+
+    /// Whether enough segments are available to rebuild the stripe.
+    pub fn is_recoverable(&self) -> bool {
+        self.shards.iter().filter(|shard| shard.is_some()).count() >= self.num_shard
+    }
+
+    /// Rebuilds the missing data segments, and returns all the data segments along with
+    /// whether each of them is rebuilt.
+    pub fn reconstruct(&self, coder: &ErasureCoder) -> Result<(Vec<Vec<u8>>, Vec<bool>), String> {
+        let rebuilt = self.shards[..self.num_shard]
+            .iter()
+            .map(|shard| shard.is_none())
+            .collect();
+        let mut shards = self.shards.clone();
+        coder
+            .codec
+            .reconstruct_data(&mut shards)
+            .map_err(|e| format!("Failed to reconstruct stripe {}: {:?}", self.index, e))?;
+
+        let data = shards
+            .into_iter()
+            .take(self.num_shard)
+            .map(|shard| shard.expect("data reconstructed"))
+            .collect();
+
+        Ok((data, rebuilt))
+    }
+
+    /// Returns the downloaded data segment that can prove the segment at `position` once
+    /// rebuilt, which is the closest one in the merkle tree.
+    ///
+    /// The proof of a segment contains the root of the largest subtree which includes the
+    /// rebuilt segment but not the proven one, and the root can be computed from the rebuilt
+    /// stripe if the subtree lies in the flow entries of the file.
+    fn prover(&self, layout: &FileStripes, position: usize) -> Option<(usize, &FlowRangeProof)> {
+        self.proofs
+            .iter()
+            .enumerate()
+            .filter_map(|(proven, proof)| Some((proven, proof.as_ref()?)))
+            .filter(|(proven, proof)| {
+                let level = subtree_level(position, *proven);
+                let start = position >> level << level;
+                proof.left_proof.position() as u64
+                    == layout.flow_segment(self.index, *proven) * PORA_CHUNK_SIZE as u64
+                    && proof.left_proof.path().len() > SEGMENT_HEIGHT + level
+                    && (start..start + (1 << level)).all(|p| layout.in_file_entries(self.index, p))
+            })
+            .min_by_key(|(proven, _)| subtree_level(position, *proven))
+    }
+
+    /// Whether the segment at `position` can be proved once rebuilt.
+    pub fn is_provable(&self, layout: &FileStripes, position: usize) -> bool {
+        self.prover(layout, position).is_some()
+    }
+
+    /// Builds the flow merkle proof of the file chunks in the rebuilt segment at `position`,
+    /// in which `data` is the rebuilt stripe.
+    ///
+    /// The proof only holds if the rebuilt segments in the subtree used to prove are correct,
+    /// which is checked when validating the proof against the flow root.
+    pub fn prove_rebuilt(
+        &self,
+        layout: &FileStripes,
+        data: &[Vec<u8>],
+        position: usize,
+    ) -> Result<FlowRangeProof, String> {
+        let (proven, proven_proof) = self
+            .prover(layout, position)
+            .ok_or_else(|| format!("No segment to prove position {}", position))?;
+        let proven_proof = &proven_proof.left_proof;
+        let level = subtree_level(position, proven);
+        let height = SEGMENT_HEIGHT + level;
+
+        // siblings of the rebuilt segment in the subtree without the proven segment
+        let start = position >> level << level;
+        let roots = data[start..start + (1 << level)]
+            .iter()
+            .map(|segment| segment_tree(segment).map(|tree| tree.root()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let subtree_proof = AppendMerkleTree::<H256, Sha3Algorithm>::new(roots, 0, None)
+            .gen_proof(position - start)
+            .map_err(|e| e.to_string())?;
+
+        // sibling of the subtree, which contains the proven segment
+        let mut sibling = proven_proof.item();
+        for (is_left, node) in proven_proof
+            .path()
+            .iter()
+            .zip(&proven_proof.lemma()[1..])
+            .take(height)
+        {
+            sibling = if *is_left {
+                Sha3Algorithm::parent(&sibling, node)
+            } else {
+                Sha3Algorithm::parent(node, &sibling)
+            };
+        }
+
+        let segment = layout
+            .file_segment(self.index, position)
+            .ok_or_else(|| format!("Position {} out of file", position))?;
+        let (from_chunk, to_chunk) = layout.chunk_range(segment);
+        let tree = segment_tree(&data[position])?;
+        let prove_chunk = |index: u64| -> Result<Proof<H256>, String> {
+            let chunk_proof = tree.gen_proof(index as usize).map_err(|e| e.to_string())?;
+
+            let mut lemma = chunk_proof.lemma()[..=SEGMENT_HEIGHT].to_vec();
+            lemma.extend_from_slice(&subtree_proof.lemma()[1..=level]);
+            lemma.push(sibling);
+            lemma.extend_from_slice(&proven_proof.lemma()[height + 2..]);
+
+            let mut path = chunk_proof.path().to_vec();
+            path.extend_from_slice(subtree_proof.path());
+            path.push((position >> level) & 1 == 0);
+            path.extend_from_slice(&proven_proof.path()[height + 1..]);
+
+            Proof::new(lemma, path).map_err(|e| e.to_string())
+        };
+
+        Ok(FlowRangeProof {
+            left_proof: prove_chunk(0)?,
+            right_proof: prove_chunk(to_chunk - from_chunk - 1)?,
+        })
+    }
+}
+
+/// Returns the level of the largest subtree that includes the segment at position `a` but
+/// not `b`, in which the leaves are segments.
+fn subtree_level(a: usize, b: usize) -> usize {
+    (usize::BITS - 1 - (a ^ b).leading_zeros()) as usize
+}
+
+/// Builds the flow merkle tree of a segment, whose leaves are chunks.
+fn segment_tree(data: &[u8]) -> Result<AppendMerkleTree<H256, Sha3Algorithm>, String> {
+    let leaves = data_to_merkle_leaves(data).map_err(|e| e.to_string())?;
+    Ok(AppendMerkleTree::new(leaves, 0, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::random;
+
+    fn random_stripe(num_shard: usize) -> Vec<Vec<u8>> {
+        (0..num_shard)
+            .map(|_| (0..SEGMENT_SIZE).map(|_| random()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_config() {
+        assert!(ErasureCoder::new(6, 3).is_err());
+        assert!(ErasureCoder::new(8, 0).is_err());
+        assert!(ErasureCoder::new(8, 8).is_err());
+        assert!(ErasureCoder::new(128, 1).is_err());
+
+        let coder = ErasureCoder::new(8, 3).unwrap();
+        assert_eq!(coder.parity_per_shard(), 2);
+        assert_eq!(ErasureCoder::new(8, 4).unwrap().parity_per_shard(), 1);
+    }
+
+    #[test]
+    fn test_file_stripes() {
+        // 2.5 segments from the flow segment 6 in stripes of 4 segments.
+        let layout = FileStripes::new(4, 6 * PORA_CHUNK_SIZE as u64, 2560);
+        assert_eq!(layout.num_segments(), 3);
+        assert_eq!(layout.stripe_of_chunk(0), 1);
+        assert_eq!(layout.stripe_of_chunk(2048), 2);
+        assert_eq!(layout.file_segment(1, 1), None);
+        assert_eq!(layout.file_segment(1, 2), Some(0));
+        assert_eq!(layout.file_segment(2, 0), Some(2));
+        assert_eq!(layout.file_segment(2, 1), None);
+        assert_eq!(layout.position(2), 0);
+        assert_eq!(layout.chunk_range(2), (2048, 2560));
+        assert_eq!(layout.first_chunk(1), 0);
+        assert_eq!(layout.first_chunk(2), 2048);
+        assert_eq!(layout.first_chunk(3), 2560);
+    }
+
+    #[test]
+    fn test_reconstruct_from_min_shards() {
+        let coder = ErasureCoder::new(8, 3).unwrap();
+        let layout = FileStripes::new(8, 0, 8 * PORA_CHUNK_SIZE as u64);
+        let data = random_stripe(8);
+        let parity: Vec<_> = (0..8).map(|i| coder.encode(&data, i).unwrap()).collect();
+
+        // Only the shards 1, 4 and 6 are reachable.
+        let mut stripe = Stripe::new(&coder, &layout, 0);
+        for position in [1, 4, 6] {
+            assert!(!stripe.is_recoverable());
+            stripe.set_data(
+                position,
+                data[position].clone(),
+                FlowRangeProof::new_empty(),
+            );
+            stripe.set_parity(position, &parity[position]).unwrap();
+        }
+        assert!(stripe.is_recoverable());
+        assert!(stripe.set_parity(0, &parity[0][1..]).is_err());
+
+        let (rebuilt_data, rebuilt) = stripe.reconstruct(&coder).unwrap();
+        assert_eq!(rebuilt_data, data);
+        assert_eq!(
+            rebuilt,
+            vec![true, false, true, true, false, true, false, true]
+        );
+    }
+
+    #[test]
+    fn test_reconstruct_partial_stripe() {
+        let coder = ErasureCoder::new(4, 2).unwrap();
+        // The file only covers the positions 1 and 2 of the stripe.
+        let layout = FileStripes::new(4, PORA_CHUNK_SIZE as u64, 2 * PORA_CHUNK_SIZE as u64);
+        let mut data = random_stripe(4);
+        data[0] = vec![0u8; SEGMENT_SIZE];
+        data[3] = vec![0u8; SEGMENT_SIZE];
+        let parity = coder.encode(&data, 3).unwrap();
+
+        let mut stripe = Stripe::new(&coder, &layout, 0);
+        assert!(stripe.has_data(0) && stripe.has_data(3));
+        stripe.set_data(1, data[1].clone(), FlowRangeProof::new_empty());
+        assert!(!stripe.is_recoverable());
+        stripe.set_parity(3, &parity).unwrap();
+        assert!(stripe.has_parity(3));
+
+        let (rebuilt_data, rebuilt) = stripe.reconstruct(&coder).unwrap();
+        assert_eq!(rebuilt_data, data);
+        assert_eq!(rebuilt, vec![false, false, true, false]);
+    }
+
+    #[test]
+    fn test_prove_rebuilt() {
+        let coder = ErasureCoder::new(8, 4).unwrap();
+        // 6.5 segments from the beginning of the flow.
+        let num_chunks = 6 * PORA_CHUNK_SIZE as u64 + 512;
+        let layout = FileStripes::new(8, 0, num_chunks);
+        let mut data = random_stripe(8);
+        data[6][512 * CHUNK_SIZE..].fill(0);
+        data[7].fill(0);
+        let flow = AppendMerkleTree::<H256, Sha3Algorithm>::new(
+            data_to_merkle_leaves(&data.concat()).unwrap(),
+            0,
+            None,
+        );
+        let parity = coder.encode(&data, 4).unwrap();
+
+        // Segments 0 and 4 are rebuilt from the segments 1, 2, 3, 5, 6 and the parity.
+        let mut stripe = Stripe::new(&coder, &layout, 0);
+        for position in [1, 2, 3, 5, 6] {
+            let (from_chunk, to_chunk) = layout.chunk_range(position as u64);
+            let proof = flow
+                .gen_range_proof(from_chunk as usize, to_chunk as usize)
+                .unwrap();
+            let segment = data[position][..(to_chunk - from_chunk) as usize * CHUNK_SIZE].to_vec();
+            stripe.set_data(position, segment, proof);
+        }
+        stripe.set_parity(4, &parity).unwrap();
+        assert!(stripe.is_provable(&layout, 0));
+        assert!(stripe.is_provable(&layout, 4));
+
+        let (rebuilt_data, rebuilt) = stripe.reconstruct(&coder).unwrap();
+        assert_eq!(rebuilt_data, data);
+        assert_eq!(
+            rebuilt,
+            vec![true, false, false, false, true, false, false, false]
+        );
+        for position in [0, 4] {
+            let proof = stripe
+                .prove_rebuilt(&layout, &rebuilt_data, position)
+                .unwrap();
+            let leaves = data_to_merkle_leaves(&rebuilt_data[position]).unwrap();
+            proof
+                .validate::<Sha3Algorithm>(&leaves, position * PORA_CHUNK_SIZE)
+                .unwrap();
+            assert_eq!(proof.root(), flow.root());
+        }
+
+        // Segments rebuilt from bad parity can not be proved.
+        let mut bad_data = rebuilt_data.clone();
+        bad_data[4][0] ^= 1;
+        let proof = stripe.prove_rebuilt(&layout, &bad_data, 4).unwrap();
+        let leaves = data_to_merkle_leaves(&bad_data[4]).unwrap();
+        assert!(proof
+            .validate::<Sha3Algorithm>(&leaves, 4 * PORA_CHUNK_SIZE)
+            .is_err());
+
+        // The segment 6 is not entirely in the file entries, so the segment 7 can not be
+        // proved with the segments 0 to 6.
+        let mut stripe = Stripe::new(&coder, &layout, 0);
+        assert!(!stripe.is_provable(&layout, 6));
+        let proof = flow
+            .gen_range_proof(4 * PORA_CHUNK_SIZE, 5 * PORA_CHUNK_SIZE)
+            .unwrap();
+        stripe.set_data(4, data[4].clone(), proof);
+        assert!(!stripe.is_provable(&layout, 6));
+    }
+}
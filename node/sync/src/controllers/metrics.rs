@@ -12,4 +12,5 @@ lazy_static::lazy_static! {
     pub static ref SERIAL_SYNC_SEGMENT_LATENCY: Arc<dyn Histogram> = Sample::ExpDecay(0.015).register("sync_controllers_serial_sync_segment_latency", 1024);
     pub static ref SERIAL_SYNC_SEGMENT_TIMEOUT: Arc<dyn Counter<usize>> = CounterUsize::register("sync_controllers_serial_sync_segment_timeout");
     pub static ref SERIAL_SYNC_UNEXPECTED_ERRORS: Arc<dyn Counter<usize>> = CounterUsize::register("sync_controllers_serial_sync_unexpected_errors");
+    pub static ref SERIAL_SYNC_SEGMENT_RECONSTRUCTED: Arc<dyn Counter<usize>> = CounterUsize::register("sync_controllers_serial_sync_segment_reconstructed");
 }
@@ -1,3 +1,4 @@
+mod erasure;
 mod metrics;
 mod peers;
 mod serial;
@@ -7,6 +8,7 @@ use std::collections::HashMap;
 use peers::PeerState;
 use serde::{Deserialize, Serialize};
 
+pub use erasure::{ErasureCoder, FileStripes, SEGMENT_SIZE};
 pub use serial::{FailureReason, SerialSyncController, SyncState};
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -1,4 +1,5 @@
 use crate::context::SyncNetworkContext;
+use crate::controllers::erasure::{ErasureCoder, FileStripes, Stripe};
 use crate::controllers::peers::{PeerState, SyncPeers};
 use crate::controllers::{metrics, FileSyncGoal, FileSyncInfo};
 use crate::{Config, InstantWrapper};
@@ -6,13 +7,15 @@ use file_location_cache::FileLocationCache;
 use libp2p::swarm::DialError;
 use network::types::FindChunks;
 use network::{
-    multiaddr::Protocol, rpc::GetChunksRequest, types::FindFile, Multiaddr, NetworkMessage,
-    PeerAction, PeerId, PubsubMessage, SyncId as RequestId,
+    multiaddr::Protocol,
+    rpc::{GetChunksRequest, GetShardParityRequest, ShardParity},
+    types::FindFile,
+    Multiaddr, NetworkMessage, PeerAction, PeerId, PubsubMessage, SyncId as RequestId,
 };
 use rand::Rng;
-use shared_types::{ChunkArrayWithProof, ShardedFile, TxID, CHUNK_SIZE};
+use shared_types::{ChunkArray, ChunkArrayWithProof, FlowProof, ShardedFile, TxID, CHUNK_SIZE};
 use ssz::Encode;
-use std::{sync::Arc, time::Instant};
+use std::{cmp, collections::HashSet, sync::Arc, time::Instant};
 use storage::log_store::log_manager::{sector_to_segment, segment_to_sector, PORA_CHUNK_SIZE};
 use storage_async::{ShardConfig, Store};
 
@@ -21,6 +24,7 @@ pub enum FailureReason {
     DBError(String),
     TxReverted(TxID),
     TimeoutFindFile,
+    ReconstructionFailed(String),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -47,6 +51,12 @@ pub enum SyncState {
         to_chunk: u64,
         since: InstantWrapper,
     },
+    DownloadingParity {
+        peer_id: PeerId,
+        stripe_index: u64,
+        shard_id: usize,
+        since: InstantWrapper,
+    },
     Completed,
     Failed {
         reason: FailureReason,
@@ -89,6 +99,18 @@ pub struct SerialSyncController {
 
     /// Cache for storing and serving gossip messages.
     file_location_cache: Arc<FileLocationCache>,
+
+    /// Erasure coder of files across shards, `None` if disabled.
+    erasure: Option<Arc<ErasureCoder>>,
+
+    /// Segments of the stripe in sync, only used when erasure coded.
+    stripe: Option<Stripe>,
+
+    /// Peers that served the parity of the stripe in sync.
+    parity_providers: Vec<PeerId>,
+
+    /// Peers that failed to serve valid parity of this file.
+    peers_without_parity: HashSet<PeerId>,
 }
 
 impl SerialSyncController {
@@ -100,6 +122,7 @@ impl SerialSyncController {
         ctx: Arc<SyncNetworkContext>,
         store: Store,
         file_location_cache: Arc<FileLocationCache>,
+        erasure: Option<Arc<ErasureCoder>>,
     ) -> Self {
         SerialSyncController {
             config,
@@ -115,6 +138,10 @@ impl SerialSyncController {
             ctx,
             store,
             file_location_cache,
+            erasure,
+            stripe: None,
+            parity_providers: vec![],
+            peers_without_parity: Default::default(),
         }
     }
 
@@ -153,11 +180,176 @@ impl SerialSyncController {
         }
 
         self.failures = 0;
+        // segments of an incomplete stripe are requested again
+        self.clear_stripe();
         self.state = SyncState::Idle;
         // remove disconnected peers
         self.peers.transition();
     }
 
+    /// Returns the erasure coder if enabled and the whole file is in sync.
+    fn erasure_coder(&self) -> Option<Arc<ErasureCoder>> {
+        self.erasure
+            .clone()
+            .filter(|_| self.goal.is_all_chunks() && self.goal.index_start == 0)
+    }
+
+    fn file_stripes(&self, coder: &ErasureCoder) -> FileStripes {
+        FileStripes::new(
+            coder.num_shard(),
+            self.tx_start_chunk_in_flow,
+            self.goal.num_chunks,
+        )
+    }
+
+    /// Clears the segments of the stripe in sync.
+    fn clear_stripe(&mut self) {
+        self.stripe = None;
+        self.parity_providers.clear();
+    }
+
+    /// Whether the remote peer holds the parity of shard at `position` in stripes, which is
+    /// either stored by the nodes of the shard, or computed by the nodes without sharding.
+    fn holds_parity(&self, peer_id: &PeerId, position: usize, coder: &ErasureCoder) -> bool {
+        match self.peers.shard_config(peer_id) {
+            Some(config) => {
+                (config.num_shard == 1
+                    || config.num_shard == coder.num_shard() && config.shard_id == position)
+                    && !self.peers_without_parity.contains(peer_id)
+            }
+            None => false,
+        }
+    }
+
+    /// Whether any `Connected` peer holds the data segment at `position` of `stripe`.
+    fn holds_data(&self, layout: &FileStripes, stripe: u64, position: usize) -> bool {
+        let segment = layout.flow_segment(stripe, position);
+        self.peers
+            .filter_peers(vec![PeerState::Connected])
+            .iter()
+            .filter_map(|peer_id| self.peers.shard_config(peer_id))
+            .any(|config| config.in_range(segment))
+    }
+
+    /// Returns the positions of `stripe` to store in the local shard, or the first position
+    /// in the file if none, so that there is always data to download for a stripe in sync.
+    fn local_positions(
+        &self,
+        layout: &FileStripes,
+        coder: &ErasureCoder,
+        stripe: u64,
+    ) -> Vec<usize> {
+        let shard_config = self.store.get_store().get_shard_config();
+        let in_file: Vec<_> = (0..coder.num_shard())
+            .filter(|position| layout.file_segment(stripe, *position).is_some())
+            .collect();
+        let local: Vec<_> = in_file
+            .iter()
+            .copied()
+            .filter(|position| shard_config.in_range(layout.flow_segment(stripe, *position)))
+            .collect();
+
+        if local.is_empty() {
+            in_file.into_iter().take(1).collect()
+        } else {
+            local
+        }
+    }
+
+    /// Returns the first stripe from `stripe` that has segments in the local shard, or `None`
+    /// if no more stripes in the file.
+    fn next_local_stripe(
+        &self,
+        layout: &FileStripes,
+        coder: &ErasureCoder,
+        stripe: u64,
+    ) -> Option<u64> {
+        let shard_config = self.store.get_store().get_shard_config();
+        (stripe..)
+            .take_while(|stripe| layout.first_chunk(*stripe) < self.goal.index_end)
+            .find(|stripe| {
+                (0..coder.num_shard()).any(|position| {
+                    layout.file_segment(*stripe, position).is_some()
+                        && shard_config.in_range(layout.flow_segment(*stripe, position))
+                })
+            })
+    }
+
+    /// Returns the stripe to sync from the next chunk, which is skipped if there are no
+    /// segments in the local shard.
+    fn stripe_in_sync(&self, layout: &FileStripes, coder: &ErasureCoder) -> u64 {
+        let stripe = layout.stripe_of_chunk(self.next_chunk);
+        self.next_local_stripe(layout, coder, stripe)
+            .unwrap_or(stripe)
+    }
+
+    /// Returns the shard of which the local node keeps the parity, if erasure coded in the
+    /// same way.
+    fn local_parity_shard(&self, coder: &ErasureCoder) -> Option<usize> {
+        let shard_config = self.store.get_store().get_shard_config();
+        if shard_config.num_shard == coder.num_shard() {
+            Some(shard_config.shard_id)
+        } else {
+            None
+        }
+    }
+
+    /// Whether peers in specified states are enough to continue the file sync.
+    ///
+    /// Without erasure coding, peers should cover data in all shards. Otherwise, peers should
+    /// hold the local segments of the stripe in sync, or the data and parity held by peers
+    /// should be enough to reconstruct the stripe.
+    fn shards_available(&self, states: Vec<PeerState>) -> bool {
+        let coder = match self.erasure_coder() {
+            Some(coder) => coder,
+            None => return self.peers.all_shards_available(states),
+        };
+
+        let layout = self.file_stripes(&coder);
+        let stripe_index = self.stripe_in_sync(&layout, &coder);
+        let stripe = self
+            .stripe
+            .as_ref()
+            .filter(|stripe| stripe.index() == stripe_index);
+        let peers = self.peers.filter_peers(states);
+        let has_data = |position: usize| {
+            let segment = layout.flow_segment(stripe_index, position);
+            let known = match stripe {
+                Some(stripe) => stripe.has_data(position),
+                None => layout.file_segment(stripe_index, position).is_none(),
+            };
+            known
+                || peers.iter().any(|peer_id| {
+                    matches!(self.peers.shard_config(peer_id), Some(config) if config.in_range(segment))
+                })
+        };
+
+        if self
+            .local_positions(&layout, &coder, stripe_index)
+            .into_iter()
+            .all(has_data)
+        {
+            return true;
+        }
+
+        let mut num_available = 0;
+        for position in 0..coder.num_shard() {
+            if has_data(position) {
+                num_available += 1;
+            }
+
+            if stripe.map_or(false, |stripe| stripe.has_parity(position))
+                || peers
+                    .iter()
+                    .any(|peer_id| self.holds_parity(peer_id, position, &coder))
+            {
+                num_available += coder.parity_per_shard();
+            }
+        }
+
+        num_available >= coder.num_shard()
+    }
+
     /// Find more peers to sync chunks. Return whether `FindFile` pubsub message published,
     fn try_find_peers(&mut self) {
         let (published, num_new_peers) = if !self.goal.is_all_chunks() {
@@ -194,7 +386,7 @@ impl SerialSyncController {
         }
 
         if num_new_peers > 0
-            && self.peers.all_shards_available(vec![
+            && self.shards_available(vec![
                 PeerState::Found,
                 PeerState::Connecting,
                 PeerState::Connected,
@@ -249,10 +441,7 @@ impl SerialSyncController {
         let mut num_peers_dialed = 0;
 
         // select a random peer
-        while !self
-            .peers
-            .all_shards_available(vec![PeerState::Connecting, PeerState::Connected])
-        {
+        while !self.shards_available(vec![PeerState::Connecting, PeerState::Connected]) {
             let (peer_id, address) = match self.peers.random_peer(PeerState::Found) {
                 Some((peer_id, address)) => (peer_id, address),
                 None => {
@@ -294,32 +483,157 @@ impl SerialSyncController {
             }
         }
 
+        if let Some(coder) = self.erasure_coder() {
+            self.try_request_next_in_stripe(&coder);
+            return;
+        }
+
         // request next chunk array
         let from_chunk = self.next_chunk;
         let to_chunk = std::cmp::min(from_chunk + PORA_CHUNK_SIZE as u64, self.goal.index_end);
+        let request = self.new_chunks_request(from_chunk, to_chunk);
+
+        // select a random peer
+        let peer_id = match self.select_peer_for_request(&request) {
+            Some(peer_id) => peer_id,
+            None => {
+                warn!(%self.tx_seq, "No peers available to request chunks");
+                self.state = SyncState::Idle;
+                return;
+            }
+        };
+
+        self.send_chunks_request(peer_id, request);
+    }
+
+    /// Select a peer to sync the next segment of the stripe in sync.
+    ///
+    /// The local data segments are downloaded along with the local parity if available.
+    /// Otherwise, the missing local segments are rebuilt from the other data segments, which
+    /// are downloaded first to prove the rebuilt ones, and then the parity of any shard.
+    fn try_request_next_in_stripe(&mut self, coder: &ErasureCoder) {
+        let layout = self.file_stripes(coder);
+        let stripe_index = self.stripe_in_sync(&layout, coder);
+        if !matches!(&self.stripe, Some(stripe) if stripe.index() == stripe_index) {
+            self.next_chunk = cmp::max(self.next_chunk, layout.first_chunk(stripe_index));
+            self.clear_stripe();
+            self.stripe = Some(Stripe::new(coder, &layout, stripe_index));
+        }
+
+        let stripe = self.stripe.as_ref().expect("stripe initialized");
+        let missing: Vec<_> = self
+            .local_positions(&layout, coder, stripe_index)
+            .into_iter()
+            .filter(|position| !stripe.has_data(*position))
+            .collect();
+        let rebuild = missing
+            .iter()
+            .any(|position| !self.holds_data(&layout, stripe_index, *position));
+
+        let mut data_positions = missing.clone();
+        let mut parity_positions = vec![];
+        if rebuild {
+            // other data segments closer to the missing ones in the merkle tree first
+            let mut others: Vec<_> = (0..coder.num_shard())
+                .filter(|position| !stripe.has_data(*position) && !missing.contains(position))
+                .collect();
+            others.sort_by_key(|position| missing.iter().map(|m| m ^ position).min());
+            data_positions.extend(others);
+            parity_positions
+                .extend((0..coder.num_shard()).filter(|position| !stripe.has_parity(*position)));
+        } else if let Some(shard_id) = self.local_parity_shard(coder) {
+            // the local parity is requested before data, so that the stripe always completes
+            // on data response
+            if !stripe.has_parity(shard_id) {
+                parity_positions.push(shard_id);
+            }
+        }
+
+        if !rebuild {
+            for shard_id in parity_positions.drain(..) {
+                if self.try_request_parity(coder, stripe_index, shard_id) {
+                    return;
+                }
+            }
+        }
+
+        for position in data_positions {
+            let segment = match layout.file_segment(stripe_index, position) {
+                Some(segment) => segment,
+                None => continue,
+            };
+            let (from_chunk, to_chunk) = layout.chunk_range(segment);
+            let request = self.new_chunks_request(from_chunk, to_chunk);
+            if let Some(peer_id) = self.select_peer_for_request(&request) {
+                self.send_chunks_request(peer_id, request);
+                return;
+            }
+        }
+
+        for shard_id in parity_positions {
+            if self.try_request_parity(coder, stripe_index, shard_id) {
+                return;
+            }
+        }
+
+        warn!(%self.tx_seq, %stripe_index, "No peers available to request stripe");
+        self.state = SyncState::Idle;
+    }
+
+    /// Requests the parity of `shard_id` in the stripe from a random peer, and returns `false`
+    /// if no peer holds the parity.
+    fn try_request_parity(
+        &mut self,
+        coder: &ErasureCoder,
+        stripe_index: u64,
+        shard_id: usize,
+    ) -> bool {
+        let peer_id = match self.select_peer_for_parity(shard_id, coder) {
+            Some(peer_id) => peer_id,
+            None => return false,
+        };
+
         let request_id =
             network::RequestId::Sync(Instant::now(), RequestId::SerialSync { tx_id: self.tx_id });
+        self.ctx.send(NetworkMessage::SendRequest {
+            peer_id,
+            request_id,
+            request: network::Request::GetShardParity(GetShardParityRequest {
+                tx_id: self.tx_id,
+                stripe_index,
+                shard_id,
+            }),
+        });
+
+        info!(%self.tx_seq, %stripe_index, %shard_id, %peer_id, "Sent request to get shard parity");
+
+        self.state = SyncState::DownloadingParity {
+            peer_id,
+            stripe_index,
+            shard_id,
+            since: Instant::now().into(),
+        };
+        true
+    }
+
+    fn new_chunks_request(&self, from_chunk: u64, to_chunk: u64) -> GetChunksRequest {
         // TODO: It's possible that we read it while `nex_tx_seq - 1` is still being committed.
         // We can wait for its commitment, but this will slow down this state machine.
         // Or we can use `next_tx_seq - 2`, but for a restarted node without receiving new
         // files, this tx seq is also unavailable.
         let committed_tx_seq = self.store.get_store().next_tx_seq().saturating_sub(1);
-        let request = GetChunksRequest {
+        GetChunksRequest {
             tx_id: self.tx_id,
             index_start: from_chunk,
             index_end: to_chunk,
             merkle_tx_seq: committed_tx_seq,
-        };
+        }
+    }
 
-        // select a random peer
-        let peer_id = match self.select_peer_for_request(&request) {
-            Some(peer_id) => peer_id,
-            None => {
-                warn!(%self.tx_seq, "No peers available to request chunks");
-                self.state = SyncState::Idle;
-                return;
-            }
-        };
+    fn send_chunks_request(&mut self, peer_id: PeerId, request: GetChunksRequest) {
+        let (from_chunk, to_chunk) = (request.index_start, request.index_end);
+        let request_id =
+            network::RequestId::Sync(Instant::now(), RequestId::SerialSync { tx_id: self.tx_id });
 
         self.ctx.send(NetworkMessage::SendRequest {
             peer_id,
@@ -421,11 +735,12 @@ impl SerialSyncController {
     }
 
     /// Handle the case that got an unexpected response:
-    /// 1. not in `Downloading` sync state.
+    /// 1. not in `Downloading` or `DownloadingParity` sync state.
     /// 2. from unexpected peer.
     fn handle_on_response_mismatch(&self, from_peer_id: PeerId) -> bool {
         match self.state {
-            SyncState::Downloading { peer_id, .. } => {
+            SyncState::Downloading { peer_id, .. }
+            | SyncState::DownloadingParity { peer_id, .. } => {
                 if from_peer_id == peer_id {
                     return false;
                 }
@@ -527,25 +842,57 @@ impl SerialSyncController {
 
         metrics::SERIAL_SYNC_SEGMENT_LATENCY.update_since(since.0);
 
+        if let Some(coder) = self.erasure_coder() {
+            self.on_stripe_data(&coder, response).await;
+            return;
+        }
+
         let shard_config = self.store.get_store().get_shard_config();
         let next_chunk = segment_to_sector(shard_config.next_segment_index(
             sector_to_segment(from_chunk),
             sector_to_segment(self.tx_start_chunk_in_flow),
         ));
         // store in db
+        if !self.put_chunks(response.chunks, None).await {
+            return;
+        }
+        self.next_chunk = next_chunk as u64;
+
+        // prepare to download next
+        if self.next_chunk < self.goal.index_end {
+            self.state = SyncState::Idle;
+            return;
+        }
+
+        // completed to download chunks
+        if !self.goal.is_all_chunks() {
+            self.state = SyncState::Completed;
+            metrics::SERIAL_SYNC_CHUNKS_COMPLETED.update_since(self.since.0);
+            return;
+        }
+
+        self.finalize_file().await;
+    }
+
+    /// Stores chunks of the file in db, and returns `false` if failed.
+    async fn put_chunks(
+        &mut self,
+        chunks: ChunkArray,
+        maybe_file_proof: Option<FlowProof>,
+    ) -> bool {
         match self
             .store
-            .put_chunks_with_tx_hash(self.tx_id.seq, self.tx_id.hash, response.chunks, None)
+            .put_chunks_with_tx_hash(self.tx_id.seq, self.tx_id.hash, chunks, maybe_file_proof)
             .await
         {
-            Ok(true) => self.next_chunk = next_chunk as u64,
+            Ok(true) => true,
             Ok(false) => {
                 warn!(%self.tx_seq, ?self.tx_id, "Transaction reverted while storing chunks");
                 metrics::SERIAL_SYNC_UNEXPECTED_ERRORS.inc(1);
                 self.state = SyncState::Failed {
                     reason: FailureReason::TxReverted(self.tx_id),
                 };
-                return;
+                false
             }
             Err(err) => {
                 error!(%err, %self.tx_seq, "Unexpected DB error while storing chunks");
@@ -553,23 +900,311 @@ impl SerialSyncController {
                 self.state = SyncState::Failed {
                     reason: FailureReason::DBError(err.to_string()),
                 };
+                false
+            }
+        }
+    }
+
+    /// Buffers a validated data segment of the stripe in sync, and stores it if in the local
+    /// shard.
+    async fn on_stripe_data(&mut self, coder: &ErasureCoder, response: ChunkArrayWithProof) {
+        let layout = self.file_stripes(coder);
+        let stripe_index = layout.stripe_of_chunk(response.chunks.start_index);
+        let position = layout.position(response.chunks.start_index / PORA_CHUNK_SIZE as u64);
+        let data = response.chunks.data.clone();
+
+        let shard_config = self.store.get_store().get_shard_config();
+        if shard_config.in_range(layout.flow_segment(stripe_index, position))
+            && !self.put_chunks(response.chunks, None).await
+        {
+            return;
+        }
+
+        match self.stripe.as_mut() {
+            Some(stripe) if stripe.index() == stripe_index => {
+                stripe.set_data(position, data, response.proof)
+            }
+            _ => {
+                self.state = SyncState::Idle;
                 return;
             }
         }
 
+        self.try_complete_stripe(coder).await;
+    }
+
+    pub async fn on_parity_response(&mut self, from_peer_id: PeerId, response: ShardParity) {
+        metrics::SERIAL_SYNC_SEGMENT_BANDWIDTH.mark(response.data.len());
+
+        if self.handle_on_response_mismatch(from_peer_id) {
+            return;
+        }
+
+        let (stripe_index, shard_id, since) = match self.state {
+            SyncState::DownloadingParity {
+                stripe_index,
+                shard_id,
+                since,
+                ..
+            } => (stripe_index, shard_id, since),
+            _ => return,
+        };
+
+        debug!(%self.tx_seq, %from_peer_id, %stripe_index, %shard_id, ?since, "Received parity response from expected peer");
+
+        let coder = match self.erasure_coder() {
+            Some(coder) => coder,
+            None => return,
+        };
+
+        // invalid stripe: may be response timeout, just ignore it
+        if response.tx_id != self.tx_id || response.stripe_index != stripe_index {
+            warn!(%self.tx_seq, "Invalid parity response stripe, expected={stripe_index}, actual={}", response.stripe_index);
+            self.ctx.report_peer(
+                from_peer_id,
+                PeerAction::LowToleranceError,
+                "Got response with unexpected stripe",
+            );
+            return;
+        }
+
+        let stripe = match self.stripe.as_mut() {
+            Some(stripe) if stripe.index() == stripe_index => stripe,
+            _ => {
+                self.state = SyncState::Idle;
+                return;
+            }
+        };
+
+        // invalid parity size: ban and re-request
+        if let Err(err) = stripe.set_parity(shard_id, &response.data) {
+            warn!(%err, %from_peer_id, %self.tx_seq, "Invalid parity response");
+            metrics::SERIAL_SYNC_UNEXPECTED_ERRORS.inc(1);
+            self.ban_peer(from_peer_id, "Invalid parity response data length");
+            self.state = SyncState::Idle;
+            return;
+        }
+        self.parity_providers.push(from_peer_id);
+
+        self.failures = 0;
+
+        metrics::SERIAL_SYNC_SEGMENT_LATENCY.update_since(since.0);
+
+        self.try_complete_stripe(coder.as_ref()).await;
+    }
+
+    /// Completes the stripe in sync once all the local segments are available, in which the
+    /// missing ones are rebuilt and proved, and then stores the local parity.
+    ///
+    /// Note, the local parity downloaded from peers is stored without validation, which is
+    /// validated by the nodes that rebuild segments from it.
+    async fn try_complete_stripe(&mut self, coder: &ErasureCoder) {
+        let layout = self.file_stripes(coder);
+        let stripe = match self.stripe.as_ref() {
+            Some(stripe) => stripe,
+            None => {
+                self.state = SyncState::Idle;
+                return;
+            }
+        };
+        let stripe_index = stripe.index();
+        let missing: Vec<_> = self
+            .local_positions(&layout, coder, stripe_index)
+            .into_iter()
+            .filter(|position| !stripe.has_data(*position))
+            .collect();
+
+        let mut stripe_data = None;
+        if !missing.is_empty() {
+            if !stripe.is_recoverable()
+                || !missing
+                    .iter()
+                    .all(|position| stripe.is_provable(&layout, *position))
+            {
+                // prepare to download next
+                self.state = SyncState::Idle;
+                return;
+            }
+
+            let data = match self.rebuild_stripe(coder, &layout, &missing).await {
+                Some(data) => data,
+                None => return,
+            };
+            stripe_data = Some(data);
+        } else if stripe.has_all_data() {
+            match stripe.reconstruct(coder) {
+                Ok((data, _)) => stripe_data = Some(data),
+                Err(err) => {
+                    self.on_reconstruction_failed(err);
+                    return;
+                }
+            }
+        }
+
+        // serve the parity of local shard for other nodes to reconstruct from
+        if let Some(shard_id) = self.local_parity_shard(coder) {
+            let parity = match &stripe_data {
+                Some(data) => match coder.encode(data, shard_id) {
+                    Ok(parity) => Some(parity),
+                    Err(err) => {
+                        self.on_reconstruction_failed(err);
+                        return;
+                    }
+                },
+                None => self
+                    .stripe
+                    .as_ref()
+                    .and_then(|stripe| stripe.parity(shard_id)),
+            };
+
+            match parity {
+                Some(parity) => {
+                    if let Err(err) = self
+                        .store
+                        .put_shard_parity(self.tx_seq, stripe_index, parity)
+                        .await
+                    {
+                        error!(%err, %self.tx_seq, "Unexpected DB error while storing shard parity");
+                        metrics::SERIAL_SYNC_UNEXPECTED_ERRORS.inc(1);
+                        self.state = SyncState::Failed {
+                            reason: FailureReason::DBError(err.to_string()),
+                        };
+                        return;
+                    }
+                }
+                None => debug!(%self.tx_seq, %stripe_index, "No parity available for local shard"),
+            }
+        }
+
+        debug!(%self.tx_seq, %stripe_index, num_rebuilt = missing.len(), "Completed to sync stripe");
+
+        self.clear_stripe();
+        self.next_chunk = match self.next_local_stripe(&layout, coder, stripe_index + 1) {
+            Some(stripe) => layout.first_chunk(stripe),
+            None => self.goal.index_end,
+        };
+
         // prepare to download next
         if self.next_chunk < self.goal.index_end {
             self.state = SyncState::Idle;
             return;
         }
 
-        // completed to download chunks
-        if !self.goal.is_all_chunks() {
-            self.state = SyncState::Completed;
-            metrics::SERIAL_SYNC_CHUNKS_COMPLETED.update_since(self.since.0);
-            return;
+        self.finalize_file().await;
+    }
+
+    /// Rebuilds the stripe in sync, and stores the `missing` local segments once proved
+    /// against the flow root. Returns all the data segments of the stripe if succeeded.
+    async fn rebuild_stripe(
+        &mut self,
+        coder: &ErasureCoder,
+        layout: &FileStripes,
+        missing: &[usize],
+    ) -> Option<Vec<Vec<u8>>> {
+        let stripe = self.stripe.as_ref().expect("stripe in sync");
+        let stripe_index = stripe.index();
+        let (data, _) = match stripe.reconstruct(coder) {
+            Ok(result) => result,
+            Err(err) => {
+                self.on_reconstruction_failed(err);
+                return None;
+            }
+        };
+
+        let mut segments = vec![];
+        for position in missing.iter().copied() {
+            let segment = layout
+                .file_segment(stripe_index, position)
+                .expect("local segments in file");
+            let (from_chunk, to_chunk) = layout.chunk_range(segment);
+            let proof = match stripe.prove_rebuilt(layout, &data, position) {
+                Ok(proof) => proof,
+                Err(err) => {
+                    self.on_reconstruction_failed(err);
+                    return None;
+                }
+            };
+
+            segments.push(ChunkArrayWithProof {
+                chunks: ChunkArray {
+                    data: data[position][..(to_chunk - from_chunk) as usize * CHUNK_SIZE].to_vec(),
+                    start_index: from_chunk,
+                },
+                proof,
+            });
+        }
+
+        for segment in segments {
+            match self
+                .store
+                .get_store()
+                .validate_and_insert_range_proof(self.tx_seq, &segment)
+            {
+                Ok(true) => {}
+                Ok(false) => {
+                    // occurs when the flow root has been reverted
+                    info!(%self.tx_seq, "Failed to validate rebuilt segment due to no root found");
+                    self.state = SyncState::AwaitingDownload {
+                        since: (Instant::now() + self.config.peer_next_chunks_request_wait_timeout)
+                            .into(),
+                    };
+                    return None;
+                }
+                Err(err) => {
+                    self.on_invalid_parity(err.to_string());
+                    return None;
+                }
+            }
+
+            metrics::SERIAL_SYNC_SEGMENT_RECONSTRUCTED.inc(1);
+            if !self.put_chunks(segment.chunks, None).await {
+                return None;
+            }
+        }
+
+        Some(data)
+    }
+
+    /// Handles the parity of the stripe in sync from which the rebuilt segments can not be
+    /// proved. Since the data segments are all proved, the peers that served the parity are
+    /// penalized, and the parity is requested from the other peers.
+    fn on_invalid_parity(&mut self, err: String) {
+        warn!(%err, %self.tx_seq, "Failed to prove rebuilt segments");
+        metrics::SERIAL_SYNC_UNEXPECTED_ERRORS.inc(1);
+
+        let providers = std::mem::take(&mut self.parity_providers);
+        if let [peer_id] = providers[..] {
+            self.ban_peer(peer_id, "Invalid shard parity");
+        } else {
+            // unknown which one is invalid
+            for peer_id in providers.iter() {
+                self.ctx.report_peer(
+                    *peer_id,
+                    PeerAction::LowToleranceError,
+                    "Invalid shard parity",
+                );
+            }
         }
+        self.peers_without_parity.extend(providers);
+
+        if let Some(stripe) = self.stripe.as_mut() {
+            stripe.clear_parity();
+        }
+        self.state = SyncState::Idle;
+    }
+
+    /// Fails the file sync if failed to decode or encode the stripe in sync.
+    fn on_reconstruction_failed(&mut self, err: String) {
+        warn!(%err, %self.tx_seq, "Failed to reconstruct stripe");
+        metrics::SERIAL_SYNC_UNEXPECTED_ERRORS.inc(1);
+        self.clear_stripe();
+        self.state = SyncState::Failed {
+            reason: FailureReason::ReconstructionFailed(err),
+        };
+    }
 
+    /// Finalizes the file once all chunks are stored.
+    async fn finalize_file(&mut self) {
         // finalize tx if all chunks downloaded
         match self
             .store
@@ -606,6 +1241,17 @@ impl SerialSyncController {
             return;
         }
 
+        if let SyncState::DownloadingParity { .. } = self.state {
+            // remote peer may not hold the parity, e.g. synced the file by another node which
+            // does not enable erasure coding, so just try other peers.
+            info!(%peer_id, %self.tx_seq, "Parity request failed");
+            self.peers_without_parity.insert(peer_id);
+            self.state = SyncState::AwaitingDownload {
+                since: Instant::now().into(),
+            };
+            return;
+        }
+
         self.handle_response_failure(peer_id, "RPC Error");
     }
 
@@ -645,6 +1291,21 @@ impl SerialSyncController {
         Some(peers[index])
     }
 
+    /// Randomly select a `Connected` peer to sync the parity of shard at `position`.
+    fn select_peer_for_parity(&self, position: usize, coder: &ErasureCoder) -> Option<PeerId> {
+        let mut peers = self.peers.filter_peers(vec![PeerState::Connected]);
+
+        peers.retain(|peer_id| self.holds_parity(peer_id, position, coder));
+
+        let len = peers.len();
+        if len == 0 {
+            return None;
+        }
+
+        let index = rand::thread_rng().gen_range(0..len);
+        Some(peers[index])
+    }
+
     pub fn transition(&mut self) {
         use PeerState::*;
 
@@ -658,10 +1319,7 @@ impl SerialSyncController {
         while !completed {
             match self.state {
                 SyncState::Idle => {
-                    if self
-                        .peers
-                        .all_shards_available(vec![Found, Connecting, Connected])
-                    {
+                    if self.shards_available(vec![Found, Connecting, Connected]) {
                         self.state = SyncState::FoundPeers;
                     } else {
                         self.try_find_peers();
@@ -669,10 +1327,7 @@ impl SerialSyncController {
                 }
 
                 SyncState::FindingPeers { since, .. } => {
-                    if self
-                        .peers
-                        .all_shards_available(vec![Found, Connecting, Connected])
-                    {
+                    if self.shards_available(vec![Found, Connecting, Connected]) {
                         self.state = SyncState::FoundPeers;
                     } else {
                         // FindFile timeout
@@ -695,7 +1350,7 @@ impl SerialSyncController {
                 }
 
                 SyncState::FoundPeers => {
-                    if self.peers.all_shards_available(vec![Connecting, Connected]) {
+                    if self.shards_available(vec![Connecting, Connected]) {
                         self.state = SyncState::ConnectingPeers {
                             origin: self.since,
                             since: Instant::now().into(),
@@ -706,11 +1361,11 @@ impl SerialSyncController {
                 }
 
                 SyncState::ConnectingPeers { .. } => {
-                    if self.peers.all_shards_available(vec![Connected]) {
+                    if self.shards_available(vec![Connected]) {
                         self.state = SyncState::AwaitingDownload {
                             since: Instant::now().into(),
                         };
-                    } else if !self.peers.all_shards_available(vec![Connecting, Connected]) {
+                    } else if !self.shards_available(vec![Connecting, Connected]) {
                         debug!(%self.tx_seq, "Connecting to peers timeout or remote peers disconnected, try to find more peers");
                         self.state = SyncState::Idle;
                     } else {
@@ -737,7 +1392,8 @@ impl SerialSyncController {
                     }
                 }
 
-                SyncState::Downloading { peer_id, since, .. } => {
+                SyncState::Downloading { peer_id, since, .. }
+                | SyncState::DownloadingParity { peer_id, since, .. } => {
                     if !matches!(self.peers.peer_state(&peer_id), Some(PeerState::Connected)) {
                         // e.g. peer disconnected by remote node
                         debug!(%self.tx_seq, "No peer to continue downloading and try to find other peers to download");
@@ -761,6 +1417,7 @@ impl SerialSyncController {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::controllers::erasure::SEGMENT_SIZE;
     use crate::test_util::create_2_store;
     use crate::test_util::tests::create_file_location_cache;
     use libp2p::identity;
@@ -1565,6 +2222,164 @@ mod tests {
         assert!(network_recv.try_recv().is_err());
     }
 
+    #[tokio::test]
+    async fn test_reconstruct_from_parity() {
+        let peer_id = identity::Keypair::generate_ed25519().public().to_peer_id();
+
+        let tx_seq = 0;
+        let chunk_count = 2 * PORA_CHUNK_SIZE;
+        let (store, peer_store, txs, data) = create_2_store(vec![chunk_count]);
+
+        let runtime = TestRuntime::default();
+        let task_executor = runtime.task_executor.clone();
+        let (mut controller, mut network_recv) = create_controller(
+            task_executor,
+            Some(peer_id),
+            store.clone(),
+            txs[0].id(),
+            chunk_count,
+        );
+
+        // The file is in one stripe, and only the peer of shard 0 is reachable.
+        let coder = Arc::new(ErasureCoder::new(2, 1).unwrap());
+        controller.erasure = Some(coder.clone());
+        controller.tx_start_chunk_in_flow = txs[0].start_entry_index;
+        controller.on_peer_announced(
+            peer_id,
+            ShardConfig {
+                num_shard: 2,
+                shard_id: 0,
+            },
+        );
+
+        controller.try_request_next();
+        assert!(matches!(
+            network_recv.try_recv().unwrap(),
+            NetworkMessage::SendRequest {
+                request: Request::GetChunks(GetChunksRequest {
+                    index_start: 0,
+                    index_end: 1024,
+                    ..
+                }),
+                ..
+            }
+        ));
+
+        let chunks = peer_store
+            .get_chunks_with_proof_by_tx_and_index_range(tx_seq, 0, PORA_CHUNK_SIZE, None)
+            .unwrap()
+            .unwrap();
+        controller.on_response(peer_id, chunks).await;
+        assert_eq!(*controller.get_status(), SyncState::Idle);
+
+        controller.try_request_next();
+        let stripe_index = match network_recv.try_recv().unwrap() {
+            NetworkMessage::SendRequest {
+                request: Request::GetShardParity(request),
+                ..
+            } => request.stripe_index,
+            _ => panic!("Not expected message: NetworkMessage::SendRequest"),
+        };
+        assert!(matches!(
+            *controller.get_status(),
+            SyncState::DownloadingParity { .. }
+        ));
+
+        let segments: Vec<_> = data[0].chunks(SEGMENT_SIZE).map(|s| s.to_vec()).collect();
+        let parity = ShardParity {
+            tx_id: txs[0].id(),
+            stripe_index,
+            data: coder.encode(&segments, 0).unwrap(),
+        };
+        controller.on_parity_response(peer_id, parity).await;
+        assert_eq!(*controller.get_status(), SyncState::Completed);
+        assert!(matches!(
+            network_recv.try_recv().unwrap(),
+            NetworkMessage::AnnounceLocalFile { .. }
+        ));
+
+        let synced = store
+            .get_chunks_by_tx_and_index_range(tx_seq, 0, chunk_count)
+            .unwrap()
+            .unwrap();
+        assert_eq!(synced.data, data[0]);
+    }
+
+    #[tokio::test]
+    async fn test_reconstruct_from_invalid_parity() {
+        let peer_id = identity::Keypair::generate_ed25519().public().to_peer_id();
+
+        let tx_seq = 0;
+        let chunk_count = 2 * PORA_CHUNK_SIZE;
+        let (store, peer_store, txs, data) = create_2_store(vec![chunk_count]);
+
+        let runtime = TestRuntime::default();
+        let task_executor = runtime.task_executor.clone();
+        let (mut controller, mut network_recv) = create_controller(
+            task_executor,
+            Some(peer_id),
+            store.clone(),
+            txs[0].id(),
+            chunk_count,
+        );
+
+        let coder = Arc::new(ErasureCoder::new(2, 1).unwrap());
+        controller.erasure = Some(coder.clone());
+        controller.tx_start_chunk_in_flow = txs[0].start_entry_index;
+        controller.on_peer_announced(
+            peer_id,
+            ShardConfig {
+                num_shard: 2,
+                shard_id: 0,
+            },
+        );
+
+        controller.try_request_next();
+        network_recv.try_recv().unwrap();
+        let chunks = peer_store
+            .get_chunks_with_proof_by_tx_and_index_range(tx_seq, 0, PORA_CHUNK_SIZE, None)
+            .unwrap()
+            .unwrap();
+        controller.on_response(peer_id, chunks).await;
+
+        controller.try_request_next();
+        let stripe_index = match network_recv.try_recv().unwrap() {
+            NetworkMessage::SendRequest {
+                request: Request::GetShardParity(request),
+                ..
+            } => request.stripe_index,
+            _ => panic!("Not expected message: NetworkMessage::SendRequest"),
+        };
+
+        let mut segments: Vec<_> = data[0].chunks(SEGMENT_SIZE).map(|s| s.to_vec()).collect();
+        segments[1][0] ^= 1;
+        let parity = ShardParity {
+            tx_id: txs[0].id(),
+            stripe_index,
+            data: coder.encode(&segments, 0).unwrap(),
+        };
+        controller.on_parity_response(peer_id, parity).await;
+        assert_eq!(*controller.get_status(), SyncState::Idle);
+
+        // the only parity provider is banned, and the rebuilt segment is not stored
+        match network_recv.try_recv().unwrap() {
+            NetworkMessage::ReportPeer {
+                peer_id: banned,
+                action,
+                ..
+            } => {
+                assert_eq!(banned, peer_id);
+                assert!(matches!(action, PeerAction::Fatal));
+            }
+            _ => panic!("Not received expected message: NetworkMessage::ReportPeer"),
+        }
+        assert!(controller.peers_without_parity.contains(&peer_id));
+        assert!(store
+            .get_chunks_by_tx_and_index_range(tx_seq, PORA_CHUNK_SIZE, chunk_count)
+            .unwrap()
+            .is_none());
+    }
+
     // FIXME(zz): enable.
     // #[tokio::test]
     #[allow(unused)]
@@ -1704,6 +2519,7 @@ mod tests {
             ctx,
             Store::new(store, task_executor),
             file_location_cache,
+            None,
         );
 
         (controller, network_recv)
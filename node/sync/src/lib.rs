@@ -50,6 +50,10 @@ pub struct Config {
     pub max_bandwidth_bytes: u64,
     #[serde(deserialize_with = "deserialize_duration")]
     pub bandwidth_wait_timeout: Duration,
+    /// Number of shards erasure coded together, 0 to disable erasure coding.
+    pub erasure_coding_num_shard: usize,
+    /// Minimum number of shards to reconstruct a stripe from.
+    pub erasure_coding_min_shards: usize,
 
     // auto sync config
     #[serde(deserialize_with = "deserialize_duration")]
@@ -87,6 +91,8 @@ impl Default for Config {
             peer_next_chunks_request_wait_timeout: Duration::from_secs(3),
             max_bandwidth_bytes: 0,
             bandwidth_wait_timeout: Duration::from_secs(5),
+            erasure_coding_num_shard: 0,
+            erasure_coding_min_shards: 0,
 
             // auto sync config
             auto_sync_idle_interval: Duration::from_secs(3),
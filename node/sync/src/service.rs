@@ -1,7 +1,8 @@
 use crate::auto_sync::manager::AutoSyncManager;
 use crate::context::SyncNetworkContext;
 use crate::controllers::{
-    FailureReason, FileSyncGoal, FileSyncInfo, SerialSyncController, SyncState,
+    ErasureCoder, FailureReason, FileStripes, FileSyncGoal, FileSyncInfo, SerialSyncController,
+    SyncState, SEGMENT_SIZE,
 };
 use crate::{Config, SyncServiceState};
 use anyhow::{anyhow, bail, Result};
@@ -10,8 +11,9 @@ use libp2p::swarm::DialError;
 use log_entry_sync::LogSyncEvent;
 use network::types::{AnnounceChunks, FindFile};
 use network::{
-    rpc::GetChunksRequest, rpc::RPCResponseErrorCode, Multiaddr, NetworkMessage, NetworkSender,
-    PeerId, PeerRequestId, PubsubMessage, SyncId as RequestId,
+    rpc::GetChunksRequest, rpc::GetShardParityRequest, rpc::RPCResponseErrorCode, rpc::ShardParity,
+    Multiaddr, NetworkMessage, NetworkSender, PeerId, PeerRequestId, PubsubMessage,
+    SyncId as RequestId,
 };
 use shared_types::{bytes_to_chunks, ChunkArrayWithProof, ShardedFile, Transaction, TxID};
 use std::sync::atomic::Ordering;
@@ -52,6 +54,16 @@ pub enum SyncMessage {
         request_id: RequestId,
         response: ChunkArrayWithProof,
     },
+    RequestShardParity {
+        peer_id: PeerId,
+        request_id: PeerRequestId,
+        request: GetShardParityRequest,
+    },
+    ShardParityResponse {
+        peer_id: PeerId,
+        request_id: RequestId,
+        response: ShardParity,
+    },
     RpcError {
         peer_id: PeerId,
         request_id: RequestId,
@@ -129,6 +141,9 @@ pub struct SyncService {
     /// Cache for storing and serving gossip messages.
     file_location_cache: Arc<FileLocationCache>,
 
+    /// Erasure coder of files across shards, `None` if disabled.
+    erasure: Option<Arc<ErasureCoder>>,
+
     /// A collection of file sync controllers.
     controllers: HashMap<u64, SerialSyncController>,
 
@@ -167,6 +182,9 @@ impl SyncService {
     ) -> Result<SyncSender> {
         let (sync_send, sync_recv) = channel::Channel::unbounded("sync");
         let store = Store::new(store, executor.clone());
+        let erasure = ErasureCoder::from_config(&config)
+            .map_err(|e| anyhow!(e))?
+            .map(Arc::new);
 
         // init auto sync
         let auto_sync_manager = if config.auto_sync_enabled {
@@ -191,6 +209,7 @@ impl SyncService {
             ctx: Arc::new(SyncNetworkContext::new(network_send)),
             store,
             file_location_cache,
+            erasure,
             controllers: Default::default(),
             auto_sync_manager,
         };
@@ -252,6 +271,24 @@ impl SyncService {
                 self.on_chunks_response(peer_id, request_id, response).await;
             }
 
+            SyncMessage::RequestShardParity {
+                request_id,
+                peer_id,
+                request,
+            } => {
+                self.on_get_shard_parity_request(peer_id, request_id, request)
+                    .await;
+            }
+
+            SyncMessage::ShardParityResponse {
+                peer_id,
+                request_id,
+                response,
+            } => {
+                self.on_shard_parity_response(peer_id, request_id, response)
+                    .await;
+            }
+
             SyncMessage::RpcError {
                 peer_id,
                 request_id,
@@ -527,6 +564,172 @@ impl SyncService {
         }
     }
 
+    async fn on_get_shard_parity_request(
+        &mut self,
+        peer_id: PeerId,
+        request_id: PeerRequestId,
+        request: GetShardParityRequest,
+    ) {
+        debug!(?request, %peer_id, ?request_id, "Received GetShardParity request");
+
+        if let Err(err) = self
+            .handle_shard_parity_request_with_db_err(peer_id, request_id, request)
+            .await
+        {
+            error!(%err, "Failed to handle shard parity request due to db error");
+            self.ctx.send(NetworkMessage::SendErrorResponse {
+                peer_id,
+                id: request_id,
+                error: RPCResponseErrorCode::ServerError,
+                reason: "DB error".into(),
+            });
+        }
+    }
+
+    async fn handle_shard_parity_request_with_db_err(
+        &mut self,
+        peer_id: PeerId,
+        request_id: PeerRequestId,
+        request: GetShardParityRequest,
+    ) -> StorageResult<()> {
+        // ban peer if invalid tx requested
+        let tx = match self.store.get_tx_by_seq_number(request.tx_id.seq).await? {
+            Some(tx) => tx,
+            None => {
+                self.ctx.ban_peer(peer_id, "Tx not found");
+                return Ok(());
+            }
+        };
+
+        // Transaction may be reverted during file sync
+        if tx.id() != request.tx_id {
+            self.ctx.send(NetworkMessage::SendErrorResponse {
+                peer_id,
+                error: RPCResponseErrorCode::InvalidRequest,
+                reason: "Tx not found (Reverted)".into(),
+                id: request_id,
+            });
+            return Ok(());
+        }
+
+        // parity is only available if erasure coded in the same way as the remote peer
+        let shard_config = self.store.get_store().get_shard_config();
+        let mut result = None;
+        if shard_config.shard_id == request.shard_id {
+            result = self
+                .store
+                .get_shard_parity(request.tx_id.seq, request.stripe_index)
+                .await?;
+        }
+        if result.is_none() {
+            result = self.compute_shard_parity(&tx, &request).await?;
+        }
+
+        match result {
+            Some(data) => {
+                self.ctx.send(NetworkMessage::SendResponse {
+                    peer_id,
+                    id: request_id,
+                    response: network::Response::ShardParity(ShardParity {
+                        tx_id: request.tx_id,
+                        stripe_index: request.stripe_index,
+                        data,
+                    }),
+                });
+            }
+            None => {
+                debug!(%request.tx_id.seq, %request.stripe_index, "Failed to handle shard parity request due to parity not found");
+                self.ctx.send(NetworkMessage::SendErrorResponse {
+                    peer_id,
+                    error: RPCResponseErrorCode::InvalidRequest,
+                    reason: "Parity not found".into(),
+                    id: request_id,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Computes the parity of any shard in a stripe, if all the data segments of the stripe
+    /// are stored locally, e.g. on a node without sharding.
+    async fn compute_shard_parity(
+        &self,
+        tx: &Transaction,
+        request: &GetShardParityRequest,
+    ) -> StorageResult<Option<Vec<u8>>> {
+        let coder = match &self.erasure {
+            Some(coder) => coder.clone(),
+            None => return Ok(None),
+        };
+        if request.shard_id >= coder.num_shard() || !self.store.check_tx_completed(tx.seq).await? {
+            return Ok(None);
+        }
+
+        let layout = FileStripes::new(
+            coder.num_shard(),
+            tx.start_entry_index,
+            bytes_to_chunks(tx.size as usize) as u64,
+        );
+        let shard_config = self.store.get_store().get_shard_config();
+        let mut data = Vec::with_capacity(coder.num_shard());
+        let mut in_file = false;
+        for position in 0..coder.num_shard() {
+            let segment = match layout.file_segment(request.stripe_index, position) {
+                Some(segment) => segment,
+                None => {
+                    data.push(vec![0u8; SEGMENT_SIZE]);
+                    continue;
+                }
+            };
+            if !shard_config.in_range(layout.flow_segment(request.stripe_index, position)) {
+                return Ok(None);
+            }
+
+            let (from_chunk, to_chunk) = layout.chunk_range(segment);
+            let mut segment_data = match self
+                .store
+                .get_chunks_by_tx_and_index_range(tx.seq, from_chunk as usize, to_chunk as usize)
+                .await?
+            {
+                Some(chunks) => chunks.data,
+                None => return Ok(None),
+            };
+            segment_data.resize(SEGMENT_SIZE, 0);
+            data.push(segment_data);
+            in_file = true;
+        }
+
+        if !in_file {
+            return Ok(None);
+        }
+
+        Ok(coder.encode(&data, request.shard_id).ok())
+    }
+
+    async fn on_shard_parity_response(
+        &mut self,
+        peer_id: PeerId,
+        request_id: RequestId,
+        response: ShardParity,
+    ) {
+        debug!(%response.stripe_index, %peer_id, ?request_id, "Received shard parity response");
+
+        let tx_seq = match request_id {
+            RequestId::SerialSync { tx_id } => tx_id.seq,
+        };
+
+        match self.controllers.get_mut(&tx_seq) {
+            Some(controller) => {
+                controller.on_parity_response(peer_id, response).await;
+                controller.transition();
+            }
+            None => {
+                warn!("Received shard parity response for non-existent controller tx_seq={tx_seq}");
+            }
+        }
+    }
+
     fn on_rpc_error(&mut self, peer_id: PeerId, request_id: RequestId) {
         info!(%peer_id, ?request_id, "Received RPC error");
 
@@ -675,6 +878,7 @@ impl SyncService {
                     self.ctx.clone(),
                     self.store.clone(),
                     self.file_location_cache.clone(),
+                    self.erasure.clone(),
                 ))
             }
         };
@@ -997,6 +1201,7 @@ mod tests {
             ctx: Arc::new(SyncNetworkContext::new(network_send)),
             store,
             file_location_cache,
+            erasure: None,
             controllers: Default::default(),
             auto_sync_manager: None,
         };
@@ -1026,6 +1231,7 @@ mod tests {
             ctx: Arc::new(SyncNetworkContext::new(network_send)),
             store,
             file_location_cache,
+            erasure: None,
             controllers: Default::default(),
             auto_sync_manager: None,
         };
@@ -39,6 +39,7 @@ class RpcTest(TestFramework):
 
         wait_until(lambda: client1.zgs_get_file_info(data_root)["finalized"])
         assert_equal(client1.zgs_download_segment(data_root, 0, 1), segment[0]["data"])
+        self.__test_file_status(client1, data_root)
 
         client2.admin_start_sync_file(0)
         wait_until(lambda: client2.sync_status_is_completed_or_unknown(0))
@@ -51,6 +52,27 @@ class RpcTest(TestFramework):
         client2.shutdown()
         wait_until(lambda: client1.zgs_get_status() == 0)
 
+    def __test_file_status(self, client, data_root):
+        # query by tx seq and data root, and the tx 1 is not submitted yet
+        status = client.zgs_get_file_status_batch([0, data_root, 1])
+        assert_equal(len(status), 3)
+        for file_status in status[:2]:
+            assert_equal(file_status["tx"]["seq"], 0)
+            assert_equal(file_status["finalized"], True)
+            assert_equal(file_status["availableRanges"], [[0, 1]])
+        assert_equal(status[2], None)
+
+        # available ranges are skipped if not needed
+        status = client.zgs_get_file_status_batch([0], False)
+        assert_equal(status[0]["availableRanges"], None)
+
+        availability = client.zgs_get_file_availability(0)
+        assert_equal(availability["txSeq"], 0)
+        assert_equal(availability["numSegments"], 1)
+        assert_equal(availability["availableSegNum"], 1)
+        assert_equal(availability["ranges"], [[0, 1]])
+        assert_equal(client.zgs_get_file_availability(1), None)
+
     def __test_upload_file_with_cli(self, client1):
         # Test with uploading files with cli
         n_files = 1
@@ -114,6 +114,12 @@ class ZgsNode(TestNode):
     def zgs_get_file_info_by_tx_seq(self, tx_seq):
         return self.rpc.zgs_getFileInfoByTxSeq([tx_seq])
 
+    def zgs_get_file_status_batch(self, tx_seq_or_roots, need_available=True):
+        return self.rpc.zgs_getFileStatusBatch([tx_seq_or_roots, need_available])
+
+    def zgs_get_file_availability(self, tx_seq):
+        return self.rpc.zgs_getFileAvailability([tx_seq])
+
     def zgs_get_flow_context(self, tx_seq):
         return self.rpc.zgs_getFlowContext([tx_seq])
 